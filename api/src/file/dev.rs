@@ -0,0 +1,95 @@
+//! ioctls of the device nodes provided by `starry_core::file`.
+
+use core::ffi::{c_int, c_void};
+
+use axerrno::{AxResult, LinuxError, LinuxResult};
+use axfs::fops::FileAttr;
+use linux_raw_sys::{
+    general::{O_ACCMODE, O_RDONLY},
+    loop_device::{
+        LO_FLAGS_READ_ONLY, LOOP_CLR_FD, LOOP_CTL_GET_FREE, LOOP_GET_STATUS64, LOOP_SET_FD,
+        loop_info64,
+    },
+};
+use starry_core::file::{
+    loopdev::{self, BackingFile, LoopDevice},
+    mount::MountFlags,
+};
+
+use super::{File, FileLike};
+use crate::{path::FilePath, ptr::UserPtr};
+
+/// Handles an ioctl on the device node at `path`.
+///
+/// Returns `ENOTTY` if the node does not support `cmd`.
+pub fn device_ioctl(path: &str, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+    if let Some(dev) = loopdev::loop_device_by_path(path) {
+        loop_ioctl(&dev, cmd, arg)
+    } else if loopdev::is_loop_control(path) {
+        loop_control_ioctl(cmd)
+    } else {
+        Err(LinuxError::ENOTTY)
+    }
+}
+
+fn loop_ioctl(dev: &LoopDevice, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+    match cmd {
+        LOOP_SET_FD => {
+            let file = File::from_fd(arg.address().as_usize() as c_int)?;
+            // Like Linux, the device is read-only unless the file was opened
+            // for writing
+            let read_only = file.flags() & O_ACCMODE == O_RDONLY
+                || FilePath::new(file.path())?
+                    .mount_flags()
+                    .contains(MountFlags::RDONLY);
+            dev.attach(file.clone(), file.path(), read_only)?;
+            Ok(0)
+        }
+        LOOP_CLR_FD => {
+            dev.detach()?;
+            Ok(0)
+        }
+        LOOP_GET_STATUS64 => {
+            let status = dev.status()?;
+            let info = UserPtr::<loop_info64>::from(arg.address().as_usize()).get_as_mut()?;
+            // SAFETY: all-zero is valid for loop_info64
+            *info = unsafe { core::mem::zeroed() };
+            info.lo_number = status.index as u32;
+            info.lo_sizelimit = 0;
+            if status.read_only {
+                info.lo_flags |= LO_FLAGS_READ_ONLY;
+            }
+            // The name is truncated and always NUL-terminated, like Linux
+            let name = status.backing_file.as_bytes();
+            let len = name.len().min(info.lo_file_name.len() - 1);
+            info.lo_file_name[..len].copy_from_slice(&name[..len]);
+            Ok(0)
+        }
+        _ => Err(LinuxError::ENOTTY),
+    }
+}
+
+fn loop_control_ioctl(cmd: u32) -> LinuxResult<isize> {
+    match cmd {
+        LOOP_CTL_GET_FREE => Ok(loopdev::free_loop_device()?.index() as isize),
+        _ => Err(LinuxError::ENOTTY),
+    }
+}
+
+impl BackingFile for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        self.inner().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        self.inner().write_at(offset, buf)
+    }
+
+    fn get_attr(&self) -> AxResult<FileAttr> {
+        self.inner().get_attr()
+    }
+
+    fn flush(&self) -> AxResult {
+        self.inner().flush()
+    }
+}
@@ -1,25 +1,45 @@
-use core::{any::Any, ffi::c_int};
+use core::{
+    any::Any,
+    ffi::{c_int, c_void},
+};
 
 use alloc::{string::String, sync::Arc};
 use axerrno::{LinuxError, LinuxResult};
 use axfs::fops::DirEntry;
-use axio::PollState;
+use axio::{PollState, SeekFrom};
 use axsync::{Mutex, MutexGuard};
-use linux_raw_sys::general::S_IFDIR;
+use linux_raw_sys::general::{O_CREAT, O_DIRECTORY, O_EXCL, O_NOCTTY, O_RDONLY, O_TRUNC, S_IFDIR};
+use starry_core::file::{
+    blkdev::{BlockDevice, get_block_device},
+    devnum,
+    proc::fd::FdInfo,
+};
 
-use super::{FileLike, Kstat, get_file_like};
+use super::{FileLike, Kstat, dev::device_ioctl, get_file_like};
+use crate::ptr::UserPtr;
 
 /// File wrapper for `axfs::fops::File`.
 pub struct File {
     inner: Mutex<axfs::fops::File>,
     path: String,
+    flags: u32,
+    /// The block device opened, which counts the file as a user.
+    device: Option<Arc<dyn BlockDevice>>,
 }
 
 impl File {
-    pub fn new(inner: axfs::fops::File, path: String) -> Self {
+    /// Create a file opened at `path` with the `open(2)` status `flags`.
+    pub fn new(inner: axfs::fops::File, path: String, flags: u32) -> Self {
+        let device = get_block_device(&path).ok();
+        if let Some(device) = &device {
+            device.acquire();
+        }
         Self {
             inner: Mutex::new(inner),
             path,
+            // Like Linux, the flags only used while opening are not kept
+            flags: flags & !(O_CREAT | O_EXCL | O_NOCTTY | O_TRUNC),
+            device,
         }
     }
 
@@ -28,12 +48,25 @@ impl File {
         &self.path
     }
 
+    /// Get the `open(2)` status flags of the file.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
     /// Get the inner node of the file.
     pub fn inner(&self) -> MutexGuard<axfs::fops::File> {
         self.inner.lock()
     }
 }
 
+impl Drop for File {
+    fn drop(&mut self) {
+        if let Some(device) = &self.device {
+            device.release();
+        }
+    }
+}
+
 impl FileLike for File {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
         Ok(self.inner().read(buf)?)
@@ -47,12 +80,15 @@ impl FileLike for File {
         let metadata = self.inner().get_attr()?;
         let ty = metadata.file_type() as u8;
         let perm = metadata.perm().bits() as u32;
+        let rdev = devnum::device_number(&self.path)
+            .map_or(0, |(major, minor)| devnum::makedev(major, minor));
 
         Ok(Kstat {
             mode: ((ty as u32) << 12) | perm,
             size: metadata.size(),
             blocks: metadata.blocks(),
             blksize: 512,
+            rdev,
             ..Default::default()
         })
     }
@@ -71,6 +107,15 @@ impl FileLike for File {
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn fd_info(&self) -> FdInfo {
+        let pos = self.inner().seek(SeekFrom::Current(0)).unwrap_or(0);
+        FdInfo::path(&self.path, pos, self.flags)
+    }
+
+    fn ioctl(&self, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        device_ioctl(&self.path, cmd, arg)
+    }
 }
 
 /// Directory wrapper for `axfs::fops::Directory`.
@@ -136,6 +181,10 @@ impl FileLike for Directory {
         Ok(())
     }
 
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::path(&self.path, 0, O_RDONLY | O_DIRECTORY)
+    }
+
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         get_file_like(fd)?
             .into_any()
@@ -1,25 +1,43 @@
+mod dev;
 mod fs;
 mod net;
+mod pidfd;
 mod pipe;
 mod stdio;
+mod tty;
 
-use core::{any::Any, ffi::c_int};
+use core::{
+    any::Any,
+    ffi::{c_int, c_void},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 
 use alloc::{sync::Arc, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
-use axns::{ResArc, def_resource};
+use axns::{AxNamespace, ResArc, def_resource};
 use flatten_objects::FlattenObjects;
-use linux_raw_sys::general::{stat, statx};
+use linux_raw_sys::general::{O_CLOEXEC, stat, statx};
 use spin::RwLock;
+use starry_core::{
+    file::{
+        devnum,
+        proc::fd::{FdInfo, FdTableIf},
+    },
+    sysctl::FILE_MAX,
+};
+
+use crate::ptr::UserPtr;
 
 pub use self::{
     fs::{Directory, File},
-    net::Socket,
+    net::{Socket, SocketInner},
+    pidfd::PidFd,
     pipe::Pipe,
+    tty::{console, hangup_session, open_tty, probe_console_size},
 };
 
-pub const AX_FILE_LIMIT: usize = 1024;
+pub use starry_core::file::AX_FILE_LIMIT;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Kstat {
@@ -31,6 +49,7 @@ pub struct Kstat {
     size: u64,
     blocks: u64,
     blksize: u32,
+    rdev: u64,
 }
 
 impl Default for Kstat {
@@ -44,6 +63,7 @@ impl Default for Kstat {
             size: 0,
             blocks: 0,
             blksize: 4096,
+            rdev: 0,
         }
     }
 }
@@ -60,6 +80,11 @@ impl Kstat {
             ..Default::default()
         }
     }
+
+    /// Returns the file type and permission bits
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
 }
 
 impl From<Kstat> for stat {
@@ -74,6 +99,7 @@ impl From<Kstat> for stat {
         stat.st_size = value.size as _;
         stat.st_blksize = value.blksize as _;
         stat.st_blocks = value.blocks as _;
+        stat.st_rdev = value.rdev as _;
 
         stat
     }
@@ -92,6 +118,8 @@ impl From<Kstat> for statx {
         statx.stx_ino = value.ino as _;
         statx.stx_size = value.size as _;
         statx.stx_blocks = value.blocks as _;
+        statx.stx_rdev_major = devnum::major(value.rdev);
+        statx.stx_rdev_minor = devnum::minor(value.rdev);
 
         statx
     }
@@ -106,6 +134,14 @@ pub trait FileLike: Send + Sync {
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
 
+    /// Describes the file for `/proc/<pid>/fd` and `/proc/<pid>/fdinfo`.
+    fn fd_info(&self) -> FdInfo;
+
+    /// Handles a device-specific `ioctl` request.
+    fn ioctl(&self, _cmd: u32, _arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        Err(LinuxError::ENOTTY)
+    }
+
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>>
     where
         Self: Sized + 'static,
@@ -124,30 +160,142 @@ pub trait FileLike: Send + Sync {
     }
 }
 
+/// The number of file descriptors open in all tables, which `fs/file-max`
+/// bounds.
+static OPEN_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// The open files of a process, by file descriptor, with their
+/// close-on-exec flags.
+pub struct FdTable {
+    files: FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>,
+    cloexec: [u64; AX_FILE_LIMIT.div_ceil(64)],
+}
+
+impl FdTable {
+    fn new() -> Self {
+        Self {
+            files: FlattenObjects::new(),
+            cloexec: [0; AX_FILE_LIMIT.div_ceil(64)],
+        }
+    }
+
+    /// Returns the file at `fd`.
+    pub fn get(&self, fd: usize) -> Option<&Arc<dyn FileLike>> {
+        self.files.get(fd)
+    }
+
+    /// Returns the open file descriptors.
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.files.ids()
+    }
+
+    /// Whether `fd` is closed by `execve`.
+    pub fn cloexec(&self, fd: usize) -> bool {
+        self.cloexec[fd / 64] & (1 << (fd % 64)) != 0
+    }
+
+    fn set_cloexec(&mut self, fd: usize, cloexec: bool) {
+        if cloexec {
+            self.cloexec[fd / 64] |= 1 << (fd % 64);
+        } else {
+            self.cloexec[fd / 64] &= !(1 << (fd % 64));
+        }
+    }
+
+    fn add(&mut self, f: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<usize> {
+        let fd = self.files.add(f).map_err(|_| LinuxError::EMFILE)?;
+        self.set_cloexec(fd, cloexec);
+        Ok(fd)
+    }
+
+    fn remove(&mut self, fd: usize) -> Option<Arc<dyn FileLike>> {
+        let f = self.files.remove(fd)?;
+        self.set_cloexec(fd, false);
+        Some(f)
+    }
+}
+
 def_resource! {
-    pub static FD_TABLE: ResArc<RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>>> = ResArc::new();
+    pub static FD_TABLE: ResArc<RwLock<FdTable>> = ResArc::new();
 }
 
 impl FD_TABLE {
     /// Return a copy of the inner table.
-    pub fn copy_inner(&self) -> RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> {
+    pub fn copy_inner(&self) -> RwLock<FdTable> {
         let table = self.read();
-        let mut new_table = FlattenObjects::new();
+        let mut new_table = FdTable::new();
         for id in table.ids() {
-            let _ = new_table.add_at(id, table.get(id).unwrap().clone());
+            let _ = new_table.files.add_at(id, table.get(id).unwrap().clone());
         }
+        new_table.cloexec = table.cloexec;
+        OPEN_FILES.fetch_add(new_table.files.count(), Ordering::Relaxed);
         RwLock::new(new_table)
     }
 
     pub fn clear(&self) {
         let mut table = self.write();
         let ids = table.ids().collect::<Vec<_>>();
+        OPEN_FILES.fetch_sub(ids.len(), Ordering::Relaxed);
+        for id in ids {
+            let _ = table.remove(id);
+        }
+    }
+
+    /// Puts `f` at `fd`, closing the file there before, as `dup2` does.
+    pub fn replace(&self, fd: usize, f: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult {
+        let mut table = self.write();
+        if table.remove(fd).is_some() {
+            OPEN_FILES.fetch_sub(1, Ordering::Relaxed);
+        }
+        table.files.add_at(fd, f).map_err(|_| LinuxError::EBADF)?;
+        table.set_cloexec(fd, cloexec);
+        OPEN_FILES.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Closes the file descriptors flagged close-on-exec, as `execve` does.
+    pub fn close_on_exec(&self) {
+        let mut table = self.write();
+        let ids = table
+            .ids()
+            .filter(|&fd| table.cloexec(fd))
+            .collect::<Vec<_>>();
+        OPEN_FILES.fetch_sub(ids.len(), Ordering::Relaxed);
         for id in ids {
             let _ = table.remove(id);
         }
     }
 }
 
+struct FdTableImpl;
+
+#[crate_interface::impl_interface]
+impl FdTableIf for FdTableImpl {
+    fn fds(ns: &AxNamespace) -> Vec<usize> {
+        FD_TABLE.deref_from(ns).read().ids().collect()
+    }
+
+    fn fd_info(ns: &AxNamespace, fd: usize) -> Option<FdInfo> {
+        let (file, cloexec) = {
+            let table = FD_TABLE.deref_from(ns).read();
+            (table.get(fd)?.clone(), table.cloexec(fd))
+        };
+        let mut info = file.fd_info();
+        info.flags &= !O_CLOEXEC;
+        if cloexec {
+            info.flags |= O_CLOEXEC;
+        }
+        Some(info)
+    }
+}
+
+/// Allocates the inode number of a pipe or socket, which lie on no
+/// filesystem.
+fn alloc_anon_ino() -> u64 {
+    static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+    NEXT_INO.fetch_add(1, Ordering::Relaxed)
+}
+
 /// Get a file-like object by `fd`.
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
     FD_TABLE
@@ -159,7 +307,34 @@ pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
 
 /// Add a file to the file descriptor table.
 pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).map_err(|_| LinuxError::EMFILE)? as c_int)
+    add_file_like_cloexec(f, false)
+}
+
+/// Add a file to the file descriptor table, closed by `execve` if
+/// `cloexec`.
+pub fn add_file_like_cloexec(f: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+    let mut table = FD_TABLE.write();
+    if OPEN_FILES.load(Ordering::Relaxed) >= FILE_MAX.get() {
+        return Err(LinuxError::ENFILE);
+    }
+    let fd = table.add(f, cloexec)?;
+    OPEN_FILES.fetch_add(1, Ordering::Relaxed);
+    Ok(fd as c_int)
+}
+
+/// Returns whether `fd` is closed by `execve`.
+pub fn get_cloexec(fd: c_int) -> LinuxResult<bool> {
+    let table = FD_TABLE.read();
+    table.get(fd as usize).ok_or(LinuxError::EBADF)?;
+    Ok(table.cloexec(fd as usize))
+}
+
+/// Sets whether `fd` is closed by `execve`.
+pub fn set_cloexec(fd: c_int, cloexec: bool) -> LinuxResult {
+    let mut table = FD_TABLE.write();
+    table.get(fd as usize).ok_or(LinuxError::EBADF)?;
+    table.set_cloexec(fd as usize, cloexec);
+    Ok(())
 }
 
 /// Close a file by `fd`.
@@ -168,20 +343,24 @@ pub fn close_file_like(fd: c_int) -> LinuxResult {
         .write()
         .remove(fd as usize)
         .ok_or(LinuxError::EBADF)?;
+    OPEN_FILES.fetch_sub(1, Ordering::Relaxed);
     debug!("close_file_like <= count: {}", Arc::strong_count(&f));
     Ok(())
 }
 
 #[ctor_bare::register_ctor]
 fn init_stdio() {
-    let mut fd_table = flatten_objects::FlattenObjects::new();
+    let mut fd_table = FdTable::new();
     fd_table
+        .files
         .add_at(0, Arc::new(stdio::stdin()) as _)
         .unwrap_or_else(|_| panic!()); // stdin
     fd_table
+        .files
         .add_at(1, Arc::new(stdio::stdout()) as _)
         .unwrap_or_else(|_| panic!()); // stdout
     fd_table
+        .files
         .add_at(2, Arc::new(stdio::stdout()) as _)
         .unwrap_or_else(|_| panic!()); // stderr
     FD_TABLE.init_new(spin::RwLock::new(fd_table));
@@ -1,68 +1,121 @@
-use core::net::SocketAddr;
+use core::{net::SocketAddr, time::Duration};
 
-use alloc::sync::Arc;
+use alloc::{format, sync::Arc};
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
 use axnet::{TcpSocket, UdpSocket};
 use axsync::Mutex;
-use linux_raw_sys::general::S_IFSOCK;
+use axtask::WaitQueue;
+use linux_raw_sys::general::{O_RDWR, S_IFSOCK};
+use starry_core::file::proc::fd::FdInfo;
 
-use super::{FileLike, Kstat};
+use super::{FileLike, Kstat, alloc_anon_ino};
+use crate::signal::{check_interrupted, wait_timeout_interruptible};
 
-pub enum Socket {
+/// How often a blocked socket polls the network interfaces.
+const SOCKET_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+pub enum SocketInner {
     Udp(Mutex<UdpSocket>),
     Tcp(Mutex<TcpSocket>),
 }
 
+pub struct Socket {
+    inner: SocketInner,
+    ino: u64,
+}
+
 macro_rules! impl_socket {
     ($pub:vis fn $name:ident(&self $(,$arg:ident: $arg_ty:ty)*) -> $ret:ty) => {
         $pub fn $name(&self, $($arg: $arg_ty),*) -> $ret {
-            match self {
-                Socket::Udp(udpsocket) => Ok(udpsocket.lock().$name($($arg),*)?),
-                Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().$name($($arg),*)?),
+            match &self.inner {
+                SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().$name($($arg),*)?),
+                SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().$name($($arg),*)?),
             }
         }
     };
 }
 
 impl Socket {
+    /// Wraps `inner`, giving it an inode number.
+    pub fn new(inner: SocketInner) -> Self {
+        Self {
+            inner,
+            ino: alloc_anon_ino(),
+        }
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => udpsocket.lock().is_nonblocking(),
+            SocketInner::Tcp(tcpsocket) => tcpsocket.lock().is_nonblocking(),
+        }
+    }
+
+    /// Waits until the socket is readable, or ready to accept, failing with
+    /// `EINTR` if a signal interrupts the wait.
+    ///
+    /// `axnet` blocks without noticing signals, so blocking sockets wait
+    /// here before calling into it. It has no readiness notifications
+    /// either, so the socket is polled every [`SOCKET_POLL_INTERVAL`].
+    fn wait_readable(&self) -> LinuxResult<()> {
+        if self.is_nonblocking() {
+            return Ok(());
+        }
+        let wq = WaitQueue::new();
+        loop {
+            axnet::poll_interfaces();
+            // Errors are left for the call itself to report
+            if !self.poll().is_ok_and(|state| !state.readable) {
+                return Ok(());
+            }
+            check_interrupted()?;
+            wait_timeout_interruptible(&wq, SOCKET_POLL_INTERVAL);
+        }
+    }
+
     pub fn recv(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        match self {
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
+        self.wait_readable()?;
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().recv_from(buf).map(|e| e.0)?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf)?),
         }
     }
 
     pub fn sendto(&self, buf: &[u8], addr: SocketAddr) -> LinuxResult<usize> {
-        match self {
+        match &self.inner {
             // diff: must bind before sendto
-            Socket::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
-            Socket::Tcp(_) => Err(LinuxError::EISCONN),
+            SocketInner::Udp(udpsocket) => Ok(udpsocket.lock().send_to(buf, addr)?),
+            SocketInner::Tcp(_) => Err(LinuxError::EISCONN),
         }
     }
 
     pub fn recvfrom(&self, buf: &mut [u8]) -> LinuxResult<(usize, Option<SocketAddr>)> {
-        match self {
+        self.wait_readable()?;
+        match &self.inner {
             // diff: must bind before recvfrom
-            Socket::Udp(udpsocket) => Ok(udpsocket
+            SocketInner::Udp(udpsocket) => Ok(udpsocket
                 .lock()
                 .recv_from(buf)
                 .map(|res| (res.0, Some(res.1)))?),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().recv(buf).map(|res| (res, None))?),
         }
     }
 
     pub fn listen(&self) -> LinuxResult {
-        match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
+        match &self.inner {
+            SocketInner::Udp(_) => Err(LinuxError::EOPNOTSUPP),
+            SocketInner::Tcp(tcpsocket) => Ok(tcpsocket.lock().listen()?),
         }
     }
 
     pub fn accept(&self) -> LinuxResult<TcpSocket> {
-        match self {
-            Socket::Udp(_) => Err(LinuxError::EOPNOTSUPP),
-            Socket::Tcp(tcpsocket) => Ok(tcpsocket.lock().accept()?),
+        match &self.inner {
+            SocketInner::Udp(_) => Err(LinuxError::EOPNOTSUPP),
+            SocketInner::Tcp(tcpsocket) => {
+                self.wait_readable()?;
+                Ok(tcpsocket.lock().accept()?)
+            }
         }
     }
 
@@ -87,6 +140,7 @@ impl FileLike for Socket {
     fn stat(&self) -> LinuxResult<Kstat> {
         // not really implemented
         Ok(Kstat {
+            ino: self.ino,
             mode: S_IFSOCK | 0o777u32, // rwxrwxrwx
             blksize: 4096,
             ..Default::default()
@@ -101,10 +155,14 @@ impl FileLike for Socket {
         self.poll()
     }
 
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::anon(format!("socket:[{}]", self.ino), O_RDWR)
+    }
+
     fn set_nonblocking(&self, nonblock: bool) -> LinuxResult {
-        match self {
-            Socket::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
-            Socket::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
+        match &self.inner {
+            SocketInner::Udp(udpsocket) => udpsocket.lock().set_nonblocking(nonblock),
+            SocketInner::Tcp(tcpsocket) => tcpsocket.lock().set_nonblocking(nonblock),
         }
         Ok(())
     }
@@ -0,0 +1,78 @@
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use alloc::sync::{Arc, Weak};
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axprocess::Process;
+use linux_raw_sys::general::{O_NONBLOCK, O_RDWR};
+use starry_core::file::proc::fd::FdInfo;
+
+use super::{FileLike, Kstat};
+
+/// A file referring to a process, created by `pidfd_open`.
+pub struct PidFd {
+    process: Weak<Process>,
+    nonblocking: AtomicBool,
+}
+
+impl PidFd {
+    /// Creates a file referring to `process`.
+    pub fn new(process: &Arc<Process>, nonblocking: bool) -> Self {
+        Self {
+            process: Arc::downgrade(process),
+            nonblocking: AtomicBool::new(nonblocking),
+        }
+    }
+
+    /// Returns the process, or `ESRCH` if it has been reaped.
+    pub fn process(&self) -> LinuxResult<Arc<Process>> {
+        self.process.upgrade().ok_or(LinuxError::ESRCH)
+    }
+
+    /// Whether the file was opened with `PIDFD_NONBLOCK`.
+    pub fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+}
+
+impl FileLike for PidFd {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        Ok(Kstat::default())
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    /// Readable once the process has exited.
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: self
+                .process
+                .upgrade()
+                .is_none_or(|process| process.is_zombie()),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn fd_info(&self) -> FdInfo {
+        let flags = O_RDWR | if self.nonblocking() { O_NONBLOCK } else { 0 };
+        FdInfo::anon("anon_inode:[pidfd]".into(), flags)
+    }
+}
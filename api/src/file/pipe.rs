@@ -1,12 +1,14 @@
 use core::any::Any;
 
-use alloc::sync::Arc;
+use alloc::{format, sync::Arc, vec, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
 use axsync::Mutex;
-use linux_raw_sys::general::S_IFIFO;
+use linux_raw_sys::general::{O_RDONLY, O_WRONLY, S_IFIFO};
+use starry_core::{file::proc::fd::FdInfo, sysctl::PIPE_MAX_SIZE};
 
-use super::{FileLike, Kstat};
+use super::{FileLike, Kstat, alloc_anon_ino};
+use crate::signal::{check_interrupted, have_signals};
 
 #[derive(Copy, Clone, PartialEq)]
 enum RingBufferStatus {
@@ -15,29 +17,54 @@ enum RingBufferStatus {
     Normal,
 }
 
-const RING_BUFFER_SIZE: usize = 256;
-
 struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
+    arr: Vec<u8>,
     head: usize,
     tail: usize,
     status: RingBufferStatus,
 }
 
+/// The capacity of new pipes, 16 pages like Linux.
+const PIPE_DEF_SIZE: usize = 16 * 4096;
+
 impl PipeRingBuffer {
-    const fn new() -> Self {
+    fn new() -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
+            arr: vec![0; PIPE_DEF_SIZE],
             head: 0,
             tail: 0,
             status: RingBufferStatus::Empty,
         }
     }
 
+    /// Changes the capacity to `size`, keeping the data in the buffer.
+    fn resize(&mut self, size: usize) -> LinuxResult {
+        let len = self.available_read();
+        if len > size {
+            return Err(LinuxError::EBUSY);
+        }
+        let mut arr = Vec::new();
+        arr.try_reserve_exact(size)
+            .map_err(|_| LinuxError::ENOMEM)?;
+        arr.resize(size, 0);
+        for byte in &mut arr[..len] {
+            *byte = self.read_byte();
+        }
+        self.arr = arr;
+        self.head = 0;
+        self.tail = len % size;
+        self.status = match len {
+            0 => RingBufferStatus::Empty,
+            len if len == size => RingBufferStatus::Full,
+            _ => RingBufferStatus::Normal,
+        };
+        Ok(())
+    }
+
     fn write_byte(&mut self, byte: u8) {
         self.status = RingBufferStatus::Normal;
         self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
+        self.tail = (self.tail + 1) % self.arr.len();
         if self.tail == self.head {
             self.status = RingBufferStatus::Full;
         }
@@ -46,7 +73,7 @@ impl PipeRingBuffer {
     fn read_byte(&mut self) -> u8 {
         self.status = RingBufferStatus::Normal;
         let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
+        self.head = (self.head + 1) % self.arr.len();
         if self.head == self.tail {
             self.status = RingBufferStatus::Empty;
         }
@@ -54,22 +81,22 @@ impl PipeRingBuffer {
     }
 
     /// Get the length of remaining data in the buffer
-    const fn available_read(&self) -> usize {
+    fn available_read(&self) -> usize {
         if matches!(self.status, RingBufferStatus::Empty) {
             0
         } else if self.tail > self.head {
             self.tail - self.head
         } else {
-            self.tail + RING_BUFFER_SIZE - self.head
+            self.tail + self.arr.len() - self.head
         }
     }
 
     /// Get the length of remaining space in the buffer
-    const fn available_write(&self) -> usize {
+    fn available_write(&self) -> usize {
         if matches!(self.status, RingBufferStatus::Full) {
             0
         } else {
-            RING_BUFFER_SIZE - self.available_read()
+            self.arr.len() - self.available_read()
         }
     }
 }
@@ -77,18 +104,23 @@ impl PipeRingBuffer {
 pub struct Pipe {
     readable: bool,
     buffer: Arc<Mutex<PipeRingBuffer>>,
+    /// The inode number, shared by both ends like on Linux
+    ino: u64,
 }
 
 impl Pipe {
     pub fn new() -> (Pipe, Pipe) {
         let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
+        let ino = alloc_anon_ino();
         let read_end = Pipe {
             readable: true,
             buffer: buffer.clone(),
+            ino,
         };
         let write_end = Pipe {
             readable: false,
             buffer,
+            ino,
         };
         (read_end, write_end)
     }
@@ -104,9 +136,33 @@ impl Pipe {
     pub fn closed(&self) -> bool {
         Arc::strong_count(&self.buffer) == 1
     }
+
+    /// Returns the capacity in bytes, as `F_GETPIPE_SZ`.
+    pub fn capacity(&self) -> usize {
+        self.buffer.lock().arr.len()
+    }
+
+    /// Sets the capacity to at least `size` bytes, rounded up to a power of
+    /// two number of pages, as `F_SETPIPE_SZ`. Returns the new capacity.
+    ///
+    /// `fs/pipe-max-size` caps the capacity, and the data in the pipe must
+    /// fit.
+    pub fn set_capacity(&self, size: usize) -> LinuxResult<usize> {
+        if size > PIPE_MAX_SIZE.get() {
+            return Err(LinuxError::EPERM);
+        }
+        let size = size.max(4096).next_power_of_two();
+        self.buffer.lock().resize(size)?;
+        Ok(size)
+    }
 }
 
 impl FileLike for Pipe {
+    fn fd_info(&self) -> FdInfo {
+        let flags = if self.readable() { O_RDONLY } else { O_WRONLY };
+        FdInfo::anon(format!("pipe:[{}]", self.ino), flags)
+    }
+
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
         if !self.readable() {
             return Err(LinuxError::EPERM);
@@ -124,6 +180,7 @@ impl FileLike for Pipe {
                 }
                 drop(ring_buffer);
                 // Data not ready, wait for write end
+                check_interrupted()?;
                 axtask::yield_now(); // TODO: use synconize primitive
                 continue;
             }
@@ -156,6 +213,10 @@ impl FileLike for Pipe {
                 }
                 drop(ring_buffer);
                 // Buffer is full, wait for read end to consume
+                if write_size > 0 && have_signals() {
+                    return Ok(write_size);
+                }
+                check_interrupted()?;
                 axtask::yield_now(); // TODO: use synconize primitive
                 continue;
             }
@@ -171,6 +232,7 @@ impl FileLike for Pipe {
 
     fn stat(&self) -> LinuxResult<Kstat> {
         Ok(Kstat {
+            ino: self.ino,
             mode: S_IFIFO | 0o600u32, // rw-------
             ..Default::default()
         })
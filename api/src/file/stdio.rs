@@ -1,125 +1,57 @@
-use core::any::Any;
+use core::{
+    any::Any,
+    ffi::c_void,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use alloc::sync::Arc;
-use alloc::vec;
-use axerrno::{AxResult, LinuxError, LinuxResult};
-use axio::{BufReader, PollState, prelude::*};
-use axsync::Mutex;
-use linux_raw_sys::general::S_IFCHR;
-
-use super::Kstat;
-
-fn console_read_bytes(buf: &mut [u8]) -> AxResult<usize> {
-    let mut kernel_buf = vec![0u8; buf.len()];
-    let len = axhal::console::read_bytes(&mut kernel_buf);
-    buf.copy_from_slice(&kernel_buf);
-    for c in &mut buf[..len] {
-        if *c == b'\r' {
-            *c = b'\n';
-        }
-    }
-    Ok(len)
-}
-
-fn console_write_bytes(buf: &[u8]) -> AxResult<usize> {
-    axhal::console::write_bytes(buf);
-    Ok(buf.len())
-}
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use linux_raw_sys::general::{O_RDONLY, O_WRONLY, S_IFCHR};
+use starry_core::file::{devnum, proc::fd::FdInfo};
 
-struct StdinRaw;
-struct StdoutRaw;
-
-impl Read for StdinRaw {
-    // Non-blocking read, returns number of bytes read.
-    fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        let mut read_len = 0;
-        while read_len < buf.len() {
-            let len = console_read_bytes(buf[read_len..].as_mut())?;
-            if len == 0 {
-                break;
-            }
-            read_len += len;
-        }
-        Ok(read_len)
-    }
-}
-
-impl Write for StdoutRaw {
-    fn write(&mut self, buf: &[u8]) -> AxResult<usize> {
-        console_write_bytes(buf)
-    }
-
-    fn flush(&mut self) -> AxResult {
-        Ok(())
-    }
-}
+use super::{Kstat, tty::console};
+use crate::ptr::UserPtr;
 
+/// The standard input, reading from the console through its line
+/// discipline.
 pub struct Stdin {
-    inner: &'static Mutex<BufReader<StdinRaw>>,
-}
-
-impl Stdin {
-    // Block until at least one byte is read.
-    fn read_blocked(&self, buf: &mut [u8]) -> AxResult<usize> {
-        let read_len = self.inner.lock().read(buf)?;
-        if buf.is_empty() || read_len > 0 {
-            return Ok(read_len);
-        }
-        // try again until we get something
-        loop {
-            let read_len = self.inner.lock().read(buf)?;
-            if read_len > 0 {
-                return Ok(read_len);
-            }
-            axtask::yield_now();
-        }
-    }
-}
-
-impl Read for Stdin {
-    fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
-        self.read_blocked(buf)
-    }
+    nonblocking: AtomicBool,
 }
 
-pub struct Stdout {
-    inner: &'static Mutex<StdoutRaw>,
-}
-
-impl Write for Stdout {
-    fn write(&mut self, buf: &[u8]) -> AxResult<usize> {
-        self.inner.lock().write(buf)
-    }
-
-    fn flush(&mut self) -> AxResult {
-        self.inner.lock().flush()
-    }
-}
+/// The standard output and error, writing to the console through its line
+/// discipline.
+pub struct Stdout;
 
 /// Constructs a new handle to the standard input of the current process.
 pub fn stdin() -> Stdin {
-    static INSTANCE: Mutex<BufReader<StdinRaw>> = Mutex::new(BufReader::new(StdinRaw));
-    Stdin { inner: &INSTANCE }
+    Stdin {
+        nonblocking: AtomicBool::new(false),
+    }
 }
 
 /// Constructs a new handle to the standard output of the current process.
 pub fn stdout() -> Stdout {
-    static INSTANCE: Mutex<StdoutRaw> = Mutex::new(StdoutRaw);
-    Stdout { inner: &INSTANCE }
+    Stdout
 }
 
 impl super::FileLike for Stdin {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        Ok(self.read_blocked(buf)?)
+        console().read(buf, self.nonblocking.load(Ordering::Acquire))
     }
 
     fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
         Err(LinuxError::EPERM)
     }
 
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::path("/dev/console", 0, O_RDONLY)
+    }
+
     fn stat(&self) -> LinuxResult<Kstat> {
         Ok(Kstat {
             mode: S_IFCHR | 0o444u32, // r--r--r--
+            rdev: devnum::makedev(devnum::CONSOLE.0, devnum::CONSOLE.1),
             ..Default::default()
         })
     }
@@ -130,14 +62,19 @@ impl super::FileLike for Stdin {
 
     fn poll(&self) -> LinuxResult<PollState> {
         Ok(PollState {
-            readable: true,
-            writable: true,
+            readable: console().poll().readable,
+            writable: false,
         })
     }
 
-    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
         Ok(())
     }
+
+    fn ioctl(&self, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        console().ioctl(cmd, arg)
+    }
 }
 
 impl super::FileLike for Stdout {
@@ -146,12 +83,17 @@ impl super::FileLike for Stdout {
     }
 
     fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
-        Ok(self.inner.lock().write(buf)?)
+        console().write(buf, false)
+    }
+
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::path("/dev/console", 0, O_WRONLY)
     }
 
     fn stat(&self) -> LinuxResult<Kstat> {
         Ok(Kstat {
             mode: S_IFCHR | 0o220u32, // -w--w----
+            rdev: devnum::makedev(devnum::CONSOLE.0, devnum::CONSOLE.1),
             ..Default::default()
         })
     }
@@ -162,7 +104,7 @@ impl super::FileLike for Stdout {
 
     fn poll(&self) -> LinuxResult<PollState> {
         Ok(PollState {
-            readable: true,
+            readable: false,
             writable: true,
         })
     }
@@ -170,4 +112,8 @@ impl super::FileLike for Stdout {
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn ioctl(&self, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        console().ioctl(cmd, arg)
+    }
 }
@@ -0,0 +1,83 @@
+//! The system console as a terminal.
+
+use core::time::Duration;
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use axhal::time::monotonic_time;
+use linux_raw_sys::general::winsize;
+use spin::Once;
+use starry_core::file::devnum;
+
+use super::{Tty, TtyDriver};
+
+struct ConsoleDriver;
+
+impl TtyDriver for ConsoleDriver {
+    fn write(&self, buf: &[u8]) {
+        axhal::console::write_bytes(buf);
+    }
+
+    fn poll_input(&self, buf: &mut [u8]) -> usize {
+        axhal::console::read_bytes(buf)
+    }
+}
+
+static CONSOLE: Once<Arc<Tty>> = Once::new();
+
+/// Returns the terminal of the system console.
+pub fn console() -> &'static Arc<Tty> {
+    CONSOLE.call_once(|| Arc::new(Tty::new(Box::new(ConsoleDriver), devnum::CONSOLE)))
+}
+
+/// How long to wait for the terminal to answer the size probe.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Parses a cursor position report `ESC [ <row> ; <col> R` in `reply`,
+/// returning the position and the range of the report.
+fn parse_cursor_report(reply: &[u8]) -> Option<(u16, u16, core::ops::Range<usize>)> {
+    let start = reply.windows(2).position(|w| w == b"\x1b[")?;
+    let end = start + reply[start..].iter().position(|&c| c == b'R')?;
+    let report = core::str::from_utf8(&reply[start + 2..end]).ok()?;
+    let (row, col) = report.split_once(';')?;
+    Some((row.parse().ok()?, col.parse().ok()?, start..end + 1))
+}
+
+/// Detects the window size of the terminal on the serial console.
+///
+/// The cursor is moved to the bottom right corner and its position is
+/// requested with a Device Status Report. Terminals that do not answer
+/// within [`PROBE_TIMEOUT`] leave the size unknown. Anything else received
+/// meanwhile is passed on as input.
+pub fn probe_console_size() {
+    // Save the cursor, move it as far as possible, report and restore it
+    axhal::console::write_bytes(b"\x1b7\x1b[999;999H\x1b[6n\x1b8");
+
+    let deadline = monotonic_time() + PROBE_TIMEOUT;
+    let mut reply = Vec::new();
+    while monotonic_time() < deadline {
+        let mut buf = [0u8; 32];
+        let len = axhal::console::read_bytes(&mut buf);
+        reply.extend_from_slice(&buf[..len]);
+        if parse_cursor_report(&reply).is_some() {
+            break;
+        }
+        if len == 0 {
+            axtask::yield_now();
+        }
+    }
+
+    match parse_cursor_report(&reply) {
+        Some((rows, cols, range)) => {
+            info!("console size: {}x{}", cols, rows);
+            console().set_winsize(winsize {
+                ws_row: rows,
+                ws_col: cols,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            });
+            reply.drain(range);
+        }
+        None => warn!("console did not report its size"),
+    }
+    console().receive(&reply);
+}
@@ -0,0 +1,205 @@
+//! Job control: controlling terminals of sessions and their foreground
+//! process groups.
+
+use core::sync::atomic::Ordering;
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+};
+use axerrno::{LinuxError, LinuxResult};
+use axprocess::{Pid, ProcessGroup};
+use axsignal::{SignalDisposition, SignalInfo, Signo};
+use axsync::Mutex;
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::{O_NOCTTY, SI_KERNEL};
+use starry_core::{
+    file::{devnum, proc::pid::TtyIf},
+    task::{SyscallRestart, get_process_group, processes},
+};
+
+use super::Tty;
+use crate::signal::{interrupted, send_signal_process, send_signal_process_group};
+
+/// The controlling terminals, by session ID.
+static CONTROLLING: Mutex<BTreeMap<Pid, Weak<Tty>>> = Mutex::new(BTreeMap::new());
+
+fn current_group() -> Arc<ProcessGroup> {
+    current().task_ext().thread.process().group()
+}
+
+/// Returns the controlling terminal of the calling process.
+pub fn controlling_tty() -> Option<Arc<Tty>> {
+    let sid = current_group().session().sid();
+    CONTROLLING.lock().get(&sid)?.upgrade()
+}
+
+struct TtyImpl;
+
+#[crate_interface::impl_interface]
+impl TtyIf for TtyImpl {
+    fn controlling_tty(sid: Pid) -> Option<(u64, Pid)> {
+        let tty = CONTROLLING.lock().get(&sid)?.upgrade()?;
+        let (major, minor) = tty.device;
+        let pgid = tty.foreground.load(Ordering::Acquire);
+        Some((devnum::makedev(major, minor), pgid))
+    }
+}
+
+/// Hangs up the controlling terminal of the session `sid`, after its leader
+/// exits.
+pub fn hangup_session(sid: Pid) {
+    let tty = CONTROLLING.lock().get(&sid).and_then(Weak::upgrade);
+    if let Some(tty) = tty {
+        tty.hangup();
+    }
+}
+
+/// Sends `signo` to every process of the session `sid`.
+fn signal_session(sid: Pid, signo: Signo) {
+    for process in processes() {
+        if process.group().session().sid() == sid {
+            let _ = send_signal_process(&process, SignalInfo::new(signo, SI_KERNEL as _));
+        }
+    }
+}
+
+impl Tty {
+    /// Whether the terminal is the controlling terminal of the calling
+    /// process.
+    pub(super) fn is_controlling(&self) -> bool {
+        let sid = self.session.load(Ordering::Acquire);
+        sid != 0 && sid == current_group().session().sid()
+    }
+
+    /// Makes the terminal the controlling terminal of the session `sid`,
+    /// with the process group `pgid` in the foreground.
+    pub fn attach(self: &Arc<Self>, sid: Pid, pgid: Pid) {
+        CONTROLLING.lock().insert(sid, Arc::downgrade(self));
+        self.session.store(sid, Ordering::Release);
+        self.set_foreground(pgid);
+    }
+
+    /// Detaches the terminal from the session it controls.
+    fn detach(&self) {
+        let sid = self.session.swap(0, Ordering::AcqRel);
+        if sid != 0 {
+            CONTROLLING.lock().remove(&sid);
+        }
+        self.set_foreground(0);
+    }
+
+    /// Gives the terminal to the calling process as it opens it, if the
+    /// process leads a session without a controlling terminal and `flags`
+    /// lacks `O_NOCTTY`.
+    pub(super) fn acquire_on_open(self: &Arc<Self>, flags: u32) {
+        if flags & O_NOCTTY != 0 || self.session.load(Ordering::Acquire) != 0 {
+            return;
+        }
+        let curr = current();
+        let process = curr.task_ext().thread.process();
+        let group = process.group();
+        let sid = group.session().sid();
+        if process.pid() == sid && !CONTROLLING.lock().contains_key(&sid) {
+            self.attach(sid, group.pgid());
+        }
+    }
+
+    /// Acquires the terminal for the session led by the calling process,
+    /// for TIOCSCTTY. With `steal`, a terminal controlling another session
+    /// is taken away from it.
+    pub(super) fn acquire(self: &Arc<Self>, steal: bool) -> LinuxResult {
+        let curr = current();
+        let process = curr.task_ext().thread.process();
+        let group = process.group();
+        let sid = group.session().sid();
+        if process.pid() != sid {
+            return Err(LinuxError::EPERM);
+        }
+        let owner = self.session.load(Ordering::Acquire);
+        if owner == sid {
+            return Ok(());
+        }
+        if CONTROLLING.lock().contains_key(&sid) {
+            return Err(LinuxError::EPERM);
+        }
+        if owner != 0 {
+            // Every process is privileged, so only the argument matters
+            if !steal {
+                return Err(LinuxError::EPERM);
+            }
+            self.detach();
+        }
+        self.attach(sid, group.pgid());
+        Ok(())
+    }
+
+    /// Gives up the terminal for TIOCNOTTY. When the session leader does
+    /// so, the foreground process group is hung up.
+    pub(super) fn release(&self) -> LinuxResult {
+        if !self.is_controlling() {
+            return Err(LinuxError::ENOTTY);
+        }
+        let pid = current().task_ext().thread.process().pid();
+        if pid == self.session.load(Ordering::Acquire) {
+            let foreground = self.foreground.load(Ordering::Acquire);
+            self.detach();
+            if let Ok(group) = get_process_group(foreground) {
+                send_signal_process_group(&group, SignalInfo::new(Signo::SIGHUP, SI_KERNEL as _));
+                send_signal_process_group(&group, SignalInfo::new(Signo::SIGCONT, SI_KERNEL as _));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hangs up the terminal: the session it controls loses it and all its
+    /// processes receive `SIGHUP` followed by `SIGCONT`.
+    pub fn hangup(&self) {
+        let sid = self.session.load(Ordering::Acquire);
+        if sid == 0 {
+            return;
+        }
+        self.detach();
+        signal_session(sid, Signo::SIGHUP);
+        signal_session(sid, Signo::SIGCONT);
+    }
+
+    /// Applies job control to an access by the calling process.
+    ///
+    /// If the caller's process group is in the background of the session
+    /// this terminal controls, the group is sent `signo` and the access is
+    /// interrupted, to be restarted after the signal is handled.
+    ///
+    /// If the caller ignores or blocks `signo`, no signal is sent. A read
+    /// (`SIGTTIN`) then fails with `EIO`, and a write (`SIGTTOU`) proceeds.
+    pub(super) fn job_control(&self, signo: Signo) -> LinuxResult {
+        if !self.is_controlling() {
+            return Ok(());
+        }
+        let group = current_group();
+        let foreground = self.foreground.load(Ordering::Acquire);
+        if foreground == 0 || foreground == group.pgid() {
+            return Ok(());
+        }
+
+        let curr = current();
+        let ignored = matches!(
+            curr.task_ext().process_data().signal.actions.lock()[signo].disposition,
+            SignalDisposition::Ignore
+        );
+        let blocked = curr
+            .task_ext()
+            .thread_data()
+            .signal
+            .with_blocked_mut(|blocked| blocked.has(signo));
+        if ignored || blocked {
+            return if signo == Signo::SIGTTIN {
+                Err(LinuxError::EIO)
+            } else {
+                Ok(())
+            };
+        }
+        send_signal_process_group(&group, SignalInfo::new(signo, SI_KERNEL as _));
+        Err(interrupted(SyscallRestart::Sys))
+    }
+}
@@ -0,0 +1,337 @@
+//! The line discipline, which edits terminal input and translates output as
+//! configured by termios.
+
+use alloc::{collections::vec_deque::VecDeque, vec::Vec};
+
+use axsignal::Signo;
+use linux_raw_sys::general::{
+    B38400, CREAD, CS8, ECHO, ECHOCTL, ECHOE, ECHOK, ECHOKE, ECHONL, HUPCL, ICANON, ICRNL, IEXTEN,
+    IGNCR, INLCR, ISIG, ISTRIP, IXON, NOFLSH, OCRNL, OLCUC, ONLCR, OPOST, VEOF, VEOL, VEOL2,
+    VERASE, VINTR, VKILL, VLNEXT, VMIN, VQUIT, VREPRINT, VSUSP, VTIME, VWERASE, termios,
+};
+
+/// The size of the input buffer, like Linux's `N_TTY_BUF_SIZE`.
+const INPUT_BUF_SIZE: usize = 4096;
+
+/// Returns the termios of a newly created terminal, like Linux's
+/// `tty_std_termios`.
+pub fn default_termios() -> termios {
+    // SAFETY: valid for termios
+    let mut termios: termios = unsafe { core::mem::zeroed() };
+    termios.c_iflag = ICRNL | IXON;
+    termios.c_oflag = OPOST | ONLCR;
+    termios.c_cflag = B38400 | CS8 | CREAD | HUPCL;
+    termios.c_lflag = ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL | ECHOKE | IEXTEN;
+    for (index, c) in [
+        (VINTR, 0x03),    // ^C
+        (VQUIT, 0x1c),    // ^\
+        (VERASE, 0x7f),   // DEL
+        (VKILL, 0x15),    // ^U
+        (VEOF, 0x04),     // ^D
+        (VMIN, 1),        // one byte per read
+        (VSUSP, 0x1a),    // ^Z
+        (VREPRINT, 0x12), // ^R
+        (VWERASE, 0x17),  // ^W
+        (VLNEXT, 0x16),   // ^V
+    ] {
+        termios.c_cc[index as usize] = c;
+    }
+    termios
+}
+
+/// Whether `c` is echoed as `^X` with `ECHOCTL`.
+fn is_control(c: u8) -> bool {
+    (c < 0x20 && c != b'\t' && c != b'\n') || c == 0x7f
+}
+
+/// The input and output processing of a terminal.
+///
+/// Input is passed in with [`receive`](Self::receive) and becomes readable
+/// immediately in non-canonical mode, or when a line is completed in
+/// canonical mode. Each completed line is kept as its own chunk, so that a
+/// read never returns more than one line; an empty chunk is an end of file
+/// entered with `VEOF`.
+pub struct LineDiscipline {
+    termios: termios,
+    /// The line being edited in canonical mode.
+    line: Vec<u8>,
+    /// Input ready to be read.
+    ready: VecDeque<Vec<u8>>,
+    /// Whether the next character is taken literally after `VLNEXT`.
+    literal_next: bool,
+}
+
+impl LineDiscipline {
+    /// Creates a line discipline with the default termios.
+    pub fn new() -> Self {
+        Self {
+            termios: default_termios(),
+            line: Vec::new(),
+            ready: VecDeque::new(),
+            literal_next: false,
+        }
+    }
+
+    /// Returns the current termios.
+    pub fn termios(&self) -> termios {
+        self.termios
+    }
+
+    /// Replaces the termios.
+    ///
+    /// Leaving canonical mode makes the line being edited readable, like
+    /// Linux.
+    pub fn set_termios(&mut self, termios: termios) {
+        let was_canonical = self.canonical();
+        self.termios = termios;
+        if was_canonical && !self.canonical() && !self.line.is_empty() {
+            let line = core::mem::take(&mut self.line);
+            self.ready.push_back(line);
+        }
+    }
+
+    /// Whether the terminal is in canonical mode.
+    pub fn canonical(&self) -> bool {
+        self.termios.c_lflag & ICANON != 0
+    }
+
+    /// Returns `VMIN` and `VTIME`, which control non-canonical reads.
+    pub fn min_time(&self) -> (usize, usize) {
+        (
+            self.termios.c_cc[VMIN as usize] as usize,
+            self.termios.c_cc[VTIME as usize] as usize,
+        )
+    }
+
+    /// Discards all input that has not been read.
+    pub fn flush_input(&mut self) {
+        self.line.clear();
+        self.ready.clear();
+    }
+
+    /// Returns the number of bytes ready to be read.
+    pub fn available(&self) -> usize {
+        self.ready.iter().map(Vec::len).sum()
+    }
+
+    /// Whether a read would return without waiting.
+    pub fn readable(&self) -> bool {
+        if self.canonical() {
+            !self.ready.is_empty()
+        } else {
+            self.available() > 0
+        }
+    }
+
+    /// Reads ready input into `buf`, stopping at the end of a line in
+    /// canonical mode.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut read = 0;
+        while let Some(chunk) = self.ready.front_mut() {
+            let len = chunk.len().min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&chunk[..len]);
+            chunk.drain(..len);
+            read += len;
+            if chunk.is_empty() {
+                self.ready.pop_front();
+                if self.canonical() {
+                    break;
+                }
+            }
+            if read == buf.len() {
+                break;
+            }
+        }
+        read
+    }
+
+    fn is_char(&self, c: u8, index: u32) -> bool {
+        // A control character of 0 is disabled (`_POSIX_VDISABLE`)
+        let cc = self.termios.c_cc[index as usize];
+        cc != 0 && c == cc
+    }
+
+    fn lflag(&self, flag: u32) -> bool {
+        self.termios.c_lflag & flag != 0
+    }
+
+    fn echo_char(&self, c: u8, echo: &mut Vec<u8>) {
+        if !self.lflag(ECHO) {
+            return;
+        }
+        if self.lflag(ECHOCTL) && is_control(c) {
+            echo.extend_from_slice(&[b'^', c ^ 0x40]);
+        } else {
+            echo.push(c);
+        }
+    }
+
+    /// Visually erases the last character of the line, which has been
+    /// removed already.
+    fn echo_erase(&self, c: u8, echo: &mut Vec<u8>) {
+        if !self.lflag(ECHO) {
+            return;
+        }
+        if !self.lflag(ECHOE) {
+            self.echo_char(self.termios.c_cc[VERASE as usize], echo);
+            return;
+        }
+        let width = if self.lflag(ECHOCTL) && is_control(c) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..width {
+            echo.extend_from_slice(b"\x08 \x08");
+        }
+    }
+
+    fn buffer_full(&self) -> bool {
+        self.line.len() + self.available() >= INPUT_BUF_SIZE - 1
+    }
+
+    fn commit_line(&mut self) {
+        let line = core::mem::take(&mut self.line);
+        self.ready.push_back(line);
+    }
+
+    /// Processes one input character.
+    ///
+    /// The bytes to echo are appended to `echo` before output processing.
+    /// Returns the signal to send to the foreground process group if `c` is
+    /// a signal character.
+    pub fn receive(&mut self, mut c: u8, echo: &mut Vec<u8>) -> Option<Signo> {
+        let iflag = self.termios.c_iflag;
+        if iflag & ISTRIP != 0 {
+            c &= 0x7f;
+        }
+
+        if core::mem::take(&mut self.literal_next) {
+            if self.lflag(ECHO) && self.lflag(ECHOCTL) {
+                // Replace the `^` echoed for VLNEXT
+                echo.push(0x08);
+            }
+            self.echo_char(c, echo);
+            if !self.buffer_full() {
+                self.line.push(c);
+            }
+            return None;
+        }
+
+        if c == b'\r' {
+            if iflag & IGNCR != 0 {
+                return None;
+            }
+            if iflag & ICRNL != 0 {
+                c = b'\n';
+            }
+        } else if c == b'\n' && iflag & INLCR != 0 {
+            c = b'\r';
+        }
+
+        if self.lflag(ISIG) {
+            for (index, signo) in [
+                (VINTR, Signo::SIGINT),
+                (VQUIT, Signo::SIGQUIT),
+                (VSUSP, Signo::SIGTSTP),
+            ] {
+                if self.is_char(c, index) {
+                    if !self.lflag(NOFLSH) {
+                        self.flush_input();
+                    }
+                    self.echo_char(c, echo);
+                    return Some(signo);
+                }
+            }
+        }
+
+        if !self.canonical() {
+            if self.buffer_full() {
+                return None;
+            }
+            self.echo_char(c, echo);
+            match self.ready.back_mut() {
+                Some(chunk) => chunk.push(c),
+                None => self.ready.push_back(alloc::vec![c]),
+            }
+            return None;
+        }
+
+        let extended = self.lflag(IEXTEN);
+        if extended && self.is_char(c, VLNEXT) {
+            self.literal_next = true;
+            if self.lflag(ECHO) && self.lflag(ECHOCTL) {
+                echo.push(b'^');
+            }
+        } else if self.is_char(c, VERASE) {
+            if let Some(erased) = self.line.pop() {
+                self.echo_erase(erased, echo);
+            }
+        } else if extended && self.is_char(c, VWERASE) {
+            while let Some(&last) = self.line.last()
+                && last.is_ascii_whitespace()
+            {
+                self.line.pop();
+                self.echo_erase(last, echo);
+            }
+            while let Some(&last) = self.line.last()
+                && !last.is_ascii_whitespace()
+            {
+                self.line.pop();
+                self.echo_erase(last, echo);
+            }
+        } else if self.is_char(c, VKILL) {
+            if self.lflag(ECHOKE) && self.lflag(ECHOE) {
+                while let Some(erased) = self.line.pop() {
+                    self.echo_erase(erased, echo);
+                }
+            } else {
+                self.line.clear();
+                self.echo_char(c, echo);
+                if self.lflag(ECHOK) {
+                    echo.push(b'\n');
+                }
+            }
+        } else if extended && self.is_char(c, VREPRINT) {
+            if self.lflag(ECHO) {
+                self.echo_char(c, echo);
+                echo.push(b'\n');
+                echo.extend_from_slice(&self.line);
+            }
+        } else if self.is_char(c, VEOF) {
+            // The line is passed on without the character; an empty line
+            // reads as end of file
+            self.commit_line();
+        } else if c == b'\n' || self.is_char(c, VEOL) || (extended && self.is_char(c, VEOL2)) {
+            if c == b'\n' && self.lflag(ECHONL) && !self.lflag(ECHO) {
+                echo.push(b'\n');
+            } else {
+                self.echo_char(c, echo);
+            }
+            self.line.push(c);
+            self.commit_line();
+        } else if !self.buffer_full() {
+            self.echo_char(c, echo);
+            self.line.push(c);
+        }
+        None
+    }
+
+    /// Applies output processing to `buf`.
+    pub fn process_output(&self, buf: &[u8]) -> Vec<u8> {
+        let oflag = self.termios.c_oflag;
+        if oflag & OPOST == 0 {
+            return buf.to_vec();
+        }
+        let mut out = Vec::with_capacity(buf.len());
+        for &c in buf {
+            match c {
+                b'\n' if oflag & ONLCR != 0 => out.extend_from_slice(b"\r\n"),
+                b'\r' if oflag & OCRNL != 0 => out.push(b'\n'),
+                c if oflag & OLCUC != 0 => out.push(c.to_ascii_uppercase()),
+                c => out.push(c),
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,522 @@
+//! Terminals: a line discipline in front of a device driver.
+
+mod console;
+mod job;
+mod ldisc;
+mod pty;
+
+use core::{
+    any::Any,
+    ffi::{c_int, c_void},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::monotonic_time;
+use axio::PollState;
+use axprocess::Pid;
+use axsignal::{SignalInfo, Signo};
+use axsync::Mutex;
+use axtask::WaitQueue;
+use linux_raw_sys::{
+    general::{O_NONBLOCK, S_IFCHR, SI_KERNEL, TOSTOP, termios, winsize},
+    ioctl::{
+        FIONREAD, TCFLSH, TCGETS, TCSBRK, TCSETS, TCSETSF, TCSETSW, TCXONC, TIOCGPGRP, TIOCGSID,
+        TIOCGWINSZ, TIOCNOTTY, TIOCSCTTY, TIOCSPGRP, TIOCSWINSZ,
+    },
+};
+use starry_core::{
+    file::{
+        chardev::{TtyKind, TtyNode},
+        devnum,
+        mount::lookup_node,
+        proc::fd::FdInfo,
+    },
+    task::get_process_group,
+};
+
+use self::ldisc::LineDiscipline;
+pub use self::{
+    console::{console, probe_console_size},
+    job::{controlling_tty, hangup_session},
+};
+use super::{FileLike, Kstat};
+use crate::{
+    ptr::{UserConstPtr, UserPtr},
+    signal::{
+        check_interrupted, have_signals, send_signal_process_group,
+        wait_timeout_until_interruptible,
+    },
+};
+
+// `queue_selector` values of TCFLSH
+const TCIFLUSH: usize = 0;
+const TCOFLUSH: usize = 1;
+const TCIOFLUSH: usize = 2;
+
+/// The device behind a terminal.
+pub trait TtyDriver: Send + Sync {
+    /// Sends processed output to the device.
+    fn write(&self, buf: &[u8]);
+
+    /// Returns how many bytes of output the device takes without waiting.
+    fn write_room(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Reads input from the device into `buf` without waiting.
+    ///
+    /// Devices that pass their input with [`Tty::receive`] return 0.
+    fn poll_input(&self, _buf: &mut [u8]) -> usize {
+        0
+    }
+
+    /// Whether the device has gone away, so that reads return end of file
+    /// and writes fail.
+    fn hung_up(&self) -> bool {
+        false
+    }
+}
+
+/// Something terminal readers or writers wait for, such as input.
+///
+/// Occurrences are counted, so that a waiter which reads the count before
+/// checking what it waits for doesn't miss one that happens in between.
+struct TtyEvent {
+    wq: WaitQueue,
+    count: AtomicUsize,
+}
+
+impl Default for TtyEvent {
+    fn default() -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            count: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl TtyEvent {
+    fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    fn notify(&self) {
+        self.count.fetch_add(1, Ordering::AcqRel);
+        self.wq.notify_all(false);
+    }
+
+    /// Waits until the event occurs after the count was `seen`, a signal is
+    /// sent to the current thread or `timeout` elapses.
+    fn wait(&self, seen: usize, timeout: Duration) {
+        wait_timeout_until_interruptible(&self.wq, timeout, || self.count() != seen);
+    }
+}
+
+/// A terminal, shared by all files that have it open.
+pub struct Tty {
+    driver: Box<dyn TtyDriver>,
+    /// The device number, as in the `tty_nr` of `/proc/<pid>/stat`.
+    device: (u32, u32),
+    ldisc: Mutex<LineDiscipline>,
+    /// Input becoming readable, or the device going away.
+    input_event: TtyEvent,
+    /// The device taking output, or going away.
+    output_event: TtyEvent,
+    /// The foreground process group, or 0 if there is none.
+    foreground: AtomicU32,
+    /// The session the terminal controls, or 0 if there is none.
+    session: AtomicU32,
+    winsize: Mutex<winsize>,
+}
+
+impl Tty {
+    /// Creates the terminal `device` with the default termios in front of
+    /// `driver`.
+    pub fn new(driver: Box<dyn TtyDriver>, device: (u32, u32)) -> Self {
+        Self {
+            driver,
+            device,
+            ldisc: Mutex::new(LineDiscipline::new()),
+            input_event: TtyEvent::default(),
+            output_event: TtyEvent::default(),
+            foreground: AtomicU32::new(0),
+            session: AtomicU32::new(0),
+            // Unknown, like Linux
+            winsize: Mutex::new(winsize {
+                ws_row: 0,
+                ws_col: 0,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            }),
+        }
+    }
+
+    /// Passes input from the device through the line discipline.
+    pub fn receive(&self, input: &[u8]) {
+        let mut echo = Vec::new();
+        let mut signals = Vec::new();
+        let echo = {
+            let mut ldisc = self.ldisc.lock();
+            for &c in input {
+                signals.extend(ldisc.receive(c, &mut echo));
+            }
+            ldisc.process_output(&echo)
+        };
+        if !echo.is_empty() {
+            self.driver.write(&echo);
+        }
+        self.input_event.notify();
+        for signo in signals {
+            self.signal_foreground(signo);
+        }
+    }
+
+    /// Wakes writers waiting for the device, which has taken output.
+    pub(super) fn output_taken(&self) {
+        self.output_event.notify();
+    }
+
+    /// Wakes readers and writers to notice that the device went away.
+    pub(super) fn device_gone(&self) {
+        self.input_event.notify();
+        self.output_event.notify();
+    }
+
+    fn pull_input(&self) {
+        let mut buf = [0u8; 64];
+        loop {
+            let len = self.driver.poll_input(&mut buf);
+            if len == 0 {
+                break;
+            }
+            self.receive(&buf[..len]);
+        }
+    }
+
+    fn signal_foreground(&self, signo: Signo) {
+        let pgid = self.foreground.load(Ordering::Acquire);
+        if pgid == 0 {
+            return;
+        }
+        if let Ok(pg) = get_process_group(pgid as Pid) {
+            send_signal_process_group(&pg, SignalInfo::new(signo, SI_KERNEL as _));
+        }
+    }
+
+    /// Sets the window size, sending `SIGWINCH` to the foreground process
+    /// group if it changed.
+    pub fn set_winsize(&self, new: winsize) {
+        let changed = {
+            let mut winsize = self.winsize.lock();
+            let old = core::mem::replace(&mut *winsize, new);
+            (old.ws_row, old.ws_col, old.ws_xpixel, old.ws_ypixel)
+                != (new.ws_row, new.ws_col, new.ws_xpixel, new.ws_ypixel)
+        };
+        if changed {
+            self.signal_foreground(Signo::SIGWINCH);
+        }
+    }
+
+    /// Sets the foreground process group, which receives the signals of
+    /// the signal characters.
+    pub fn set_foreground(&self, pgid: Pid) {
+        self.foreground.store(pgid, Ordering::Release);
+    }
+
+    /// Reads input, waiting as specified by the termios unless
+    /// `nonblocking`.
+    pub fn read(&self, buf: &mut [u8], nonblocking: bool) -> LinuxResult<usize> {
+        self.job_control(Signo::SIGTTIN)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let start = monotonic_time();
+        let mut deadline = None;
+        let mut read = 0;
+        loop {
+            let seen = self.input_event.count();
+            self.pull_input();
+            {
+                let mut ldisc = self.ldisc.lock();
+                if ldisc.canonical() {
+                    if ldisc.readable() {
+                        return Ok(ldisc.read(buf));
+                    }
+                } else {
+                    let (min, time) = ldisc.min_time();
+                    let timeout = Duration::from_millis(time as u64 * 100);
+                    let len = ldisc.read(&mut buf[read..]);
+                    read += len;
+                    // With VMIN = 0 the timer runs from the start of the
+                    // read, otherwise it restarts after every byte
+                    if time > 0 {
+                        if min == 0 {
+                            deadline.get_or_insert(start + timeout);
+                        } else if len > 0 {
+                            deadline = Some(monotonic_time() + timeout);
+                        }
+                    }
+                    let min = min.min(buf.len());
+                    if (min == 0 && (time == 0 || read > 0)) || (min > 0 && read >= min) {
+                        return Ok(read);
+                    }
+                    if deadline.is_some_and(|deadline| monotonic_time() >= deadline) {
+                        return Ok(read);
+                    }
+                }
+            }
+            if self.driver.hung_up() {
+                return Ok(read);
+            }
+            if nonblocking {
+                return if read > 0 {
+                    Ok(read)
+                } else {
+                    Err(LinuxError::EAGAIN)
+                };
+            }
+            if read > 0 && have_signals() {
+                return Ok(read);
+            }
+            check_interrupted()?;
+            // Devices polled for input are checked again at least every
+            // `SIGNAL_CHECK_INTERVAL`
+            let timeout = deadline.map_or(Duration::MAX, |deadline| {
+                deadline.saturating_sub(monotonic_time())
+            });
+            self.input_event.wait(seen, timeout);
+        }
+    }
+
+    /// Returns how many bytes of `write` input fit into the device, given
+    /// that output processing at most doubles the length.
+    fn write_chunk(&self) -> usize {
+        self.driver.write_room() / 2
+    }
+
+    /// Writes output through the line discipline, waiting for the device to
+    /// take it unless `nonblocking`.
+    ///
+    /// Fails with `EIO` once the device has gone away.
+    pub fn write(&self, buf: &[u8], nonblocking: bool) -> LinuxResult<usize> {
+        if self.driver.hung_up() {
+            return Err(LinuxError::EIO);
+        }
+        if self.ldisc.lock().termios().c_lflag & TOSTOP != 0 {
+            self.job_control(Signo::SIGTTOU)?;
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            let seen = self.output_event.count();
+            if self.driver.hung_up() {
+                return Ok(written);
+            }
+            let len = self.write_chunk().min(buf.len() - written);
+            if len > 0 {
+                let out = self
+                    .ldisc
+                    .lock()
+                    .process_output(&buf[written..written + len]);
+                self.driver.write(&out);
+                written += len;
+                continue;
+            }
+            if nonblocking {
+                return if written > 0 {
+                    Ok(written)
+                } else {
+                    Err(LinuxError::EAGAIN)
+                };
+            }
+            if written > 0 && have_signals() {
+                return Ok(written);
+            }
+            check_interrupted()?;
+            self.output_event.wait(seen, Duration::MAX);
+        }
+        Ok(written)
+    }
+
+    /// Returns the poll state of files that have the terminal open.
+    pub fn poll(&self) -> PollState {
+        self.pull_input();
+        let hung_up = self.driver.hung_up();
+        PollState {
+            readable: self.ldisc.lock().readable() || hung_up,
+            writable: self.write_chunk() > 0 || hung_up,
+        }
+    }
+
+    /// Handles the terminal ioctls.
+    pub fn ioctl(self: &Arc<Self>, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        let arg = arg.address().as_usize();
+        // Changing the terminal from a background process group is subject
+        // to job control, like writing with TOSTOP
+        if matches!(cmd, TCSETS | TCSETSW | TCSETSF | TCFLSH | TIOCSPGRP) {
+            self.job_control(Signo::SIGTTOU)?;
+        }
+        match cmd {
+            TCGETS => {
+                *UserPtr::<termios>::from(arg).get_as_mut()? = self.ldisc.lock().termios();
+            }
+            TCSETS | TCSETSW | TCSETSF => {
+                // Output is written synchronously, so there is nothing to
+                // drain for TCSETSW
+                let termios = *UserConstPtr::<termios>::from(arg).get_as_ref()?;
+                let mut ldisc = self.ldisc.lock();
+                if cmd == TCSETSF {
+                    ldisc.flush_input();
+                }
+                // Leaving canonical mode may make input readable
+                ldisc.set_termios(termios);
+                drop(ldisc);
+                self.input_event.notify();
+            }
+            TIOCGPGRP => {
+                if !self.is_controlling() {
+                    return Err(LinuxError::ENOTTY);
+                }
+                *UserPtr::<c_int>::from(arg).get_as_mut()? =
+                    self.foreground.load(Ordering::Acquire) as c_int;
+            }
+            TIOCSPGRP => {
+                if !self.is_controlling() {
+                    return Err(LinuxError::ENOTTY);
+                }
+                let pgid = *UserConstPtr::<c_int>::from(arg).get_as_ref()?;
+                if pgid < 0 {
+                    return Err(LinuxError::EINVAL);
+                }
+                let group = get_process_group(pgid as Pid).map_err(|_| LinuxError::ESRCH)?;
+                if group.session().sid() != self.session.load(Ordering::Acquire) {
+                    return Err(LinuxError::EPERM);
+                }
+                self.set_foreground(pgid as Pid);
+            }
+            TIOCGSID => {
+                if !self.is_controlling() {
+                    return Err(LinuxError::ENOTTY);
+                }
+                *UserPtr::<c_int>::from(arg).get_as_mut()? =
+                    self.session.load(Ordering::Acquire) as c_int;
+            }
+            TIOCSCTTY => self.acquire(arg == 1)?,
+            TIOCNOTTY => self.release()?,
+            TIOCGWINSZ => {
+                *UserPtr::<winsize>::from(arg).get_as_mut()? = *self.winsize.lock();
+            }
+            TIOCSWINSZ => {
+                self.set_winsize(*UserConstPtr::<winsize>::from(arg).get_as_ref()?);
+            }
+            FIONREAD => {
+                self.pull_input();
+                *UserPtr::<c_int>::from(arg).get_as_mut()? = self.ldisc.lock().available() as c_int;
+            }
+            TCFLSH => match arg {
+                TCIFLUSH | TCIOFLUSH => self.ldisc.lock().flush_input(),
+                // Output is never queued
+                TCOFLUSH => {}
+                _ => return Err(LinuxError::EINVAL),
+            },
+            // Output is never queued or suspended
+            TCSBRK | TCXONC => {}
+            _ => return Err(LinuxError::ENOTTY),
+        }
+        Ok(0)
+    }
+}
+
+/// Opens the terminal at `path`, or returns `None` if `path` is not a
+/// terminal node.
+///
+/// The terminal is chosen by the node, so that e.g. a devpts instance
+/// mounted outside `/dev/pts` works as well.
+pub fn open_tty(path: &str, flags: u32) -> LinuxResult<Option<Arc<dyn FileLike>>> {
+    let Some(node) = lookup_node(path) else {
+        return Ok(None);
+    };
+    let Some(node) = node.as_any().downcast_ref::<TtyNode>() else {
+        return Ok(None);
+    };
+    Ok(Some(match node.kind {
+        // Neither makes the console the controlling terminal, like Linux
+        TtyKind::Console => Arc::new(TtyFile::new(
+            console().clone(),
+            devnum::CONSOLE,
+            path,
+            flags,
+        )),
+        TtyKind::Controlling => {
+            let tty = controlling_tty().ok_or(LinuxError::ENXIO)?;
+            Arc::new(TtyFile::new(tty, devnum::TTY, path, flags))
+        }
+        TtyKind::PtyMaster => pty::open_master(flags)?,
+        TtyKind::PtySlave(index) => pty::open_slave(index, flags)?,
+    }))
+}
+
+/// An open file of a terminal through `/dev/console` or `/dev/tty`.
+struct TtyFile {
+    tty: Arc<Tty>,
+    /// The device number of the node the file was opened through.
+    rdev: (u32, u32),
+    path: String,
+    flags: u32,
+    nonblocking: AtomicBool,
+}
+
+impl TtyFile {
+    fn new(tty: Arc<Tty>, rdev: (u32, u32), path: &str, flags: u32) -> Self {
+        Self {
+            tty,
+            rdev,
+            path: path.into(),
+            flags,
+            nonblocking: AtomicBool::new(flags & O_NONBLOCK != 0),
+        }
+    }
+}
+
+impl FileLike for TtyFile {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        self.tty.read(buf, self.nonblocking.load(Ordering::Acquire))
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        self.tty
+            .write(buf, self.nonblocking.load(Ordering::Acquire))
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        let (major, minor) = self.rdev;
+        Ok(Kstat {
+            mode: S_IFCHR | 0o620u32, // rw--w----
+            rdev: devnum::makedev(major, minor),
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(self.tty.poll())
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::path(&self.path, 0, self.flags)
+    }
+
+    fn ioctl(&self, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        self.tty.ioctl(cmd, arg)
+    }
+}
@@ -0,0 +1,316 @@
+//! Pseudo-terminals, created by opening `/dev/ptmx`.
+//!
+//! Input written to the master goes through the line discipline of the slave,
+//! and output written to the slave, including the echo, is read from the
+//! master.
+
+use core::{
+    any::Any,
+    ffi::{c_int, c_void},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    format,
+    sync::{Arc, Weak},
+};
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axsync::Mutex;
+use linux_raw_sys::{
+    general::{O_CLOEXEC, O_NONBLOCK, S_IFCHR},
+    ioctl::{TIOCGPGRP, TIOCGPTLCK, TIOCGPTN, TIOCGPTPEER, TIOCSPTLCK},
+};
+use starry_core::file::{
+    devnum,
+    devpts::{alloc_pty_index, free_pty_index},
+    proc::fd::FdInfo,
+};
+
+use super::{Tty, TtyDriver, TtyEvent};
+use crate::{
+    file::{FileLike, Kstat, add_file_like_cloexec},
+    ptr::{UserConstPtr, UserPtr},
+    signal::check_interrupted,
+};
+
+/// The number of output bytes a pseudo-terminal buffers before writes to
+/// the slave wait for the master to read.
+const PTY_OUTPUT_MAX: usize = 8192;
+
+/// The output of the slave, waiting to be read from the master.
+#[derive(Default)]
+struct PtyLink {
+    output: Mutex<VecDeque<u8>>,
+    master_closed: AtomicBool,
+    /// Output becoming readable, or the slave hanging up.
+    event: TtyEvent,
+}
+
+struct PtyDriver(Arc<PtyLink>);
+
+impl TtyDriver for PtyDriver {
+    // Echo is written without checking the room, so the buffer may briefly
+    // exceed the limit
+    fn write(&self, buf: &[u8]) {
+        self.0.output.lock().extend(buf);
+        self.0.event.notify();
+    }
+
+    fn write_room(&self) -> usize {
+        PTY_OUTPUT_MAX.saturating_sub(self.0.output.lock().len())
+    }
+
+    fn hung_up(&self) -> bool {
+        self.0.master_closed.load(Ordering::Acquire)
+    }
+}
+
+/// A pseudo-terminal pair.
+struct Pty {
+    index: u32,
+    tty: Arc<Tty>,
+    link: Arc<PtyLink>,
+    /// Whether opening the slave is refused, until unlocked with TIOCSPTLCK.
+    locked: AtomicBool,
+    /// The number of open files of the slave.
+    slaves: AtomicUsize,
+    /// Whether the slave has ever been opened.
+    slave_opened: AtomicBool,
+}
+
+impl Pty {
+    /// Whether the slave has been opened and closed again, which hangs up
+    /// the master.
+    fn slave_hung_up(&self) -> bool {
+        self.slave_opened.load(Ordering::Acquire) && self.slaves.load(Ordering::Acquire) == 0
+    }
+}
+
+/// The pseudo-terminals whose master is open, by index.
+static PTYS: Mutex<BTreeMap<u32, Weak<Pty>>> = Mutex::new(BTreeMap::new());
+
+/// An open file of the master side of a pseudo-terminal.
+pub struct PtyMaster {
+    pty: Arc<Pty>,
+    flags: u32,
+    nonblocking: AtomicBool,
+}
+
+/// Allocates a pseudo-terminal and opens its master.
+pub fn open_master(flags: u32) -> LinuxResult<Arc<dyn FileLike>> {
+    let index = alloc_pty_index()?;
+    let link = Arc::new(PtyLink::default());
+    let pty = Arc::new(Pty {
+        index,
+        tty: Arc::new(Tty::new(
+            Box::new(PtyDriver(link.clone())),
+            (devnum::PTS_MAJOR, index),
+        )),
+        link,
+        // Like Linux, the slave must be unlocked first (see unlockpt(3))
+        locked: AtomicBool::new(true),
+        slaves: AtomicUsize::new(0),
+        slave_opened: AtomicBool::new(false),
+    });
+    PTYS.lock().insert(index, Arc::downgrade(&pty));
+    Ok(Arc::new(PtyMaster {
+        pty,
+        flags,
+        nonblocking: AtomicBool::new(flags & O_NONBLOCK != 0),
+    }))
+}
+
+impl Drop for PtyMaster {
+    fn drop(&mut self) {
+        self.pty.link.master_closed.store(true, Ordering::Release);
+        self.pty.tty.device_gone();
+        self.pty.tty.hangup();
+        PTYS.lock().remove(&self.pty.index);
+        free_pty_index(self.pty.index);
+    }
+}
+
+impl FileLike for PtyMaster {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let seen = self.pty.link.event.count();
+            let len = {
+                let mut output = self.pty.link.output.lock();
+                let len = buf.len().min(output.len());
+                for (dst, src) in buf.iter_mut().zip(output.drain(..len)) {
+                    *dst = src;
+                }
+                len
+            };
+            if len > 0 {
+                self.pty.tty.output_taken();
+                return Ok(len);
+            }
+            if self.pty.slave_hung_up() {
+                return Err(LinuxError::EIO);
+            }
+            if self.nonblocking.load(Ordering::Acquire) {
+                return Err(LinuxError::EAGAIN);
+            }
+            check_interrupted()?;
+            self.pty.link.event.wait(seen, Duration::MAX);
+        }
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        self.pty.tty.receive(buf);
+        Ok(buf.len())
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        let (major, minor) = devnum::PTMX;
+        Ok(Kstat {
+            mode: S_IFCHR | 0o666u32, // rw-rw-rw-
+            rdev: devnum::makedev(major, minor),
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: !self.pty.link.output.lock().is_empty() || self.pty.slave_hung_up(),
+            writable: true,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::path("/dev/ptmx", 0, self.flags)
+    }
+
+    fn ioctl(&self, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        let addr = arg.address().as_usize();
+        match cmd {
+            TIOCGPTN => {
+                *UserPtr::<u32>::from(addr).get_as_mut()? = self.pty.index;
+                Ok(0)
+            }
+            TIOCSPTLCK => {
+                let lock = *UserConstPtr::<c_int>::from(addr).get_as_ref()?;
+                self.pty.locked.store(lock != 0, Ordering::Release);
+                Ok(0)
+            }
+            TIOCGPTLCK => {
+                *UserPtr::<c_int>::from(addr).get_as_mut()? =
+                    self.pty.locked.load(Ordering::Acquire) as c_int;
+                Ok(0)
+            }
+            TIOCGPTPEER => {
+                // The argument holds the open flags of the slave
+                let slave = open_slave_of(&self.pty, addr as u32)?;
+                Ok(add_file_like_cloexec(slave, addr as u32 & O_CLOEXEC != 0)? as isize)
+            }
+            // The master reports the foreground process group of the slave
+            // to any process
+            TIOCGPGRP => {
+                *UserPtr::<c_int>::from(addr).get_as_mut()? =
+                    self.pty.tty.foreground.load(Ordering::Acquire) as c_int;
+                Ok(0)
+            }
+            _ => self.pty.tty.ioctl(cmd, arg),
+        }
+    }
+}
+
+/// An open file of the slave side of a pseudo-terminal.
+pub struct PtySlave {
+    pty: Arc<Pty>,
+    flags: u32,
+    nonblocking: AtomicBool,
+}
+
+fn open_slave_of(pty: &Arc<Pty>, flags: u32) -> LinuxResult<Arc<dyn FileLike>> {
+    if pty.locked.load(Ordering::Acquire) || pty.link.master_closed.load(Ordering::Acquire) {
+        return Err(LinuxError::EIO);
+    }
+    pty.slaves.fetch_add(1, Ordering::AcqRel);
+    pty.slave_opened.store(true, Ordering::Release);
+    pty.tty.acquire_on_open(flags);
+    Ok(Arc::new(PtySlave {
+        pty: pty.clone(),
+        flags,
+        nonblocking: AtomicBool::new(flags & O_NONBLOCK != 0),
+    }))
+}
+
+/// Opens the slave of the pseudo-terminal `index`.
+pub fn open_slave(index: u32, flags: u32) -> LinuxResult<Arc<dyn FileLike>> {
+    let pty = PTYS
+        .lock()
+        .get(&index)
+        .and_then(Weak::upgrade)
+        .ok_or(LinuxError::ENOENT)?;
+    open_slave_of(&pty, flags)
+}
+
+impl Drop for PtySlave {
+    fn drop(&mut self) {
+        if self.pty.slaves.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // The master reads end of file now
+            self.pty.link.event.notify();
+        }
+    }
+}
+
+impl FileLike for PtySlave {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        self.pty
+            .tty
+            .read(buf, self.nonblocking.load(Ordering::Acquire))
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        self.pty
+            .tty
+            .write(buf, self.nonblocking.load(Ordering::Acquire))
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        Ok(Kstat {
+            mode: S_IFCHR | 0o620u32, // rw--w----
+            rdev: devnum::makedev(devnum::PTS_MAJOR, self.pty.index),
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(self.pty.tty.poll())
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::path(&format!("/dev/pts/{}", self.pty.index), 0, self.flags)
+    }
+
+    fn ioctl(&self, cmd: u32, arg: UserPtr<c_void>) -> LinuxResult<isize> {
+        self.pty.tty.ioctl(cmd, arg)
+    }
+}
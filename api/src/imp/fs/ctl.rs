@@ -6,24 +6,22 @@ use core::{
 use alloc::ffi::CString;
 use axerrno::{LinuxError, LinuxResult};
 use axfs::fops::DirEntry;
-use axtask::{TaskExtRef, current};
-use linux_raw_sys::general::{
-    AT_FDCWD, AT_REMOVEDIR, DT_BLK, DT_CHR, DT_DIR, DT_FIFO, DT_LNK, DT_REG, DT_SOCK, DT_UNKNOWN,
-    linux_dirent64, termios,
+use linux_raw_sys::{
+    general::{
+        AT_FDCWD, AT_REMOVEDIR, DT_BLK, DT_CHR, DT_DIR, DT_FIFO, DT_LNK, DT_REG, DT_SOCK,
+        DT_UNKNOWN, linux_dirent64,
+    },
+    ioctl::{FIOCLEX, FIONBIO, FIONCLEX},
 };
 
-// Define ioctl constants directly since they're behind a feature flag
-const TIOCGPGRP: u32 = 21519;
-const TIOCSPGRP: u32 = 21520;
-const TCGETS: u32 = 21505;
-const TCSETS: u32 = 21506;
-
 use crate::{
-    file::{Directory, FileLike},
+    file::{Directory, FileLike, get_file_like, set_cloexec},
     path::{HARDLINK_MANAGER, handle_file_path},
     ptr::{UserConstPtr, UserPtr, nullable},
 };
 
+use super::mount::check_writable;
+
 /// The ioctl() system call manipulates the underlying device parameters
 /// of special files.
 ///
@@ -35,53 +33,21 @@ use crate::{
 pub fn sys_ioctl(fd: i32, op: usize, argp: UserPtr<c_void>) -> LinuxResult<isize> {
     debug!("sys_ioctl <= fd: {}, op: 0x{:x}", fd, op);
 
+    let file = get_file_like(fd)?;
+    // These apply to every file; the others are up to the file
     match op as u32 {
-        TIOCGPGRP => {
-            // Get foreground process group
-            let current_task = current();
-            let pgid = current_task.task_ext().thread.process().group().pgid();
-            let pgid_ptr: UserPtr<i32> = UserPtr::from(argp.address().as_usize());
-            *pgid_ptr.get_as_mut()? = pgid as i32;
-            debug!("TIOCGPGRP returning pgid: {}", pgid);
-            Ok(0)
-        }
-        TIOCSPGRP => {
-            // Set foreground process group
-            let pgid_ptr_const: UserConstPtr<i32> = UserConstPtr::from(argp.address().as_usize());
-            let pgid = *pgid_ptr_const.get_as_ref()? as u32;
-            debug!("TIOCSPGRP setting pgid: {}", pgid);
-            // For now, just return success - actual terminal control would require more complex state
-            Ok(0)
-        }
-        TCGETS => {
-            // Get terminal attributes
-            let termios_ptr: UserPtr<termios> = UserPtr::from(argp.address().as_usize());
-            let termios_data = termios_ptr.get_as_mut()?;
-
-            // Initialize with default terminal settings
-            *termios_data = termios {
-                c_iflag: 0x500,  // BRKINT | ISTRIP
-                c_oflag: 0x5,    // OPOST | ONLCR
-                c_cflag: 0xbf,   // CS8 | CREAD | HUPCL
-                c_lflag: 0x8a3b, // ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL | ECHOKE | IEXTEN
-                c_line: 0,
-                c_cc: [
-                    3, 28, 127, 21, 4, 0, 1, 0, 17, 19, 26, 0, 18, 15, 23, 22, 0, 0, 0,
-                ],
-            };
-
-            debug!("TCGETS returning default termios");
-            Ok(0)
-        }
-        TCSETS => {
-            // Set terminal attributes
-            debug!("TCSETS called - ignoring for now");
+        FIONBIO => {
+            let nonblocking =
+                *UserConstPtr::<c_int>::from(argp.address().as_usize()).get_as_ref()?;
+            file.set_nonblocking(nonblocking != 0)?;
             Ok(0)
         }
-        _ => {
-            warn!("Unimplemented ioctl operation: 0x{:x}", op);
+        // TODO: close-on-exec is not tracked yet
+        FIOCLEX | FIONCLEX => {
+            set_cloexec(fd, op as u32 == FIOCLEX)?;
             Ok(0)
         }
+        op => file.ioctl(op, argp),
     }
 }
 
@@ -105,6 +71,7 @@ pub fn sys_mkdirat(dirfd: i32, path: UserConstPtr<c_char>, mode: u32) -> LinuxRe
     }
 
     let path = handle_file_path(dirfd, path)?;
+    check_writable(&path)?;
     axfs::api::create_dir(path.as_str())?;
 
     Ok(0)
@@ -254,6 +221,7 @@ pub fn sys_linkat(
     let old_path = handle_file_path(old_dirfd, old_path)?;
     // handle new path
     let new_path = handle_file_path(new_dirfd, new_path)?;
+    check_writable(&new_path)?;
 
     HARDLINK_MANAGER.create_link(&new_path, &old_path)?;
 
@@ -280,6 +248,7 @@ pub fn sys_unlinkat(dirfd: c_int, path: UserConstPtr<c_char>, flags: u32) -> Lin
     );
 
     let path = handle_file_path(dirfd, path)?;
+    check_writable(&path)?;
 
     if flags == AT_REMOVEDIR {
         axfs::api::remove_dir(path.as_str())?;
@@ -333,6 +302,7 @@ pub fn sys_symlinkat(
     );
 
     let new_path = handle_file_path(new_dirfd, new_path)?;
+    check_writable(&new_path)?;
     axfs::api::create_symlink(target, &new_path)?;
 
     Ok(0)
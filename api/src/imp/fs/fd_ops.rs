@@ -1,23 +1,29 @@
-use core::{
-    ffi::{c_char, c_int},
-    panic,
-};
+use core::ffi::{c_char, c_int};
 
-use alloc::string::ToString;
+use alloc::{string::ToString, sync::Arc};
 use axerrno::{AxError, LinuxError, LinuxResult};
 use axfs::fops::OpenOptions;
 use linux_raw_sys::general::{
-    __kernel_mode_t, AT_FDCWD, F_DUPFD, F_DUPFD_CLOEXEC, F_SETFL, O_APPEND, O_CREAT, O_DIRECTORY,
-    O_NONBLOCK, O_PATH, O_RDONLY, O_TRUNC, O_WRONLY,
+    __kernel_mode_t, AT_FDCWD, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_SETFD, F_SETFL, FD_CLOEXEC,
+    O_APPEND, O_CLOEXEC, O_CREAT, O_DIRECTORY, O_NONBLOCK, O_PATH, O_RDONLY, O_RDWR, O_TRUNC,
+    O_WRONLY, S_IFBLK, S_IFCHR, S_IFMT,
 };
 
 use crate::{
-    file::{Directory, FD_TABLE, File, FileLike, add_file_like, close_file_like, get_file_like},
+    file::{
+        Directory, FD_TABLE, File, FileLike, Pipe, add_file_like_cloexec, close_file_like,
+        get_cloexec, get_file_like, open_tty, set_cloexec,
+    },
     path::handle_file_path,
     ptr::UserConstPtr,
 };
 
+use super::mount::{check_device_access, check_writable};
+
 const O_EXEC: u32 = O_PATH;
+/// `F_LINUX_SPECIFIC_BASE + 7` and `+ 8`, missing from `linux_raw_sys`.
+const F_SETPIPE_SZ: u32 = 1031;
+const F_GETPIPE_SZ: u32 = 1032;
 
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: __kernel_mode_t) -> OpenOptions {
@@ -66,34 +72,43 @@ pub fn sys_openat(
     let opts = flags_to_options(flags, mode);
     debug!("sys_openat <= {} {} {:?}", dirfd, path, opts);
 
-    let dir = if path.starts_with('/') || dirfd == AT_FDCWD {
-        None
-    } else {
-        Some(Directory::from_fd(dirfd)?)
-    };
+    // Relative paths go through the same bind mount resolution as absolute
+    // ones, so they are opened by their resolved path
     let real_path = handle_file_path(dirfd, path)?;
+    // Opening an existing file with `O_CREAT` creates nothing
+    let creates = flags as u32 & O_CREAT != 0 && !real_path.exists();
+    if flags as u32 & (O_WRONLY | O_RDWR | O_TRUNC) != 0 || creates {
+        check_writable(&real_path)?;
+    }
+
+    let cloexec = flags as u32 & O_CLOEXEC != 0;
+    // Terminals are not backed by the node they are opened through
+    if let Some(tty) = open_tty(real_path.as_str(), flags as u32)? {
+        check_device_access(&real_path)?;
+        return Ok(add_file_like_cloexec(tty, cloexec)? as _);
+    }
 
     if !opts.has_directory() {
-        match dir.as_ref().map_or_else(
-            || axfs::fops::File::open(path, &opts),
-            |dir| dir.inner().open_file_at(path, &opts),
-        ) {
+        match axfs::fops::File::open(real_path.as_str(), &opts) {
             Err(AxError::IsADirectory) => {}
             r => {
-                let fd = File::new(r?, real_path.to_string()).add_to_fd_table()?;
+                let file = File::new(r?, real_path.to_string(), flags as u32);
+                if matches!(file.stat()?.mode() & S_IFMT, S_IFCHR | S_IFBLK) {
+                    check_device_access(&real_path)?;
+                }
+                let fd = add_file_like_cloexec(Arc::new(file), cloexec)?;
                 return Ok(fd as _);
             }
         }
     }
 
-    let fd = Directory::new(
-        dir.map_or_else(
-            || axfs::fops::Directory::open_dir(path, &opts),
-            |dir| dir.inner().open_dir_at(path, &opts),
-        )?,
-        real_path.to_string(),
-    )
-    .add_to_fd_table()?;
+    // Directories keep the path the caller sees, which paths relative to them
+    // are resolved from
+    let dir = Directory::new(
+        axfs::fops::Directory::open_dir(real_path.as_str(), &opts)?,
+        real_path.visible().to_string(),
+    );
+    let fd = add_file_like_cloexec(Arc::new(dir), cloexec)?;
     Ok(fd as _)
 }
 
@@ -115,43 +130,54 @@ pub fn sys_close(fd: c_int) -> LinuxResult<isize> {
     Ok(0)
 }
 
-fn dup_fd(old_fd: c_int) -> LinuxResult<isize> {
+/// Duplicates `old_fd` to the lowest free descriptor, which is closed by
+/// `execve` if `cloexec`.
+fn dup_fd(old_fd: c_int, cloexec: bool) -> LinuxResult<isize> {
     let f = get_file_like(old_fd)?;
-    let new_fd = add_file_like(f)?;
+    let new_fd = add_file_like_cloexec(f, cloexec)?;
     Ok(new_fd as _)
 }
 
 pub fn sys_dup(old_fd: c_int) -> LinuxResult<isize> {
     debug!("sys_dup <= {}", old_fd);
-    dup_fd(old_fd)
+    dup_fd(old_fd, false)
 }
 
 pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> LinuxResult<isize> {
     debug!("sys_dup2 <= old_fd: {}, new_fd: {}", old_fd, new_fd);
-    let mut fd_table = FD_TABLE.write();
-    let f = fd_table
-        .get(old_fd as _)
-        .cloned()
-        .ok_or(LinuxError::EBADF)?;
-
+    let f = get_file_like(old_fd)?;
     if old_fd != new_fd {
-        fd_table.remove(new_fd as _);
-        fd_table
-            .add_at(new_fd as _, f)
-            .unwrap_or_else(|_| panic!("new_fd should be valid"));
+        FD_TABLE.replace(new_fd as _, f, false)?;
     }
 
     Ok(new_fd as _)
 }
 
+/// Like `dup2`, but fails with `EINVAL` if the descriptors are the same and
+/// takes `O_CLOEXEC` in `flags`.
+pub fn sys_dup3(old_fd: c_int, new_fd: c_int, flags: u32) -> LinuxResult<isize> {
+    debug!(
+        "sys_dup3 <= old_fd: {}, new_fd: {}, flags: {:#x}",
+        old_fd, new_fd, flags
+    );
+    if old_fd == new_fd || flags & !O_CLOEXEC != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let f = get_file_like(old_fd)?;
+    FD_TABLE.replace(new_fd as _, f, flags & O_CLOEXEC != 0)?;
+    Ok(new_fd as _)
+}
+
 pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> LinuxResult<isize> {
     debug!("sys_fcntl <= fd: {} cmd: {} arg: {}", fd, cmd, arg);
 
     match cmd as u32 {
-        F_DUPFD => dup_fd(fd),
-        F_DUPFD_CLOEXEC => {
-            warn!("sys_fcntl: treat F_DUPFD_CLOEXEC as F_DUPFD");
-            dup_fd(fd)
+        F_DUPFD => dup_fd(fd, false),
+        F_DUPFD_CLOEXEC => dup_fd(fd, true),
+        F_GETFD => Ok(if get_cloexec(fd)? { FD_CLOEXEC as _ } else { 0 }),
+        F_SETFD => {
+            set_cloexec(fd, arg & FD_CLOEXEC as usize != 0)?;
+            Ok(0)
         }
         F_SETFL => {
             if fd == 0 || fd == 1 || fd == 2 {
@@ -160,6 +186,20 @@ pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> LinuxResult<isize> {
             get_file_like(fd)?.set_nonblocking(arg & (O_NONBLOCK as usize) > 0)?;
             Ok(0)
         }
+        F_SETPIPE_SZ => {
+            let pipe = get_file_like(fd)?
+                .into_any()
+                .downcast::<Pipe>()
+                .map_err(|_| LinuxError::EBADF)?;
+            Ok(pipe.set_capacity(arg)? as _)
+        }
+        F_GETPIPE_SZ => {
+            let pipe = get_file_like(fd)?
+                .into_any()
+                .downcast::<Pipe>()
+                .map_err(|_| LinuxError::EBADF)?;
+            Ok(pipe.capacity() as _)
+        }
         _ => {
             warn!("unsupported fcntl parameters: cmd: {}", cmd);
             Ok(0)
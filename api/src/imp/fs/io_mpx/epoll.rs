@@ -2,15 +2,21 @@
 
 use core::{ffi::c_int, time::Duration};
 
-use crate::file::{FileLike, Kstat, add_file_like, get_file_like};
+use crate::file::{FileLike, Kstat, add_file_like_cloexec, get_file_like};
 use crate::ptr::UserPtr;
+use crate::signal::check_interrupted_with;
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
-use axhal::time::wall_time;
+use axhal::time::monotonic_time;
 use linux_raw_sys::general::{
-    EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD, EPOLLERR, EPOLLIN, EPOLLOUT, sigset_t,
+    EPOLL_CLOEXEC, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD, EPOLLERR, EPOLLIN, EPOLLOUT,
+    O_RDWR, sigset_t,
 };
 use spin::Mutex;
+use starry_core::{
+    file::proc::fd::FdInfo,
+    task::{SyscallRestart, block_current},
+};
 
 /// Structure representing epoll_event for user space
 #[repr(C)]
@@ -110,6 +116,10 @@ impl FileLike for EpollInstance {
         Err(LinuxError::ENOSYS)
     }
 
+    fn fd_info(&self) -> FdInfo {
+        FdInfo::anon("anon_inode:[eventpoll]".into(), O_RDWR)
+    }
+
     fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
         Err(LinuxError::ENOSYS)
     }
@@ -143,7 +153,7 @@ pub fn sys_epoll_create(size: c_int) -> LinuxResult<isize> {
     }
 
     let epoll_instance = Arc::new(EpollInstance::new(0));
-    let fd = add_file_like(epoll_instance)?;
+    let fd = add_file_like_cloexec(epoll_instance, false)?;
     Ok(fd as isize)
 }
 
@@ -151,8 +161,13 @@ pub fn sys_epoll_create(size: c_int) -> LinuxResult<isize> {
 pub fn sys_epoll_create1(flags: c_int) -> LinuxResult<isize> {
     debug!("sys_epoll_create1 <= flags: {}", flags);
 
-    // For simplicity, ignore flags for now
-    sys_epoll_create(1)
+    // EPOLL_CLOEXEC is the only flag
+    if flags as u32 & !EPOLL_CLOEXEC != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let epoll_instance = Arc::new(EpollInstance::new(0));
+    let fd = add_file_like_cloexec(epoll_instance, flags as u32 & EPOLL_CLOEXEC != 0)?;
+    Ok(fd as isize)
 }
 
 /// Implementation of epoll_ctl system call
@@ -198,7 +213,7 @@ pub fn sys_epoll_wait(
     }
 
     let deadline =
-        (!timeout.is_negative()).then(|| wall_time() + Duration::from_millis(timeout as u64));
+        (!timeout.is_negative()).then(|| monotonic_time() + Duration::from_millis(timeout as u64));
     let epoll_instance = EpollInstance::from_fd(epfd)?;
 
     loop {
@@ -216,11 +231,12 @@ pub fn sys_epoll_wait(
             return Ok(events_num as isize);
         }
 
-        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
             return Ok(0);
         }
 
-        axtask::sleep(Duration::from_millis(1));
+        check_interrupted_with(SyscallRestart::NoHand)?;
+        block_current(|| axtask::sleep(Duration::from_millis(1)));
     }
 }
 
@@ -4,9 +4,21 @@ use core::{ffi::c_int, time::Duration};
 
 use crate::file::get_file_like;
 use crate::ptr::UserPtr;
+use crate::signal::check_interrupted_with;
 use axerrno::LinuxResult;
-use axhal::time::wall_time;
+use axhal::time::{TimeValue, monotonic_time};
 use linux_raw_sys::general::{POLLERR, POLLIN, POLLNVAL, POLLOUT, pollfd, sigset_t, timespec};
+use starry_core::task::{SyscallRestart, block_current};
+
+/// Sleeps until `deadline` for a poll without file descriptors, failing with
+/// `EINTR` if a signal interrupts it.
+fn poll_sleep(deadline: TimeValue) -> LinuxResult<()> {
+    while monotonic_time() < deadline {
+        check_interrupted_with(SyscallRestart::NoHand)?;
+        block_current(|| axtask::sleep(Duration::from_millis(1)));
+    }
+    Ok(())
+}
 
 /// Implementation of poll system call
 pub fn sys_poll(fds: UserPtr<pollfd>, nfds: usize, timeout_ms: c_int) -> LinuxResult<isize> {
@@ -19,13 +31,13 @@ pub fn sys_poll(fds: UserPtr<pollfd>, nfds: usize, timeout_ms: c_int) -> LinuxRe
 
     if nfds == 0 {
         if timeout_ms > 0 {
-            axtask::sleep(Duration::from_millis(timeout_ms as u64));
+            poll_sleep(monotonic_time() + Duration::from_millis(timeout_ms as u64))?;
         }
         return Ok(0);
     }
 
-    let deadline =
-        (!timeout_ms.is_negative()).then(|| wall_time() + Duration::from_millis(timeout_ms as u64));
+    let deadline = (!timeout_ms.is_negative())
+        .then(|| monotonic_time() + Duration::from_millis(timeout_ms as u64));
 
     loop {
         axnet::poll_interfaces();
@@ -69,11 +81,12 @@ pub fn sys_poll(fds: UserPtr<pollfd>, nfds: usize, timeout_ms: c_int) -> LinuxRe
             return Ok(ready_count);
         }
 
-        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
             return Ok(0);
         }
 
-        axtask::sleep(Duration::from_millis(1));
+        check_interrupted_with(SyscallRestart::NoHand)?;
+        block_current(|| axtask::sleep(Duration::from_millis(1)));
     }
 }
 
@@ -91,7 +104,7 @@ pub fn sys_ppoll(
             let ts = timeout.get_as_mut()?;
             let duration =
                 Duration::from_secs(ts.tv_sec as u64) + Duration::from_nanos(ts.tv_nsec as u64);
-            axtask::sleep(duration);
+            poll_sleep(monotonic_time() + duration)?;
         }
         return Ok(0);
     }
@@ -101,7 +114,7 @@ pub fn sys_ppoll(
     } else {
         let ts = timeout.get_as_mut()?;
         Some(
-            wall_time()
+            monotonic_time()
                 + Duration::from_secs(ts.tv_sec as u64)
                 + Duration::from_nanos(ts.tv_nsec as u64),
         )
@@ -149,10 +162,11 @@ pub fn sys_ppoll(
             return Ok(ready_count);
         }
 
-        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
             return Ok(0);
         }
 
-        axtask::sleep(Duration::from_millis(1));
+        check_interrupted_with(SyscallRestart::NoHand)?;
+        block_current(|| axtask::sleep(Duration::from_millis(1)));
     }
 }
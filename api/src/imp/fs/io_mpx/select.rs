@@ -4,9 +4,11 @@ use core::{ffi::c_int, time::Duration};
 
 use crate::file::get_file_like;
 use crate::ptr::UserPtr;
+use crate::signal::check_interrupted_with;
 use axerrno::{LinuxError, LinuxResult};
-use axhal::time::wall_time;
+use axhal::time::monotonic_time;
 use linux_raw_sys::general::{sigset_t, timespec, timeval};
+use starry_core::task::{SyscallRestart, block_current};
 
 const FD_SETSIZE: usize = 1024;
 const BITS_PER_USIZE: usize = usize::BITS as usize;
@@ -165,7 +167,7 @@ pub fn sys_select(
     } else {
         let tv = timeout.get_as_mut()?;
         Some(
-            wall_time()
+            monotonic_time()
                 + Duration::from_secs(tv.tv_sec as u64)
                 + Duration::from_micros(tv.tv_usec as u64),
         )
@@ -198,11 +200,12 @@ pub fn sys_select(
             return Ok(res as isize);
         }
 
-        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
             return Ok(0);
         }
 
-        axtask::sleep(Duration::from_millis(1));
+        check_interrupted_with(SyscallRestart::NoHand)?;
+        block_current(|| axtask::sleep(Duration::from_millis(1)));
     }
 }
 
@@ -227,7 +230,7 @@ pub fn sys_pselect6(
     } else {
         let ts = timeout.get_as_mut()?;
         Some(
-            wall_time()
+            monotonic_time()
                 + Duration::from_secs(ts.tv_sec as u64)
                 + Duration::from_nanos(ts.tv_nsec as u64),
         )
@@ -260,10 +263,11 @@ pub fn sys_pselect6(
             return Ok(res as isize);
         }
 
-        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+        if deadline.is_some_and(|ddl| monotonic_time() >= ddl) {
             return Ok(0);
         }
 
-        axtask::sleep(Duration::from_millis(1));
+        check_interrupted_with(SyscallRestart::NoHand)?;
+        block_current(|| axtask::sleep(Duration::from_millis(1)));
     }
 }
@@ -1,144 +1,176 @@
-use core::ffi::{c_char, c_void};
+use core::ffi::{c_char, c_int, c_void};
 
-use alloc::vec::Vec;
+use alloc::string::ToString;
 use axerrno::{LinuxError, LinuxResult};
-use axsync::Mutex;
-use linux_raw_sys::general::AT_FDCWD;
+use linux_raw_sys::general::{AT_FDCWD, statfs};
+use starry_core::file::{
+    fs::FsType,
+    mount::{self, MountFlags, UmountFlags},
+};
 
 use crate::{
+    file::{Directory, File, FileLike, get_file_like},
     path::{FilePath, handle_file_path},
-    ptr::UserConstPtr,
+    ptr::{UserConstPtr, UserPtr, nullable},
 };
 
+// Define statfs flags directly since they are not exported by linux-raw-sys
+const ST_RDONLY: u32 = 1;
+const ST_NOSUID: u32 = 2;
+const ST_NODEV: u32 = 4;
+const ST_NOEXEC: u32 = 8;
+const ST_VALID: u32 = 0x20;
+
 pub fn sys_mount(
     source: UserConstPtr<c_char>,
     target: UserConstPtr<c_char>,
     fs_type: UserConstPtr<c_char>,
-    flags: i32,
-    _data: UserConstPtr<c_void>,
+    flags: u32,
+    data: UserConstPtr<c_void>,
 ) -> LinuxResult<isize> {
-    let source = source.get_as_str()?;
+    let source = nullable!(source.get_as_str())?.unwrap_or_default();
     let target = target.get_as_str()?;
-    let fs_type = fs_type.get_as_str()?;
+    let fs_type = nullable!(fs_type.get_as_str())?.unwrap_or_default();
+    let flags = MountFlags::from_bits_truncate(flags);
+    let data = UserConstPtr::<c_char>::from(data.address().as_usize());
+    let data = nullable!(data.get_as_str())?.unwrap_or_default();
     info!(
-        "sys_mount <= source: {}, target: {}, fs_type: {}, flags: {}",
-        source, target, fs_type, flags
+        "sys_mount <= source: {}, target: {}, fs_type: {}, flags: {:?}, data: {:?}",
+        source, target, fs_type, flags, data
     );
 
-    let device_path = handle_file_path(AT_FDCWD, source)?;
     let mount_path = handle_file_path(AT_FDCWD, target)?;
-    info!(
-        "mount {:?} to {:?} with fs_type={:?}",
-        device_path, mount_path, fs_type
-    );
-
-    if fs_type != "vfat" {
-        debug!("fs_type can only be vfat.");
-        return Err(LinuxError::EPERM);
-    }
-
     if !mount_path.exists() {
         debug!("mount path not exist");
-        return Err(LinuxError::EPERM);
+        return Err(LinuxError::ENOENT);
     }
 
-    if check_mounted(&mount_path) {
-        debug!("mount path includes mounted fs");
-        return Err(LinuxError::EPERM);
+    if flags.contains(MountFlags::REMOUNT) {
+        mount::remount(mount_path.visible(), flags)?;
+        return Ok(0);
     }
 
-    if !mount_fat_fs(&device_path, &mount_path) {
-        debug!("mount error");
-        return Err(LinuxError::EPERM);
+    if flags.contains(MountFlags::BIND) {
+        let source_path = handle_file_path(AT_FDCWD, source)?;
+        if !source_path.exists() {
+            return Err(LinuxError::ENOENT);
+        }
+        mount::bind_mount(&source_path, mount_path.visible(), flags)?;
+        return Ok(0);
     }
+
+    let fs_type = FsType::from_name(fs_type).ok_or(LinuxError::ENODEV)?;
+    let source = if fs_type.requires_device() {
+        handle_file_path(AT_FDCWD, source)?.to_string()
+    } else {
+        source.into()
+    };
+    info!(
+        "mount {:?} to {:?} with fs_type={:?}",
+        source, mount_path, fs_type
+    );
+
+    mount::mount(&source, mount_path.visible(), fs_type, flags, data)?;
     Ok(0)
 }
 
-pub fn sys_umount2(target: UserConstPtr<c_char>, flags: i32) -> LinuxResult<isize> {
+pub fn sys_umount2(target: UserConstPtr<c_char>, flags: u32) -> LinuxResult<isize> {
     let target = target.get_as_str()?;
-    info!("sys_umount2 <= target: {}, flags: {}", target, flags);
+    let flags = UmountFlags::from_bits(flags).ok_or(LinuxError::EINVAL)?;
+    info!("sys_umount2 <= target: {}, flags: {:?}", target, flags);
 
     let mount_path = handle_file_path(AT_FDCWD, target)?;
-    if flags != 0 {
-        debug!("flags unimplemented");
-        return Err(LinuxError::EPERM);
-    }
-
     if !mount_path.exists() {
         debug!("mount path not exist");
-        return Err(LinuxError::EPERM);
+        return Err(LinuxError::ENOENT);
     }
 
-    if !umount_fat_fs(&mount_path) {
-        debug!("umount error");
-        return Err(LinuxError::EPERM);
-    }
+    mount::umount(mount_path.visible(), flags)?;
     Ok(0)
 }
 
-/// Mounted File System
-/// "Mount" means read&write a file as a file system now
-struct MountedFs {
-    //pub inner: Arc<Mutex<FATFileSystem>>,
-    pub device: FilePath,
-    pub mnt_dir: FilePath,
-}
-
-impl MountedFs {
-    pub fn new(device: &FilePath, mnt_dir: &FilePath) -> Self {
-        Self {
-            device: device.clone(),
-            mnt_dir: mnt_dir.clone(),
+/// Fills `buf` with the statistics of the filesystem containing `path`.
+fn statfs_at(path: &FilePath, buf: UserPtr<statfs>) -> LinuxResult<isize> {
+    let mount = mount::find_mount(path).ok_or(LinuxError::ENOENT)?;
+    let stats = mount.stats();
+    let flags = path.mount_flags();
+
+    let mut f_flags = ST_VALID;
+    for (flag, st_flag) in [
+        (MountFlags::RDONLY, ST_RDONLY),
+        (MountFlags::NOSUID, ST_NOSUID),
+        (MountFlags::NODEV, ST_NODEV),
+        (MountFlags::NOEXEC, ST_NOEXEC),
+    ] {
+        if flags.contains(flag) {
+            f_flags |= st_flag;
         }
     }
 
-    #[allow(unused)]
-    pub fn device(&self) -> FilePath {
-        self.device.clone()
-    }
+    let buf = buf.get_as_mut()?;
+    // SAFETY: valid for statfs
+    *buf = unsafe { core::mem::zeroed() };
+    buf.f_type = mount.fs_type.magic() as _;
+    buf.f_bsize = stats.block_size as _;
+    buf.f_frsize = stats.block_size as _;
+    buf.f_blocks = stats.blocks as _;
+    buf.f_bfree = stats.blocks_free as _;
+    buf.f_bavail = stats.blocks_available as _;
+    buf.f_files = stats.files as _;
+    buf.f_ffree = stats.files_free as _;
+    buf.f_fsid.val = [mount.id as _, 0];
+    buf.f_namelen = stats.name_max as _;
+    buf.f_flags = f_flags as _;
+    Ok(0)
+}
 
-    pub fn mnt_dir(&self) -> FilePath {
-        self.mnt_dir.clone()
+pub fn sys_statfs(path: UserConstPtr<c_char>, buf: UserPtr<statfs>) -> LinuxResult<isize> {
+    let path = path.get_as_str()?;
+    debug!("sys_statfs <= path: {}", path);
+
+    let path = handle_file_path(AT_FDCWD, path)?;
+    if !path.exists() {
+        return Err(LinuxError::ENOENT);
     }
+    statfs_at(&path, buf)
+}
+
+pub fn sys_fstatfs(fd: c_int, buf: UserPtr<statfs>) -> LinuxResult<isize> {
+    debug!("sys_fstatfs <= fd: {}", fd);
+
+    let path = if let Ok(file) = File::from_fd(fd) {
+        FilePath::new(file.path())?
+    } else if let Ok(dir) = Directory::from_fd(fd) {
+        FilePath::new(dir.path())?
+    } else {
+        // Pipes and sockets live on internal filesystems we don't model
+        get_file_like(fd)?;
+        return Err(LinuxError::EINVAL);
+    };
+    statfs_at(&path, buf)
 }
 
-/// List of mounted file system
-/// Note that the startup file system is not in the vec, but in mod.rs
-static MOUNTED: Mutex<Vec<MountedFs>> = Mutex::new(Vec::new());
-
-/// Mount a fatfs device
-pub fn mount_fat_fs(device_path: &FilePath, mount_path: &FilePath) -> bool {
-    // device_path needs symlink lookup, but mount_path does not
-    // only opened files will be added to the symlink table for now, so do not convert now
-    // debug!("mounting {} to {}", device_path.path(), mount_path.path());
-    // if let Some(true_device_path) = real_path(device_path) {
-    if mount_path.exists() {
-        MOUNTED.lock().push(MountedFs::new(device_path, mount_path));
-        info!(
-            "mounted {} to {}",
-            device_path.as_str(),
-            mount_path.as_str()
-        );
-        return true;
+/// Fails with `EROFS` if `path` lies on a read-only mount.
+pub(crate) fn check_writable(path: &FilePath) -> LinuxResult {
+    if path.mount_flags().contains(MountFlags::RDONLY) {
+        return Err(LinuxError::EROFS);
     }
-    info!(
-        "mount failed: {} to {}",
-        device_path.as_str(),
-        mount_path.as_str()
-    );
-    false
+    Ok(())
 }
 
-/// unmount a fatfs device
-pub fn umount_fat_fs(mount_path: &FilePath) -> bool {
-    let mut mounted = MOUNTED.lock();
-    let length_before_deletion = mounted.len();
-    mounted.retain(|m| m.mnt_dir() != *mount_path);
-    length_before_deletion > mounted.len()
+/// Fails with `EACCES` if `path` lies on a mount with program execution
+/// disabled.
+pub(crate) fn check_executable(path: &FilePath) -> LinuxResult {
+    if path.mount_flags().contains(MountFlags::NOEXEC) {
+        return Err(LinuxError::EACCES);
+    }
+    Ok(())
 }
 
-/// check if a path is mounted
-pub fn check_mounted(path: &FilePath) -> bool {
-    let mounted = MOUNTED.lock();
-    mounted.iter().any(|m| path.starts_with(&m.mnt_dir()))
+/// Fails with `EACCES` if `path` lies on a mount with device files disabled.
+pub(crate) fn check_device_access(path: &FilePath) -> LinuxResult {
+    if path.mount_flags().contains(MountFlags::NODEV) {
+        return Err(LinuxError::EACCES);
+    }
+    Ok(())
 }
@@ -1,23 +1,25 @@
 use core::ffi::c_int;
 
+use alloc::sync::Arc;
 use axerrno::LinuxResult;
+use linux_raw_sys::general::O_CLOEXEC;
 
 use crate::{
-    file::{FileLike, Pipe, close_file_like},
+    file::{Pipe, add_file_like_cloexec, close_file_like},
     ptr::UserPtr,
 };
 
 pub fn sys_pipe2(fds: UserPtr<[c_int; 2]>, flags: i32) -> LinuxResult<isize> {
-    if flags != 0 {
+    let cloexec = flags as u32 & O_CLOEXEC != 0;
+    if flags as u32 & !O_CLOEXEC != 0 {
         warn!("sys_pipe2: unsupported flags: {}", flags);
     }
 
     let fds = fds.get_as_mut()?;
 
     let (read_end, write_end) = Pipe::new();
-    let read_fd = read_end.add_to_fd_table()?;
-    let write_fd = write_end
-        .add_to_fd_table()
+    let read_fd = add_file_like_cloexec(Arc::new(read_end), cloexec)?;
+    let write_fd = add_file_like_cloexec(Arc::new(write_end), cloexec)
         .inspect_err(|_| close_file_like(read_fd).unwrap())?;
 
     fds[0] = read_fd;
@@ -2,7 +2,7 @@ use core::ffi::{c_char, c_int};
 
 use axerrno::{AxError, LinuxError, LinuxResult};
 use axfs::fops::OpenOptions;
-use linux_raw_sys::general::{AT_EMPTY_PATH, AT_SYMLINK_NOFOLLOW, stat, statx};
+use linux_raw_sys::general::{AT_EMPTY_PATH, AT_FDCWD, AT_SYMLINK_NOFOLLOW, stat, statx};
 
 use crate::{
     file::{Directory, File, FileLike, Kstat, get_file_like},
@@ -13,7 +13,7 @@ use crate::{
 fn stat_at_path(path: &str) -> LinuxResult<Kstat> {
     let opts = OpenOptions::new().set_read(true);
     match axfs::fops::File::open(path, &opts) {
-        Ok(file) => File::new(file, path.into()).stat(),
+        Ok(file) => File::new(file, path.into(), 0).stat(),
         Err(AxError::IsADirectory) => {
             let dir = axfs::fops::Directory::open_dir(path, &opts)?;
             Directory::new(dir, path.into()).stat()
@@ -43,8 +43,9 @@ fn lstat_at_path(path: &str) -> LinuxResult<Kstat> {
 pub fn sys_stat(path: UserConstPtr<c_char>, statbuf: UserPtr<stat>) -> LinuxResult<isize> {
     let path = path.get_as_str()?;
     debug!("sys_stat <= path: {}", path);
+    let path = handle_file_path(AT_FDCWD, path)?;
 
-    *statbuf.get_as_mut()? = stat_at_path(path)?.into();
+    *statbuf.get_as_mut()? = stat_at_path(path.as_str())?.into();
 
     Ok(0)
 }
@@ -64,8 +65,9 @@ pub fn sys_fstat(fd: i32, statbuf: UserPtr<stat>) -> LinuxResult<isize> {
 pub fn sys_lstat(path: UserConstPtr<c_char>, statbuf: UserPtr<stat>) -> LinuxResult<isize> {
     let path = path.get_as_str()?;
     debug!("sys_lstat <= path: {}", path);
+    let path = handle_file_path(AT_FDCWD, path)?;
 
-    *statbuf.get_as_mut()? = lstat_at_path(path)?.into();
+    *statbuf.get_as_mut()? = lstat_at_path(path.as_str())?.into();
 
     Ok(0)
 }
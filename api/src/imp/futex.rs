@@ -1,14 +1,74 @@
+use alloc::boxed::Box;
+
 use axerrno::{LinuxError, LinuxResult};
+use axhal::time::{TimeValue, monotonic_time};
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::{
     FUTEX_CMD_MASK, FUTEX_CMP_REQUEUE, FUTEX_REQUEUE, FUTEX_WAIT, FUTEX_WAKE, timespec,
 };
+use starry_core::task::SyscallRestart;
 
 use crate::{
     ptr::{UserConstPtr, UserPtr, nullable},
+    signal::{SIGNAL_CHECK_INTERVAL, have_signals, interrupted, wait_timeout_until_interruptible},
     time::TimeValueLike,
 };
 
+/// Waits on the futex at `uaddr` while it holds `value`, until woken or, if
+/// given, the monotonic clock reaches `deadline`.
+fn futex_wait(
+    uaddr: UserConstPtr<u32>,
+    value: u32,
+    deadline: Option<TimeValue>,
+) -> LinuxResult<isize> {
+    let word = uaddr.get_as_ref()?;
+    if *word != value {
+        return Err(LinuxError::EAGAIN);
+    }
+    let curr = current();
+    let futex_table = &curr.task_ext().process_data().futex_table;
+    let wq = futex_table.get_or_insert(uaddr.address().as_usize());
+    let wakes = futex_table.wakes();
+    // Checked with the queue locked, so that a wake after the word changes
+    // finds the thread queued. The word was just read, so reading it again
+    // doesn't fault.
+    let woken = || {
+        // SAFETY: `word` is a valid reference, read again as it changes
+        let word = unsafe { core::ptr::read_volatile(word) };
+        word != value || futex_table.wakes() != wakes
+    };
+
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let now = monotonic_time();
+                if now >= deadline {
+                    return Err(LinuxError::ETIMEDOUT);
+                }
+                (deadline - now).min(SIGNAL_CHECK_INTERVAL)
+            }
+            None => SIGNAL_CHECK_INTERVAL,
+        };
+        if have_signals() {
+            // Like Linux, a timed wait resumes towards the same deadline
+            let restart = match deadline {
+                Some(deadline) => {
+                    let addr = uaddr.address().as_usize();
+                    SyscallRestart::Block(Box::new(move || {
+                        futex_wait(addr.into(), value, Some(deadline))
+                    }))
+                }
+                None => SyscallRestart::Sys,
+            };
+            return Err(interrupted(restart));
+        }
+        let timed_out = wait_timeout_until_interruptible(&wq, timeout, woken);
+        if !timed_out && !have_signals() {
+            return Ok(0);
+        }
+    }
+}
+
 pub fn sys_futex(
     uaddr: UserConstPtr<u32>,
     futex_op: u32,
@@ -26,23 +86,15 @@ pub fn sys_futex(
     let command = futex_op & (FUTEX_CMD_MASK as u32);
     match command {
         FUTEX_WAIT => {
-            if *uaddr.get_as_ref()? != value {
-                return Err(LinuxError::EAGAIN);
-            }
-            let wq = futex_table.get_or_insert(addr);
-
-            if let Some(timeout) = nullable!(timeout.get_as_ref())? {
-                wq.wait_timeout(timeout.to_time_value());
-            } else {
-                wq.wait();
-            }
-
-            Ok(0)
+            let deadline = nullable!(timeout.get_as_ref())?
+                .map(|timeout| monotonic_time() + timeout.to_time_value());
+            futex_wait(uaddr, value, deadline)
         }
         FUTEX_WAKE => {
             let wq = futex_table.get(addr);
             let mut count = 0;
             if let Some(wq) = wq {
+                futex_table.count_wake();
                 for _ in 0..value {
                     if !wq.notify_one(false) {
                         break;
@@ -64,6 +116,7 @@ pub fn sys_futex(
 
             let mut count = 0;
             if let Some(wq) = wq {
+                futex_table.count_wake();
                 for _ in 0..value {
                     if !wq.notify_one(false) {
                         break;
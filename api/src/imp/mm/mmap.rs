@@ -4,6 +4,10 @@ use axhal::paging::{MappingFlags, PageSize};
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::*;
 use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange, align_up_4k};
+use starry_core::{
+    file::devnum::{self, device_number},
+    mm::may_commit,
+};
 
 use crate::file::{File, FileLike};
 
@@ -78,6 +82,7 @@ pub fn sys_mmap(
     let curr = current();
     let process_data = curr.task_ext().process_data();
     let mut aspace = process_data.aspace.lock();
+    let mut file_mappings = process_data.file_mappings.lock();
     let permission_flags = MmapProt::from_bits_truncate(prot);
     // TODO: check illegal flags for mmap
     // An example is the flags contained none of MAP_PRIVATE, MAP_SHARED, or MAP_SHARED_VALIDATE.
@@ -107,12 +112,17 @@ pub fn sys_mmap(
         start, end, aligned_length
     );
 
+    if !may_commit(aligned_length) {
+        return Err(LinuxError::ENOMEM);
+    }
+
     let start_addr = if map_flags.contains(MmapFlags::FIXED) {
         if start == 0 {
             return Err(LinuxError::EINVAL);
         }
         let dst_addr = VirtAddr::from(start);
         aspace.unmap(dst_addr, aligned_length)?;
+        file_mappings.remove(start, end);
         dst_addr
     } else {
         aspace
@@ -131,11 +141,13 @@ pub fn sys_mmap(
             .ok_or(LinuxError::ENOMEM)?
     };
 
-    let populate = if fd == -1 {
-        false
+    // Mapping /dev/zero is the traditional way to get anonymous memory
+    let file = if fd == -1 || map_flags.contains(MmapFlags::ANONYMOUS) {
+        None
     } else {
-        !map_flags.contains(MmapFlags::ANONYMOUS)
+        Some(File::from_fd(fd)?).filter(|file| device_number(file.path()) != Some(devnum::ZERO))
     };
+    let populate = file.is_some();
 
     aspace.map_alloc(
         start_addr,
@@ -145,18 +157,23 @@ pub fn sys_mmap(
         page_size,
     )?;
 
-    if populate {
-        let file = File::from_fd(fd)?;
-        let file = file.inner();
-        let file_size = file.get_attr()?.size() as usize;
+    if let Some(file) = file {
+        let inner = file.inner();
+        let file_size = inner.get_attr()?.size() as usize;
         if offset < 0 || offset as usize >= file_size {
             return Err(LinuxError::EINVAL);
         }
         let offset = offset as usize;
         let length = core::cmp::min(length, file_size - offset);
         let mut buf = vec![0u8; length];
-        file.read_at(offset as u64, &mut buf)?;
+        inner.read_at(offset as u64, &mut buf)?;
         aspace.write(start_addr, page_size, &buf)?;
+        file_mappings.insert(
+            start_addr.as_usize(),
+            start_addr.as_usize() + aligned_length,
+            file.path().into(),
+            offset as u64,
+        );
     }
     Ok(start_addr.as_usize() as _)
 }
@@ -168,6 +185,10 @@ pub fn sys_munmap(addr: usize, length: usize) -> LinuxResult<isize> {
     let length = align_up_4k(length);
     let start_addr = VirtAddr::from(addr);
     aspace.unmap(start_addr, length)?;
+    process_data
+        .file_mappings
+        .lock()
+        .remove(addr, addr + length);
     axhal::arch::flush_tlb(None);
     Ok(0)
 }
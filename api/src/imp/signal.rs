@@ -10,8 +10,9 @@ use linux_raw_sys::general::{
     MINSIGSTKSZ, SI_TKILL, SI_USER, SIG_BLOCK, SIG_SETMASK, SIG_UNBLOCK, kernel_sigaction, siginfo,
     timespec,
 };
-use starry_core::task::{get_process, get_process_group, get_thread, processes};
+use starry_core::task::{SyscallRestart, get_process, get_process_group, get_thread, processes};
 
+use super::timer::collect_timer_overrun;
 use crate::{
     ptr::{UserConstPtr, UserPtr, nullable},
     signal::{check_signals, send_signal_process, send_signal_process_group, send_signal_thread},
@@ -223,7 +224,7 @@ pub fn sys_rt_sigtimedwait(
     let set = *set.get_as_ref()?;
     let timeout: Option<Duration> = nullable!(timeout.get_as_ref())?.map(|ts| ts.to_time_value());
 
-    let Some(sig) = current()
+    let Some(mut sig) = current()
         .task_ext()
         .thread_data()
         .signal
@@ -231,6 +232,7 @@ pub fn sys_rt_sigtimedwait(
     else {
         return Err(LinuxError::EAGAIN);
     };
+    collect_timer_overrun(&mut sig);
 
     if let Some(info) = nullable!(info.get_as_mut())? {
         *info = sig.0;
@@ -293,3 +295,12 @@ pub fn sys_sigaltstack(
             Ok(0)
         })
 }
+
+/// Continues a syscall interrupted by a signal with `ERESTART_RESTARTBLOCK`.
+pub fn sys_restart_syscall() -> LinuxResult<isize> {
+    let restart = current().task_ext().thread_data().restart.lock().take();
+    match restart {
+        Some(SyscallRestart::Block(resume)) => resume(),
+        _ => Err(LinuxError::EINTR),
+    }
+}
@@ -1,7 +1,11 @@
 use core::ffi::c_char;
 
-use axerrno::LinuxResult;
-use linux_raw_sys::system::new_utsname;
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::{
+    general::{GRND_INSECURE, GRND_NONBLOCK, GRND_RANDOM},
+    system::new_utsname,
+};
+use starry_core::{KERNEL_RELEASE, random, sysctl::HOSTNAME};
 
 use crate::ptr::UserPtr;
 
@@ -34,13 +38,38 @@ const fn pad_str(info: &str) -> [c_char; 65] {
 const UTSNAME: new_utsname = new_utsname {
     sysname: pad_str("Starry"),
     nodename: pad_str("Starry - machine[0]"),
-    release: pad_str("10.0.0"),
+    release: pad_str(KERNEL_RELEASE),
     version: pad_str("10.0.0"),
     machine: pad_str("10.0.0"),
     domainname: pad_str("https://github.com/oscomp/starry-next"),
 };
 
 pub fn sys_uname(name: UserPtr<new_utsname>) -> LinuxResult<isize> {
-    *name.get_as_mut()? = UTSNAME;
+    let mut uts = UTSNAME;
+    let hostname = HOSTNAME.get();
+    uts.nodename = [0; 65];
+    for (dst, src) in uts.nodename.iter_mut().zip(hostname.bytes()) {
+        *dst = src as c_char;
+    }
+    *name.get_as_mut()? = uts;
     Ok(0)
 }
+
+pub fn sys_getrandom(buf: UserPtr<u8>, len: usize, flags: u32) -> LinuxResult<isize> {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0
+        || flags & (GRND_RANDOM | GRND_INSECURE) == (GRND_RANDOM | GRND_INSECURE)
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    if flags & GRND_INSECURE == 0 && !random::is_seeded() {
+        if flags & GRND_NONBLOCK != 0 {
+            return Err(LinuxError::EAGAIN);
+        }
+        random::wait_until_seeded();
+    }
+
+    // Like Linux, a single call returns at most 32 MiB
+    let len = len.min((1 << 25) - 1);
+    random::fill_bytes(buf.get_as_mut_slice(len)?);
+    Ok(len as _)
+}
@@ -10,7 +10,9 @@ use bitflags::bitflags;
 use linux_raw_sys::general::*;
 use starry_core::{
     mm::copy_from_kernel,
-    task::{ProcessData, TaskExt, ThreadData, add_thread_to_table, new_user_task},
+    task::{
+        ProcessData, ThreadData, add_thread_to_table, alloc_pid, new_user_task, spawn_user_task,
+    },
 };
 
 use crate::{file::FD_TABLE, ptr::UserPtr};
@@ -119,10 +121,10 @@ pub fn sys_clone(
         None
     };
 
+    let tid = alloc_pid()?;
     let curr = current();
     let mut new_task = new_user_task(curr.name(), new_uctx, set_child_tid);
 
-    let tid = new_task.id().as_u64() as Pid;
     if flags.contains(CloneFlags::PARENT_SETTID) {
         *UserPtr::<Pid>::from(parent_tid).get_as_mut()? = tid;
     }
@@ -149,13 +151,18 @@ pub fn sys_clone(
         };
         let builder = parent.fork(tid);
 
-        let aspace = if flags.contains(CloneFlags::VM) {
-            curr.task_ext().process_data().aspace.clone()
+        let data = curr.task_ext().process_data();
+        let (aspace, file_mappings) = if flags.contains(CloneFlags::VM) {
+            (data.aspace.clone(), data.file_mappings.clone())
         } else {
-            let mut aspace = curr.task_ext().process_data().aspace.lock();
+            let mut aspace = data.aspace.lock();
             let mut aspace = aspace.try_clone()?;
             copy_from_kernel(&mut aspace)?;
-            Arc::new(Mutex::new(aspace))
+            let file_mappings = data.file_mappings.lock().clone();
+            (
+                Arc::new(Mutex::new(aspace)),
+                Arc::new(Mutex::new(file_mappings)),
+            )
         };
         new_task
             .ctx_mut()
@@ -171,9 +178,12 @@ pub fn sys_clone(
         let process_data = ProcessData::new(
             curr.task_ext().process_data().exe_path.read().clone(),
             aspace,
+            file_mappings,
             signal_actions,
             exit_signal,
         );
+        let parent_data = curr.task_ext().process_data();
+        process_data.set_command_line(&parent_data.cmdline.read(), &parent_data.environ.read());
 
         if flags.contains(CloneFlags::FILES) {
             FD_TABLE
@@ -210,8 +220,7 @@ pub fn sys_clone(
 
     let thread = process.new_thread(tid).data(thread_data).build();
     add_thread_to_table(&thread);
-    new_task.init_task_ext(TaskExt::new(thread));
-    axtask::spawn_task(new_task);
+    spawn_user_task(new_task, thread);
 
     Ok(tid as _)
 }
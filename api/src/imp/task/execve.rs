@@ -7,7 +7,9 @@ use axtask::{TaskExtRef, current};
 use starry_core::mm::{load_user_app, map_trampoline};
 use xmas_elf::ElfFile;
 
-use crate::ptr::UserConstPtr;
+use crate::{
+    check_executable, delete_posix_timers, file::FD_TABLE, path::FilePath, ptr::UserConstPtr,
+};
 
 /// Validate if the file is a valid executable format
 fn validate_executable(data: &[u8]) -> LinuxResult<()> {
@@ -51,21 +53,35 @@ pub fn sys_execve(
         return Err(LinuxError::EAGAIN);
     }
 
+    let file_path = FilePath::new(&path)?;
+    check_executable(&file_path)?;
+    // Set-user-ID and set-group-ID bits are never honored, so there is
+    // nothing to refuse on `MS_NOSUID` mounts
+
     // Validate the executable without modifying the address space
-    let file_data = axfs::api::read(&path).map_err(|_| LinuxError::ENOENT)?;
+    let file_data = axfs::api::read(file_path.as_str()).map_err(|_| LinuxError::ENOENT)?;
     validate_executable(&file_data)?;
 
     // Proceed with execve
     let mut aspace = curr_ext.process_data().aspace.lock();
+    let mut file_mappings = curr_ext.process_data().file_mappings.lock();
     aspace.unmap_user_areas()?;
+    file_mappings.clear();
     map_trampoline(&mut aspace)?;
     axhal::arch::flush_tlb(None);
 
-    let (entry_point, user_stack_base) =
-        load_user_app(&mut aspace, &path, &args, &envs).map_err(|e| {
-            error!("Failed to load app {}: {:?}", path, e);
-            LinuxError::ENOENT
-        })?;
+    let (entry_point, user_stack_base) = load_user_app(
+        &mut aspace,
+        file_path.as_str(),
+        &args,
+        &envs,
+        &mut file_mappings,
+    )
+    .map_err(|e| {
+        error!("Failed to load app {}: {:?}", path, e);
+        LinuxError::ENOENT
+    })?;
+    drop(file_mappings);
     drop(aspace);
 
     // Set process name and executable path
@@ -74,8 +90,9 @@ pub fn sys_execve(
         .map_or(path.as_str(), |(_, name)| name);
     curr.set_name(name);
     *curr_ext.process_data().exe_path.write() = path;
-
-    // TODO: fd close-on-exec
+    curr_ext.process_data().set_command_line(&args, &envs);
+    delete_posix_timers();
+    FD_TABLE.close_on_exec();
 
     tf.set_ip(entry_point.as_usize());
     tf.set_sp(user_stack_base.as_usize());
@@ -2,12 +2,12 @@ use axprocess::Pid;
 use axsignal::{SignalInfo, Signo};
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::SI_KERNEL;
-use starry_core::task::ProcessData;
+use starry_core::task::{ExitStatus, ProcessData};
 
 use crate::{
-    file::FD_TABLE,
+    file::{FD_TABLE, hangup_session},
     ptr::UserPtr,
-    signal::{send_signal_process, send_signal_thread},
+    signal::{child_signal_info, send_signal_process, send_signal_thread},
 };
 
 pub fn do_exit(exit_code: i32, group_exit: bool) -> ! {
@@ -21,27 +21,35 @@ pub fn do_exit(exit_code: i32, group_exit: bool) -> ! {
     if let Ok(clear_tid) = clear_child_tid.get_as_mut() {
         *clear_tid = 0;
 
-        let guard = curr_ext
-            .process_data()
-            .futex_table
-            .get(clear_tid as *const _ as usize);
-        if let Some(futex) = guard {
+        let futex_table = &curr_ext.process_data().futex_table;
+        if let Some(futex) = futex_table.get(clear_tid as *const _ as usize) {
+            futex_table.count_wake();
             futex.notify_one(false);
         }
         axtask::yield_now();
     }
 
     let process = thread.process();
+    // The usage of the thread counts for the process from now on
+    let usage = curr_ext.thread_data().usage();
+    *curr_ext.process_data().usage.lock() += usage;
     if thread.exit(exit_code) {
+        curr_ext.process_data().sample_rss();
         process.exit();
         if let Some(parent) = process.parent() {
             if let Some(signo) = process.data::<ProcessData>().and_then(|it| it.exit_signal) {
-                let _ = send_signal_process(&parent, SignalInfo::new(signo, SI_KERNEL as _));
+                let sig = child_signal_info(signo, process, exit_code);
+                let _ = send_signal_process(&parent, sig);
             }
             if let Some(data) = parent.data::<ProcessData>() {
                 data.child_exit_wq.notify_all(false)
             }
         }
+        // The session loses its controlling terminal with its leader
+        let sid = process.group().session().sid();
+        if process.pid() == sid {
+            hangup_session(sid);
+        }
 
         process.exit();
         // TODO: clear namespace resources
@@ -59,9 +67,9 @@ pub fn do_exit(exit_code: i32, group_exit: bool) -> ! {
 }
 
 pub fn sys_exit(exit_code: i32) -> ! {
-    do_exit(exit_code << 8, false)
+    do_exit(ExitStatus::Exited(exit_code as u8).wait_status(), false)
 }
 
 pub fn sys_exit_group(exit_code: i32) -> ! {
-    do_exit(exit_code << 8, true)
+    do_exit(ExitStatus::Exited(exit_code as u8).wait_status(), true)
 }
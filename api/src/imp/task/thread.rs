@@ -1,8 +1,18 @@
+use alloc::{string::String, sync::Arc};
 use axerrno::{LinuxError, LinuxResult};
 use axprocess::Pid;
 use axtask::{TaskExtRef, current};
+use linux_raw_sys::{
+    general::O_NONBLOCK,
+    prctl::{PR_GET_NAME, PR_SET_NAME},
+};
 use num_enum::TryFromPrimitive;
-use starry_core::task::{get_process, get_process_group};
+use starry_core::task::{add_process_group_to_table, get_process, get_process_group};
+
+use crate::{
+    file::{PidFd, add_file_like_cloexec},
+    ptr::{UserConstPtr, UserPtr},
+};
 
 pub fn sys_getpid() -> LinuxResult<isize> {
     Ok(axtask::current().task_ext().thread.process().pid() as _)
@@ -19,7 +29,7 @@ pub fn sys_getppid() -> LinuxResult<isize> {
 }
 
 pub fn sys_gettid() -> LinuxResult<isize> {
-    Ok(axtask::current().id().as_u64() as _)
+    Ok(axtask::current().task_ext().thread.tid() as _)
 }
 
 pub fn sys_getpgid(pid: Pid) -> LinuxResult<isize> {
@@ -65,9 +75,10 @@ pub fn sys_setpgid(pid: Pid, pgid: Pid) -> LinuxResult<isize> {
     }
 
     if target_pgid == target_process.pid() {
-        if target_process.create_group().is_none() {
+        let Some(group) = target_process.create_group() else {
             return Err(LinuxError::EPERM);
-        }
+        };
+        add_process_group_to_table(&group);
     } else {
         let target_group = get_process_group(target_pgid);
         if target_group.is_err() {
@@ -87,6 +98,44 @@ pub fn sys_setpgid(pid: Pid, pgid: Pid) -> LinuxResult<isize> {
     Ok(0)
 }
 
+pub fn sys_getsid(pid: Pid) -> LinuxResult<isize> {
+    let process = if pid == 0 {
+        current().task_ext().thread.process().clone()
+    } else {
+        get_process(pid)?
+    };
+    Ok(process.group().session().sid() as _)
+}
+
+/// Creates a new session led by the calling process, which loses its
+/// controlling terminal.
+pub fn sys_setsid() -> LinuxResult<isize> {
+    let curr = current();
+    let process = curr.task_ext().thread.process();
+    // A process group leader cannot leave its group
+    if process.group().pgid() == process.pid() {
+        return Err(LinuxError::EPERM);
+    }
+    let (session, group) = process.create_session().ok_or(LinuxError::EPERM)?;
+    add_process_group_to_table(&group);
+    Ok(session.sid() as _)
+}
+
+/// Opens a file referring to the process `pid`.
+pub fn sys_pidfd_open(pid: Pid, flags: u32) -> LinuxResult<isize> {
+    // PIDFD_NONBLOCK is the only flag
+    if flags & !O_NONBLOCK != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let process = get_process(pid)?;
+    // Like Linux, pidfds are always close-on-exec
+    add_file_like_cloexec(
+        Arc::new(PidFd::new(&process, flags & O_NONBLOCK != 0)),
+        true,
+    )
+    .map(|fd| fd as _)
+}
+
 /// ARCH_PRCTL codes
 ///
 /// It is only avaliable on x86_64, and is not convenient
@@ -116,7 +165,32 @@ pub fn sys_set_tid_address(clear_child_tid: usize) -> LinuxResult<isize> {
     curr.task_ext()
         .thread_data()
         .set_clear_child_tid(clear_child_tid);
-    Ok(curr.id().as_u64() as isize)
+    Ok(curr.task_ext().thread.tid() as isize)
+}
+
+/// The size of a thread name buffer, including the NUL, like Linux's
+/// `TASK_COMM_LEN`.
+const TASK_COMM_LEN: usize = 16;
+
+pub fn sys_prctl(option: u32, arg2: usize) -> LinuxResult<isize> {
+    debug!("sys_prctl: option = {}, arg2 = {:#x}", option, arg2);
+    match option {
+        PR_SET_NAME => {
+            let name = UserConstPtr::<u8>::from(arg2).get_as_null_terminated()?;
+            let name = &name[..name.len().min(TASK_COMM_LEN - 1)];
+            current().set_name(&String::from_utf8_lossy(name));
+            Ok(0)
+        }
+        PR_GET_NAME => {
+            let buf = UserPtr::<u8>::from(arg2).get_as_mut_slice(TASK_COMM_LEN)?;
+            let name = current().name();
+            let name = &name.as_bytes()[..name.len().min(TASK_COMM_LEN - 1)];
+            buf.fill(0);
+            buf[..name.len()].copy_from_slice(name);
+            Ok(0)
+        }
+        _ => Err(LinuxError::EINVAL),
+    }
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -125,8 +199,6 @@ pub fn sys_arch_prctl(
     code: i32,
     addr: usize,
 ) -> LinuxResult<isize> {
-    use crate::ptr::UserPtr;
-
     let code = ArchPrctlCode::try_from(code).map_err(|_| axerrno::LinuxError::EINVAL)?;
     debug!("sys_arch_prctl: code = {:?}, addr = {:#x}", code, addr);
 
@@ -1,17 +1,24 @@
 use alloc::{sync::Arc, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
 use axprocess::{Pid, Process};
+use axsignal::Signo;
 use axtask::{TaskExtRef, current};
 use bitflags::bitflags;
 use linux_raw_sys::general::{
-    __WALL, __WCLONE, __WNOTHREAD, WCONTINUED, WEXITED, WNOHANG, WNOWAIT, WUNTRACED,
+    __WALL, __WCLONE, __WNOTHREAD, P_ALL, P_PGID, P_PID, P_PIDFD, WCONTINUED, WEXITED, WNOHANG,
+    WNOWAIT, WUNTRACED, rusage, siginfo,
 };
-use starry_core::task::ProcessData;
+use starry_core::task::{JobEvent, ProcessData, ResourceUsage, process_usage};
 
-use crate::ptr::{UserPtr, nullable};
+use crate::{
+    file::{FileLike, PidFd},
+    ptr::{UserPtr, nullable},
+    signal::{child_signal_info, wait_interruptible},
+    time::make_rusage,
+};
 
 bitflags! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
     struct WaitOptions: u32 {
         /// Do not block when there are no processes wishing to report status.
         const WNOHANG = WNOHANG;
@@ -45,6 +52,24 @@ enum WaitPid {
     Pgid(Pid),
 }
 
+/// Returns the job control change of `child` that `options` asks for, with
+/// its wait status.
+fn job_event(child: &Process, options: WaitOptions) -> Option<i32> {
+    let job = &child.data::<ProcessData>()?.job;
+    let event = job.event()?;
+    let wanted = match event {
+        JobEvent::Stopped(_) => options.contains(WaitOptions::WUNTRACED),
+        JobEvent::Continued => options.contains(WaitOptions::WCONTINUED),
+    };
+    if !wanted {
+        return None;
+    }
+    if !options.contains(WaitOptions::WNOWAIT) {
+        job.clear_event();
+    }
+    Some(event.wait_status())
+}
+
 impl WaitPid {
     fn apply(&self, child: &Arc<Process>) -> bool {
         match self {
@@ -55,24 +80,31 @@ impl WaitPid {
     }
 }
 
-pub fn sys_waitpid(pid: i32, exit_code_ptr: UserPtr<i32>, options: u32) -> LinuxResult<isize> {
-    let options = WaitOptions::from_bits_truncate(options);
-    info!("sys_waitpid <= pid: {:?}, options: {:?}", pid, options);
+/// A state change of a child, collected by [`wait_child`].
+struct Waited {
+    child: Arc<Process>,
+    /// The wait status describing the change.
+    status: i32,
+    /// The usage of the child and its reaped descendants.
+    usage: ResourceUsage,
+}
+
+/// Returns the usage of `child` and its reaped descendants.
+fn child_usage(child: &Process) -> ResourceUsage {
+    let mut usage = process_usage(child);
+    usage += *child.data::<ProcessData>().unwrap().children_usage.lock();
+    usage
+}
 
+/// Waits for a state change of a child selected by `pid` and `options`,
+/// reaping it if it has terminated.
+///
+/// Returns `None` with `WNOHANG` if no selected child has changed yet.
+fn wait_child(pid: WaitPid, options: WaitOptions) -> LinuxResult<Option<Waited>> {
     let curr = current();
     let proc_data = curr.task_ext().process_data();
     let process = curr.task_ext().thread.process();
 
-    let pid = if pid == -1 {
-        WaitPid::Any
-    } else if pid == 0 {
-        WaitPid::Pgid(process.group().pgid())
-    } else if pid > 0 {
-        WaitPid::Pid(pid as _)
-    } else {
-        WaitPid::Pgid(-pid as _)
-    };
-
     let children = process
         .children()
         .into_iter()
@@ -87,20 +119,116 @@ pub fn sys_waitpid(pid: i32, exit_code_ptr: UserPtr<i32>, options: u32) -> Linux
         return Err(LinuxError::ECHILD);
     }
 
-    let exit_code = nullable!(exit_code_ptr.get_as_mut())?;
     loop {
-        if let Some(child) = children.iter().find(|child| child.is_zombie()) {
+        if options.contains(WaitOptions::WEXITED)
+            && let Some(child) = children.iter().find(|child| child.is_zombie())
+        {
+            let usage = child_usage(child);
             if !options.contains(WaitOptions::WNOWAIT) {
+                *proc_data.children_usage.lock() += usage;
                 child.free();
             }
-            if let Some(exit_code) = exit_code {
-                *exit_code = child.exit_code();
-            }
-            return Ok(child.pid() as _);
+            return Ok(Some(Waited {
+                child: child.clone(),
+                status: child.exit_code(),
+                usage,
+            }));
+        } else if let Some((child, status)) = children
+            .iter()
+            .find_map(|child| Some((child, job_event(child, options)?)))
+        {
+            return Ok(Some(Waited {
+                child: child.clone(),
+                status,
+                usage: child_usage(child),
+            }));
         } else if options.contains(WaitOptions::WNOHANG) {
-            return Ok(0);
+            return Ok(None);
         } else {
-            proc_data.child_exit_wq.wait();
+            wait_interruptible(&proc_data.child_exit_wq)?;
+        }
+    }
+}
+
+pub fn sys_waitpid(
+    pid: i32,
+    exit_code_ptr: UserPtr<i32>,
+    options: u32,
+    rusage: UserPtr<rusage>,
+) -> LinuxResult<isize> {
+    let options = WaitOptions::from_bits_truncate(options) | WaitOptions::WEXITED;
+    info!("sys_waitpid <= pid: {:?}, options: {:?}", pid, options);
+
+    let pid = if pid == -1 {
+        WaitPid::Any
+    } else if pid == 0 {
+        WaitPid::Pgid(current().task_ext().thread.process().group().pgid())
+    } else if pid > 0 {
+        WaitPid::Pid(pid as _)
+    } else {
+        WaitPid::Pgid(-pid as _)
+    };
+
+    let exit_code = nullable!(exit_code_ptr.get_as_mut())?;
+    let rusage = nullable!(rusage.get_as_mut())?;
+    let Some(waited) = wait_child(pid, options)? else {
+        return Ok(0);
+    };
+    if let Some(exit_code) = exit_code {
+        *exit_code = waited.status;
+    }
+    if let Some(rusage) = rusage {
+        *rusage = make_rusage(&waited.usage);
+    }
+    Ok(waited.child.pid() as _)
+}
+
+pub fn sys_waitid(
+    idtype: u32,
+    id: u32,
+    info: UserPtr<siginfo>,
+    options: u32,
+    rusage: UserPtr<rusage>,
+) -> LinuxResult<isize> {
+    let mut options = WaitOptions::from_bits_truncate(options);
+    info!(
+        "sys_waitid <= idtype: {}, id: {}, options: {:?}",
+        idtype, id, options
+    );
+    if !options.intersects(WaitOptions::WEXITED | WaitOptions::WUNTRACED | WaitOptions::WCONTINUED)
+    {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let pid = match idtype {
+        P_ALL => WaitPid::Any,
+        P_PID => WaitPid::Pid(id),
+        P_PGID if id == 0 => WaitPid::Pgid(current().task_ext().thread.process().group().pgid()),
+        P_PGID => WaitPid::Pgid(id),
+        P_PIDFD => {
+            let pidfd = PidFd::from_fd(id as _)?;
+            if pidfd.nonblocking() {
+                options |= WaitOptions::WNOHANG;
+            }
+            WaitPid::Pid(pidfd.process()?.pid())
         }
+        _ => return Err(LinuxError::EINVAL),
+    };
+
+    let info = nullable!(info.get_as_mut())?;
+    let rusage = nullable!(rusage.get_as_mut())?;
+    let waited = wait_child(pid, options)?;
+    if let Some(info) = info {
+        *info = match &waited {
+            Some(waited) => child_signal_info(Signo::SIGCHLD, &waited.child, waited.status).0,
+            // Like Linux, no change yet is reported with a cleared siginfo
+            // SAFETY: valid for siginfo
+            None => unsafe { core::mem::zeroed() },
+        };
+    }
+    if let Some(rusage) = rusage {
+        let usage = waited.map_or_else(ResourceUsage::default, |waited| waited.usage);
+        *rusage = make_rusage(&usage);
     }
+    Ok(0)
 }
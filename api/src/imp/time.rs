@@ -1,36 +1,447 @@
+use alloc::{
+    boxed::Box,
+    sync::{Arc, Weak},
+};
+use core::ffi::c_long;
+
 use axerrno::{LinuxError, LinuxResult};
-use axhal::time::{monotonic_time, monotonic_time_nanos, nanos_to_ticks, wall_time};
+use axhal::time::{TimeValue, monotonic_time, monotonic_time_nanos};
+use axprocess::{Pid, Process};
+use axsignal::{SignalInfo, Signo};
+use axsync::Mutex;
+use axtask::{TaskExtRef, WaitQueue, current};
 use linux_raw_sys::general::{
-    __kernel_clockid_t, CLOCK_MONOTONIC, CLOCK_REALTIME, timespec, timeval,
+    __kernel_clockid_t, CLOCK_BOOTTIME, CLOCK_BOOTTIME_ALARM, CLOCK_MONOTONIC,
+    CLOCK_MONOTONIC_COARSE, CLOCK_MONOTONIC_RAW, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME,
+    CLOCK_REALTIME_ALARM, CLOCK_REALTIME_COARSE, CLOCK_TAI, CLOCK_THREAD_CPUTIME_ID, SI_KERNEL,
+    TIMER_ABSTIME, itimerval, rusage, timespec, timeval,
 };
-use starry_core::task::time_stat_output;
+use starry_core::{
+    task::{
+        ProcessData, SyscallRestart, ThreadData, get_process, get_thread, process_cpu_time,
+        process_usage, update_time_stat,
+    },
+    time::{
+        ITimerValue, RealTimer, TimerClock, TimerType, USER_HZ, clock_ticks, realtime,
+        realtime_slew_left, set_realtime, slew_realtime, step_realtime,
+    },
+    timer::{cancel_timer, set_timer},
+};
+
+use super::timer::realtime_was_set;
+use crate::{
+    ptr::{UserConstPtr, UserPtr, nullable},
+    signal::{
+        SIGNAL_CHECK_INTERVAL, have_signals, interrupted, send_signal_process,
+        wait_timeout_interruptible,
+    },
+    time::{TimeValueLike, make_rusage, parse_timespec, parse_timeval},
+};
+
+/// Decodes the clock id of the CPU time of another process or thread, as
+/// `clock_getcpuclockid` and `pthread_getcpuclockid` make them.
+fn parse_cpu_clock(clock_id: __kernel_clockid_t) -> LinuxResult<TimerClock> {
+    // Encoded like `MAKE_PROCESS_CPUCLOCK` and `MAKE_THREAD_CPUCLOCK` of
+    // Linux. The profiling, virtual and scheduler clocks are all measured
+    // as user and kernel time together.
+    let pid = !(clock_id >> 3) as Pid;
+    if clock_id & 3 == 3 {
+        return Err(LinuxError::EINVAL);
+    }
+    let curr = current();
+    let own_process = curr.task_ext().thread.process();
+    if clock_id & 4 != 0 {
+        if pid == 0 {
+            return Ok(TimerClock::ThreadCpu(curr.task_ext().thread.tid()));
+        }
+        // Only threads of the same process may be measured, like Linux
+        match get_thread(pid) {
+            Ok(thread) if thread.process().pid() == own_process.pid() => {
+                Ok(TimerClock::ThreadCpu(pid))
+            }
+            _ => Err(LinuxError::EINVAL),
+        }
+    } else if pid == 0 {
+        Ok(TimerClock::ProcessCpu(own_process.pid()))
+    } else {
+        get_process(pid).map_err(|_| LinuxError::EINVAL)?;
+        Ok(TimerClock::ProcessCpu(pid))
+    }
+}
+
+/// Parses a `clockid_t`.
+pub(crate) fn parse_clock(clock_id: __kernel_clockid_t) -> LinuxResult<TimerClock> {
+    if clock_id < 0 {
+        return parse_cpu_clock(clock_id);
+    }
+    let curr = current();
+    Ok(match clock_id as u32 {
+        // Without leap seconds and suspend, the variants of each read the
+        // same time
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE | CLOCK_REALTIME_ALARM | CLOCK_TAI => {
+            TimerClock::Realtime
+        }
+        CLOCK_MONOTONIC
+        | CLOCK_MONOTONIC_RAW
+        | CLOCK_MONOTONIC_COARSE
+        | CLOCK_BOOTTIME
+        | CLOCK_BOOTTIME_ALARM => TimerClock::Monotonic,
+        CLOCK_PROCESS_CPUTIME_ID => TimerClock::ProcessCpu(curr.task_ext().thread.process().pid()),
+        CLOCK_THREAD_CPUTIME_ID => TimerClock::ThreadCpu(curr.task_ext().thread.tid()),
+        _ => {
+            warn!("Unsupported clock {}", clock_id);
+            return Err(LinuxError::EINVAL);
+        }
+    })
+}
 
-use crate::{ptr::UserPtr, time::TimeValueLike};
+/// Reads a clock, failing if the process or thread it measures is gone.
+pub(crate) fn clock_now(clock: TimerClock) -> LinuxResult<TimeValue> {
+    Ok(match clock {
+        TimerClock::Realtime => realtime(),
+        TimerClock::Monotonic => monotonic_time(),
+        TimerClock::ProcessCpu(pid) => {
+            process_cpu_time(&get_process(pid).map_err(|_| LinuxError::EINVAL)?)
+        }
+        TimerClock::ThreadCpu(tid) => get_thread(tid)
+            .ok()
+            .and_then(|thread| thread.data::<ThreadData>().map(ThreadData::cpu_time))
+            .ok_or(LinuxError::EINVAL)?,
+    })
+}
 
 pub fn sys_clock_gettime(
     clock_id: __kernel_clockid_t,
     ts: UserPtr<timespec>,
 ) -> LinuxResult<isize> {
-    let now = match clock_id as u32 {
-        CLOCK_REALTIME => wall_time(),
-        CLOCK_MONOTONIC => monotonic_time(),
-        _ => {
-            warn!(
-                "Called sys_clock_gettime for unsupported clock {}",
-                clock_id
-            );
+    let clock = parse_clock(clock_id)?;
+    if clock.is_cpu() {
+        update_time_stat();
+    }
+    *ts.get_as_mut()? = timespec::from_time_value(clock_now(clock)?);
+    Ok(0)
+}
+
+pub fn sys_clock_getres(
+    clock_id: __kernel_clockid_t,
+    res: UserPtr<timespec>,
+) -> LinuxResult<isize> {
+    parse_clock(clock_id)?;
+    // All clocks read the precise time here, even the coarse ones
+    if let Some(res) = nullable!(res.get_as_mut())? {
+        *res = timespec::from_time_value(TimeValue::from_nanos(1));
+    }
+    Ok(0)
+}
+
+/// Sleeps until `clock` reaches `deadline`, returning the time that was left
+/// if a signal interrupted the sleep.
+fn sleep_until(clock: TimerClock, deadline: TimeValue) -> LinuxResult<Option<TimeValue>> {
+    loop {
+        let Some(left) = deadline
+            .checked_sub(clock_now(clock)?)
+            .filter(|left| !left.is_zero())
+        else {
+            return Ok(None);
+        };
+        if have_signals() {
+            return Ok(Some(left));
+        }
+        // Nothing else wakes us, but signals do, and we wake up now and
+        // then to notice the clock being set
+        wait_timeout_interruptible(&WaitQueue::new(), left.min(SIGNAL_CHECK_INTERVAL));
+    }
+}
+
+/// Sleeps for `nanosleep` and `clock_nanosleep`, writing the time left to
+/// `rem` if a signal interrupts a relative sleep.
+///
+/// Like Linux, relative sleeps continue towards the same deadline through
+/// `restart_syscall`, and absolute ones are simply restarted.
+fn do_nanosleep(
+    clock: TimerClock,
+    deadline: TimeValue,
+    absolute: bool,
+    rem: UserPtr<timespec>,
+) -> LinuxResult<isize> {
+    let Some(left) = sleep_until(clock, deadline)? else {
+        return Ok(0);
+    };
+    if absolute {
+        return Err(interrupted(SyscallRestart::NoHand));
+    }
+    if let Some(rem) = nullable!(rem.get_as_mut())? {
+        *rem = timespec::from_time_value(left);
+    }
+    let rem = rem.address().as_usize();
+    Err(interrupted(SyscallRestart::Block(Box::new(move || {
+        do_nanosleep(clock, deadline, false, rem.into())
+    }))))
+}
+
+pub fn sys_nanosleep(req: UserConstPtr<timespec>, rem: UserPtr<timespec>) -> LinuxResult<isize> {
+    let req = parse_timespec(req.get_as_ref()?)?;
+    debug!("sys_nanosleep <= {:?}", req);
+    let deadline = monotonic_time() + req;
+    do_nanosleep(TimerClock::Monotonic, deadline, false, rem)
+}
+
+pub fn sys_clock_nanosleep(
+    clock_id: __kernel_clockid_t,
+    flags: u32,
+    req: UserConstPtr<timespec>,
+    rem: UserPtr<timespec>,
+) -> LinuxResult<isize> {
+    let clock = parse_clock(clock_id)?;
+    let curr = current();
+    // The own thread's CPU time can't advance while it sleeps, so Linux
+    // refuses it. Other threads still run up the process's.
+    match clock {
+        TimerClock::ThreadCpu(tid) if tid == curr.task_ext().thread.tid() => {
             return Err(LinuxError::EINVAL);
         }
+        _ => {}
+    }
+    let req = parse_timespec(req.get_as_ref()?)?;
+    let absolute = flags & TIMER_ABSTIME != 0;
+    debug!("sys_clock_nanosleep <= {:?} {:?} {}", clock, req, absolute);
+
+    // Relative sleeps last as long whatever happens to the wall clock
+    let clock = match clock {
+        TimerClock::Realtime if !absolute => TimerClock::Monotonic,
+        clock => clock,
     };
-    *ts.get_as_mut()? = timespec::from_time_value(now);
-    Ok(0)
+    let deadline = if absolute {
+        req
+    } else {
+        clock_now(clock)? + req
+    };
+    do_nanosleep(clock, deadline, absolute, rem)
 }
 
 pub fn sys_gettimeofday(ts: UserPtr<timeval>) -> LinuxResult<isize> {
-    *ts.get_as_mut()? = timeval::from_time_value(wall_time());
+    *ts.get_as_mut()? = timeval::from_time_value(realtime());
     Ok(0)
 }
 
+fn set_realtime_clock(time: TimeValue) {
+    info!("Set the wall clock to {:?}", time);
+    realtime_was_set(set_realtime(time));
+}
+
+pub fn sys_clock_settime(
+    clock_id: __kernel_clockid_t,
+    ts: UserConstPtr<timespec>,
+) -> LinuxResult<isize> {
+    parse_clock(clock_id)?;
+    // Only the wall clock can be set
+    if clock_id as u32 != CLOCK_REALTIME {
+        return Err(LinuxError::EINVAL);
+    }
+    set_realtime_clock(parse_timespec(ts.get_as_ref()?)?);
+    Ok(0)
+}
+
+pub fn sys_settimeofday(tv: UserConstPtr<timeval>, _tz: UserConstPtr<u8>) -> LinuxResult<isize> {
+    // The timezone is obsolete and ignored
+    if let Some(tv) = nullable!(tv.get_as_ref())? {
+        set_realtime_clock(parse_timeval(tv)?);
+    }
+    Ok(0)
+}
+
+/// `struct timex` of `adjtimex`, defined here since linux_raw_sys doesn't
+/// have it.
+#[repr(C)]
+pub struct Timex {
+    modes: u32,
+    offset: c_long,
+    freq: c_long,
+    maxerror: c_long,
+    esterror: c_long,
+    status: i32,
+    constant: c_long,
+    precision: c_long,
+    tolerance: c_long,
+    time: timeval,
+    tick: c_long,
+    ppsfreq: c_long,
+    jitter: c_long,
+    shift: i32,
+    stabil: c_long,
+    jitcnt: c_long,
+    calcnt: c_long,
+    errcnt: c_long,
+    stbcnt: c_long,
+    tai: i32,
+    _reserved: [i32; 11],
+}
+
+// The modes and status bits of `adjtimex`, defined here since linux_raw_sys
+// doesn't have them
+const ADJ_OFFSET: u32 = 0x0001;
+const ADJ_FREQUENCY: u32 = 0x0002;
+const ADJ_MAXERROR: u32 = 0x0004;
+const ADJ_ESTERROR: u32 = 0x0008;
+const ADJ_STATUS: u32 = 0x0010;
+const ADJ_TIMECONST: u32 = 0x0020;
+const ADJ_TAI: u32 = 0x0080;
+const ADJ_SETOFFSET: u32 = 0x0100;
+const ADJ_MICRO: u32 = 0x1000;
+const ADJ_NANO: u32 = 0x2000;
+const ADJ_TICK: u32 = 0x4000;
+const ADJ_ADJTIME: u32 = 0x8000;
+const ADJ_OFFSET_SINGLESHOT: u32 = 0x8001;
+const ADJ_OFFSET_SS_READ: u32 = 0xa001;
+const STA_UNSYNC: i32 = 0x0040;
+const STA_NANO: i32 = 0x2000;
+/// The status bits only the kernel changes
+const STA_RONLY: i32 = 0xff00;
+const TIME_OK: isize = 0;
+const TIME_ERROR: isize = 5;
+
+/// The largest offset slewed by `ADJ_OFFSET`, in nanoseconds.
+const MAXPHASE: i128 = 500_000_000;
+/// The largest frequency offset, 500 ppm scaled by `1 << 16`.
+const MAXFREQ_SCALED: c_long = 500 << 16;
+/// The largest time constant.
+const MAXTC: c_long = 10;
+
+/// The NTP parameters of the wall clock. They are reported back as set, but
+/// only offsets act on the clock, slewing at a fixed rate rather than
+/// through the phase-locked loop of Linux.
+struct NtpState {
+    freq: c_long,
+    maxerror: c_long,
+    esterror: c_long,
+    status: i32,
+    constant: c_long,
+    tick: c_long,
+    tai: i32,
+}
+
+static NTP: Mutex<NtpState> = Mutex::new(NtpState {
+    freq: 0,
+    maxerror: 16_000_000,
+    esterror: 16_000_000,
+    status: STA_UNSYNC,
+    constant: 2,
+    tick: (1_000_000 / USER_HZ) as _,
+    tai: 0,
+});
+
+fn do_adjtimex(txc: &mut Timex) -> LinuxResult<isize> {
+    let modes = txc.modes;
+    let mut ntp = NTP.lock();
+    let nanos = |ntp: &NtpState| ntp.status & STA_NANO != 0;
+
+    if modes & ADJ_ADJTIME != 0 {
+        // The `adjtime` interface, in microseconds
+        if modes != ADJ_OFFSET_SINGLESHOT && modes != ADJ_OFFSET_SS_READ {
+            return Err(LinuxError::EINVAL);
+        }
+        let left = realtime_slew_left() / 1000;
+        if modes == ADJ_OFFSET_SINGLESHOT {
+            slew_realtime(txc.offset as i128 * 1000);
+        }
+        txc.offset = left as _;
+        return Ok(if ntp.status & STA_UNSYNC != 0 {
+            TIME_ERROR
+        } else {
+            TIME_OK
+        });
+    }
+
+    if modes & ADJ_SETOFFSET != 0 {
+        let sub_max = if modes & ADJ_NANO != 0 {
+            1_000_000_000
+        } else {
+            1_000_000
+        };
+        if !(0..sub_max).contains(&txc.time.tv_usec) {
+            return Err(LinuxError::EINVAL);
+        }
+        let sub = txc.time.tv_usec as i128 * (1_000_000_000 / sub_max) as i128;
+        let delta = txc.time.tv_sec as i128 * 1_000_000_000 + sub;
+        info!("Step the wall clock by {}ns", delta);
+        step_realtime(delta);
+        realtime_was_set(delta);
+    }
+    if modes & ADJ_TICK != 0 {
+        let tick = 1_000_000 / USER_HZ as c_long;
+        if !(tick * 9 / 10..=tick * 11 / 10).contains(&txc.tick) {
+            return Err(LinuxError::EINVAL);
+        }
+        ntp.tick = txc.tick;
+    }
+    if modes & ADJ_NANO != 0 {
+        ntp.status |= STA_NANO;
+    }
+    if modes & ADJ_MICRO != 0 {
+        ntp.status &= !STA_NANO;
+    }
+    if modes & ADJ_STATUS != 0 {
+        ntp.status = (ntp.status & STA_RONLY) | (txc.status & !STA_RONLY);
+    }
+    if modes & ADJ_FREQUENCY != 0 {
+        ntp.freq = txc.freq.clamp(-MAXFREQ_SCALED, MAXFREQ_SCALED);
+    }
+    if modes & ADJ_MAXERROR != 0 {
+        ntp.maxerror = txc.maxerror;
+    }
+    if modes & ADJ_ESTERROR != 0 {
+        ntp.esterror = txc.esterror;
+    }
+    if modes & ADJ_TIMECONST != 0 {
+        ntp.constant = txc.constant.clamp(0, MAXTC);
+    }
+    if modes & ADJ_TAI != 0 && txc.constant >= 0 {
+        ntp.tai = txc.constant as _;
+    }
+    if modes & ADJ_OFFSET != 0 {
+        let unit = if nanos(&ntp) { 1 } else { 1000 };
+        slew_realtime((txc.offset as i128 * unit).clamp(-MAXPHASE, MAXPHASE));
+    }
+
+    let unit = if nanos(&ntp) { 1 } else { 1000 };
+    txc.offset = (realtime_slew_left() / unit) as _;
+    txc.freq = ntp.freq;
+    txc.maxerror = ntp.maxerror;
+    txc.esterror = ntp.esterror;
+    txc.status = ntp.status;
+    txc.constant = ntp.constant;
+    txc.precision = 1;
+    txc.tolerance = MAXFREQ_SCALED;
+    txc.tick = ntp.tick;
+    txc.tai = ntp.tai;
+    let now = realtime();
+    txc.time = timeval {
+        tv_sec: now.as_secs() as _,
+        tv_usec: if nanos(&ntp) {
+            now.subsec_nanos()
+        } else {
+            now.subsec_micros()
+        } as _,
+    };
+    Ok(if ntp.status & STA_UNSYNC != 0 {
+        TIME_ERROR
+    } else {
+        TIME_OK
+    })
+}
+
+pub fn sys_adjtimex(buf: UserPtr<Timex>) -> LinuxResult<isize> {
+    do_adjtimex(buf.get_as_mut()?)
+}
+
+pub fn sys_clock_adjtime(clock_id: __kernel_clockid_t, buf: UserPtr<Timex>) -> LinuxResult<isize> {
+    parse_clock(clock_id)?;
+    if clock_id as u32 != CLOCK_REALTIME {
+        return Err(LinuxError::EOPNOTSUPP);
+    }
+    do_adjtimex(buf.get_as_mut()?)
+}
+
 #[repr(C)]
 pub struct Tms {
     /// user time
@@ -44,12 +455,183 @@ pub struct Tms {
 }
 
 pub fn sys_times(tms: UserPtr<Tms>) -> LinuxResult<isize> {
-    let (_, utime_us, _, stime_us) = time_stat_output();
-    *tms.get_as_mut()? = Tms {
-        tms_utime: utime_us,
-        tms_stime: stime_us,
-        tms_cutime: utime_us,
-        tms_cstime: stime_us,
-    };
-    Ok(nanos_to_ticks(monotonic_time_nanos()) as _)
+    update_time_stat();
+    let curr = current();
+    let usage = process_usage(curr.task_ext().thread.process());
+    let children = *curr.task_ext().process_data().children_usage.lock();
+    let ticks = |nanos: usize| clock_ticks(nanos as u64) as usize;
+    if let Some(tms) = nullable!(tms.get_as_mut())? {
+        *tms = Tms {
+            tms_utime: ticks(usage.utime_ns),
+            tms_stime: ticks(usage.stime_ns),
+            tms_cutime: ticks(children.utime_ns),
+            tms_cstime: ticks(children.stime_ns),
+        };
+    }
+    Ok(clock_ticks(monotonic_time_nanos()) as _)
+}
+
+// `who` values of getrusage, defined here since they have different types
+// in linux_raw_sys
+const RUSAGE_SELF: i32 = 0;
+const RUSAGE_CHILDREN: i32 = -1;
+const RUSAGE_THREAD: i32 = 1;
+
+pub fn sys_getrusage(who: i32, ru: UserPtr<rusage>) -> LinuxResult<isize> {
+    update_time_stat();
+    let curr = current();
+    let process = curr.task_ext().thread.process();
+    let usage = match who {
+        RUSAGE_SELF => process_usage(process),
+        RUSAGE_CHILDREN => *curr.task_ext().process_data().children_usage.lock(),
+        RUSAGE_THREAD => {
+            let mut usage = curr.task_ext().thread_data().usage();
+            // Memory is shared by the threads, like on Linux
+            usage.maxrss = curr.task_ext().process_data().sample_rss();
+            usage
+        }
+        _ => return Err(LinuxError::EINVAL),
+    };
+    *ru.get_as_mut()? = make_rusage(&usage);
+    Ok(0)
+}
+
+fn make_itimerval(value: ITimerValue) -> itimerval {
+    itimerval {
+        it_interval: timeval::from_time_value(value.interval),
+        it_value: timeval::from_time_value(value.value),
+    }
+}
+
+/// Arms `ITIMER_REAL` of `proc` to expire at `deadline`.
+fn arm_real_timer(proc: &Arc<Process>, real: &mut RealTimer, deadline: TimeValue) {
+    let proc = Arc::downgrade(proc);
+    real.deadline = Some(deadline);
+    real.timer = Some(set_timer(deadline, move || fire_real_timer(proc, deadline)));
+}
+
+fn fire_real_timer(proc: Weak<Process>, deadline: TimeValue) {
+    let Some(proc) = proc.upgrade() else {
+        return;
+    };
+    let Some(data) = proc.data::<ProcessData>() else {
+        return;
+    };
+    if proc.is_zombie() {
+        return;
+    }
+    {
+        let mut real = data.itimers.real.lock();
+        // The timer was set again meanwhile
+        if real.deadline != Some(deadline) {
+            return;
+        }
+        real.timer = None;
+        real.deadline = None;
+        if !real.interval.is_zero() {
+            let now = monotonic_time();
+            // Expirations missed while the system was busy are coalesced
+            let mut next = deadline + real.interval;
+            if next <= now {
+                next = now + real.interval;
+            }
+            arm_real_timer(&proc, &mut real, next);
+        }
+    }
+    let _ = send_signal_process(&proc, SignalInfo::new(Signo::SIGALRM, SI_KERNEL as _));
+}
+
+/// Sets an interval timer of the current process, returning its previous
+/// setting.
+fn set_itimer(which: TimerType, new: ITimerValue) -> ITimerValue {
+    let curr = current();
+    let proc_data = curr.task_ext().process_data();
+    if which != TimerType::REAL {
+        return proc_data.itimers.set_cpu_timer(which, new);
+    }
+
+    let mut real = proc_data.itimers.real.lock();
+    let old = real_timer_value(&real);
+    if let Some(timer) = real.timer.take() {
+        cancel_timer(timer);
+    }
+    real.deadline = None;
+    real.interval = new.interval;
+    if !new.value.is_zero() {
+        arm_real_timer(
+            curr.task_ext().thread.process(),
+            &mut real,
+            monotonic_time() + new.value,
+        );
+    }
+    old
+}
+
+fn real_timer_value(real: &RealTimer) -> ITimerValue {
+    let value = match real.deadline {
+        // An expired timer still armed reports a tiny value, like Linux
+        Some(deadline) => deadline
+            .checked_sub(monotonic_time())
+            .filter(|left| !left.is_zero())
+            .unwrap_or(TimeValue::from_micros(1)),
+        None => TimeValue::ZERO,
+    };
+    ITimerValue {
+        interval: real.interval,
+        value,
+    }
+}
+
+fn get_itimer(which: TimerType) -> ITimerValue {
+    let curr = current();
+    let proc_data = curr.task_ext().process_data();
+    if which == TimerType::REAL {
+        real_timer_value(&proc_data.itimers.real.lock())
+    } else {
+        proc_data.itimers.cpu_timer(which)
+    }
+}
+
+pub fn sys_getitimer(which: i32, curr_value: UserPtr<itimerval>) -> LinuxResult<isize> {
+    let which = TimerType::try_from(which).map_err(|_| LinuxError::EINVAL)?;
+    *curr_value.get_as_mut()? = make_itimerval(get_itimer(which));
+    Ok(0)
+}
+
+pub fn sys_setitimer(
+    which: i32,
+    new_value: UserConstPtr<itimerval>,
+    old_value: UserPtr<itimerval>,
+) -> LinuxResult<isize> {
+    let which = TimerType::try_from(which).map_err(|_| LinuxError::EINVAL)?;
+    // A null `new_value` disarms the timer, like Linux
+    let new = match nullable!(new_value.get_as_ref())? {
+        Some(new) => ITimerValue {
+            interval: parse_timeval(&new.it_interval)?,
+            value: parse_timeval(&new.it_value)?,
+        },
+        None => ITimerValue::default(),
+    };
+    info!("sys_setitimer <= {:?} {:?}", which, new);
+    let old = set_itimer(which, new);
+    if let Some(old_value) = nullable!(old_value.get_as_mut())? {
+        *old_value = make_itimerval(old);
+    }
+    Ok(0)
+}
+
+pub fn sys_alarm(seconds: u32) -> LinuxResult<isize> {
+    let old = set_itimer(
+        TimerType::REAL,
+        ITimerValue {
+            interval: TimeValue::ZERO,
+            value: TimeValue::from_secs(seconds as u64),
+        },
+    );
+    // Rounded to the nearest second, and an armed timer never reports zero
+    let mut left = old.value.as_secs();
+    if old.value.subsec_micros() >= 500_000 || (left == 0 && !old.value.is_zero()) {
+        left += 1;
+    }
+    Ok(left as _)
 }
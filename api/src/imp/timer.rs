@@ -0,0 +1,382 @@
+use alloc::sync::{Arc, Weak};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::{TimeValue, monotonic_time};
+use axprocess::{Pid, Process};
+use axsignal::{SignalInfo, Signo};
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::{
+    __kernel_clockid_t, SIGEV_NONE, SIGEV_SIGNAL, SIGEV_THREAD, SIGEV_THREAD_ID, TIMER_ABSTIME,
+    itimerspec, timespec,
+};
+use starry_core::{
+    task::{ProcessData, ThreadData, get_thread, process_cpu_time, processes},
+    time::{PosixTimer, TimerClock, TimerNotify, realtime},
+    timer::{cancel_timer, set_timer},
+};
+
+use super::time::{clock_now, parse_clock};
+use crate::{
+    ptr::{UserConstPtr, UserPtr, nullable},
+    signal::{send_signal_process, send_signal_thread, timer_signal_info, timer_signal_overrun},
+    time::{TimeValueLike, parse_timespec},
+};
+
+/// `struct sigevent`, defined here since linux_raw_sys names the fields of
+/// its union differently across versions.
+#[repr(C)]
+pub struct SigEvent {
+    value: usize,
+    signo: i32,
+    notify: i32,
+    /// `sigev_notify_thread_id`, for `SIGEV_THREAD_ID`
+    tid: i32,
+    _pad: [i32; 11],
+}
+
+/// The largest overrun count reported, like Linux.
+const DELAYTIMER_MAX: u32 = i32::MAX as u32;
+
+/// Reads the clock of a timer, which reads zero once a thread it measures
+/// is gone.
+fn timer_now(clock: TimerClock) -> TimeValue {
+    clock_now(clock).unwrap_or_default()
+}
+
+/// Starts a kernel timer firing `timer`, if it is armed and measures a wall
+/// clock.
+fn arm_timer(process: &Arc<Process>, id: i32, timer: &mut PosixTimer) {
+    let Some(expires) = timer.expires else {
+        return;
+    };
+    let deadline = match timer.clock {
+        TimerClock::Monotonic => expires,
+        // Converted to monotonic time with the current offset of the wall
+        // clock
+        TimerClock::Realtime => (expires + monotonic_time()).saturating_sub(realtime()),
+        TimerClock::ProcessCpu(_) | TimerClock::ThreadCpu(_) => return,
+    };
+    let process = Arc::downgrade(process);
+    timer.timer = Some(set_timer(deadline, move || {
+        fire_timer(process, id, expires)
+    }));
+}
+
+fn fire_timer(process: Weak<Process>, id: i32, expires: TimeValue) {
+    let Some(process) = process.upgrade() else {
+        return;
+    };
+    let Some(data) = process.data::<ProcessData>() else {
+        return;
+    };
+    if process.is_zombie() {
+        return;
+    }
+    let mut table = data.posix_timers.table.lock();
+    // The timer was deleted or set again meanwhile
+    let Some(timer) = table
+        .get_mut(&id)
+        .filter(|timer| timer.expires == Some(expires))
+    else {
+        return;
+    };
+    timer.timer = None;
+    let expirations = timer.advance(timer_now(timer.clock));
+    notify_timer(&process, id, timer, expirations);
+    arm_timer(&process, id, timer);
+}
+
+/// Sends the signal of `timer` for its `expirations`.
+///
+/// Like Linux, a timer has at most one signal queued, and expirations while
+/// it is pending are counted as overruns. We can't tell our own signal from
+/// others of the same number, so any pending one counts.
+fn notify_timer(process: &Process, id: i32, timer: &mut PosixTimer, expirations: u32) {
+    if expirations == 0 {
+        return;
+    }
+    let (signo, value, thread) = match timer.notify {
+        TimerNotify::None => return,
+        TimerNotify::Process { signo, value } => (signo, value, None),
+        TimerNotify::Thread { signo, value, tid } => (signo, value, get_thread(tid).ok()),
+    };
+    let pending = match &thread {
+        Some(thread) => thread
+            .data::<ThreadData>()
+            .is_some_and(|data| data.signal.pending().has(signo)),
+        None => process
+            .data::<ProcessData>()
+            .is_some_and(|data| data.signal.pending().has(signo)),
+    };
+    let overrun = timer
+        .pending_overrun
+        .saturating_add(expirations - 1)
+        .min(DELAYTIMER_MAX);
+    if pending {
+        timer.pending_overrun = overrun.saturating_add(1).min(DELAYTIMER_MAX);
+        return;
+    }
+    timer.pending_overrun = 0;
+    timer.overrun = overrun;
+
+    let sig = timer_signal_info(signo, id, overrun, value);
+    let _ = match thread {
+        Some(thread) => send_signal_thread(&thread, sig),
+        // The target thread exited
+        None => send_signal_process(process, sig),
+    };
+}
+
+/// Adds the expirations counted while the signal `sig` of a timer of the
+/// current process was pending to its overrun, as it is dequeued, like
+/// Linux.
+pub fn collect_timer_overrun(sig: &mut SignalInfo) {
+    let Some((id, overrun)) = timer_signal_overrun(sig) else {
+        return;
+    };
+    let curr = current();
+    let mut table = curr.task_ext().process_data().posix_timers.table.lock();
+    let Some(timer) = table.get_mut(&id) else {
+        return;
+    };
+    let total = (*overrun as u32)
+        .saturating_add(timer.pending_overrun)
+        .min(DELAYTIMER_MAX);
+    timer.pending_overrun = 0;
+    timer.overrun = total;
+    *overrun = total as _;
+}
+
+/// Fires the timers of CPU clocks of the current process which expired.
+/// Called on traps, as CPU time is only charged then.
+pub fn check_cpu_timers() {
+    let curr = current();
+    let proc_data = curr.task_ext().process_data();
+    if !proc_data.posix_timers.cpu_armed() {
+        return;
+    }
+    let process = curr.task_ext().thread.process();
+    let tid = curr.task_ext().thread.tid();
+    let mut table = proc_data.posix_timers.table.lock();
+    let mut process_time = None;
+    for (&id, timer) in table.iter_mut() {
+        let Some(expires) = timer.expires else {
+            continue;
+        };
+        let now = match timer.clock {
+            TimerClock::ProcessCpu(_) => {
+                *process_time.get_or_insert_with(|| process_cpu_time(process))
+            }
+            // Only the thread itself advances its clock
+            TimerClock::ThreadCpu(owner) if owner == tid => {
+                curr.task_ext().thread_data().cpu_time()
+            }
+            _ => continue,
+        };
+        if now >= expires {
+            let expirations = timer.advance(now);
+            notify_timer(process, id, timer, expirations);
+        }
+    }
+    proc_data.posix_timers.update_cpu_armed(&table);
+}
+
+fn parse_sigevent(event: &SigEvent) -> LinuxResult<TimerNotify> {
+    let signo = || {
+        u8::try_from(event.signo)
+            .ok()
+            .and_then(Signo::from_repr)
+            .ok_or(LinuxError::EINVAL)
+    };
+    let notify = event.notify as u32;
+    Ok(if notify == SIGEV_NONE {
+        TimerNotify::None
+    } else if notify == SIGEV_SIGNAL || notify == SIGEV_THREAD {
+        TimerNotify::Process {
+            signo: signo()?,
+            value: event.value,
+        }
+    } else if notify == SIGEV_SIGNAL | SIGEV_THREAD_ID {
+        let tid = event.tid as Pid;
+        let curr = current();
+        // The thread must be in the same process
+        if !get_thread(tid)
+            .is_ok_and(|thread| thread.process().pid() == curr.task_ext().thread.process().pid())
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        TimerNotify::Thread {
+            signo: signo()?,
+            value: event.value,
+            tid,
+        }
+    } else {
+        return Err(LinuxError::EINVAL);
+    })
+}
+
+pub fn sys_timer_create(
+    clock_id: __kernel_clockid_t,
+    sevp: UserConstPtr<SigEvent>,
+    timer_id: UserPtr<i32>,
+) -> LinuxResult<isize> {
+    let clock = parse_clock(clock_id)?;
+    // CPU clocks are checked as the process runs, so only its own work
+    if let TimerClock::ProcessCpu(pid) = clock
+        && pid != current().task_ext().thread.process().pid()
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    let notify = nullable!(sevp.get_as_ref())?
+        .map(parse_sigevent)
+        .transpose()?;
+    let timer_id = timer_id.get_as_mut()?;
+
+    let curr = current();
+    let timers = &curr.task_ext().process_data().posix_timers;
+    let id = timers.insert(PosixTimer::new(clock, TimerNotify::None));
+    // Without an event, SIGALRM is sent with the timer id as value
+    let notify = notify.unwrap_or(TimerNotify::Process {
+        signo: Signo::SIGALRM,
+        value: id as usize,
+    });
+    if let Some(timer) = timers.table.lock().get_mut(&id) {
+        timer.notify = notify;
+    }
+    info!("sys_timer_create <= {:?} {:?}: {}", clock, notify, id);
+    *timer_id = id;
+    Ok(0)
+}
+
+fn make_itimerspec(timer: &PosixTimer) -> itimerspec {
+    let value = match timer.expires {
+        // An expired timer still armed reports a tiny value
+        Some(expires) => expires
+            .checked_sub(timer_now(timer.clock))
+            .filter(|left| !left.is_zero())
+            .unwrap_or(TimeValue::from_nanos(1)),
+        None => TimeValue::ZERO,
+    };
+    itimerspec {
+        it_interval: timespec::from_time_value(timer.interval),
+        it_value: timespec::from_time_value(value),
+    }
+}
+
+pub fn sys_timer_settime(
+    timer_id: i32,
+    flags: u32,
+    new_value: UserConstPtr<itimerspec>,
+    old_value: UserPtr<itimerspec>,
+) -> LinuxResult<isize> {
+    let new = new_value.get_as_ref()?;
+    let interval = parse_timespec(&new.it_interval)?;
+    let value = parse_timespec(&new.it_value)?;
+    if flags & !TIMER_ABSTIME != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let curr = current();
+    let process = curr.task_ext().thread.process();
+    let timers = &curr.task_ext().process_data().posix_timers;
+    let mut table = timers.table.lock();
+    let timer = table.get_mut(&timer_id).ok_or(LinuxError::EINVAL)?;
+    let old = make_itimerspec(timer);
+
+    if let Some(kernel_timer) = timer.timer.take() {
+        cancel_timer(kernel_timer);
+    }
+    timer.interval = interval;
+    timer.absolute = flags & TIMER_ABSTIME != 0;
+    timer.pending_overrun = 0;
+    timer.expires = if value.is_zero() {
+        None
+    } else if timer.absolute {
+        Some(value)
+    } else {
+        Some(timer_now(timer.clock) + value)
+    };
+    debug!(
+        "sys_timer_settime <= {} {:?} {:?}",
+        timer_id, timer.expires, interval
+    );
+    arm_timer(process, timer_id, timer);
+    timers.update_cpu_armed(&table);
+    drop(table);
+
+    if let Some(old_value) = nullable!(old_value.get_as_mut())? {
+        *old_value = old;
+    }
+    Ok(0)
+}
+
+pub fn sys_timer_gettime(timer_id: i32, curr_value: UserPtr<itimerspec>) -> LinuxResult<isize> {
+    let curr = current();
+    let table = curr.task_ext().process_data().posix_timers.table.lock();
+    let timer = table.get(&timer_id).ok_or(LinuxError::EINVAL)?;
+    let value = make_itimerspec(timer);
+    drop(table);
+    *curr_value.get_as_mut()? = value;
+    Ok(0)
+}
+
+pub fn sys_timer_getoverrun(timer_id: i32) -> LinuxResult<isize> {
+    let curr = current();
+    let table = curr.task_ext().process_data().posix_timers.table.lock();
+    let timer = table.get(&timer_id).ok_or(LinuxError::EINVAL)?;
+    Ok(timer.overrun as _)
+}
+
+pub fn sys_timer_delete(timer_id: i32) -> LinuxResult<isize> {
+    let curr = current();
+    let timers = &curr.task_ext().process_data().posix_timers;
+    let mut table = timers.table.lock();
+    let timer = table.remove(&timer_id).ok_or(LinuxError::EINVAL)?;
+    if let Some(kernel_timer) = timer.timer {
+        cancel_timer(kernel_timer);
+    }
+    timers.update_cpu_armed(&table);
+    Ok(0)
+}
+
+/// Re-arms the `CLOCK_REALTIME` timers of all processes after the clock
+/// jumped by `delta_ns`.
+///
+/// Timers set to an absolute time now expire at a different monotonic time,
+/// while relative ones keep their remaining time like on Linux.
+pub(crate) fn realtime_was_set(delta_ns: i128) {
+    for process in processes() {
+        let Some(data) = process.data::<ProcessData>() else {
+            continue;
+        };
+        let mut table = data.posix_timers.table.lock();
+        for (&id, timer) in table.iter_mut() {
+            if timer.clock != TimerClock::Realtime || timer.expires.is_none() {
+                continue;
+            }
+            if let Some(kernel_timer) = timer.timer.take() {
+                cancel_timer(kernel_timer);
+            }
+            if !timer.absolute {
+                timer.expires = timer.expires.map(|expires| {
+                    let expires = expires.as_nanos() as i128 + delta_ns;
+                    TimeValue::from_nanos(expires.clamp(0, u64::MAX as i128) as u64)
+                });
+            }
+            arm_timer(&process, id, timer);
+        }
+    }
+}
+
+/// Deletes all POSIX timers of the current process, as `execve` does.
+pub fn delete_posix_timers() {
+    let curr = current();
+    let timers = &curr.task_ext().process_data().posix_timers;
+    let mut table = timers.table.lock();
+    for (_, timer) in core::mem::take(&mut *table) {
+        if let Some(kernel_timer) = timer.timer {
+            cancel_timer(kernel_timer);
+        }
+    }
+    timers.update_cpu_armed(&table);
+}
@@ -1,4 +1,4 @@
-use core::{ffi::c_int, fmt, ops::Deref};
+use core::{cmp::Ordering, ffi::c_int, fmt, ops::Deref};
 
 use alloc::{
     collections::btree_map::BTreeMap,
@@ -8,12 +8,22 @@ use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
 use axfs::api::canonicalize;
 use linux_raw_sys::general::AT_FDCWD;
 use spin::RwLock;
+use starry_core::file::mount::{MountFlags, resolve_path};
 
 use crate::file::{Directory, File, FileLike};
 
 /// 一个规范化的文件路径表示
-#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
-pub struct FilePath(String);
+///
+/// 路径中的绑定挂载已被解析，同时记录解析过程中经过的挂载点标志。
+/// 比较时只看解析后的路径。
+#[derive(Clone, Debug)]
+pub struct FilePath {
+    /// 解析绑定挂载后的路径
+    path: String,
+    /// 调用者看到的规范路径
+    visible: String,
+    mount_flags: MountFlags,
+}
 
 impl FilePath {
     /// 从路径字符串创建一个新的 `FilePath`，路径将被规范化。
@@ -33,12 +43,29 @@ impl FilePath {
             "canonical path should start with /"
         );
 
-        Ok(Self(HARDLINK_MANAGER.real_path(&new_path)))
+        let (path, mount_flags) = resolve_path(&new_path);
+        Ok(Self {
+            path: HARDLINK_MANAGER.real_path(&path),
+            visible: new_path,
+            mount_flags,
+        })
+    }
+
+    /// 返回路径所在挂载点的标志，包括解析时经过的绑定挂载的标志
+    pub fn mount_flags(&self) -> MountFlags {
+        self.mount_flags
     }
 
     /// 返回底层路径的字符串切片
     pub fn as_str(&self) -> &str {
-        &self.0
+        &self.path
+    }
+
+    /// 返回解析绑定挂载之前、调用者看到的路径
+    ///
+    /// 相对路径应基于此路径解析，挂载点也记录在此路径上。
+    pub fn visible(&self) -> &str {
+        &self.visible
     }
 
     /// 返回父目录路径
@@ -79,12 +106,12 @@ impl FilePath {
 
     /// 判断是否为根目录
     pub fn is_root(&self) -> bool {
-        self.0 == "/"
+        self.path == "/"
     }
 
     /// 判断是否为目录（以 '/' 结尾）
     pub fn is_dir(&self) -> bool {
-        self.0.ends_with('/')
+        self.path.ends_with('/')
     }
 
     /// 判断是否为常规文件（不以 '/' 结尾）
@@ -94,22 +121,23 @@ impl FilePath {
 
     /// Whether the path exists
     pub fn exists(&self) -> bool {
-        axfs::api::absolute_path_exists(&self.0)
+        axfs::api::absolute_path_exists(&self.path)
     }
 
     /// 判断此路径是否以给定前缀路径开头
     pub fn starts_with(&self, prefix: &FilePath) -> bool {
-        self.0.starts_with(&prefix.0)
+        self.path.starts_with(&prefix.path)
     }
 
     /// 判断此路径是否以给定后缀路径结尾
     pub fn ends_with(&self, suffix: &FilePath) -> bool {
-        self.0.ends_with(&suffix.0)
+        self.path.ends_with(&suffix.path)
     }
 
     /// 将此路径与相对路径组件连接
     pub fn join<P: AsRef<str>>(&self, path: P) -> AxResult<Self> {
-        let mut new_path = self.0.clone();
+        // 从调用者看到的路径重新解析，以经过相同的绑定挂载
+        let mut new_path = self.visible.clone();
         if !new_path.ends_with('/') {
             new_path.push('/');
         }
@@ -119,19 +147,39 @@ impl FilePath {
 
     /// 返回此路径组件的迭代器
     pub fn components(&self) -> impl Iterator<Item = &str> {
-        self.0.trim_matches('/').split('/')
+        self.path.trim_matches('/').split('/')
     }
 }
 
 impl fmt::Display for FilePath {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(&self.0)
+        f.write_str(&self.path)
+    }
+}
+
+impl PartialEq for FilePath {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for FilePath {}
+
+impl PartialOrd for FilePath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FilePath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.path.cmp(&other.path)
     }
 }
 
 impl AsRef<str> for FilePath {
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.path
     }
 }
 
@@ -145,7 +193,7 @@ impl Deref for FilePath {
     type Target = str;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.path
     }
 }
 
@@ -1,17 +1,30 @@
+use core::{ffi::c_long, time::Duration};
+
 use axerrno::{LinuxError, LinuxResult};
 use axhal::{
     arch::TrapFrame,
     trap::{POST_TRAP, register_trap_handler},
 };
 use axprocess::{Process, ProcessGroup, Thread};
-use axsignal::{SignalInfo, SignalOSAction, SignalSet};
-use axtask::{TaskExtRef, current};
-use starry_core::task::{ProcessData, ThreadData};
+use axsignal::{
+    SignalActionFlags, SignalDisposition, SignalInfo, SignalOSAction, SignalSet, Signo,
+};
+use axtask::{TaskExtRef, WaitQueue, current};
+use linux_raw_sys::general::{
+    CLD_CONTINUED, CLD_DUMPED, CLD_EXITED, CLD_KILLED, CLD_STOPPED, SI_KERNEL, SI_TIMER, siginfo,
+};
+use starry_core::{
+    cpustat, random,
+    task::{
+        ExitStatus, JobEvent, ProcessData, SyscallRestart, ThreadData, block_current, process_usage,
+    },
+    time::{TimerType, clock_ticks},
+};
 
-use crate::do_exit;
+use crate::{check_cpu_timers, collect_timer_overrun, do_exit};
 
 pub fn check_signals(tf: &mut TrapFrame, restore_blocked: Option<SignalSet>) -> bool {
-    let Some((sig, os_action)) = current()
+    let Some((mut sig, os_action)) = current()
         .task_ext()
         .thread_data()
         .signal
@@ -19,22 +32,24 @@ pub fn check_signals(tf: &mut TrapFrame, restore_blocked: Option<SignalSet>) ->
     else {
         return false;
     };
+    // The handler already has its copy of the signal information, so only
+    // `timer_getoverrun` sees the overruns counted while it was pending
+    collect_timer_overrun(&mut sig);
 
     let signo = sig.signo();
     match os_action {
         SignalOSAction::Terminate => {
-            do_exit(128 + signo as i32, true);
+            do_exit(ExitStatus::Signaled(signo, false).wait_status(), true);
         }
         SignalOSAction::CoreDump => {
             // TODO: implement core dump
-            do_exit(128 + signo as i32, true);
+            do_exit(ExitStatus::Signaled(signo, true).wait_status(), true);
         }
         SignalOSAction::Stop => {
-            // TODO: implement stop
-            do_exit(1, true);
+            stop_current(signo);
         }
         SignalOSAction::Continue => {
-            // TODO: implement continue
+            // The process was resumed when the signal was sent
         }
         SignalOSAction::Handler => {
             // do nothing
@@ -43,30 +58,347 @@ pub fn check_signals(tf: &mut TrapFrame, restore_blocked: Option<SignalSet>) ->
     true
 }
 
+/// Returns the pending signals the current thread doesn't block.
+fn unblocked_pending() -> SignalSet {
+    let curr = current();
+    let signal = &curr.task_ext().thread_data().signal;
+    let blocked = signal.with_blocked_mut(|blocked| *blocked);
+    signal.pending() & !blocked
+}
+
+/// Returns whether the current thread has a pending signal it doesn't block,
+/// which interrupts blocking syscalls.
+pub fn have_signals() -> bool {
+    unblocked_pending() != SignalSet::default()
+}
+
+/// How long a blocked thread goes without noticing signals at most.
+///
+/// Sending a signal wakes its target, but a signal sent while the target is
+/// about to sleep may be missed, so interruptible waits also wake up on
+/// their own now and then to check.
+pub const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Fails with `EINTR` if a signal interrupts the current syscall, which is
+/// then continued as `restart` says.
+pub fn check_interrupted_with(restart: SyscallRestart) -> LinuxResult<()> {
+    if have_signals() {
+        Err(interrupted(restart))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns `EINTR` for the current syscall, which a signal interrupted, and
+/// records how it continues.
+pub fn interrupted(restart: SyscallRestart) -> LinuxError {
+    *current().task_ext().thread_data().restart.lock() = Some(restart);
+    LinuxError::EINTR
+}
+
+/// Fails with `EINTR` if a signal interrupts the current syscall, which is
+/// then restarted like `ERESTARTSYS`.
+pub fn check_interrupted() -> LinuxResult<()> {
+    check_interrupted_with(SyscallRestart::Sys)
+}
+
+/// Blocks on `wq` until notified, a signal is sent to the current thread or
+/// `timeout` elapses, returning whether it timed out.
+pub fn wait_timeout_interruptible(wq: &WaitQueue, timeout: Duration) -> bool {
+    let curr = current();
+    let data = curr.task_ext().thread_data();
+    // Set before checking, so that a signal sent after the check wakes us
+    data.set_interrupt_wq(Some(wq));
+    let timed_out =
+        !have_signals() && block_current(|| wq.wait_timeout(timeout.min(SIGNAL_CHECK_INTERVAL)));
+    data.set_interrupt_wq(None);
+    timed_out
+}
+
+/// Blocks on `wq` until `condition` holds, a signal is sent to the current
+/// thread or `timeout` elapses, returning whether it timed out.
+///
+/// `condition` is checked with the queue locked, so that a notification
+/// sent once it turns true is not missed, and must not block. Unlike
+/// [`wait_timeout_interruptible`], a notification alone doesn't end the wait.
+pub fn wait_timeout_until_interruptible(
+    wq: &WaitQueue,
+    timeout: Duration,
+    condition: impl Fn() -> bool,
+) -> bool {
+    let curr = current();
+    let data = curr.task_ext().thread_data();
+    data.set_interrupt_wq(Some(wq));
+    // Signals sent after the check below change the count
+    let interrupts = data.interrupt_count();
+    let timed_out = !have_signals()
+        && block_current(|| {
+            wq.wait_timeout_until(timeout.min(SIGNAL_CHECK_INTERVAL), || {
+                condition() || data.interrupt_count() != interrupts
+            })
+        });
+    data.set_interrupt_wq(None);
+    timed_out
+}
+
+/// Blocks on `wq` until notified or woken up to check for signals, failing
+/// with `EINTR` if a signal interrupts the wait, like [`check_interrupted`].
+///
+/// Callers check what they wait for again when this returns, as wakeups
+/// may be spurious.
+pub fn wait_interruptible(wq: &WaitQueue) -> LinuxResult<()> {
+    check_interrupted()?;
+    wait_timeout_interruptible(wq, SIGNAL_CHECK_INTERVAL);
+    check_interrupted()
+}
+
+/// Returns whether a handler runs for the signal the current thread handles
+/// next, and if so whether it has `SA_RESTART`.
+fn next_handler_restarts() -> Option<bool> {
+    let pending = unblocked_pending();
+    // Signals are delivered lowest first
+    let signo = (1..=64)
+        .filter_map(Signo::from_repr)
+        .find(|signo| pending.has(*signo))?;
+    let curr = current();
+    let actions = curr.task_ext().process_data().signal.actions.lock();
+    let action = &actions[signo];
+    matches!(action.disposition, SignalDisposition::Handler(_))
+        .then(|| action.flags.contains(SignalActionFlags::RESTART))
+}
+
+/// Takes how the current syscall continues, if a signal interrupted it, and
+/// whether it is to be restarted given the signal about to be handled.
+pub fn take_syscall_restart() -> Option<(SyscallRestart, bool)> {
+    let restart = current().task_ext().thread_data().restart.lock().take()?;
+    let restarted = match (&restart, next_handler_restarts()) {
+        (_, None) => true,
+        (SyscallRestart::Sys, Some(sa_restart)) => sa_restart,
+        (_, Some(_)) => false,
+    };
+    Some((restart, restarted))
+}
+
+/// Keeps the function continuing the current syscall for `restart_syscall`.
+pub fn set_restart_block(restart: SyscallRestart) {
+    *current().task_ext().thread_data().restart.lock() = Some(restart);
+}
+
 #[register_trap_handler(POST_TRAP)]
 fn post_trap_callback(tf: &mut TrapFrame, from_user: bool) {
+    cpustat::account_trap(from_user, from_user);
+    cpustat::count_trap();
+    random::add_interrupt_randomness();
     if !from_user {
         return;
     }
+    send_itimer_signals();
+    check_cpu_timers();
 
+    // Other threads of a stopped process stop here
+    current().task_ext().process_data().job.wait_while_stopped();
     check_signals(tf, None);
 }
 
+/// Sends the signals of the CPU time interval timers of the current process
+/// which expired.
+fn send_itimer_signals() {
+    let curr = current();
+    let itimers = &curr.task_ext().process_data().itimers;
+    for (which, signo) in [
+        (TimerType::VIRTUAL, Signo::SIGVTALRM),
+        (TimerType::PROF, Signo::SIGPROF),
+    ] {
+        if itimers.take_expired(which) {
+            let _ = send_signal_process(
+                curr.task_ext().thread.process(),
+                SignalInfo::new(signo, SI_KERNEL as _),
+            );
+        }
+    }
+}
+
+/// The start of `siginfo_t` with the fields of `SIGCHLD`, on 64-bit
+/// architectures.
+#[repr(C)]
+struct ChildSignalFields {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad: i32,
+    pid: i32,
+    uid: u32,
+    status: i32,
+    utime: c_long,
+    stime: c_long,
+}
+
+/// Splits a wait status into the `si_code` and `si_status` of `SIGCHLD`.
+pub fn child_status_code(wait_status: i32) -> (u32, i32) {
+    if wait_status == 0xffff {
+        (CLD_CONTINUED, Signo::SIGCONT as i32)
+    } else if wait_status & 0xff == 0x7f {
+        (CLD_STOPPED, (wait_status >> 8) & 0xff)
+    } else if wait_status & 0x7f == 0 {
+        (CLD_EXITED, (wait_status >> 8) & 0xff)
+    } else if wait_status & 0x80 != 0 {
+        (CLD_DUMPED, wait_status & 0x7f)
+    } else {
+        (CLD_KILLED, wait_status & 0x7f)
+    }
+}
+
+/// Builds the signal information about a state change of `child`, given as
+/// its wait status, like Linux fills in for `SIGCHLD`.
+pub fn child_signal_info(signo: Signo, child: &Process, wait_status: i32) -> SignalInfo {
+    let (code, status) = child_status_code(wait_status);
+    let mut sig = SignalInfo::new(signo, code as _);
+    let usage = process_usage(child);
+    // SAFETY: `siginfo` starts with these fields for SIGCHLD
+    let fields = unsafe { &mut *(&mut sig.0 as *mut siginfo).cast::<ChildSignalFields>() };
+    fields.pid = child.pid() as _;
+    fields.status = status;
+    fields.utime = clock_ticks(usage.utime_ns as u64) as _;
+    fields.stime = clock_ticks(usage.stime_ns as u64) as _;
+    sig
+}
+
+/// The start of `siginfo_t` with the fields of POSIX timers.
+#[repr(C)]
+struct TimerSignalFields {
+    signo: i32,
+    errno: i32,
+    code: i32,
+    _pad: i32,
+    timer_id: i32,
+    overrun: i32,
+    value: usize,
+}
+
+/// Builds the signal information of an expiration of a POSIX timer.
+pub fn timer_signal_info(signo: Signo, timer_id: i32, overrun: u32, value: usize) -> SignalInfo {
+    let mut sig = SignalInfo::new(signo, SI_TIMER as _);
+    // SAFETY: `siginfo` starts with these fields for SI_TIMER
+    let fields = unsafe { &mut *(&mut sig.0 as *mut siginfo).cast::<TimerSignalFields>() };
+    fields.timer_id = timer_id;
+    fields.overrun = overrun as _;
+    fields.value = value;
+    sig
+}
+
+/// Returns the timer ID and the overrun count of the signal information of
+/// a POSIX timer, or `None` for other signals.
+pub fn timer_signal_overrun(sig: &mut SignalInfo) -> Option<(i32, &mut i32)> {
+    if sig.code() != SI_TIMER as i32 {
+        return None;
+    }
+    // SAFETY: as in `timer_signal_info`
+    let fields = unsafe { &mut *(&mut sig.0 as *mut siginfo).cast::<TimerSignalFields>() };
+    Some((fields.timer_id, &mut fields.overrun))
+}
+
+/// Tells the parent of `proc` that it stopped or continued, with `SIGCHLD`
+/// unless the parent set `SA_NOCLDSTOP` for it.
+fn notify_parent(proc: &Process, wait_status: i32) {
+    let Some(parent) = proc.parent() else {
+        return;
+    };
+    let Some(data) = parent.data::<ProcessData>() else {
+        return;
+    };
+    let nocldstop = data.signal.actions.lock()[Signo::SIGCHLD]
+        .flags
+        .contains(SignalActionFlags::NOCLDSTOP);
+    if !nocldstop {
+        let _ = send_signal_process(
+            &parent,
+            child_signal_info(Signo::SIGCHLD, proc, wait_status),
+        );
+    }
+    data.child_exit_wq.notify_all(false);
+}
+
+/// Stops the current process by `signo`, waiting until it is resumed.
+fn stop_current(signo: Signo) {
+    let curr = current();
+    let job = &curr.task_ext().process_data().job;
+    if job.stop(signo) {
+        info!("{:?} stopped by {:?}", curr.task_ext().thread, signo);
+        let status = JobEvent::Stopped(signo).wait_status();
+        notify_parent(curr.task_ext().thread.process(), status);
+    }
+    job.wait_while_stopped();
+}
+
+/// Discards the signals in `set` pending for `proc` or any of its threads.
+fn discard_pending(proc: &Process, set: SignalSet) {
+    for thr in proc.threads() {
+        if let Some(data) = thr.data::<ThreadData>() {
+            while data.signal.dequeue_signal(&set).is_some() {}
+        }
+    }
+}
+
+/// Applies the effects signals have on a process when sent rather than when
+/// delivered: `SIGCONT` resumes a stopped process and discards its pending
+/// stop signals, which in turn discard a pending `SIGCONT`.
+fn resume_on_signal(proc: &Process, signo: Signo) {
+    let Some(data) = proc.data::<ProcessData>() else {
+        return;
+    };
+    match signo {
+        Signo::SIGCONT => {
+            let mut stops = SignalSet::default();
+            for stop in [
+                Signo::SIGSTOP,
+                Signo::SIGTSTP,
+                Signo::SIGTTIN,
+                Signo::SIGTTOU,
+            ] {
+                stops.add(stop);
+            }
+            discard_pending(proc, stops);
+            if data.job.resume(true) {
+                notify_parent(proc, JobEvent::Continued.wait_status());
+            }
+        }
+        Signo::SIGSTOP | Signo::SIGTSTP | Signo::SIGTTIN | Signo::SIGTTOU => {
+            let mut cont = SignalSet::default();
+            cont.add(Signo::SIGCONT);
+            discard_pending(proc, cont);
+        }
+        // Let the process die
+        Signo::SIGKILL => {
+            data.job.resume(false);
+        }
+        _ => {}
+    }
+}
+
 pub fn send_signal_thread(thr: &Thread, sig: SignalInfo) -> LinuxResult<()> {
     info!("Send signal {:?} to thread {}", sig.signo(), thr.tid());
-    let Some(thr) = thr.data::<ThreadData>() else {
+    let Some(data) = thr.data::<ThreadData>() else {
         return Err(LinuxError::EPERM);
     };
-    thr.signal.send_signal(sig);
+    resume_on_signal(thr.process(), sig.signo());
+    data.signal.send_signal(sig);
+    data.interrupt();
     Ok(())
 }
 
 pub fn send_signal_process(proc: &Process, sig: SignalInfo) -> LinuxResult<()> {
     info!("Send signal {:?} to process {}", sig.signo(), proc.pid());
-    let Some(proc) = proc.data::<ProcessData>() else {
+    let Some(data) = proc.data::<ProcessData>() else {
         return Err(LinuxError::EPERM);
     };
-    proc.signal.send_signal(sig);
+    resume_on_signal(proc, sig.signo());
+    data.signal.send_signal(sig);
+    // Any thread which doesn't block the signal may take it
+    for thr in proc.threads() {
+        if let Some(data) = thr.data::<ThreadData>() {
+            data.interrupt();
+        }
+    }
     Ok(())
 }
 
@@ -1,8 +1,26 @@
+use axerrno::{LinuxError, LinuxResult};
 use axhal::time::TimeValue;
 use linux_raw_sys::general::{
-    __kernel_old_timespec, __kernel_old_timeval, __kernel_sock_timeval, __kernel_timespec,
+    __kernel_old_timespec, __kernel_old_timeval, __kernel_sock_timeval, __kernel_timespec, rusage,
     timespec, timeval,
 };
+use starry_core::task::ResourceUsage;
+
+/// Converts a `timespec` from userspace, which must be normalized.
+pub fn parse_timespec(ts: &timespec) -> LinuxResult<TimeValue> {
+    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(ts.to_time_value())
+}
+
+/// Converts a `timeval` from userspace, which must be normalized.
+pub fn parse_timeval(tv: &timeval) -> LinuxResult<TimeValue> {
+    if tv.tv_sec < 0 || !(0..1_000_000).contains(&tv.tv_usec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(tv.to_time_value())
+}
 
 /// A helper trait for converting from and to `TimeValue`.
 pub trait TimeValueLike {
@@ -100,3 +118,17 @@ impl TimeValueLike for __kernel_sock_timeval {
         TimeValue::new(self.tv_sec as u64, self.tv_usec as u32 * 1000)
     }
 }
+
+/// Converts resource usage to the `struct rusage` of Linux.
+pub fn make_rusage(usage: &ResourceUsage) -> rusage {
+    // SAFETY: valid for rusage
+    let mut ru: rusage = unsafe { core::mem::zeroed() };
+    ru.ru_utime = TimeValueLike::from_time_value(TimeValue::from_nanos(usage.utime_ns as u64));
+    ru.ru_stime = TimeValueLike::from_time_value(TimeValue::from_nanos(usage.stime_ns as u64));
+    ru.ru_maxrss = (usage.maxrss / 1024) as _;
+    ru.ru_minflt = usage.minflt as _;
+    ru.ru_majflt = usage.majflt as _;
+    ru.ru_nvcsw = usage.nvcsw as _;
+    ru.ru_nivcsw = usage.nivcsw as _;
+    ru
+}
@@ -0,0 +1,84 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+static int read_file(const char *path, char *buf, int size) {
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int len = read(fd, buf, size - 1);
+  close(fd);
+  if (len >= 0)
+    buf[len] = 0;
+  return len;
+}
+
+static int write_file(const char *path, const char *data) {
+  int fd = open(path, O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  if (fd < 0)
+    return -1;
+  int len = write(fd, data, strlen(data));
+  close(fd);
+  return len;
+}
+
+void test_bind() {
+  char buf[64];
+  mkdir("/tmp/src", 0755);
+  mkdir("/tmp/dst", 0755);
+  write_file("/tmp/src/file", "hello");
+  if (mount("/tmp/src", "/tmp/dst", NULL, MS_BIND, NULL) != 0) {
+    perror("bind mount");
+    return;
+  }
+  if (read_file("/tmp/dst/file", buf, sizeof(buf)) == 5 &&
+      strcmp(buf, "hello") == 0) {
+    puts("test_bind ok1");
+  }
+  // Both paths show the same files
+  write_file("/tmp/dst/new", "world");
+  if (read_file("/tmp/src/new", buf, sizeof(buf)) == 5 &&
+      strcmp(buf, "world") == 0) {
+    puts("test_bind ok2");
+  }
+  // A read-only bind leaves the source writable
+  mount(NULL, "/tmp/dst", NULL, MS_BIND | MS_REMOUNT | MS_RDONLY, NULL);
+  if (open("/tmp/dst/file", O_WRONLY) < 0 && errno == EROFS &&
+      write_file("/tmp/src/file", "again") == 5) {
+    puts("test_bind ok3");
+  }
+  umount("/tmp/dst");
+  struct stat st;
+  if (stat("/tmp/dst/file", &st) < 0 && errno == ENOENT &&
+      stat("/tmp/src/file", &st) == 0) {
+    puts("test_bind ok4");
+  }
+}
+
+void test_rbind() {
+  struct stat st;
+  mkdir("/tmp/src/sub", 0755);
+  mount("tmpfs", "/tmp/src/sub", "tmpfs", 0, NULL);
+  write_file("/tmp/src/sub/inner", "x");
+
+  // The mount below the source shows up below the target as well
+  mount("/tmp/src", "/tmp/dst", NULL, MS_BIND | MS_REC, NULL);
+  if (stat("/tmp/dst/sub/inner", &st) == 0) {
+    puts("test_rbind ok1");
+  }
+  if (umount("/tmp/dst") < 0 && errno == EBUSY) {
+    puts("test_rbind ok2");
+  }
+  umount2("/tmp/dst", MNT_DETACH);
+  umount("/tmp/src/sub");
+}
+
+int main() {
+  test_bind();
+  test_rbind();
+  return 0;
+}
@@ -0,0 +1,107 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static int is_chardev(const char *path, unsigned major_nr, unsigned minor_nr) {
+  struct stat st;
+  return stat(path, &st) == 0 && S_ISCHR(st.st_mode) && major(st.st_rdev) == major_nr &&
+         minor(st.st_rdev) == minor_nr;
+}
+
+void test_stat() {
+  if (is_chardev("/dev/null", 1, 3) && is_chardev("/dev/zero", 1, 5) &&
+      is_chardev("/dev/full", 1, 7) && is_chardev("/dev/kmsg", 1, 11)) {
+    puts("test_stat ok1");
+  }
+  if (is_chardev("/dev/tty", 5, 0) && is_chardev("/dev/console", 5, 1)) {
+    puts("test_stat ok2");
+  }
+}
+
+void test_null() {
+  char buf[16];
+  int fd = open("/dev/null", O_RDWR);
+  if (write(fd, "discarded", 9) == 9 && read(fd, buf, sizeof(buf)) == 0) {
+    puts("test_null ok1");
+  }
+  close(fd);
+}
+
+void test_zero() {
+  char buf[64];
+  memset(buf, 0xff, sizeof(buf));
+  int fd = open("/dev/zero", O_RDWR);
+  int zeros = read(fd, buf, sizeof(buf)) == sizeof(buf);
+  for (int i = 0; i < (int)sizeof(buf); i++)
+    zeros &= buf[i] == 0;
+  if (zeros && write(fd, buf, sizeof(buf)) == sizeof(buf)) {
+    puts("test_zero ok1");
+  }
+  // Mapping it gives zeroed anonymous memory
+  char *p = mmap(NULL, 8192, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+  if (p != MAP_FAILED && p[0] == 0 && p[8191] == 0) {
+    p[100] = 'x';
+    if (p[100] == 'x') {
+      puts("test_zero ok2");
+    }
+    munmap(p, 8192);
+  }
+  close(fd);
+  // The device itself is unchanged
+  fd = open("/dev/zero", O_RDONLY);
+  if (read(fd, buf, 1) == 1 && buf[0] == 0) {
+    puts("test_zero ok3");
+  }
+  close(fd);
+}
+
+void test_full() {
+  char buf[8] = {1};
+  int fd = open("/dev/full", O_RDWR);
+  if (write(fd, "x", 1) < 0 && errno == ENOSPC) {
+    puts("test_full ok1");
+  }
+  if (read(fd, buf, sizeof(buf)) == sizeof(buf) && buf[0] == 0) {
+    puts("test_full ok2");
+  }
+  close(fd);
+}
+
+void test_kmsg() {
+  int fd = open("/dev/kmsg", O_WRONLY);
+  if (fd >= 0 && write(fd, "chardev: hello\n", 15) == 15) {
+    puts("test_kmsg ok1");
+  }
+  close(fd);
+}
+
+void test_tty() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    // A new session has no controlling terminal
+    setsid();
+    _exit(open("/dev/tty", O_RDWR) < 0 && errno == ENXIO ? 0 : 1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_tty ok1");
+  }
+}
+
+int main() {
+  test_stat();
+  test_null();
+  test_zero();
+  test_full();
+  test_kmsg();
+  test_tty();
+  return 0;
+}
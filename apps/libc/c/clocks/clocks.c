@@ -0,0 +1,202 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/time.h>
+#include <time.h>
+#include <unistd.h>
+
+static const clockid_t all_clocks[] = {
+    CLOCK_REALTIME,        CLOCK_MONOTONIC,          CLOCK_MONOTONIC_RAW,
+    CLOCK_BOOTTIME,        CLOCK_REALTIME_COARSE,    CLOCK_MONOTONIC_COARSE,
+    CLOCK_PROCESS_CPUTIME_ID, CLOCK_THREAD_CPUTIME_ID,
+};
+
+static long ms_of(struct timespec ts) {
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static long now_ms(clockid_t clock) {
+  struct timespec ts;
+  clock_gettime(clock, &ts);
+  return ms_of(ts);
+}
+
+static struct timespec ts_of_ms(long ms) {
+  struct timespec ts = {ms / 1000, ms % 1000 * 1000000};
+  return ts;
+}
+
+static void spin_ms(long ms) {
+  long start = now_ms(CLOCK_MONOTONIC);
+  while (now_ms(CLOCK_MONOTONIC) - start < ms)
+    ;
+}
+
+static volatile int alrm;
+
+static void on_alarm(int sig) {
+  (void)sig;
+  alrm++;
+}
+
+void test_getres() {
+  int ok = 1;
+  for (size_t i = 0; i < sizeof(all_clocks) / sizeof(all_clocks[0]); i++) {
+    struct timespec res = {-1, -1};
+    if (clock_getres(all_clocks[i], &res) != 0 || res.tv_sec != 0 || res.tv_nsec <= 0 ||
+        res.tv_nsec > 10000000) {
+      ok = 0;
+    }
+  }
+  if (ok) {
+    puts("test_getres ok1");
+  }
+  // The resolution may be omitted, but the clock is still checked
+  if (clock_getres(CLOCK_MONOTONIC, NULL) == 0 && clock_getres(100, NULL) == -1 &&
+      errno == EINVAL) {
+    puts("test_getres ok2");
+  }
+}
+
+void test_gettime() {
+  // The raw clock isn't slewed, so only its pace matches
+  long mono = now_ms(CLOCK_MONOTONIC);
+  long raw = now_ms(CLOCK_MONOTONIC_RAW);
+  usleep(100000);
+  long mono_passed = now_ms(CLOCK_MONOTONIC) - mono;
+  long raw_passed = now_ms(CLOCK_MONOTONIC_RAW) - raw;
+  long coarse = now_ms(CLOCK_MONOTONIC_COARSE);
+  long boot = now_ms(CLOCK_BOOTTIME);
+  mono = now_ms(CLOCK_MONOTONIC);
+  if (labs(raw_passed - mono_passed) < 10 && labs(coarse - mono) < 50 && boot >= mono - 50) {
+    puts("test_gettime ok1");
+  }
+  if (labs(now_ms(CLOCK_REALTIME_COARSE) - now_ms(CLOCK_REALTIME)) < 50) {
+    puts("test_gettime ok2");
+  }
+  // The CPU clocks advance as the thread runs, and the process's includes it
+  long thread = now_ms(CLOCK_THREAD_CPUTIME_ID);
+  spin_ms(100);
+  long thread_used = now_ms(CLOCK_THREAD_CPUTIME_ID) - thread;
+  if (thread_used >= 50 && thread_used <= 200 &&
+      now_ms(CLOCK_PROCESS_CPUTIME_ID) >= now_ms(CLOCK_THREAD_CPUTIME_ID)) {
+    puts("test_gettime ok3");
+  }
+}
+
+void test_sleep() {
+  struct timespec req = ts_of_ms(100);
+  long start = now_ms(CLOCK_MONOTONIC);
+  int ret = clock_nanosleep(CLOCK_MONOTONIC, 0, &req, NULL);
+  long elapsed = now_ms(CLOCK_MONOTONIC) - start;
+  if (ret == 0 && elapsed >= 100 && elapsed < 400) {
+    puts("test_sleep ok1");
+  }
+  // An absolute deadline on either clock
+  struct timespec deadline;
+  clockid_t clocks[] = {CLOCK_MONOTONIC, CLOCK_REALTIME, CLOCK_BOOTTIME};
+  int ok = 1;
+  for (int i = 0; i < 3; i++) {
+    clock_gettime(clocks[i], &deadline);
+    deadline.tv_sec += 1;
+    deadline.tv_nsec = 0;
+    ret = clock_nanosleep(clocks[i], TIMER_ABSTIME, &deadline, NULL);
+    struct timespec now;
+    clock_gettime(clocks[i], &now);
+    if (ret != 0 || now.tv_sec < deadline.tv_sec || ms_of(now) - ms_of(deadline) > 300) {
+      ok = 0;
+    }
+  }
+  if (ok) {
+    puts("test_sleep ok2");
+  }
+  // A deadline in the past returns at once
+  clock_gettime(CLOCK_MONOTONIC, &deadline);
+  deadline.tv_sec -= 1;
+  start = now_ms(CLOCK_MONOTONIC);
+  ret = clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &deadline, NULL);
+  if (ret == 0 && now_ms(CLOCK_MONOTONIC) - start < 50) {
+    puts("test_sleep ok3");
+  }
+  struct timespec bad = {0, 1000000000};
+  if (clock_nanosleep(CLOCK_MONOTONIC, 0, &bad, NULL) == EINVAL &&
+      clock_nanosleep(100, 0, &req, NULL) == EINVAL) {
+    puts("test_sleep ok4");
+  }
+}
+
+static volatile int spinning;
+
+static void *spinner(void *arg) {
+  (void)arg;
+  while (spinning)
+    ;
+  return NULL;
+}
+
+void test_cpu_sleep() {
+  // The own thread's CPU time can't advance while it sleeps
+  struct timespec req = ts_of_ms(50);
+  if (clock_nanosleep(CLOCK_THREAD_CPUTIME_ID, 0, &req, NULL) == EINVAL) {
+    puts("test_cpu_sleep ok1");
+  }
+  // But another thread can run up the process's
+  spinning = 1;
+  pthread_t thread;
+  pthread_create(&thread, NULL, spinner, NULL);
+  long start = now_ms(CLOCK_PROCESS_CPUTIME_ID);
+  int ret = clock_nanosleep(CLOCK_PROCESS_CPUTIME_ID, 0, &req, NULL);
+  long used = now_ms(CLOCK_PROCESS_CPUTIME_ID) - start;
+  spinning = 0;
+  pthread_join(thread, NULL);
+  if (ret == 0 && used >= 50) {
+    puts("test_cpu_sleep ok2");
+  }
+}
+
+void test_eintr() {
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = on_alarm;
+  sigaction(SIGALRM, &sa, NULL);
+  struct itimerval it = {{0, 0}, {0, 100000}};
+
+  // A relative sleep tells how much was left
+  struct timespec req = ts_of_ms(1000), rem = {0, 0};
+  setitimer(ITIMER_REAL, &it, NULL);
+  int ret = clock_nanosleep(CLOCK_MONOTONIC, 0, &req, &rem);
+  if (ret == EINTR && alrm == 1 && ms_of(rem) >= 700 && ms_of(rem) <= 900) {
+    puts("test_eintr ok1");
+  }
+  rem.tv_sec = rem.tv_nsec = 0;
+  setitimer(ITIMER_REAL, &it, NULL);
+  ret = nanosleep(&req, &rem);
+  if (ret == -1 && errno == EINTR && alrm == 2 && ms_of(rem) >= 700 && ms_of(rem) <= 900) {
+    puts("test_eintr ok2");
+  }
+  // An absolute one leaves the remaining time alone
+  struct timespec deadline;
+  clock_gettime(CLOCK_MONOTONIC, &deadline);
+  deadline.tv_sec += 1;
+  rem.tv_sec = 42;
+  rem.tv_nsec = 0;
+  setitimer(ITIMER_REAL, &it, NULL);
+  ret = clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &deadline, &rem);
+  if (ret == EINTR && alrm == 3 && rem.tv_sec == 42) {
+    puts("test_eintr ok3");
+  }
+  signal(SIGALRM, SIG_DFL);
+}
+
+int main() {
+  test_getres();
+  test_gettime();
+  test_sleep();
+  test_cpu_sleep();
+  test_eintr();
+  return 0;
+}
@@ -0,0 +1,122 @@
+#define _GNU_SOURCE
+#include <fcntl.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static long ms_of(clockid_t clock) {
+  struct timespec ts;
+  clock_gettime(clock, &ts);
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static double seconds(struct timeval tv) {
+  return tv.tv_sec + tv.tv_usec / 1e6;
+}
+
+void test_sleep() {
+  long cpu = ms_of(CLOCK_PROCESS_CPUTIME_ID);
+  usleep(300000);
+  // Sleeping is not billed
+  if (ms_of(CLOCK_PROCESS_CPUTIME_ID) - cpu < 50) {
+    puts("test_sleep ok1");
+  }
+}
+
+void test_runnable() {
+  long ncpu = sysconf(_SC_NPROCESSORS_ONLN);
+  if (ncpu > 2)
+    ncpu = 2;
+  long end = ms_of(CLOCK_MONOTONIC) + 400;
+  int fds[2];
+  pipe(fds);
+  pid_t pid = fork();
+  long cpu = ms_of(CLOCK_PROCESS_CPUTIME_ID);
+  while (ms_of(CLOCK_MONOTONIC) < end) {
+  }
+  cpu = ms_of(CLOCK_PROCESS_CPUTIME_ID) - cpu;
+  if (pid == 0) {
+    write(fds[1], &cpu, sizeof(cpu));
+    _exit(0);
+  }
+  long child_cpu = 0;
+  read(fds[0], &child_cpu, sizeof(child_cpu));
+  waitpid(pid, NULL, 0);
+  // Waiting for the CPU is not billed either, so the two spinners share
+  // the time of the CPUs they ran on
+  if (cpu + child_cpu <= 400 * ncpu + 50 && cpu >= 100 / ncpu && child_cpu >= 100 / ncpu) {
+    puts("test_runnable ok1");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_split() {
+  struct rusage before, after;
+  getrusage(RUSAGE_SELF, &before);
+  long end = ms_of(CLOCK_MONOTONIC) + 300;
+  volatile unsigned long n = 0;
+  while (ms_of(CLOCK_MONOTONIC) < end) {
+    for (int i = 0; i < 10000; i++)
+      n++;
+  }
+  getrusage(RUSAGE_SELF, &after);
+  double user = seconds(after.ru_utime) - seconds(before.ru_utime);
+  double sys = seconds(after.ru_stime) - seconds(before.ru_stime);
+  if (user > 0.1 && user > sys) {
+    puts("test_split ok1");
+  }
+
+  // Copying from /dev/zero happens in the kernel
+  static char buf[65536];
+  int fd = open("/dev/zero", O_RDONLY);
+  getrusage(RUSAGE_SELF, &before);
+  end = ms_of(CLOCK_MONOTONIC) + 300;
+  while (ms_of(CLOCK_MONOTONIC) < end)
+    read(fd, buf, sizeof(buf));
+  getrusage(RUSAGE_SELF, &after);
+  close(fd);
+  user = seconds(after.ru_utime) - seconds(before.ru_utime);
+  sys = seconds(after.ru_stime) - seconds(before.ru_stime);
+  if (sys > 0.1 && sys > user) {
+    puts("test_split ok2");
+  }
+}
+
+static long worker_cpu;
+
+static void *worker(void *arg) {
+  (void)arg;
+  long start = ms_of(CLOCK_THREAD_CPUTIME_ID);
+  while (ms_of(CLOCK_THREAD_CPUTIME_ID) - start < 200) {
+  }
+  worker_cpu = ms_of(CLOCK_THREAD_CPUTIME_ID);
+  return NULL;
+}
+
+void test_thread() {
+  long process = ms_of(CLOCK_PROCESS_CPUTIME_ID);
+  long thread = ms_of(CLOCK_THREAD_CPUTIME_ID);
+  pthread_t t;
+  pthread_create(&t, NULL, worker, NULL);
+  pthread_join(t, NULL);
+  // The joining thread slept, while the process clock counts the worker
+  if (worker_cpu >= 200 && ms_of(CLOCK_THREAD_CPUTIME_ID) - thread < 50) {
+    puts("test_thread ok1");
+  }
+  if (ms_of(CLOCK_PROCESS_CPUTIME_ID) - process >= 200) {
+    puts("test_thread ok2");
+  }
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  test_sleep();
+  test_runnable();
+  test_split();
+  test_thread();
+  return 0;
+}
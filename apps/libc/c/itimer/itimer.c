@@ -0,0 +1,147 @@
+#define _GNU_SOURCE
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static volatile int alrm, vtalrm, prof;
+
+static void on_signal(int sig) {
+  if (sig == SIGALRM)
+    alrm++;
+  else if (sig == SIGVTALRM)
+    vtalrm++;
+  else if (sig == SIGPROF)
+    prof++;
+}
+
+static long now_ms() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static void set_timer(int which, long value_ms, long interval_ms) {
+  struct itimerval it = {
+      .it_value = {value_ms / 1000, value_ms % 1000 * 1000},
+      .it_interval = {interval_ms / 1000, interval_ms % 1000 * 1000},
+  };
+  setitimer(which, &it, NULL);
+}
+
+static long ms_of(struct timeval tv) {
+  return tv.tv_sec * 1000 + tv.tv_usec / 1000;
+}
+
+void test_real() {
+  sigset_t mask, old;
+  sigemptyset(&mask);
+  sigaddset(&mask, SIGALRM);
+  sigprocmask(SIG_BLOCK, &mask, &old);
+  long start = now_ms();
+  // Fires after 100ms, then reloads every 50ms
+  set_timer(ITIMER_REAL, 100, 50);
+  while (alrm < 3)
+    sigsuspend(&old);
+  long elapsed = now_ms() - start;
+  if (elapsed >= 190 && elapsed < 500) {
+    puts("test_real ok1");
+  }
+  struct itimerval it;
+  getitimer(ITIMER_REAL, &it);
+  if (ms_of(it.it_interval) == 50 && ms_of(it.it_value) <= 50) {
+    puts("test_real ok2");
+  }
+  // Disarming returns the old setting and stops the signals
+  struct itimerval zero = {{0, 0}, {0, 0}}, prev;
+  setitimer(ITIMER_REAL, &zero, &prev);
+  sigprocmask(SIG_SETMASK, &old, NULL);
+  int count = alrm;
+  usleep(200000);
+  if (ms_of(prev.it_interval) == 50 && alrm == count) {
+    puts("test_real ok3");
+  }
+}
+
+void test_alarm() {
+  alarm(5);
+  struct itimerval it;
+  getitimer(ITIMER_REAL, &it);
+  if (ms_of(it.it_value) > 4900 && ms_of(it.it_value) <= 5000 && ms_of(it.it_interval) == 0) {
+    puts("test_alarm ok1");
+  }
+  if (alarm(0) == 5 && alarm(0) == 0) {
+    puts("test_alarm ok2");
+  }
+  int count = alrm;
+  alarm(1);
+  usleep(1300000);
+  if (alrm == count + 1) {
+    puts("test_alarm ok3");
+  }
+}
+
+void test_virtual() {
+  // Sleeping uses no user time
+  set_timer(ITIMER_VIRTUAL, 50, 0);
+  usleep(200000);
+  if (vtalrm == 0) {
+    puts("test_virtual ok1");
+  }
+  long start = now_ms();
+  volatile unsigned long n = 0;
+  while (vtalrm == 0)
+    n++;
+  if (now_ms() - start >= 40) {
+    puts("test_virtual ok2");
+  }
+}
+
+void test_prof() {
+  static char buf[65536];
+  int fd = open("/dev/zero", O_RDONLY);
+  set_timer(ITIMER_PROF, 100, 0);
+  long start = now_ms();
+  // Time spent in the kernel counts too
+  while (prof == 0 && now_ms() - start < 2000)
+    read(fd, buf, sizeof(buf));
+  close(fd);
+  if (prof == 1) {
+    puts("test_prof ok1");
+  }
+}
+
+void test_fork() {
+  set_timer(ITIMER_REAL, 10000, 0);
+  pid_t pid = fork();
+  if (pid == 0) {
+    // Timers are not inherited
+    struct itimerval it;
+    getitimer(ITIMER_REAL, &it);
+    _exit(it.it_value.tv_sec == 0 && it.it_value.tv_usec == 0 ? 0 : 1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_fork ok1");
+  }
+  set_timer(ITIMER_REAL, 0, 0);
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  struct sigaction sa = {.sa_handler = on_signal, .sa_flags = SA_RESTART};
+  sigaction(SIGALRM, &sa, NULL);
+  sigaction(SIGVTALRM, &sa, NULL);
+  sigaction(SIGPROF, &sa, NULL);
+  test_real();
+  test_alarm();
+  test_virtual();
+  test_prof();
+  test_fork();
+  return 0;
+}
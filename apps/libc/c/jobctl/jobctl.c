@@ -0,0 +1,192 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/wait.h>
+#include <termios.h>
+#include <unistd.h>
+
+static int master = -1;
+static char slave_path[32];
+static int tty = -1;
+
+// Runs `f` in a child moved to a background process group, and returns its
+// wait status.
+static int run_background(void (*f)(void)) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setpgid(0, 0);
+    f();
+    _exit(0);
+  }
+  setpgid(pid, pid);
+  int status;
+  waitpid(pid, &status, WUNTRACED);
+  if (WIFSTOPPED(status)) {
+    kill(pid, SIGKILL);
+    waitpid(pid, NULL, 0);
+  }
+  return status;
+}
+
+static void bg_read(void) {
+  char c;
+  read(tty, &c, 1);
+}
+
+static void bg_read_ignored(void) {
+  char c;
+  signal(SIGTTIN, SIG_IGN);
+  _exit(read(tty, &c, 1) < 0 && errno == EIO ? 0 : 1);
+}
+
+static void bg_setattr(void) {
+  struct termios t;
+  tcgetattr(tty, &t);
+  tcsetattr(tty, TCSANOW, &t);
+}
+
+static void bg_write(void) {
+  _exit(write(tty, "bg\n", 3) == 3 ? 0 : 1);
+}
+
+static void bg_write_tostop(void) {
+  write(tty, "bg\n", 3);
+}
+
+// The session leader, controlling the terminal.
+static void leader(void) {
+  setsid();
+  tty = open(slave_path, O_RDWR);
+  if (getsid(0) == getpid() && tcgetsid(tty) == getpid() && tcgetpgrp(tty) == getpgrp()) {
+    puts("test_session ok1");
+  }
+  if (setsid() < 0 && errno == EPERM) {
+    puts("test_session ok2");
+  }
+
+  int status = run_background(bg_read);
+  if (WIFSTOPPED(status) && WSTOPSIG(status) == SIGTTIN) {
+    puts("test_background ok1");
+  }
+  status = run_background(bg_read_ignored);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_background ok2");
+  }
+  status = run_background(bg_setattr);
+  if (WIFSTOPPED(status) && WSTOPSIG(status) == SIGTTOU) {
+    puts("test_background ok3");
+  }
+  // Without TOSTOP, background output is allowed
+  status = run_background(bg_write);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_background ok4");
+  }
+  struct termios t;
+  tcgetattr(tty, &t);
+  t.c_lflag |= TOSTOP;
+  tcsetattr(tty, TCSANOW, &t);
+  status = run_background(bg_write_tostop);
+  if (WIFSTOPPED(status) && WSTOPSIG(status) == SIGTTOU) {
+    puts("test_background ok5");
+  }
+  t.c_lflag &= ~TOSTOP;
+  tcsetattr(tty, TCSANOW, &t);
+
+  // Hand the terminal to another group and take it back
+  pid_t child = fork();
+  if (child == 0) {
+    setpgid(0, 0);
+    pause();
+    _exit(0);
+  }
+  setpgid(child, child);
+  signal(SIGTTOU, SIG_IGN);
+  if (tcsetpgrp(tty, child) == 0 && tcgetpgrp(tty) == child) {
+    puts("test_foreground ok1");
+  }
+  if (tcsetpgrp(tty, getpgrp()) == 0 && tcgetpgrp(tty) == getpgrp()) {
+    puts("test_foreground ok2");
+  }
+  // The group of the parent lies in another session
+  if (tcsetpgrp(tty, getpgid(getppid())) < 0 && errno == EPERM) {
+    puts("test_foreground ok3");
+  }
+  kill(child, SIGKILL);
+  waitpid(child, NULL, 0);
+}
+
+static int hup[2];
+
+static void on_sighup(int sig) {
+  (void)sig;
+  write(hup[1], "h", 1);
+}
+
+void test_job_control() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    leader();
+    _exit(0);
+  }
+  waitpid(pid, NULL, 0);
+}
+
+void test_hangup() {
+  pipe(hup);
+  int ready[2];
+  pipe(ready);
+  pid_t pid = fork();
+  if (pid == 0) {
+    setsid();
+    tty = open(slave_path, O_RDWR);
+    if (fork() == 0) {
+      // In the foreground group of the session, outliving the leader
+      signal(SIGHUP, on_sighup);
+      write(ready[1], "r", 1);
+      pause();
+      _exit(0);
+    }
+    char c;
+    read(ready[0], &c, 1);
+    _exit(0);
+  }
+  waitpid(pid, NULL, 0);
+  struct pollfd pfd = {.fd = hup[0], .events = POLLIN};
+  if (poll(&pfd, 1, 1000) == 1) {
+    puts("test_hangup ok1");
+  }
+}
+
+void test_notty() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setsid();
+    tty = open(slave_path, O_RDWR);
+    signal(SIGHUP, SIG_IGN);
+    int ok = ioctl(tty, TIOCNOTTY) == 0 && tcgetpgrp(tty) < 0 && errno == ENOTTY;
+    _exit(ok ? 0 : 1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_notty ok1");
+  }
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  master = posix_openpt(O_RDWR | O_NOCTTY);
+  grantpt(master);
+  unlockpt(master);
+  strcpy(slave_path, ptsname(master));
+  test_job_control();
+  test_hangup();
+  test_notty();
+  return 0;
+}
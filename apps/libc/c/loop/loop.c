@@ -0,0 +1,94 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <linux/loop.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+static char pattern[4096];
+static char loop_path[32];
+static int loop_fd = -1;
+
+void test_attach() {
+  for (int i = 0; i < (int)sizeof(pattern); i++) {
+    pattern[i] = i * 7;
+  }
+  int fd = open("/tmp/backing", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, pattern, sizeof(pattern));
+
+  int ctl = open("/dev/loop-control", O_RDWR);
+  int index = ioctl(ctl, LOOP_CTL_GET_FREE);
+  close(ctl);
+  if (index < 0) {
+    perror("LOOP_CTL_GET_FREE");
+    return;
+  }
+  sprintf(loop_path, "/dev/loop%d", index);
+  loop_fd = open(loop_path, O_RDWR);
+  if (ioctl(loop_fd, LOOP_SET_FD, fd) == 0) {
+    puts("test_attach ok1");
+  }
+  // Already bound
+  if (ioctl(loop_fd, LOOP_SET_FD, fd) < 0 && errno == EBUSY) {
+    puts("test_attach ok2");
+  }
+  close(fd);
+
+  struct loop_info64 info;
+  if (ioctl(loop_fd, LOOP_GET_STATUS64, &info) == 0 &&
+      info.lo_number == (unsigned)index &&
+      strcmp((char *)info.lo_file_name, "/tmp/backing") == 0) {
+    puts("test_attach ok3");
+  }
+  struct stat st;
+  if (stat(loop_path, &st) == 0 && S_ISBLK(st.st_mode) &&
+      major(st.st_rdev) == 7 && minor(st.st_rdev) == (unsigned)index) {
+    puts("test_attach ok4");
+  }
+}
+
+void test_io() {
+  char buf[512];
+  if (pread(loop_fd, buf, sizeof(buf), 512) == sizeof(buf) &&
+      memcmp(buf, pattern + 512, sizeof(buf)) == 0) {
+    puts("test_io ok1");
+  }
+  // Writes go through to the backing file
+  pwrite(loop_fd, "ABCD", 4, 1024);
+  fsync(loop_fd);
+  int fd = open("/tmp/backing", O_RDONLY);
+  if (pread(fd, buf, 4, 1024) == 4 && memcmp(buf, "ABCD", 4) == 0) {
+    puts("test_io ok2");
+  }
+  close(fd);
+}
+
+void test_detach() {
+  // Busy while another file has the device open
+  int other = open(loop_path, O_RDONLY);
+  if (ioctl(loop_fd, LOOP_CLR_FD) < 0 && errno == EBUSY) {
+    puts("test_detach ok1");
+  }
+  close(other);
+  if (ioctl(loop_fd, LOOP_CLR_FD) == 0) {
+    puts("test_detach ok2");
+  }
+  struct loop_info64 info;
+  if (ioctl(loop_fd, LOOP_GET_STATUS64, &info) < 0 && errno == ENXIO) {
+    puts("test_detach ok3");
+  }
+  close(loop_fd);
+  unlink("/tmp/backing");
+}
+
+int main() {
+  test_attach();
+  if (loop_fd < 0)
+    return 1;
+  test_io();
+  test_detach();
+  return 0;
+}
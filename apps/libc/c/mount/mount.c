@@ -0,0 +1,93 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/vfs.h>
+#include <unistd.h>
+
+#define RAMFS_MAGIC 0x858458f6
+#define TMPFS_MAGIC 0x01021994
+
+static int mounts_contain(const char *entry) {
+  char buf[4096];
+  int fd = open("/proc/mounts", O_RDONLY);
+  if (fd < 0)
+    return 0;
+  int len = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (len < 0)
+    return 0;
+  buf[len] = 0;
+  return strstr(buf, entry) != NULL;
+}
+
+void test_mount() {
+  mkdir("/tmp/mnt", 0755);
+  if (mount("none", "/tmp/mnt", "ramfs", 0, NULL) != 0) {
+    perror("mount ramfs");
+    return;
+  }
+  int fd = open("/tmp/mnt/file", O_CREAT | O_WRONLY, 0644);
+  write(fd, "data", 4);
+  close(fd);
+
+  struct statfs st;
+  if (statfs("/tmp/mnt", &st) == 0 && st.f_type == RAMFS_MAGIC) {
+    puts("test_mount ok1");
+  }
+  if (mounts_contain("none /tmp/mnt ramfs rw")) {
+    puts("test_mount ok2");
+  }
+  if (mount("none", "/tmp/mnt", "nosuchfs", 0, NULL) < 0 && errno == ENODEV) {
+    puts("test_mount ok3");
+  }
+}
+
+void test_umount() {
+  mkdir("/tmp/mnt/sub", 0755);
+  mount("tmpfs", "/tmp/mnt/sub", "tmpfs", MS_RDONLY, NULL);
+  struct statfs st;
+  if (statfs("/tmp/mnt/sub", &st) == 0 && st.f_type == TMPFS_MAGIC &&
+      mounts_contain("tmpfs /tmp/mnt/sub tmpfs ro")) {
+    puts("test_umount ok1");
+  }
+  // Busy while another mount lies below it
+  if (umount("/tmp/mnt") < 0 && errno == EBUSY) {
+    puts("test_umount ok2");
+  }
+  struct stat sb;
+  if (umount2("/tmp/mnt", MNT_DETACH) == 0 &&
+      stat("/tmp/mnt/file", &sb) < 0 && errno == ENOENT &&
+      !mounts_contain("/tmp/mnt")) {
+    puts("test_umount ok3");
+  }
+  // Nothing is pending, so a forced unmount is a normal one
+  mount("none", "/tmp/mnt", "ramfs", 0, NULL);
+  if (umount2("/tmp/mnt", MNT_FORCE) == 0 && !mounts_contain("/tmp/mnt")) {
+    puts("test_umount ok4");
+  }
+  if (umount("/tmp/mnt") < 0 && errno == EINVAL) {
+    puts("test_umount ok5");
+  }
+}
+
+void test_device() {
+  int fd = open("/tmp/image", O_CREAT | O_WRONLY, 0644);
+  close(fd);
+  // A regular file is not a block device
+  if (mount("/tmp/image", "/tmp/mnt", "vfat", 0, NULL) < 0 &&
+      errno == ENOTBLK) {
+    puts("test_device ok");
+  }
+  unlink("/tmp/image");
+}
+
+int main() {
+  test_mount();
+  test_umount();
+  test_device();
+  rmdir("/tmp/mnt");
+  return 0;
+}
@@ -0,0 +1,201 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <time.h>
+#include <unistd.h>
+
+#ifndef SIGEV_THREAD_ID
+#define SIGEV_THREAD_ID 4
+#endif
+
+// The kernel's `struct sigevent`, used with the raw syscalls so that timer
+// IDs are the kernel's under any libc.
+struct kernel_sigevent {
+  union sigval value;
+  int signo;
+  int notify;
+  int tid;
+  int pad[11];
+};
+
+static int create_timer(clockid_t clock, int notify, int signo, int value, int tid) {
+  struct kernel_sigevent ev = {.value.sival_int = value, .signo = signo, .notify = notify, .tid = tid};
+  int id = -1;
+  if (syscall(SYS_timer_create, clock, &ev, &id) < 0)
+    return -1;
+  return id;
+}
+
+static int set_timer(int id, int flags, long value_ms, long interval_ms) {
+  struct itimerspec its = {
+      .it_value = {value_ms / 1000, value_ms % 1000 * 1000000},
+      .it_interval = {interval_ms / 1000, interval_ms % 1000 * 1000000},
+  };
+  return syscall(SYS_timer_settime, id, flags, &its, NULL);
+}
+
+static long ms_of(struct timespec ts) {
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static long now_ms(clockid_t clock) {
+  struct timespec ts;
+  clock_gettime(clock, &ts);
+  return ms_of(ts);
+}
+
+static sigset_t usr_signals;
+
+void test_signal() {
+  int id = create_timer(CLOCK_MONOTONIC, SIGEV_SIGNAL, SIGUSR1, 42, 0);
+  long start = now_ms(CLOCK_MONOTONIC);
+  set_timer(id, 0, 100, 0);
+  struct itimerspec its;
+  syscall(SYS_timer_gettime, id, &its);
+  if (ms_of(its.it_value) > 50 && ms_of(its.it_value) <= 100 && ms_of(its.it_interval) == 0) {
+    puts("test_signal ok1");
+  }
+  siginfo_t info;
+  if (sigwaitinfo(&usr_signals, &info) == SIGUSR1 && info.si_code == SI_TIMER &&
+      info.si_value.sival_int == 42 && info.si_timerid == id &&
+      now_ms(CLOCK_MONOTONIC) - start >= 90) {
+    puts("test_signal ok2");
+  }
+  // A one-shot timer is disarmed once it fired
+  syscall(SYS_timer_gettime, id, &its);
+  if (its.it_value.tv_sec == 0 && its.it_value.tv_nsec == 0) {
+    puts("test_signal ok3");
+  }
+  syscall(SYS_timer_delete, id);
+  if (syscall(SYS_timer_gettime, id, &its) < 0 && errno == EINVAL) {
+    puts("test_signal ok4");
+  }
+}
+
+void test_overrun() {
+  int id = create_timer(CLOCK_MONOTONIC, SIGEV_SIGNAL, SIGUSR1, 1, 0);
+  set_timer(id, 0, 10, 10);
+  // Expirations while the signal is pending are counted as overruns
+  usleep(200000);
+  siginfo_t info;
+  sigwaitinfo(&usr_signals, &info);
+  int overrun = syscall(SYS_timer_getoverrun, id);
+  if (info.si_overrun >= 10 && info.si_overrun < 25 && overrun == info.si_overrun) {
+    puts("test_overrun ok1");
+  }
+  syscall(SYS_timer_delete, id);
+}
+
+void test_none() {
+  int id = create_timer(CLOCK_MONOTONIC, SIGEV_NONE, 0, 0, 0);
+  set_timer(id, 0, 100, 0);
+  usleep(50000);
+  struct itimerspec its;
+  syscall(SYS_timer_gettime, id, &its);
+  if (ms_of(its.it_value) > 0 && ms_of(its.it_value) <= 50) {
+    puts("test_none ok1");
+  }
+  usleep(100000);
+  sigset_t pending;
+  sigpending(&pending);
+  syscall(SYS_timer_gettime, id, &its);
+  if (its.it_value.tv_sec == 0 && its.it_value.tv_nsec == 0 && !sigismember(&pending, SIGUSR1)) {
+    puts("test_none ok2");
+  }
+  syscall(SYS_timer_delete, id);
+}
+
+static volatile int worker_tid;
+static volatile int worker_value;
+
+static void *worker(void *arg) {
+  (void)arg;
+  worker_tid = syscall(SYS_gettid);
+  siginfo_t info;
+  if (sigwaitinfo(&usr_signals, &info) == SIGUSR2)
+    worker_value = info.si_value.sival_int;
+  return NULL;
+}
+
+void test_thread_id() {
+  pthread_t thread;
+  pthread_create(&thread, NULL, worker, NULL);
+  while (worker_tid == 0)
+    usleep(1000);
+  int id = create_timer(CLOCK_MONOTONIC, SIGEV_SIGNAL | SIGEV_THREAD_ID, SIGUSR2, 7, worker_tid);
+  set_timer(id, 0, 50, 0);
+  pthread_join(thread, NULL);
+  sigset_t pending;
+  sigpending(&pending);
+  // Sent to the worker alone
+  if (worker_value == 7 && !sigismember(&pending, SIGUSR2)) {
+    puts("test_thread_id ok1");
+  }
+  syscall(SYS_timer_delete, id);
+  // The thread must belong to the process
+  if (create_timer(CLOCK_MONOTONIC, SIGEV_SIGNAL | SIGEV_THREAD_ID, SIGUSR2, 7, 1) < 0 &&
+      errno == EINVAL) {
+    puts("test_thread_id ok2");
+  }
+}
+
+void test_clocks() {
+  int id = create_timer(CLOCK_PROCESS_CPUTIME_ID, SIGEV_SIGNAL, SIGUSR1, 3, 0);
+  long start = now_ms(CLOCK_PROCESS_CPUTIME_ID);
+  set_timer(id, 0, 100, 0);
+  sigset_t pending;
+  do {
+    sigpending(&pending);
+  } while (!sigismember(&pending, SIGUSR1));
+  siginfo_t info;
+  sigwaitinfo(&usr_signals, &info);
+  if (info.si_value.sival_int == 3 && now_ms(CLOCK_PROCESS_CPUTIME_ID) - start >= 100) {
+    puts("test_clocks ok1");
+  }
+  syscall(SYS_timer_delete, id);
+
+  // An absolute expiry on the wall clock
+  id = create_timer(CLOCK_REALTIME, SIGEV_SIGNAL, SIGUSR1, 4, 0);
+  long start_mono = now_ms(CLOCK_MONOTONIC);
+  set_timer(id, TIMER_ABSTIME, now_ms(CLOCK_REALTIME) + 100, 0);
+  sigwaitinfo(&usr_signals, &info);
+  if (info.si_value.sival_int == 4 && now_ms(CLOCK_MONOTONIC) - start_mono >= 90) {
+    puts("test_clocks ok2");
+  }
+  syscall(SYS_timer_delete, id);
+
+  if (create_timer(1234, SIGEV_SIGNAL, SIGUSR1, 0, 0) < 0 && errno == EINVAL) {
+    puts("test_clocks ok3");
+  }
+}
+
+void test_delete() {
+  int id = create_timer(CLOCK_MONOTONIC, SIGEV_SIGNAL, SIGUSR1, 5, 0);
+  set_timer(id, 0, 50, 0);
+  syscall(SYS_timer_delete, id);
+  usleep(100000);
+  sigset_t pending;
+  sigpending(&pending);
+  if (!sigismember(&pending, SIGUSR1)) {
+    puts("test_delete ok1");
+  }
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  sigemptyset(&usr_signals);
+  sigaddset(&usr_signals, SIGUSR1);
+  sigaddset(&usr_signals, SIGUSR2);
+  sigprocmask(SIG_BLOCK, &usr_signals, NULL);
+  test_signal();
+  test_overrun();
+  test_none();
+  test_thread_id();
+  test_clocks();
+  test_delete();
+  return 0;
+}
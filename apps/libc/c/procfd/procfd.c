@@ -0,0 +1,114 @@
+#include <dirent.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+static char buf[256];
+
+static int count_fds() {
+  DIR *dir = opendir("/proc/self/fd");
+  if (dir == NULL)
+    return -1;
+  int n = 0;
+  struct dirent *entry;
+  while ((entry = readdir(dir)) != NULL) {
+    if (entry->d_name[0] != '.')
+      n++;
+  }
+  closedir(dir);
+  // The directory itself was open while listing
+  return n - 1;
+}
+
+static int read_link(const char *path) {
+  int len = readlink(path, buf, sizeof(buf) - 1);
+  if (len >= 0)
+    buf[len] = 0;
+  return len;
+}
+
+void test_fd() {
+  int before = count_fds();
+  int fd = open("/tmp/procfd", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  if (count_fds() == before + 1) {
+    puts("test_fd ok1");
+  }
+  char path[64];
+  sprintf(path, "/proc/self/fd/%d", fd);
+  if (read_link(path) > 0 && strcmp(buf, "/tmp/procfd") == 0) {
+    puts("test_fd ok2");
+  }
+  // Opening the link opens the file again
+  write(fd, "hello", 5);
+  int again = open(path, O_RDONLY);
+  if (again >= 0 && read(again, buf, 5) == 5 && memcmp(buf, "hello", 5) == 0) {
+    puts("test_fd ok3");
+  }
+  close(again);
+  close(fd);
+  if (count_fds() == before) {
+    puts("test_fd ok4");
+  }
+
+  int fds[2];
+  pipe(fds);
+  sprintf(path, "/proc/self/fd/%d", fds[0]);
+  if (read_link(path) > 0 && strncmp(buf, "pipe:[", 6) == 0) {
+    puts("test_fd ok5");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_fdinfo() {
+  int fd = open("/tmp/procfd", O_RDWR | O_APPEND | O_CLOEXEC);
+  lseek(fd, 3, SEEK_SET);
+  char path[64];
+  sprintf(path, "/proc/self/fdinfo/%d", fd);
+  int info = open(path, O_RDONLY);
+  int len = read(info, buf, sizeof(buf) - 1);
+  close(info);
+  if (len <= 0)
+    return;
+  buf[len] = 0;
+  long pos = -1;
+  unsigned flags = 0;
+  int mnt_id = -1;
+  sscanf(buf, "pos:\t%ld\nflags:\t%o\nmnt_id:\t%d", &pos, &flags, &mnt_id);
+  if (pos == 3) {
+    puts("test_fdinfo ok1");
+  }
+  if ((flags & O_ACCMODE) == O_RDWR && (flags & O_APPEND) && (flags & O_CLOEXEC) &&
+      !(flags & O_CREAT)) {
+    puts("test_fdinfo ok2");
+  }
+  if (mnt_id > 0) {
+    puts("test_fdinfo ok3");
+  }
+  close(fd);
+  unlink("/tmp/procfd");
+}
+
+void test_dev() {
+  if (read_link("/dev/stdin") > 0 && strcmp(buf, "/proc/self/fd/0") == 0 &&
+      read_link("/dev/stdout") > 0 && strcmp(buf, "/proc/self/fd/1") == 0 &&
+      read_link("/dev/stderr") > 0 && strcmp(buf, "/proc/self/fd/2") == 0) {
+    puts("test_dev ok1");
+  }
+  if (read_link("/dev/fd") > 0 && strcmp(buf, "/proc/self/fd") == 0) {
+    puts("test_dev ok2");
+  }
+  fflush(stdout);
+  int fd = open("/dev/fd/1", O_WRONLY);
+  if (fd >= 0 && write(fd, "test_dev ok3\n", 13) == 13) {
+    close(fd);
+  }
+}
+
+int main() {
+  test_fd();
+  test_fdinfo();
+  test_dev();
+  return 0;
+}
@@ -0,0 +1,121 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#define PAGE 4096
+
+static char buf[65536];
+
+static int read_file(const char *path) {
+  int fd = open(path, O_RDONLY);
+  int len = 0, n;
+  while ((n = read(fd, buf + len, sizeof(buf) - 1 - len)) > 0)
+    len += n;
+  close(fd);
+  buf[len] = 0;
+  return len;
+}
+
+// Finds the line of the area starting at `addr`.
+static char *find_area(void *addr) {
+  char prefix[32];
+  sprintf(prefix, "%08lx-", (unsigned long)addr);
+  for (char *line = buf; line && *line; line = strchr(line, '\n') ? strchr(line, '\n') + 1 : NULL) {
+    if (strncmp(line, prefix, strlen(prefix)) == 0)
+      return line;
+  }
+  return NULL;
+}
+
+void test_maps() {
+  char *p = mmap(NULL, 16 * PAGE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  mprotect(p + 8 * PAGE, 8 * PAGE, PROT_READ);
+  read_file("/proc/self/maps");
+  unsigned long start, end;
+  char perms[5];
+  char *line = find_area(p);
+  if (line && sscanf(line, "%lx-%lx %4s", &start, &end, perms) == 3 &&
+      end - start == 8 * PAGE && strcmp(perms, "rw-p") == 0) {
+    puts("test_maps ok1");
+  }
+  line = find_area(p + 8 * PAGE);
+  if (line && sscanf(line, "%lx-%lx %4s", &start, &end, perms) == 3 &&
+      end - start == 8 * PAGE && strcmp(perms, "r--p") == 0) {
+    puts("test_maps ok2");
+  }
+  if (strstr(buf, "[stack]\n") != NULL) {
+    puts("test_maps ok3");
+  }
+  munmap(p, 16 * PAGE);
+  read_file("/proc/self/maps");
+  if (find_area(p) == NULL) {
+    puts("test_maps ok4");
+  }
+}
+
+void test_file() {
+  int fd = open("/tmp/procmaps", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  ftruncate(fd, 2 * PAGE);
+  char *p = mmap(NULL, PAGE, PROT_READ, MAP_PRIVATE, fd, PAGE);
+  close(fd);
+  read_file("/proc/self/maps");
+  unsigned long start, end, offset;
+  char perms[5], dev[16], path[64];
+  unsigned long inode;
+  char *line = find_area(p);
+  if (line &&
+      sscanf(line, "%lx-%lx %4s %lx %15s %lu %63s", &start, &end, perms, &offset, dev, &inode,
+             path) == 7 &&
+      offset == PAGE && strcmp(path, "/tmp/procmaps") == 0) {
+    puts("test_file ok1");
+  }
+  munmap(p, PAGE);
+  unlink("/tmp/procmaps");
+}
+
+// Reads the `key` field of the smaps entry starting at `line`.
+static long smaps_field(char *line, const char *key) {
+  char *field = strstr(line, key);
+  long kb = -1;
+  if (field)
+    sscanf(field + strlen(key), " %ld kB", &kb);
+  return kb;
+}
+
+void test_smaps() {
+  // Guard pages keep the area from being merged with its neighbours
+  char *guard = mmap(NULL, 18 * PAGE, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  char *p = guard + PAGE;
+  mprotect(p, 16 * PAGE, PROT_READ | PROT_WRITE);
+  read_file("/proc/self/smaps");
+  char *line = find_area(p);
+  // Nothing is resident before the pages are touched
+  if (line && smaps_field(line, "\nSize:") == 64 && smaps_field(line, "\nRss:") == 0) {
+    puts("test_smaps ok1");
+  }
+  for (int i = 0; i < 4; i++)
+    p[i * PAGE] = 1;
+  read_file("/proc/self/smaps");
+  line = find_area(p);
+  if (line && smaps_field(line, "\nRss:") == 16 && smaps_field(line, "\nPss:") == 16 &&
+      smaps_field(line, "\nAnonymous:") == 16) {
+    puts("test_smaps ok2");
+  }
+  long total = 0;
+  for (char *rss = strstr(buf, "\nRss:"); rss; rss = strstr(rss + 1, "\nRss:"))
+    total += smaps_field(rss, "\nRss:");
+  read_file("/proc/self/smaps_rollup");
+  if (smaps_field(buf, "\nRss:") == total) {
+    puts("test_smaps ok3");
+  }
+  munmap(guard, 18 * PAGE);
+}
+
+int main() {
+  test_maps();
+  test_file();
+  test_smaps();
+  return 0;
+}
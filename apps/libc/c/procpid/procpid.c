@@ -0,0 +1,144 @@
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static char buf[4096];
+
+static int read_file(const char *path) {
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int len = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (len < 0)
+    return -1;
+  buf[len] = 0;
+  return len;
+}
+
+void test_self() {
+  char link[64], expected[64];
+  int len = readlink("/proc/self", link, sizeof(link) - 1);
+  sprintf(expected, "%d", getpid());
+  if (len > 0 && (link[len] = 0, strcmp(link, expected) == 0)) {
+    puts("test_self ok1");
+  }
+  len = readlink("/proc/thread-self", link, sizeof(link) - 1);
+  sprintf(expected, "%d/task/%ld", getpid(), syscall(SYS_gettid));
+  if (len > 0 && (link[len] = 0, strcmp(link, expected) == 0)) {
+    puts("test_self ok2");
+  }
+  chdir("/tmp");
+  len = readlink("/proc/self/cwd", link, sizeof(link) - 1);
+  if (len > 0 && (link[len] = 0, strcmp(link, "/tmp") == 0)) {
+    puts("test_self ok3");
+  }
+  chdir("/");
+  len = readlink("/proc/self/exe", link, sizeof(link) - 1);
+  if (len > 0 && (link[len] = 0, strstr(link, "procpid") != NULL)) {
+    puts("test_self ok4");
+  }
+}
+
+void test_stat() {
+  if (read_file("/proc/self/stat") < 0)
+    return;
+  int pid, ppid, pgrp, session;
+  char state;
+  char *comm_end = strrchr(buf, ')');
+  if (sscanf(buf, "%d", &pid) == 1 && pid == getpid() && comm_end &&
+      strstr(buf, "(procpid") != NULL) {
+    puts("test_stat ok1");
+  }
+  if (comm_end && sscanf(comm_end + 2, "%c %d %d %d", &state, &ppid, &pgrp, &session) == 4 &&
+      state == 'R' && ppid == getppid() && pgrp == getpgrp() && session == getsid(0)) {
+    puts("test_stat ok2");
+  }
+
+  if (read_file("/proc/self/status") < 0)
+    return;
+  char line[64];
+  sprintf(line, "\nPid:\t%d\n", getpid());
+  if (strncmp(buf, "Name:\tprocpid", 13) == 0 && strstr(buf, line) != NULL) {
+    puts("test_stat ok3");
+  }
+  sprintf(line, "\nPPid:\t%d\n", getppid());
+  if (strstr(buf, line) != NULL && strstr(buf, "\nThreads:\t1\n") != NULL) {
+    puts("test_stat ok4");
+  }
+}
+
+void test_cmdline(char *argv0, char *env0) {
+  int len = read_file("/proc/self/cmdline");
+  if (len == (int)strlen(argv0) + 1 && strcmp(buf, argv0) == 0) {
+    puts("test_cmdline ok1");
+  }
+  len = read_file("/proc/self/environ");
+  if (env0 == NULL || (len > 0 && strcmp(buf, env0) == 0)) {
+    puts("test_cmdline ok2");
+  }
+}
+
+static int listed(pid_t pid) {
+  char name[16];
+  sprintf(name, "%d", pid);
+  DIR *dir = opendir("/proc");
+  struct dirent *entry;
+  int found = 0;
+  while ((entry = readdir(dir)) != NULL) {
+    if (strcmp(entry->d_name, name) == 0)
+      found = 1;
+  }
+  closedir(dir);
+  return found;
+}
+
+void test_children() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    pause();
+    exit(0);
+  }
+  char path[64];
+  if (listed(pid)) {
+    puts("test_children ok1");
+  }
+  sprintf(path, "/proc/%d/task/%d", pid, pid);
+  struct stat st;
+  if (stat(path, &st) == 0 && S_ISDIR(st.st_mode)) {
+    puts("test_children ok2");
+  }
+  kill(pid, SIGKILL);
+  // Reaped only after the state is read, so the zombie is still there
+  usleep(100000);
+  char state = 0;
+  sprintf(path, "/proc/%d/stat", pid);
+  if (read_file(path) > 0) {
+    sscanf(strrchr(buf, ')') + 2, "%c", &state);
+  }
+  if (state == 'Z') {
+    puts("test_children ok3");
+  }
+  waitpid(pid, NULL, 0);
+  sprintf(path, "/proc/%d", pid);
+  if (!listed(pid) && stat(path, &st) < 0 && errno == ENOENT) {
+    puts("test_children ok4");
+  }
+}
+
+int main(int argc, char **argv, char **envp) {
+  (void)argc;
+  test_self();
+  test_stat();
+  test_cmdline(argv[0], envp[0]);
+  test_children();
+  return 0;
+}
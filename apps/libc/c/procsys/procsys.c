@@ -0,0 +1,140 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/utsname.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static char buf[16384];
+
+static int read_file(const char *path) {
+  int fd = open(path, O_RDONLY);
+  int len = 0, n;
+  if (fd < 0)
+    return -1;
+  while ((n = read(fd, buf + len, sizeof(buf) - 1 - len)) > 0)
+    len += n;
+  close(fd);
+  buf[len] = 0;
+  return len;
+}
+
+static long field(const char *key) {
+  char *line = strstr(buf, key);
+  long value = -1;
+  if (line)
+    sscanf(line + strlen(key), " %ld", &value);
+  return value;
+}
+
+static void spin(long ms) {
+  struct timespec start, now;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  do {
+    clock_gettime(CLOCK_MONOTONIC, &now);
+  } while ((now.tv_sec - start.tv_sec) * 1000 + (now.tv_nsec - start.tv_nsec) / 1000000 < ms);
+}
+
+void test_meminfo() {
+  if (read_file("/proc/meminfo") <= 0)
+    return;
+  long total = field("MemTotal:"), mem_free = field("MemFree:");
+  if (total > 0 && mem_free > 0 && mem_free <= total && field("MemAvailable:") >= 0) {
+    puts("test_meminfo ok1");
+  }
+  // Touching 8 MiB takes it from the free memory
+  char *p = malloc(8 << 20);
+  memset(p, 1, 8 << 20);
+  read_file("/proc/meminfo");
+  if (field("MemFree:") < mem_free - 4096) {
+    puts("test_meminfo ok2");
+  }
+  free(p);
+}
+
+void test_cpuinfo() {
+  if (read_file("/proc/cpuinfo") <= 0)
+    return;
+  int cpus = 0;
+  char expected[32];
+  for (;;) {
+    sprintf(expected, "processor\t: %d\n", cpus);
+    if (strstr(buf, expected) == NULL)
+      break;
+    cpus++;
+  }
+  if (cpus >= 1) {
+    puts("test_cpuinfo ok1");
+  }
+}
+
+void test_stat() {
+  read_file("/proc/stat");
+  long user0 = -1, processes0 = field("\nprocesses");
+  sscanf(buf, "cpu %ld", &user0);
+  if (strncmp(buf, "cpu ", 4) == 0 && strstr(buf, "\ncpu0 ") != NULL &&
+      field("\nctxt") > 0 && processes0 > 0) {
+    puts("test_stat ok1");
+  }
+  // Busy user time is charged to the user column
+  spin(300);
+  pid_t pid = fork();
+  if (pid == 0)
+    _exit(0);
+  waitpid(pid, NULL, 0);
+  read_file("/proc/stat");
+  long user1 = -1;
+  sscanf(buf, "cpu %ld", &user1);
+  if (user1 >= user0 + 10) {
+    puts("test_stat ok2");
+  }
+  if (field("\nprocesses") > processes0 && field("\nprocs_running") >= 1) {
+    puts("test_stat ok3");
+  }
+}
+
+void test_uptime() {
+  double up0 = 0, idle = -1, up1 = 0;
+  read_file("/proc/uptime");
+  sscanf(buf, "%lf %lf", &up0, &idle);
+  usleep(300000);
+  read_file("/proc/uptime");
+  sscanf(buf, "%lf", &up1);
+  if (up0 > 0 && idle >= 0 && up1 - up0 >= 0.25 && up1 - up0 < 1) {
+    puts("test_uptime ok1");
+  }
+}
+
+void test_loadavg() {
+  read_file("/proc/loadavg");
+  double avg[3];
+  int running, threads, last_pid;
+  if (sscanf(buf, "%lf %lf %lf %d/%d %d", &avg[0], &avg[1], &avg[2], &running, &threads,
+             &last_pid) == 6 &&
+      running >= 1 && threads >= running && last_pid >= getpid()) {
+    puts("test_loadavg ok1");
+  }
+}
+
+void test_version() {
+  struct utsname uts;
+  uname(&uts);
+  read_file("/proc/version");
+  char expected[128];
+  sprintf(expected, "Linux version %s ", uts.release);
+  if (strncmp(buf, expected, strlen(expected)) == 0) {
+    puts("test_version ok1");
+  }
+}
+
+int main() {
+  test_meminfo();
+  test_cpuinfo();
+  test_stat();
+  test_uptime();
+  test_loadavg();
+  test_version();
+  return 0;
+}
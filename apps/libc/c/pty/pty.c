@@ -0,0 +1,122 @@
+#define _GNU_SOURCE
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <unistd.h>
+
+#ifndef TIOCGPTPEER
+#define TIOCGPTPEER 0x5441
+#endif
+
+static int master = -1, slave = -1;
+static unsigned index_nr;
+static char buf[256];
+
+static int readable(int fd) {
+  struct pollfd pfd = {.fd = fd, .events = POLLIN};
+  return poll(&pfd, 1, 100) == 1 && (pfd.revents & POLLIN);
+}
+
+void test_open() {
+  master = posix_openpt(O_RDWR | O_NOCTTY);
+  if (master < 0) {
+    perror("posix_openpt");
+    return;
+  }
+  char path[32];
+  ioctl(master, TIOCGPTN, &index_nr);
+  sprintf(path, "/dev/pts/%u", index_nr);
+  if (strcmp(ptsname(master), path) == 0) {
+    puts("test_open ok1");
+  }
+  // Locked until unlockpt
+  if (open(path, O_RDWR | O_NOCTTY) < 0 && errno == EIO) {
+    puts("test_open ok2");
+  }
+  grantpt(master);
+  unlockpt(master);
+  slave = open(path, O_RDWR | O_NOCTTY);
+  struct stat st;
+  if (slave >= 0 && fstat(slave, &st) == 0 && S_ISCHR(st.st_mode) && major(st.st_rdev) == 136 &&
+      minor(st.st_rdev) == index_nr) {
+    puts("test_open ok3");
+  }
+  char name[16];
+  sprintf(name, "%u", index_nr);
+  DIR *dir = opendir("/dev/pts");
+  struct dirent *entry;
+  int found = 0;
+  while (dir && (entry = readdir(dir)) != NULL) {
+    if (strcmp(entry->d_name, name) == 0)
+      found = 1;
+  }
+  if (dir)
+    closedir(dir);
+  if (found) {
+    puts("test_open ok4");
+  }
+}
+
+void test_io() {
+  if (readable(master) == 0) {
+    puts("test_io ok1");
+  }
+  // Input is echoed to the master, with ONLCR applied
+  write(master, "hello\n", 6);
+  int len = read(slave, buf, sizeof(buf));
+  if (len == 6 && memcmp(buf, "hello\n", 6) == 0) {
+    puts("test_io ok2");
+  }
+  len = read(master, buf, sizeof(buf));
+  if (len == 7 && memcmp(buf, "hello\r\n", 7) == 0) {
+    puts("test_io ok3");
+  }
+  write(slave, "out\n", 4);
+  if (readable(master) && read(master, buf, sizeof(buf)) == 5 && memcmp(buf, "out\r\n", 5) == 0) {
+    puts("test_io ok4");
+  }
+}
+
+void test_peer() {
+  int peer = ioctl(master, TIOCGPTPEER, O_RDWR | O_NOCTTY);
+  struct stat st;
+  if (peer >= 0 && fstat(peer, &st) == 0 && minor(st.st_rdev) == index_nr) {
+    puts("test_peer ok1");
+  }
+  // Both slave files share the terminal
+  write(peer, "x", 1);
+  if (read(master, buf, sizeof(buf)) == 1 && buf[0] == 'x') {
+    puts("test_peer ok2");
+  }
+  close(peer);
+}
+
+void test_hangup() {
+  close(slave);
+  if (read(master, buf, sizeof(buf)) < 0 && errno == EIO) {
+    puts("test_hangup ok1");
+  }
+  char path[32];
+  sprintf(path, "/dev/pts/%u", index_nr);
+  close(master);
+  if (open(path, O_RDWR | O_NOCTTY) < 0) {
+    puts("test_hangup ok2");
+  }
+}
+
+int main() {
+  test_open();
+  if (slave < 0)
+    return 1;
+  test_io();
+  test_peer();
+  test_hangup();
+  return 0;
+}
@@ -0,0 +1,110 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/auxv.h>
+#include <sys/random.h>
+#include <sys/stat.h>
+#include <sys/sysmacros.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#ifndef GRND_INSECURE
+#define GRND_INSECURE 0x4
+#endif
+
+static unsigned char buf[65536];
+
+static int all_zero(const unsigned char *p, int len) {
+  for (int i = 0; i < len; i++) {
+    if (p[i])
+      return 0;
+  }
+  return 1;
+}
+
+void test_getrandom() {
+  unsigned char a[64], b[64];
+  if (getrandom(a, sizeof(a), 0) == sizeof(a) && getrandom(b, sizeof(b), 0) == sizeof(b) &&
+      !all_zero(a, sizeof(a)) && memcmp(a, b, sizeof(a)) != 0) {
+    puts("test_getrandom ok1");
+  }
+  // Seeded once a blocking call has returned
+  if (getrandom(a, sizeof(a), GRND_NONBLOCK) == sizeof(a) &&
+      getrandom(a, sizeof(a), GRND_RANDOM) == sizeof(a)) {
+    puts("test_getrandom ok2");
+  }
+  if (getrandom(a, sizeof(a), 0x80) < 0 && errno == EINVAL &&
+      getrandom(a, sizeof(a), GRND_RANDOM | GRND_INSECURE) < 0 && errno == EINVAL) {
+    puts("test_getrandom ok3");
+  }
+  // Every byte value turns up in 64 KiB
+  int seen[256] = {0}, distinct = 0;
+  getrandom(buf, sizeof(buf), 0);
+  for (int i = 0; i < (int)sizeof(buf); i++) {
+    if (!seen[buf[i]]++)
+      distinct++;
+  }
+  if (distinct == 256) {
+    puts("test_getrandom ok4");
+  }
+}
+
+void test_devices() {
+  struct stat st;
+  if (stat("/dev/random", &st) == 0 && S_ISCHR(st.st_mode) && major(st.st_rdev) == 1 &&
+      minor(st.st_rdev) == 8 && stat("/dev/urandom", &st) == 0 && S_ISCHR(st.st_mode) &&
+      major(st.st_rdev) == 1 && minor(st.st_rdev) == 9) {
+    puts("test_devices ok1");
+  }
+  unsigned char a[32], b[32];
+  int fd = open("/dev/urandom", O_RDWR);
+  if (read(fd, a, sizeof(a)) == sizeof(a) && read(fd, b, sizeof(b)) == sizeof(b) &&
+      !all_zero(a, sizeof(a)) && memcmp(a, b, sizeof(a)) != 0) {
+    puts("test_devices ok2");
+  }
+  // Writes are mixed into the pool
+  if (write(fd, "seed", 4) == 4) {
+    puts("test_devices ok3");
+  }
+  close(fd);
+  fd = open("/dev/random", O_RDONLY);
+  if (read(fd, a, sizeof(a)) == sizeof(a) && !all_zero(a, sizeof(a))) {
+    puts("test_devices ok4");
+  }
+  close(fd);
+}
+
+void test_auxv() {
+  unsigned char *mine = (unsigned char *)getauxval(AT_RANDOM);
+  if (mine == NULL || all_zero(mine, 16))
+    return;
+  int fds[2];
+  pipe(fds);
+  pid_t pid = fork();
+  if (pid == 0) {
+    dup2(fds[1], 1);
+    execl("/proc/self/exe", "random", "auxv", NULL);
+    _exit(1);
+  }
+  close(fds[1]);
+  unsigned char theirs[16];
+  int len = read(fds[0], theirs, sizeof(theirs));
+  close(fds[0]);
+  waitpid(pid, NULL, 0);
+  // Each program gets its own bytes
+  if (len == 16 && memcmp(mine, theirs, 16) != 0) {
+    puts("test_auxv ok1");
+  }
+}
+
+int main(int argc, char **argv) {
+  if (argc > 1 && strcmp(argv[1], "auxv") == 0) {
+    write(1, (void *)getauxval(AT_RANDOM), 16);
+    return 0;
+  }
+  test_getrandom();
+  test_devices();
+  test_auxv();
+  return 0;
+}
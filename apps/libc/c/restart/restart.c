@@ -0,0 +1,203 @@
+#define _GNU_SOURCE
+#include <arpa/inet.h>
+#include <errno.h>
+#include <linux/futex.h>
+#include <netinet/in.h>
+#include <poll.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static volatile int handled;
+
+static void on_alarm(int sig) {
+  (void)sig;
+  handled++;
+}
+
+static long now_ms() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+// Handles SIGALRM, restarting interrupted calls if `restart`, and has it sent
+// after `ms`
+static void alarm_after(int restart, long ms) {
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = on_alarm;
+  sa.sa_flags = restart ? SA_RESTART : 0;
+  sigaction(SIGALRM, &sa, NULL);
+  handled = 0;
+  struct itimerval it = {{0, 0}, {ms / 1000, ms % 1000 * 1000}};
+  setitimer(ITIMER_REAL, &it, NULL);
+}
+
+// Stops the child `pid` for a while during its call, then waits for its
+// exit code
+static int stop_and_continue(pid_t pid) {
+  usleep(100000);
+  kill(pid, SIGSTOP);
+  usleep(100000);
+  kill(pid, SIGCONT);
+  int status;
+  waitpid(pid, &status, 0);
+  return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+void test_pipe() {
+  int fds[2];
+  pipe(fds);
+  char buf[8];
+  alarm_after(0, 100);
+  if (read(fds[0], buf, sizeof(buf)) == -1 && errno == EINTR && handled == 1) {
+    puts("test_pipe ok1");
+  }
+  // With SA_RESTART, the read goes on after the handler until data arrives
+  pid_t pid = fork();
+  if (pid == 0) {
+    usleep(300000);
+    write(fds[1], "hi", 2);
+    _exit(0);
+  }
+  alarm_after(1, 100);
+  ssize_t n = read(fds[0], buf, sizeof(buf));
+  if (n == 2 && memcmp(buf, "hi", 2) == 0 && handled == 1) {
+    puts("test_pipe ok2");
+  }
+  waitpid(pid, NULL, 0);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_wait() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    usleep(400000);
+    _exit(7);
+  }
+  int status;
+  alarm_after(0, 100);
+  if (waitpid(pid, &status, 0) == -1 && errno == EINTR && handled == 1) {
+    puts("test_wait ok1");
+  }
+  alarm_after(1, 100);
+  if (waitpid(pid, &status, 0) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 7 &&
+      handled == 1) {
+    puts("test_wait ok2");
+  }
+}
+
+static volatile int futex_word;
+
+static long futex_wait(const struct timespec *timeout) {
+  return syscall(SYS_futex, &futex_word, FUTEX_WAIT, 0, timeout, NULL, 0);
+}
+
+static void *waker(void *arg) {
+  (void)arg;
+  usleep(300000);
+  futex_word = 1;
+  syscall(SYS_futex, &futex_word, FUTEX_WAKE, 1, NULL, NULL, 0);
+  return NULL;
+}
+
+void test_futex() {
+  futex_word = 0;
+  alarm_after(0, 100);
+  if (futex_wait(NULL) == -1 && errno == EINTR && handled == 1) {
+    puts("test_futex ok1");
+  }
+  // Without a timeout, SA_RESTART waits on until woken
+  pthread_t thread;
+  pthread_create(&thread, NULL, waker, NULL);
+  alarm_after(1, 100);
+  long ret = futex_wait(NULL);
+  pthread_join(thread, NULL);
+  if (ret == 0 && futex_word == 1 && handled == 1) {
+    puts("test_futex ok2");
+  }
+  // With one, a handler always interrupts it
+  futex_word = 0;
+  struct timespec timeout = {1, 0};
+  alarm_after(1, 100);
+  long start = now_ms();
+  if (futex_wait(&timeout) == -1 && errno == EINTR && handled == 1 && now_ms() - start < 500) {
+    puts("test_futex ok3");
+  }
+}
+
+void test_nanosleep() {
+  // A handler interrupts it even with SA_RESTART
+  struct timespec req = {1, 0}, rem = {0, 0};
+  alarm_after(1, 100);
+  if (nanosleep(&req, &rem) == -1 && errno == EINTR && handled == 1 && rem.tv_sec == 0 &&
+      rem.tv_nsec >= 700000000) {
+    puts("test_nanosleep ok1");
+  }
+  // Being stopped doesn't, and the sleep keeps its deadline
+  pid_t pid = fork();
+  if (pid == 0) {
+    struct timespec nap = {0, 400000000};
+    long start = now_ms();
+    int ret = nanosleep(&nap, NULL);
+    long elapsed = now_ms() - start;
+    _exit(ret == 0 && elapsed >= 400 && elapsed < 700 ? 0 : 1);
+  }
+  if (stop_and_continue(pid) == 0) {
+    puts("test_nanosleep ok2");
+  }
+}
+
+void test_poll() {
+  int fds[2];
+  pipe(fds);
+  struct pollfd pfd = {.fd = fds[0], .events = POLLIN};
+  alarm_after(1, 100);
+  if (poll(&pfd, 1, -1) == -1 && errno == EINTR && handled == 1) {
+    puts("test_poll ok1");
+  }
+  pid_t pid = fork();
+  if (pid == 0) {
+    long start = now_ms();
+    int ret = poll(&pfd, 1, 300);
+    _exit(ret == 0 && now_ms() - start >= 300 ? 0 : 1);
+  }
+  if (stop_and_continue(pid) == 0) {
+    puts("test_poll ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_accept() {
+  int fd = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr = {.sin_family = AF_INET, .sin_port = htons(18562)};
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  bind(fd, (struct sockaddr *)&addr, sizeof(addr));
+  listen(fd, 1);
+  alarm_after(0, 100);
+  if (accept(fd, NULL, NULL) == -1 && errno == EINTR && handled == 1) {
+    puts("test_accept ok1");
+  }
+  close(fd);
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  test_pipe();
+  test_wait();
+  test_futex();
+  test_nanosleep();
+  test_poll();
+  test_accept();
+  return 0;
+}
@@ -0,0 +1,132 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/resource.h>
+#include <sys/times.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static double seconds(struct timeval tv) {
+  return tv.tv_sec + tv.tv_usec / 1e6;
+}
+
+static double cpu_seconds(int who) {
+  struct rusage ru;
+  getrusage(who, &ru);
+  return seconds(ru.ru_utime) + seconds(ru.ru_stime);
+}
+
+// Spins until the calling thread has used `ms` of CPU time.
+static void spin(long ms) {
+  struct timespec start, now;
+  clock_gettime(CLOCK_THREAD_CPUTIME_ID, &start);
+  do {
+    clock_gettime(CLOCK_THREAD_CPUTIME_ID, &now);
+  } while ((now.tv_sec - start.tv_sec) * 1000 + (now.tv_nsec - start.tv_nsec) / 1000000 < ms);
+}
+
+void test_children() {
+  if (cpu_seconds(RUSAGE_CHILDREN) == 0) {
+    puts("test_children ok1");
+  }
+  pid_t pid = fork();
+  if (pid == 0) {
+    spin(300);
+    _exit(0);
+  }
+  // Not counted before it is reaped
+  siginfo_t info;
+  waitid(P_PID, pid, &info, WEXITED | WNOWAIT);
+  if (cpu_seconds(RUSAGE_CHILDREN) == 0) {
+    puts("test_children ok2");
+  }
+  waitpid(pid, NULL, 0);
+  if (cpu_seconds(RUSAGE_CHILDREN) >= 0.25) {
+    puts("test_children ok3");
+  }
+}
+
+void test_times() {
+  long hz = sysconf(_SC_CLK_TCK);
+  struct tms before, after;
+  clock_t start = times(&before);
+  spin(200);
+  clock_t end = times(&after);
+  if (after.tms_utime + after.tms_stime - before.tms_utime - before.tms_stime >= hz * 15 / 100 &&
+      end - start >= hz * 15 / 100) {
+    puts("test_times ok1");
+  }
+  // The children totals come from the reaped child only
+  if (after.tms_cutime + after.tms_cstime >= hz * 25 / 100 &&
+      after.tms_cutime + after.tms_cstime < hz * 2) {
+    puts("test_times ok2");
+  }
+}
+
+void test_memory() {
+  struct rusage before, after;
+  getrusage(RUSAGE_SELF, &before);
+  char *p = mmap(NULL, 8 << 20, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  for (int i = 0; i < (8 << 20); i += 4096)
+    p[i] = 1;
+  getrusage(RUSAGE_SELF, &after);
+  if (after.ru_minflt - before.ru_minflt >= 2048) {
+    puts("test_memory ok1");
+  }
+  // In kilobytes
+  if (after.ru_maxrss >= 8192) {
+    puts("test_memory ok2");
+  }
+  munmap(p, 8 << 20);
+}
+
+static double thread_cpu;
+
+static void *worker(void *arg) {
+  (void)arg;
+  spin(200);
+  thread_cpu = cpu_seconds(RUSAGE_THREAD);
+  return NULL;
+}
+
+void test_thread() {
+  struct rusage before, after;
+  getrusage(RUSAGE_THREAD, &before);
+  for (int i = 0; i < 10; i++)
+    usleep(1000);
+  getrusage(RUSAGE_THREAD, &after);
+  // Each sleep gives up the CPU
+  if (after.ru_nvcsw - before.ru_nvcsw >= 10) {
+    puts("test_thread ok1");
+  }
+  double self = cpu_seconds(RUSAGE_SELF);
+  double main_thread = cpu_seconds(RUSAGE_THREAD);
+  pthread_t thread;
+  pthread_create(&thread, NULL, worker, NULL);
+  pthread_join(thread, NULL);
+  if (thread_cpu >= 0.15 && cpu_seconds(RUSAGE_THREAD) - main_thread < 0.1) {
+    puts("test_thread ok2");
+  }
+  // The time of the exited thread stays with the process
+  if (cpu_seconds(RUSAGE_SELF) - self >= 0.15) {
+    puts("test_thread ok3");
+  }
+  struct rusage ru;
+  if (getrusage(5, &ru) < 0 && errno == EINVAL) {
+    puts("test_thread ok4");
+  }
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  test_children();
+  test_times();
+  test_memory();
+  test_thread();
+  return 0;
+}
@@ -0,0 +1,201 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/time.h>
+#include <sys/timex.h>
+#include <time.h>
+#include <unistd.h>
+
+// The wall clock to put back at the end, at the monotonic time saved with it
+static struct timespec saved_real, saved_mono;
+
+static long ms_of(struct timespec ts) {
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static long now_ms(clockid_t clock) {
+  struct timespec ts;
+  clock_gettime(clock, &ts);
+  return ms_of(ts);
+}
+
+static void set_real_sec(time_t sec) {
+  struct timespec ts = {sec, 0};
+  clock_settime(CLOCK_REALTIME, &ts);
+}
+
+static void restore_clock() {
+  struct timespec mono, real = saved_real;
+  clock_gettime(CLOCK_MONOTONIC, &mono);
+  real.tv_sec += mono.tv_sec - saved_mono.tv_sec;
+  real.tv_nsec += mono.tv_nsec - saved_mono.tv_nsec;
+  if (real.tv_nsec < 0) {
+    real.tv_sec--;
+    real.tv_nsec += 1000000000;
+  } else if (real.tv_nsec >= 1000000000) {
+    real.tv_sec++;
+    real.tv_nsec -= 1000000000;
+  }
+  clock_settime(CLOCK_REALTIME, &real);
+}
+
+void test_settime() {
+  time_t base = saved_real.tv_sec + 1000;
+  long mono = now_ms(CLOCK_MONOTONIC);
+  set_real_sec(base);
+  long real = now_ms(CLOCK_REALTIME);
+  if (real >= base * 1000 && real - base * 1000 < 100) {
+    puts("test_settime ok1");
+  }
+  // The monotonic clock doesn't jump
+  if (now_ms(CLOCK_MONOTONIC) - mono < 100) {
+    puts("test_settime ok2");
+  }
+  struct timespec ts = {base, 0};
+  if (clock_settime(CLOCK_MONOTONIC, &ts) == -1 && errno == EINVAL) {
+    puts("test_settime ok3");
+  }
+  ts.tv_nsec = 1000000000;
+  if (clock_settime(CLOCK_REALTIME, &ts) == -1 && errno == EINVAL &&
+      now_ms(CLOCK_REALTIME) - base * 1000 < 100) {
+    puts("test_settime ok4");
+  }
+}
+
+void test_settimeofday() {
+  time_t base = saved_real.tv_sec + 2000;
+  struct timeval tv = {base, 500000};
+  settimeofday(&tv, NULL);
+  struct timeval now;
+  gettimeofday(&now, NULL);
+  long diff = (now.tv_sec - base) * 1000 + (now.tv_usec - 500000) / 1000;
+  if (diff >= 0 && diff < 100) {
+    puts("test_settimeofday ok1");
+  }
+  if (time(NULL) == base && labs(now_ms(CLOCK_REALTIME) - (base * 1000 + 500)) < 100) {
+    puts("test_settimeofday ok2");
+  }
+}
+
+void test_adjtimex() {
+  time_t base = saved_real.tv_sec + 3000;
+  set_real_sec(base);
+  // Steps the clock back by 500s
+  struct timex tx;
+  memset(&tx, 0, sizeof(tx));
+  tx.modes = ADJ_SETOFFSET;
+  tx.time.tv_sec = -500;
+  tx.time.tv_usec = 0;
+  int state = adjtimex(&tx);
+  long real = now_ms(CLOCK_REALTIME) - (base - 500) * 1000;
+  if (state >= 0 && real >= 0 && real < 100) {
+    puts("test_adjtimex ok1");
+  }
+  // Reading reports the time without changing anything
+  memset(&tx, 0, sizeof(tx));
+  state = adjtimex(&tx);
+  if (state >= 0 && labs(tx.time.tv_sec - (base - 500)) <= 1) {
+    puts("test_adjtimex ok2");
+  }
+  // An offset is slewed gradually rather than stepped, so most of it is
+  // still left
+  struct timeval delta = {0, 100000}, left = {0, 0};
+  long before = now_ms(CLOCK_REALTIME) - now_ms(CLOCK_MONOTONIC);
+  adjtime(&delta, NULL);
+  usleep(100000);
+  adjtime(NULL, &left);
+  long after = now_ms(CLOCK_REALTIME) - now_ms(CLOCK_MONOTONIC);
+  if (left.tv_sec == 0 && left.tv_usec >= 90000 && left.tv_usec <= 100000 &&
+      labs(after - before) < 50) {
+    puts("test_adjtimex ok3");
+  }
+  struct timeval zero = {0, 0};
+  adjtime(&zero, &left);
+  if (left.tv_sec == 0 && left.tv_usec > 0 && adjtime(NULL, &left) == 0 && left.tv_usec == 0) {
+    puts("test_adjtimex ok4");
+  }
+}
+
+static timer_t create_timer() {
+  struct sigevent sev;
+  memset(&sev, 0, sizeof(sev));
+  sev.sigev_notify = SIGEV_SIGNAL;
+  sev.sigev_signo = SIGUSR1;
+  timer_t id;
+  timer_create(CLOCK_REALTIME, &sev, &id);
+  return id;
+}
+
+static int wait_usr1(long ms) {
+  sigset_t set;
+  sigemptyset(&set);
+  sigaddset(&set, SIGUSR1);
+  struct timespec timeout = {ms / 1000, ms % 1000 * 1000000};
+  return sigtimedwait(&set, NULL, &timeout) == SIGUSR1;
+}
+
+static void *abs_sleeper(void *arg) {
+  struct timespec *deadline = arg;
+  long ret = clock_nanosleep(CLOCK_REALTIME, TIMER_ABSTIME, deadline, NULL);
+  return (void *)ret;
+}
+
+void test_timer_jump() {
+  sigset_t set;
+  sigemptyset(&set);
+  sigaddset(&set, SIGUSR1);
+  sigprocmask(SIG_BLOCK, &set, NULL);
+  time_t base = saved_real.tv_sec + 4000;
+  set_real_sec(base);
+
+  // An absolute timer fires as soon as the clock jumps past it
+  timer_t id = create_timer();
+  struct itimerspec its = {{0, 0}, {base + 100, 0}};
+  timer_settime(id, TIMER_ABSTIME, &its, NULL);
+  long start = now_ms(CLOCK_MONOTONIC);
+  set_real_sec(base + 200);
+  if (wait_usr1(1000) && now_ms(CLOCK_MONOTONIC) - start < 500) {
+    puts("test_timer_jump ok1");
+  }
+
+  // A relative one still lasts as long
+  struct itimerspec rel = {{0, 0}, {0, 300000000}};
+  timer_settime(id, 0, &rel, NULL);
+  start = now_ms(CLOCK_MONOTONIC);
+  set_real_sec(base + 1000);
+  int fired = wait_usr1(1000);
+  long elapsed = now_ms(CLOCK_MONOTONIC) - start;
+  if (fired && elapsed >= 250 && elapsed < 800) {
+    puts("test_timer_jump ok2");
+  }
+  timer_delete(id);
+
+  // So does an absolute sleep
+  struct timespec deadline = {base + 1100, 0};
+  pthread_t thread;
+  pthread_create(&thread, NULL, abs_sleeper, &deadline);
+  usleep(100000);
+  start = now_ms(CLOCK_MONOTONIC);
+  set_real_sec(base + 1200);
+  void *ret;
+  pthread_join(thread, &ret);
+  if (ret == NULL && now_ms(CLOCK_MONOTONIC) - start < 500) {
+    puts("test_timer_jump ok3");
+  }
+  sigprocmask(SIG_UNBLOCK, &set, NULL);
+}
+
+int main() {
+  clock_gettime(CLOCK_REALTIME, &saved_real);
+  clock_gettime(CLOCK_MONOTONIC, &saved_mono);
+  test_settime();
+  test_settimeofday();
+  test_adjtimex();
+  test_timer_jump();
+  restore_clock();
+  return 0;
+}
@@ -0,0 +1,162 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static int ticks[2];
+static volatile int chld_code;
+
+static void on_sigchld(int sig, siginfo_t *info, void *ctx) {
+  (void)sig;
+  (void)ctx;
+  chld_code = info->si_code;
+}
+
+static void *ticker(void *arg) {
+  (void)arg;
+  for (;;) {
+    write(ticks[1], "t", 1);
+    usleep(10000);
+  }
+  return NULL;
+}
+
+// Starts a child whose two threads write to the ticks pipe.
+static pid_t start_ticker() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    pthread_t thread;
+    pthread_create(&thread, NULL, ticker, NULL);
+    ticker(NULL);
+  }
+  return pid;
+}
+
+// Counts the ticks written in the next `ms` milliseconds.
+static int count_ticks(int ms) {
+  char buf[256];
+  while (read(ticks[0], buf, sizeof(buf)) > 0) {
+  }
+  usleep(ms * 1000);
+  int n = 0, len;
+  while ((len = read(ticks[0], buf, sizeof(buf))) > 0)
+    n += len;
+  return n;
+}
+
+static char proc_state(pid_t pid) {
+  char path[64], buf[256], state = 0;
+  sprintf(path, "/proc/%d/stat", pid);
+  int fd = open(path, O_RDONLY);
+  int len = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (len > 0) {
+    buf[len] = 0;
+    sscanf(strrchr(buf, ')') + 2, "%c", &state);
+  }
+  return state;
+}
+
+void test_stop() {
+  struct sigaction sa = {.sa_sigaction = on_sigchld, .sa_flags = SA_SIGINFO | SA_RESTART};
+  sigaction(SIGCHLD, &sa, NULL);
+  pid_t pid = start_ticker();
+  usleep(50000);
+
+  kill(pid, SIGSTOP);
+  int status;
+  if (waitpid(pid, &status, WUNTRACED) == pid && WIFSTOPPED(status) &&
+      WSTOPSIG(status) == SIGSTOP) {
+    puts("test_stop ok1");
+  }
+  // Neither thread runs while stopped
+  if (count_ticks(100) == 0 && proc_state(pid) == 'T') {
+    puts("test_stop ok2");
+  }
+  if (chld_code == CLD_STOPPED) {
+    puts("test_stop ok3");
+  }
+  // Stops are only reported with WUNTRACED
+  if (waitpid(pid, &status, WNOHANG) == 0) {
+    puts("test_stop ok4");
+  }
+
+  kill(pid, SIGCONT);
+  if (waitpid(pid, &status, WCONTINUED) == pid && WIFCONTINUED(status)) {
+    puts("test_continue ok1");
+  }
+  if (count_ticks(100) > 0 && chld_code == CLD_CONTINUED) {
+    puts("test_continue ok2");
+  }
+  kill(pid, SIGKILL);
+  waitpid(pid, NULL, 0);
+}
+
+static volatile int tstp_caught;
+
+static void on_sigtstp(int sig) {
+  (void)sig;
+  tstp_caught = 1;
+}
+
+void test_tstp() {
+  pid_t pid = start_ticker();
+  kill(pid, SIGTSTP);
+  int status;
+  if (waitpid(pid, &status, WUNTRACED) == pid && WIFSTOPPED(status) &&
+      WSTOPSIG(status) == SIGTSTP) {
+    puts("test_tstp ok1");
+  }
+  kill(pid, SIGKILL);
+  waitpid(pid, NULL, 0);
+
+  // A handled SIGTSTP does not stop
+  pid = fork();
+  if (pid == 0) {
+    signal(SIGTSTP, on_sigtstp);
+    while (!tstp_caught)
+      pause();
+    _exit(7);
+  }
+  usleep(50000);
+  kill(pid, SIGTSTP);
+  if (waitpid(pid, &status, WUNTRACED) == pid && WIFEXITED(status) && WEXITSTATUS(status) == 7) {
+    puts("test_tstp ok2");
+  }
+}
+
+void test_nocldstop() {
+  struct sigaction sa = {.sa_sigaction = on_sigchld,
+                         .sa_flags = SA_SIGINFO | SA_RESTART | SA_NOCLDSTOP};
+  sigaction(SIGCHLD, &sa, NULL);
+  chld_code = 0;
+  pid_t pid = start_ticker();
+  kill(pid, SIGSTOP);
+  int status;
+  waitpid(pid, &status, WUNTRACED);
+  usleep(50000);
+  if (chld_code == 0) {
+    puts("test_nocldstop ok1");
+  }
+  kill(pid, SIGKILL);
+  waitpid(pid, NULL, 0);
+  // Exits are still reported
+  if (chld_code == CLD_KILLED) {
+    puts("test_nocldstop ok2");
+  }
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  pipe2(ticks, O_NONBLOCK);
+  test_stop();
+  test_tstp();
+  test_nocldstop();
+  return 0;
+}
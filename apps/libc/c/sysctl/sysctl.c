@@ -0,0 +1,138 @@
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/utsname.h>
+#include <unistd.h>
+
+#ifndef F_SETPIPE_SZ
+#define F_SETPIPE_SZ 1031
+#endif
+
+static char buf[256];
+
+static int read_sysctl(const char *path) {
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int len = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (len < 0)
+    return -1;
+  buf[len] = 0;
+  return len;
+}
+
+static int write_sysctl(const char *path, const char *value) {
+  int fd = open(path, O_WRONLY);
+  if (fd < 0)
+    return -1;
+  int ret = write(fd, value, strlen(value));
+  close(fd);
+  return ret;
+}
+
+void test_hostname() {
+  struct utsname uts;
+  uname(&uts);
+  char old[128];
+  read_sysctl("/proc/sys/kernel/hostname");
+  strcpy(old, buf);
+  if (strcmp(buf, strcat(uts.nodename, "\n")) == 0) {
+    puts("test_hostname ok1");
+  }
+  write_sysctl("/proc/sys/kernel/hostname", "sysctl-test\n");
+  uname(&uts);
+  if (strcmp(uts.nodename, "sysctl-test") == 0) {
+    puts("test_hostname ok2");
+  }
+  write_sysctl("/proc/sys/kernel/hostname", old);
+}
+
+void test_bounds() {
+  read_sysctl("/proc/sys/kernel/pid_max");
+  char old[32];
+  strcpy(old, buf);
+  // Below the minimum of 301
+  if (write_sysctl("/proc/sys/kernel/pid_max", "100") < 0 && errno == EINVAL) {
+    puts("test_bounds ok1");
+  }
+  if (write_sysctl("/proc/sys/kernel/pid_max", "abc") < 0 && errno == EINVAL) {
+    puts("test_bounds ok2");
+  }
+  read_sysctl("/proc/sys/kernel/pid_max");
+  if (strcmp(buf, old) == 0) {
+    puts("test_bounds ok3");
+  }
+  if (write_sysctl("/proc/sys/vm/overcommit_memory", "3") < 0 && errno == EINVAL) {
+    puts("test_bounds ok4");
+  }
+}
+
+void test_pipe_size() {
+  char old[32];
+  read_sysctl("/proc/sys/fs/pipe-max-size");
+  strcpy(old, buf);
+  write_sysctl("/proc/sys/fs/pipe-max-size", "8192");
+  int fds[2];
+  pipe(fds);
+  if (fcntl(fds[0], F_SETPIPE_SZ, 65536) < 0 && errno == EPERM) {
+    puts("test_pipe_size ok1");
+  }
+  if (fcntl(fds[0], F_SETPIPE_SZ, 8192) == 8192) {
+    puts("test_pipe_size ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+  write_sysctl("/proc/sys/fs/pipe-max-size", old);
+}
+
+void test_file_max() {
+  char old[32];
+  read_sysctl("/proc/sys/fs/file-max");
+  strcpy(old, buf);
+  // Kept open to restore the limit once nothing else can be opened
+  int fd = open("/proc/sys/fs/file-max", O_WRONLY);
+  pwrite(fd, "1", 1, 0);
+  if (open("/proc/sys/fs/file-max", O_RDONLY) < 0 && errno == ENFILE) {
+    puts("test_file_max ok1");
+  }
+  pwrite(fd, old, strlen(old), 0);
+  close(fd);
+}
+
+void test_printk() {
+  write_sysctl("/proc/sys/kernel/printk", "4");
+  read_sysctl("/proc/sys/kernel/printk");
+  if (strcmp(buf, "4\t4\t1\t7\n") == 0) {
+    puts("test_printk ok1");
+  }
+  write_sysctl("/proc/sys/kernel/printk", "7");
+}
+
+void test_list() {
+  DIR *dir = opendir("/proc/sys/fs");
+  if (dir == NULL)
+    return;
+  int found = 0;
+  struct dirent *entry;
+  while ((entry = readdir(dir)) != NULL) {
+    if (strcmp(entry->d_name, "file-max") == 0 || strcmp(entry->d_name, "pipe-max-size") == 0)
+      found++;
+  }
+  closedir(dir);
+  if (found == 2) {
+    puts("test_list ok1");
+  }
+}
+
+int main() {
+  test_hostname();
+  test_bounds();
+  test_pipe_size();
+  test_file_max();
+  test_printk();
+  test_list();
+  return 0;
+}
@@ -0,0 +1,124 @@
+#include <dirent.h>
+#include <fcntl.h>
+#include <linux/loop.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/vfs.h>
+#include <unistd.h>
+
+#define SYSFS_MAGIC 0x62656572
+
+static char buf[256];
+
+static int read_attr(const char *path) {
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int len = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (len < 0)
+    return -1;
+  buf[len] = 0;
+  return len;
+}
+
+static int has_entry(const char *path, const char *name) {
+  DIR *dir = opendir(path);
+  if (dir == NULL)
+    return 0;
+  int found = 0;
+  struct dirent *entry;
+  while ((entry = readdir(dir)) != NULL) {
+    if (strcmp(entry->d_name, name) == 0)
+      found = 1;
+  }
+  closedir(dir);
+  return found;
+}
+
+void test_mount() {
+  struct statfs st;
+  if (statfs("/sys", &st) == 0 && st.f_type == SYSFS_MAGIC) {
+    puts("test_mount ok1");
+  }
+}
+
+void test_cpu() {
+  int first = -1, last = -1;
+  if (read_attr("/sys/devices/system/cpu/online") <= 0)
+    return;
+  if (sscanf(buf, "%d-%d", &first, &last) == 1)
+    last = first;
+  if (first == 0 && last >= 0) {
+    puts("test_cpu ok1");
+  }
+  char name[16];
+  int listed = 1;
+  for (int cpu = first; cpu <= last; cpu++) {
+    sprintf(name, "cpu%d", cpu);
+    listed &= has_entry("/sys/devices/system/cpu", name);
+  }
+  read_attr("/sys/devices/system/cpu/possible");
+  if (listed && buf[0] == '0') {
+    puts("test_cpu ok2");
+  }
+}
+
+void test_net() {
+  if (read_attr("/sys/class/net/lo/address") > 0 && strcmp(buf, "00:00:00:00:00:00\n") == 0) {
+    puts("test_net ok1");
+  }
+  if (read_attr("/sys/class/net/lo/mtu") > 0 && strcmp(buf, "65536\n") == 0) {
+    puts("test_net ok2");
+  }
+}
+
+void test_thp() {
+  if (read_attr("/sys/kernel/mm/transparent_hugepage/enabled") > 0 && strchr(buf, '[') &&
+      strstr(buf, "never") != NULL) {
+    puts("test_thp ok1");
+  }
+}
+
+void test_block() {
+  char pattern[4096];
+  memset(pattern, 'x', sizeof(pattern));
+  int fd = open("/tmp/sysfs_backing", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, pattern, sizeof(pattern));
+  int ctl = open("/dev/loop-control", O_RDWR);
+  int index = ioctl(ctl, LOOP_CTL_GET_FREE);
+  close(ctl);
+  char path[64], name[16];
+  sprintf(path, "/dev/loop%d", index);
+  sprintf(name, "loop%d", index);
+  int loop_fd = open(path, O_RDWR);
+  ioctl(loop_fd, LOOP_SET_FD, fd);
+  close(fd);
+
+  sprintf(path, "/sys/block/loop%d/size", index);
+  // The size is in 512-byte sectors
+  if (has_entry("/sys/block", name) && read_attr(path) > 0 && strcmp(buf, "8\n") == 0) {
+    puts("test_block ok1");
+  }
+  sprintf(path, "/sys/block/loop%d/ro", index);
+  if (read_attr(path) > 0 && strcmp(buf, "0\n") == 0) {
+    puts("test_block ok2");
+  }
+  ioctl(loop_fd, LOOP_CLR_FD);
+  close(loop_fd);
+  sprintf(path, "/sys/block/loop%d/size", index);
+  if (read_attr(path) > 0 && strcmp(buf, "0\n") == 0) {
+    puts("test_block ok3");
+  }
+  unlink("/tmp/sysfs_backing");
+}
+
+int main() {
+  test_mount();
+  test_cpu();
+  test_net();
+  test_thp();
+  test_block();
+  return 0;
+}
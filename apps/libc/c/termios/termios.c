@@ -0,0 +1,169 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/wait.h>
+#include <termios.h>
+#include <time.h>
+#include <unistd.h>
+
+static int master = -1, slave = -1;
+static char slave_path[32];
+static char buf[256];
+
+static int readable(int fd) {
+  struct pollfd pfd = {.fd = fd, .events = POLLIN};
+  return poll(&pfd, 1, 100) == 1 && (pfd.revents & POLLIN);
+}
+
+static void open_pty() {
+  master = posix_openpt(O_RDWR | O_NOCTTY);
+  grantpt(master);
+  unlockpt(master);
+  strcpy(slave_path, ptsname(master));
+  slave = open(slave_path, O_RDWR | O_NOCTTY);
+}
+
+static void set_echo(int on) {
+  struct termios t;
+  tcgetattr(slave, &t);
+  if (on)
+    t.c_lflag |= ECHO;
+  else
+    t.c_lflag &= ~ECHO;
+  tcsetattr(slave, TCSANOW, &t);
+}
+
+void test_default() {
+  struct termios t;
+  tcgetattr(slave, &t);
+  if ((t.c_lflag & (ICANON | ECHO | ISIG)) == (ICANON | ECHO | ISIG) && (t.c_iflag & ICRNL) &&
+      (t.c_oflag & ONLCR) && t.c_cc[VINTR] == 3 && t.c_cc[VERASE] == 0x7f) {
+    puts("test_default ok1");
+  }
+  int fd = open("/dev/null", O_RDONLY);
+  if (tcgetattr(fd, &t) < 0 && errno == ENOTTY) {
+    puts("test_default ok2");
+  }
+  close(fd);
+  // Unknown requests are refused
+  if (ioctl(slave, 0x54ff, 0) < 0 && errno == ENOTTY) {
+    puts("test_default ok3");
+  }
+}
+
+void test_canonical() {
+  set_echo(0);
+  write(master, "abc\x7f\n", 5);
+  if (read(slave, buf, sizeof(buf)) == 3 && memcmp(buf, "ab\n", 3) == 0) {
+    puts("test_canonical ok1");
+  }
+  write(master, "xyz\x15ok\n", 7);
+  if (read(slave, buf, sizeof(buf)) == 3 && memcmp(buf, "ok\n", 3) == 0) {
+    puts("test_canonical ok2");
+  }
+  // ICRNL turns the carriage return into a newline
+  write(master, "hi\r", 3);
+  if (read(slave, buf, sizeof(buf)) == 3 && memcmp(buf, "hi\n", 3) == 0) {
+    puts("test_canonical ok3");
+  }
+  // An incomplete line is not readable
+  write(master, "part", 4);
+  if (!readable(slave)) {
+    puts("test_canonical ok4");
+  }
+  tcflush(slave, TCIFLUSH);
+}
+
+void test_raw() {
+  struct termios saved, t;
+  tcgetattr(slave, &saved);
+  t = saved;
+  cfmakeraw(&t);
+  t.c_cc[VMIN] = 1;
+  t.c_cc[VTIME] = 0;
+  tcsetattr(slave, TCSANOW, &t);
+  write(master, "a\r", 2);
+  if (read(slave, buf, sizeof(buf)) == 2 && memcmp(buf, "a\r", 2) == 0 && !readable(master)) {
+    puts("test_raw ok1");
+  }
+  // OPOST is off, so newlines are written as is
+  write(slave, "b\n", 2);
+  if (read(master, buf, sizeof(buf)) == 2 && memcmp(buf, "b\n", 2) == 0) {
+    puts("test_raw ok2");
+  }
+  // VMIN=0 and VTIME=2 time out after 0.2s
+  t.c_cc[VMIN] = 0;
+  t.c_cc[VTIME] = 2;
+  tcsetattr(slave, TCSANOW, &t);
+  struct timespec start, end;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  int len = read(slave, buf, sizeof(buf));
+  clock_gettime(CLOCK_MONOTONIC, &end);
+  long ms = (end.tv_sec - start.tv_sec) * 1000 + (end.tv_nsec - start.tv_nsec) / 1000000;
+  if (len == 0 && ms >= 150 && ms < 1000) {
+    puts("test_raw ok3");
+  }
+  tcsetattr(slave, TCSANOW, &saved);
+}
+
+void test_flush() {
+  write(master, "junk\n", 5);
+  struct termios t;
+  tcgetattr(slave, &t);
+  // TCSAFLUSH throws the pending input away
+  tcsetattr(slave, TCSAFLUSH, &t);
+  int pending = -1;
+  if (ioctl(slave, FIONREAD, &pending) == 0 && pending == 0) {
+    puts("test_flush ok1");
+  }
+}
+
+static volatile int interrupted;
+
+static void on_sigint(int sig) {
+  (void)sig;
+  interrupted = 1;
+}
+
+void test_isig() {
+  int ready[2];
+  pipe(ready);
+  pid_t pid = fork();
+  if (pid == 0) {
+    setsid();
+    signal(SIGINT, on_sigint);
+    // Opening the slave makes it the controlling terminal
+    int fd = open(slave_path, O_RDWR);
+    write(ready[1], "r", 1);
+    while (!interrupted)
+      pause();
+    _exit(fd >= 0 ? 0 : 1);
+  }
+  read(ready[0], buf, 1);
+  write(master, "\x03", 1);
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_isig ok1");
+  }
+  close(ready[0]);
+  close(ready[1]);
+}
+
+int main() {
+  open_pty();
+  if (slave < 0)
+    return 1;
+  test_default();
+  test_canonical();
+  test_raw();
+  test_flush();
+  test_isig();
+  return 0;
+}
@@ -0,0 +1,100 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/vfs.h>
+#include <unistd.h>
+
+#define TMPFS_MAGIC 0x01021994
+
+static char buf[65536];
+
+void test_default() {
+  struct statfs st;
+  if (statfs("/tmp", &st) == 0 && st.f_type == TMPFS_MAGIC) {
+    puts("test_default ok1");
+  }
+  if (statfs("/dev/shm", &st) == 0 && st.f_type == TMPFS_MAGIC) {
+    puts("test_default ok2");
+  }
+}
+
+void test_options() {
+  mkdir("/tmp/small", 0755);
+  if (mount("tmpfs", "/tmp/small", "tmpfs", 0, "size=64k,nr_inodes=4,mode=700") < 0) {
+    perror("mount");
+    return;
+  }
+  struct statfs st;
+  // The root directory takes one of the inodes
+  if (statfs("/tmp/small", &st) == 0 && st.f_bsize == 4096 && st.f_blocks == 16 &&
+      st.f_bfree == 16 && st.f_files == 4 && st.f_ffree == 3) {
+    puts("test_options ok1");
+  }
+  struct stat s;
+  if (stat("/tmp/small", &s) == 0 && (s.st_mode & 07777) == 0700) {
+    puts("test_options ok2");
+  }
+  mkdir("/tmp/bad", 0755);
+  if (mount("tmpfs", "/tmp/bad", "tmpfs", 0, "bogus=1") < 0 && errno == EINVAL) {
+    puts("test_options ok3");
+  }
+  rmdir("/tmp/bad");
+}
+
+void test_space() {
+  int fd = open("/tmp/small/big", O_CREAT | O_RDWR, 0644);
+  memset(buf, 'x', sizeof(buf));
+  if (write(fd, buf, sizeof(buf)) == sizeof(buf)) {
+    puts("test_space ok1");
+  }
+  if (write(fd, buf, 1) < 0 && errno == ENOSPC) {
+    puts("test_space ok2");
+  }
+  struct statfs st;
+  if (statfs("/tmp/small", &st) == 0 && st.f_bfree == 0) {
+    puts("test_space ok3");
+  }
+  // Truncating gives the pages back
+  ftruncate(fd, 0);
+  if (statfs("/tmp/small", &st) == 0 && st.f_bfree == 16) {
+    puts("test_space ok4");
+  }
+  close(fd);
+  unlink("/tmp/small/big");
+}
+
+void test_inodes() {
+  char path[32];
+  int created = 0;
+  for (int i = 0; i < 4; i++) {
+    sprintf(path, "/tmp/small/f%d", i);
+    int fd = open(path, O_CREAT | O_WRONLY, 0644);
+    if (fd < 0) {
+      if (errno == ENOSPC && created == 3) {
+        puts("test_inodes ok1");
+      }
+      break;
+    }
+    close(fd);
+    created++;
+  }
+  unlink("/tmp/small/f0");
+  int fd = open("/tmp/small/again", O_CREAT | O_WRONLY, 0644);
+  if (fd >= 0) {
+    puts("test_inodes ok2");
+    close(fd);
+  }
+  umount("/tmp/small");
+  rmdir("/tmp/small");
+}
+
+int main() {
+  test_default();
+  test_options();
+  test_space();
+  test_inodes();
+  return 0;
+}
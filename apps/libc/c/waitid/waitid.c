@@ -0,0 +1,167 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/resource.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#ifndef P_PIDFD
+#define P_PIDFD 3
+#endif
+#ifndef SYS_pidfd_open
+#define SYS_pidfd_open 434
+#endif
+
+static pid_t spawn_exit(int code) {
+  pid_t pid = fork();
+  if (pid == 0)
+    _exit(code);
+  return pid;
+}
+
+static pid_t spawn_pause() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    pause();
+    _exit(0);
+  }
+  return pid;
+}
+
+void test_waitid() {
+  pid_t pid = spawn_exit(3);
+  siginfo_t info;
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_PID, pid, &info, WEXITED) == 0 && info.si_signo == SIGCHLD &&
+      info.si_code == CLD_EXITED && info.si_pid == pid && info.si_status == 3) {
+    puts("test_waitid ok1");
+  }
+
+  pid = spawn_pause();
+  kill(pid, SIGTERM);
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_ALL, 0, &info, WEXITED) == 0 && info.si_code == CLD_KILLED &&
+      info.si_pid == pid && info.si_status == SIGTERM) {
+    puts("test_waitid ok2");
+  }
+
+  // WNOHANG without a change clears si_pid
+  pid = spawn_pause();
+  info.si_pid = 1;
+  if (waitid(P_PID, pid, &info, WEXITED | WNOHANG) == 0 && info.si_pid == 0) {
+    puts("test_waitid ok3");
+  }
+  kill(pid, SIGSTOP);
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_PID, pid, &info, WSTOPPED) == 0 && info.si_code == CLD_STOPPED &&
+      info.si_status == SIGSTOP) {
+    puts("test_waitid ok4");
+  }
+  kill(pid, SIGKILL);
+  waitpid(pid, NULL, 0);
+
+  if (waitid(P_PID, pid, &info, 0) < 0 && errno == EINVAL) {
+    puts("test_waitid ok5");
+  }
+  if (waitid(P_ALL, 0, &info, WEXITED) < 0 && errno == ECHILD) {
+    puts("test_waitid ok6");
+  }
+}
+
+void test_idtypes() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setpgid(0, 0);
+    _exit(4);
+  }
+  setpgid(pid, pid);
+  siginfo_t info;
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_PGID, pid, &info, WEXITED) == 0 && info.si_pid == pid && info.si_status == 4) {
+    puts("test_idtypes ok1");
+  }
+
+  pid = spawn_exit(5);
+  int pidfd = syscall(SYS_pidfd_open, pid, 0);
+  memset(&info, 0, sizeof(info));
+  if (pidfd >= 0 && waitid(P_PIDFD, pidfd, &info, WEXITED) == 0 && info.si_pid == pid &&
+      info.si_status == 5) {
+    puts("test_idtypes ok2");
+  }
+  close(pidfd);
+
+  // WNOWAIT leaves the child to be waited for again
+  pid = spawn_exit(6);
+  memset(&info, 0, sizeof(info));
+  waitid(P_PID, pid, &info, WEXITED | WNOWAIT);
+  int status;
+  if (info.si_status == 6 && waitpid(pid, &status, 0) == pid && WEXITSTATUS(status) == 6) {
+    puts("test_idtypes ok3");
+  }
+}
+
+void test_status() {
+  int status;
+  pid_t pid = spawn_exit(143);
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && !WIFSIGNALED(status) && WEXITSTATUS(status) == 143) {
+    puts("test_status ok1");
+  }
+  pid = spawn_pause();
+  kill(pid, SIGTERM);
+  waitpid(pid, &status, 0);
+  if (!WIFEXITED(status) && WIFSIGNALED(status) && WTERMSIG(status) == SIGTERM) {
+    puts("test_status ok2");
+  }
+}
+
+static double seconds(struct timeval tv) {
+  return tv.tv_sec + tv.tv_usec / 1e6;
+}
+
+void test_rusage() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    // Fault in 256 pages, then spin for 0.3s
+    char *p = mmap(NULL, 256 * 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    for (int i = 0; i < 256; i++)
+      p[i * 4096] = 1;
+    struct timespec ts;
+    do {
+      clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &ts);
+    } while (ts.tv_sec * 1000 + ts.tv_nsec / 1000000 < 300);
+    _exit(0);
+  }
+  struct rusage ru;
+  memset(&ru, 0, sizeof(ru));
+  int status;
+  if (wait4(pid, &status, 0, &ru) == pid && seconds(ru.ru_utime) + seconds(ru.ru_stime) >= 0.25) {
+    puts("test_rusage ok1");
+  }
+  if (ru.ru_minflt >= 256) {
+    puts("test_rusage ok2");
+  }
+
+  pid = spawn_exit(0);
+  siginfo_t info;
+  memset(&ru, 0xff, sizeof(ru));
+  if (syscall(SYS_waitid, P_PID, pid, &info, WEXITED, &ru) == 0 && ru.ru_utime.tv_sec >= 0 &&
+      ru.ru_utime.tv_usec < 1000000 && ru.ru_minflt >= 0) {
+    puts("test_rusage ok3");
+  }
+}
+
+int main() {
+  setvbuf(stdout, NULL, _IONBF, 0);
+  test_waitid();
+  test_idtypes();
+  test_status();
+  test_rusage();
+  return 0;
+}
@@ -0,0 +1,93 @@
+#define _GNU_SOURCE
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/ioctl.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static int master = -1;
+static char slave_path[32];
+static int ack[2];
+
+static void on_sigwinch(int sig) {
+  (void)sig;
+  write(ack[1], "w", 1);
+}
+
+// Waits up to `ms` for the child to report a SIGWINCH.
+static int got_sigwinch(int ms) {
+  struct pollfd pfd = {.fd = ack[0], .events = POLLIN};
+  char c;
+  return poll(&pfd, 1, ms) == 1 && read(ack[0], &c, 1) == 1;
+}
+
+static int set_size(int fd, int rows, int cols) {
+  struct winsize ws = {.ws_row = rows, .ws_col = cols};
+  return ioctl(fd, TIOCSWINSZ, &ws);
+}
+
+void test_winsize() {
+  int slave = open(slave_path, O_RDWR | O_NOCTTY);
+  struct winsize ws;
+  set_size(slave, 24, 80);
+  // Both sides see the same size
+  if (ioctl(master, TIOCGWINSZ, &ws) == 0 && ws.ws_row == 24 && ws.ws_col == 80) {
+    puts("test_winsize ok1");
+  }
+  set_size(master, 50, 132);
+  if (ioctl(slave, TIOCGWINSZ, &ws) == 0 && ws.ws_row == 50 && ws.ws_col == 132) {
+    puts("test_winsize ok2");
+  }
+  close(slave);
+}
+
+void test_sigwinch() {
+  int ready[2], done[2];
+  pipe(ready);
+  pipe(done);
+  pipe(ack);
+  pid_t pid = fork();
+  if (pid == 0) {
+    struct sigaction sa = {.sa_handler = on_sigwinch, .sa_flags = SA_RESTART};
+    sigaction(SIGWINCH, &sa, NULL);
+    // The child leads a session controlled by the terminal, so it is in
+    // the foreground
+    setsid();
+    int fd = open(slave_path, O_RDWR);
+    write(ready[1], "r", 1);
+    char c;
+    read(done[0], &c, 1);
+    _exit(fd >= 0 ? 0 : 1);
+  }
+  char c;
+  read(ready[0], &c, 1);
+  set_size(master, 30, 100);
+  if (got_sigwinch(1000)) {
+    puts("test_sigwinch ok1");
+  }
+  // Nothing is sent if the size stays the same
+  set_size(master, 30, 100);
+  if (!got_sigwinch(200)) {
+    puts("test_sigwinch ok2");
+  }
+  set_size(master, 31, 100);
+  if (got_sigwinch(1000)) {
+    puts("test_sigwinch ok3");
+  }
+  write(done[1], "d", 1);
+  waitpid(pid, NULL, 0);
+}
+
+int main() {
+  master = posix_openpt(O_RDWR | O_NOCTTY);
+  grantpt(master);
+  unlockpt(master);
+  strcpy(slave_path, ptsname(master));
+  test_winsize();
+  test_sigwinch();
+  return 0;
+}
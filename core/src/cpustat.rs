@@ -0,0 +1,309 @@
+//! Per-CPU time accounting and load averages, as shown in `/proc/stat`,
+//! `/proc/uptime` and `/proc/loadavg`.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use axhal::time::monotonic_time_nanos;
+use axprocess::Pid;
+
+use crate::task;
+
+/// The time a CPU spent in each state, in nanoseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuTimes {
+    /// Time spent running user code.
+    pub user: u64,
+    /// Time spent running kernel code on behalf of tasks.
+    pub system: u64,
+    /// Time spent in the idle task.
+    pub idle: u64,
+}
+
+impl CpuTimes {
+    fn add(&mut self, other: &CpuTimes) {
+        self.user += other.user;
+        self.system += other.system;
+        self.idle += other.idle;
+    }
+}
+
+/// What a CPU runs from one accounting point on.
+#[derive(Debug, Clone, Copy)]
+struct Running {
+    /// The thread, or 0 for a kernel task.
+    tid: Pid,
+    /// Whether the task is the idle task.
+    idle: bool,
+    /// Whether the thread runs in user mode.
+    user: bool,
+    /// Whether the thread has switched out voluntarily.
+    switched_out: bool,
+}
+
+impl Running {
+    /// Describes the current task, running in user mode if `user`.
+    fn current(user: bool) -> Self {
+        Self {
+            tid: task::current_tid(),
+            idle: axtask::current().is_idle(),
+            user,
+            switched_out: false,
+        }
+    }
+
+    const fn to_bits(self) -> u64 {
+        ((self.tid as u64) << 3)
+            | ((self.idle as u64) << 2)
+            | ((self.user as u64) << 1)
+            | self.switched_out as u64
+    }
+
+    const fn from_bits(bits: u64) -> Self {
+        Self {
+            tid: (bits >> 3) as Pid,
+            idle: bits & 0b100 != 0,
+            user: bits & 0b10 != 0,
+            switched_out: bits & 1 != 0,
+        }
+    }
+}
+
+struct CpuStat {
+    user: AtomicU64,
+    system: AtomicU64,
+    idle: AtomicU64,
+    /// The time of the last accounting point on this CPU.
+    last: AtomicU64,
+    /// What this CPU has run since the last accounting point, as
+    /// [`Running`] bits, in one word so that interrupts see it consistent.
+    running: AtomicU64,
+    /// The interrupts handled on this CPU.
+    interrupts: AtomicU64,
+    /// The context switches on this CPU.
+    switches: AtomicU64,
+    /// Whether the trap being handled is an exception.
+    exception: AtomicBool,
+}
+
+impl CpuStat {
+    const fn new() -> Self {
+        Self {
+            user: AtomicU64::new(0),
+            system: AtomicU64::new(0),
+            idle: AtomicU64::new(0),
+            last: AtomicU64::new(0),
+            running: AtomicU64::new(
+                Running {
+                    tid: 0,
+                    idle: true,
+                    user: false,
+                    switched_out: false,
+                }
+                .to_bits(),
+            ),
+            interrupts: AtomicU64::new(0),
+            switches: AtomicU64::new(0),
+            exception: AtomicBool::new(false),
+        }
+    }
+}
+
+static CPU_STATS: [CpuStat; axconfig::plat::CPU_NUM] =
+    [const { CpuStat::new() }; axconfig::plat::CPU_NUM];
+
+/// Fixed-point load averages scaled by `1 << FSHIFT`, as in Linux.
+static LOAD_AVG: [AtomicU64; 3] = [const { AtomicU64::new(0) }; 3];
+static LOAD_AVG_NEXT: AtomicU64 = AtomicU64::new(LOAD_FREQ);
+
+const FSHIFT: u32 = 11;
+const FIXED_1: u64 = 1 << FSHIFT;
+/// `FIXED_1 / exp(5s / 1min)`, `FIXED_1 / exp(5s / 5min)` and
+/// `FIXED_1 / exp(5s / 15min)`.
+const EXP: [u64; 3] = [1884, 2014, 2037];
+/// Load averages are sampled every 5 seconds.
+const LOAD_FREQ: u64 = 5_000_000_000;
+
+/// Charges the time since the previous accounting point on this CPU to the
+/// state the CPU was trapped from, and to the running task, which goes on in
+/// user mode if `to_user`. Called on every trap and syscall boundary, so
+/// timer interrupts sample idle CPUs as well.
+pub fn account_trap(from_user: bool, to_user: bool) {
+    account(from_user, Running::current(to_user), true);
+}
+
+/// Accounts for the current thread switching out voluntarily, charging its
+/// time up to now. The time until it runs again goes to whatever runs
+/// meanwhile.
+pub fn account_switch_out() {
+    let mut next = Running::current(false);
+    next.switched_out = true;
+    account(false, next, false);
+    CPU_STATS[axhal::cpu::this_cpu_id()]
+        .switches
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Accounts for the current thread running again after
+/// [`account_switch_out`].
+pub fn account_switch_in() {
+    account(false, Running::current(false), false);
+}
+
+/// Charges the time since the previous accounting point on this CPU and
+/// records `next` as running from now on.
+///
+/// Task switches happen inside `axtask` without notice, so they are noticed
+/// at the first accounting point after them, where another thread shows up.
+/// If the thread recorded before did not switch out voluntarily, it was
+/// preempted by an interrupt, whose trap is when the switch happened: the
+/// time until now is then its own, and it counts an involuntary switch.
+/// After a voluntary switch the time goes to the task running now.
+///
+/// With `keep_switched_out`, a thread that switched out voluntarily stays
+/// marked so while it is only interrupted on the way to blocking.
+fn account(from_user: bool, mut next: Running, keep_switched_out: bool) {
+    let now = monotonic_time_nanos();
+    let stat = &CPU_STATS[axhal::cpu::this_cpu_id()];
+    let delta = now.saturating_sub(stat.last.swap(now, Ordering::Relaxed));
+    let switching_out = next.switched_out;
+    let prev = stat
+        .running
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            let prev = Running::from_bits(bits);
+            next.switched_out =
+                switching_out || (keep_switched_out && prev.switched_out && prev.tid == next.tid);
+            Some(next.to_bits())
+        })
+        .unwrap_or_else(|bits| bits);
+    let prev = Running::from_bits(prev);
+
+    let preempted = prev.tid != next.tid && !prev.switched_out;
+    let ran = if preempted {
+        prev
+    } else {
+        Running {
+            user: from_user && !prev.switched_out,
+            ..next
+        }
+    };
+    let counter = if ran.user {
+        &stat.user
+    } else if ran.idle {
+        &stat.idle
+    } else {
+        &stat.system
+    };
+    counter.fetch_add(delta, Ordering::Relaxed);
+    if ran.tid != 0 {
+        task::charge_thread(ran.tid, ran.user, delta);
+    }
+    if preempted {
+        stat.switches.fetch_add(1, Ordering::Relaxed);
+        if prev.tid != 0 {
+            task::count_switch(prev.tid, false);
+        }
+    }
+
+    update_load_avg(now);
+}
+
+/// Marks the trap being handled on this CPU as an exception, such as a
+/// syscall or a page fault, rather than an interrupt.
+pub fn mark_exception() {
+    CPU_STATS[axhal::cpu::this_cpu_id()]
+        .exception
+        .store(true, Ordering::Relaxed);
+}
+
+/// Counts the trap just handled on this CPU as an interrupt, unless it was
+/// marked as an exception. Called once after every trap.
+///
+/// An interrupt taken between an exception handler marking its trap and
+/// this call takes the mark, so the count stays right.
+pub fn count_trap() {
+    let stat = &CPU_STATS[axhal::cpu::this_cpu_id()];
+    if !stat.exception.swap(false, Ordering::Relaxed) {
+        stat.interrupts.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn update_load_avg(now: u64) {
+    let next = LOAD_AVG_NEXT.load(Ordering::Relaxed);
+    if now < next {
+        return;
+    }
+    // Sampled again at the next trap if the thread table is busy
+    let Some(running) = task::nr_running() else {
+        return;
+    };
+    if LOAD_AVG_NEXT
+        .compare_exchange(next, next + LOAD_FREQ, Ordering::Relaxed, Ordering::Relaxed)
+        .is_err()
+    {
+        return;
+    }
+    let active = running as u64 * FIXED_1;
+    for (load, exp) in LOAD_AVG.iter().zip(EXP) {
+        let old = load.load(Ordering::Relaxed);
+        let new = (old * exp + active * (FIXED_1 - exp)) / FIXED_1;
+        load.store(new, Ordering::Relaxed);
+    }
+}
+
+/// Returns the number of CPUs.
+pub fn cpu_count() -> usize {
+    axconfig::plat::CPU_NUM
+}
+
+/// Returns the times of the CPU `cpu`.
+pub fn cpu_times(cpu: usize) -> CpuTimes {
+    let stat = &CPU_STATS[cpu];
+    CpuTimes {
+        user: stat.user.load(Ordering::Relaxed),
+        system: stat.system.load(Ordering::Relaxed),
+        idle: stat.idle.load(Ordering::Relaxed),
+    }
+}
+
+/// Returns the sum of the times of all CPUs.
+pub fn total_cpu_times() -> CpuTimes {
+    let mut total = CpuTimes::default();
+    for cpu in 0..cpu_count() {
+        total.add(&cpu_times(cpu));
+    }
+    total
+}
+
+/// Returns the number of threads running or ready to run.
+pub fn nr_running() -> usize {
+    loop {
+        if let Some(running) = task::nr_running() {
+            return running;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Returns the number of interrupts handled since boot.
+pub fn interrupt_count() -> u64 {
+    CPU_STATS
+        .iter()
+        .map(|stat| stat.interrupts.load(Ordering::Relaxed))
+        .sum()
+}
+
+/// Returns the number of context switches since boot.
+pub fn context_switches() -> u64 {
+    CPU_STATS
+        .iter()
+        .map(|stat| stat.switches.load(Ordering::Relaxed))
+        .sum()
+}
+
+/// Returns the 1, 5 and 15 minute load averages, scaled by 100.
+pub fn load_avg() -> [u64; 3] {
+    LOAD_AVG.each_ref().map(|load| {
+        // Round to two decimal places like Linux's `LOAD_INT`/`LOAD_FRAC`
+        ((load.load(Ordering::Relaxed) + FIXED_1 / 200) * 100) >> FSHIFT
+    })
+}
@@ -0,0 +1,166 @@
+//! Block devices that can back a mounted filesystem.
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use axerrno::{AxError, AxResult};
+use spin::RwLock;
+
+/// A device that is accessed in fixed-size blocks.
+pub trait BlockDevice: Send + Sync {
+    /// The size of a block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// The number of blocks of the device.
+    fn num_blocks(&self) -> u64;
+
+    /// Reads the block `block_id` into `buf`, which must be exactly one block.
+    fn read_block(&self, block_id: u64, buf: &mut [u8]) -> AxResult;
+
+    /// Writes `buf`, which must be exactly one block, to the block `block_id`.
+    fn write_block(&self, block_id: u64, buf: &[u8]) -> AxResult;
+
+    /// Flushes cached data to the underlying storage.
+    fn flush(&self) -> AxResult {
+        Ok(())
+    }
+
+    /// Whether the device rejects writes.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Records a new user of the device, such as a mounted filesystem or an
+    /// open file, until the matching [`BlockDevice::release`].
+    fn acquire(&self) {}
+
+    /// Records that a user from [`BlockDevice::acquire`] is gone.
+    fn release(&self) {}
+}
+
+/// A byte-addressed cursor over a [`BlockDevice`], which counts as a user of
+/// the device while it exists.
+///
+/// Filesystem drivers read and write at arbitrary offsets, so partial blocks
+/// are handled here with a read-modify-write cycle.
+pub struct Disk {
+    dev: Arc<dyn BlockDevice>,
+    pos: u64,
+}
+
+impl Disk {
+    /// Creates a new cursor at the beginning of `dev`.
+    pub fn new(dev: Arc<dyn BlockDevice>) -> Self {
+        dev.acquire();
+        Self { dev, pos: 0 }
+    }
+
+    /// The size of the device in bytes.
+    pub fn size(&self) -> u64 {
+        self.dev.num_blocks() * self.dev.block_size() as u64
+    }
+
+    /// The current position of the cursor.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Moves the cursor to `pos`.
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// Reads from the current position, returning the number of bytes read.
+    pub fn read_one(&mut self, buf: &mut [u8]) -> AxResult<usize> {
+        let block_size = self.dev.block_size() as u64;
+        let block_id = self.pos / block_size;
+        let offset = (self.pos % block_size) as usize;
+        if block_id >= self.dev.num_blocks() {
+            return Ok(0);
+        }
+
+        let read_size = if offset == 0 && buf.len() >= block_size as usize {
+            self.dev
+                .read_block(block_id, &mut buf[..block_size as usize])?;
+            block_size as usize
+        } else {
+            let mut data = vec![0u8; block_size as usize];
+            self.dev.read_block(block_id, &mut data)?;
+            let len = buf.len().min(block_size as usize - offset);
+            buf[..len].copy_from_slice(&data[offset..offset + len]);
+            len
+        };
+        self.pos += read_size as u64;
+        Ok(read_size)
+    }
+
+    /// Writes at the current position, returning the number of bytes written.
+    pub fn write_one(&mut self, buf: &[u8]) -> AxResult<usize> {
+        if self.dev.is_read_only() {
+            return Err(AxError::PermissionDenied);
+        }
+        let block_size = self.dev.block_size() as u64;
+        let block_id = self.pos / block_size;
+        let offset = (self.pos % block_size) as usize;
+        if block_id >= self.dev.num_blocks() {
+            return Ok(0);
+        }
+
+        let write_size = if offset == 0 && buf.len() >= block_size as usize {
+            self.dev
+                .write_block(block_id, &buf[..block_size as usize])?;
+            block_size as usize
+        } else {
+            let mut data = vec![0u8; block_size as usize];
+            self.dev.read_block(block_id, &mut data)?;
+            let len = buf.len().min(block_size as usize - offset);
+            data[offset..offset + len].copy_from_slice(&buf[..len]);
+            self.dev.write_block(block_id, &data)?;
+            len
+        };
+        self.pos += write_size as u64;
+        Ok(write_size)
+    }
+
+    /// Flushes the underlying device.
+    pub fn sync(&self) -> AxResult {
+        self.dev.flush()
+    }
+}
+
+impl Drop for Disk {
+    fn drop(&mut self) {
+        self.dev.release();
+    }
+}
+
+static BLOCK_DEVICES: RwLock<BTreeMap<String, Arc<dyn BlockDevice>>> = RwLock::new(BTreeMap::new());
+
+/// Registers a block device under `path` (e.g. `/dev/loop0`), so that it can
+/// be used as the source of a mount.
+pub fn register_block_device(path: &str, dev: Arc<dyn BlockDevice>) {
+    BLOCK_DEVICES.write().insert(path.to_string(), dev);
+}
+
+/// Removes the block device registered under `path`.
+pub fn unregister_block_device(path: &str) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.write().remove(path)
+}
+
+/// Lists the paths of all registered block devices.
+pub fn block_devices() -> Vec<String> {
+    BLOCK_DEVICES.read().keys().cloned().collect()
+}
+
+/// Finds the block device registered under `path`.
+pub fn get_block_device(path: &str) -> AxResult<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES
+        .read()
+        .get(path)
+        .cloned()
+        .ok_or(AxError::NotFound)
+}
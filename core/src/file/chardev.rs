@@ -0,0 +1,183 @@
+//! Character devices created in `/dev` during boot.
+use core::any::Any;
+
+use alloc::{sync::Arc, vec, vec::Vec};
+use axfs_devfs::{NullDev, ZeroDev};
+use axfs_vfs::{
+    VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult,
+};
+
+use super::add_node;
+use crate::random;
+
+fn char_device_attr() -> VfsResult<VfsNodeAttr> {
+    Ok(VfsNodeAttr::new(
+        VfsNodePerm::from_bits_truncate(0o666),
+        VfsNodeType::CharDevice,
+        0,
+        0,
+    ))
+}
+
+/// The node `/dev/full`, which reads as zeros and is always out of space.
+pub struct FullDev;
+
+impl VfsNodeOps for FullDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        char_device_attr()
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::StorageFull)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The node `/dev/kmsg`. Each write is logged as one kernel message; the
+/// log is not kept, so reads return end of file.
+pub struct KmsgDev;
+
+impl VfsNodeOps for KmsgDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        char_device_attr()
+    }
+
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Ok(0)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let msg = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        info!("{}", msg.trim_end());
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The nodes `/dev/random` and `/dev/urandom`.
+///
+/// Both read from the kernel CSPRNG; `/dev/random` additionally waits until
+/// it has been seeded. Writes are mixed into the pool without being credited
+/// as entropy.
+pub struct RandomDev {
+    blocking: bool,
+}
+
+impl VfsNodeOps for RandomDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        char_device_attr()
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.blocking {
+            random::wait_until_seeded();
+        }
+        random::fill_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        random::add_entropy(buf, 0);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The terminal a [`TtyNode`] opens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtyKind {
+    /// The system console, `/dev/console`.
+    Console,
+    /// The controlling terminal of the opener, `/dev/tty`.
+    Controlling,
+    /// A new pseudo-terminal, through `ptmx`.
+    PtyMaster,
+    /// The slave of the pseudo-terminal with the index, `/dev/pts/<n>`.
+    PtySlave(u32),
+}
+
+/// A terminal node such as `/dev/console` or `/dev/ptmx`.
+///
+/// Opening a terminal creates a file of the tty layer in the API crate,
+/// which finds the node by [`lookup_node`](super::mount::lookup_node) and
+/// opens the terminal of its kind, wherever the node is mounted. Reads and
+/// writes never reach the node.
+pub struct TtyNode {
+    /// The terminal the node opens.
+    pub kind: TtyKind,
+}
+
+impl TtyNode {
+    /// Creates a node opening the terminal `kind`.
+    pub fn new(kind: TtyKind) -> Self {
+        Self { kind }
+    }
+}
+
+impl VfsNodeOps for TtyNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        char_device_attr()
+    }
+
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::Unsupported)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::Unsupported)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The character devices of every devtmpfs instance, by name.
+pub(crate) fn char_devices() -> Vec<(&'static str, VfsNodeRef)> {
+    vec![
+        ("null", Arc::new(NullDev) as VfsNodeRef),
+        ("zero", Arc::new(ZeroDev)),
+        ("full", Arc::new(FullDev)),
+        ("random", Arc::new(RandomDev { blocking: true })),
+        ("urandom", Arc::new(RandomDev { blocking: false })),
+        ("kmsg", Arc::new(KmsgDev)),
+        ("tty", Arc::new(TtyNode::new(TtyKind::Controlling))),
+        ("console", Arc::new(TtyNode::new(TtyKind::Console))),
+        ("ptmx", Arc::new(TtyNode::new(TtyKind::PtyMaster))),
+    ]
+}
+
+/// Creates the character devices in `/dev`.
+pub(crate) fn init_char_devices() {
+    for (name, node) in char_devices() {
+        if let Err(e) = add_node("/dev", name, node) {
+            warn!("failed to create /dev/{}: {:?}", name, e);
+        }
+    }
+}
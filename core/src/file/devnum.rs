@@ -0,0 +1,67 @@
+//! Device numbers, as reported in `st_rdev`.
+//!
+//! The numbers follow Linux's `Documentation/admin-guide/devices.txt`, so
+//! that programs recognizing devices by number keep working.
+
+/// `/dev/null`.
+pub const NULL: (u32, u32) = (1, 3);
+/// `/dev/zero`.
+pub const ZERO: (u32, u32) = (1, 5);
+/// `/dev/full`.
+pub const FULL: (u32, u32) = (1, 7);
+/// `/dev/random`.
+pub const RANDOM: (u32, u32) = (1, 8);
+/// `/dev/urandom`.
+pub const URANDOM: (u32, u32) = (1, 9);
+/// `/dev/kmsg`.
+pub const KMSG: (u32, u32) = (1, 11);
+/// `/dev/tty`.
+pub const TTY: (u32, u32) = (5, 0);
+/// `/dev/console`.
+pub const CONSOLE: (u32, u32) = (5, 1);
+/// `/dev/ptmx`.
+pub const PTMX: (u32, u32) = (5, 2);
+/// The major number of pseudo-terminal slaves, whose minor is the index.
+pub const PTS_MAJOR: u32 = 136;
+/// The major number of loop devices, whose minor is the index.
+pub const LOOP_MAJOR: u32 = 7;
+/// `/dev/loop-control`.
+pub const LOOP_CONTROL: (u32, u32) = (10, 237);
+
+/// Encodes a device number like glibc's `makedev`.
+pub const fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    ((major & 0xffff_f000) << 32)
+        | ((major & 0xfff) << 8)
+        | ((minor & 0xffff_ff00) << 12)
+        | (minor & 0xff)
+}
+
+/// Extracts the major number of `dev`.
+pub const fn major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff)) as u32
+}
+
+/// Extracts the minor number of `dev`.
+pub const fn minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0xff)) as u32
+}
+
+/// Returns the major and minor number of the device node at `path`.
+pub fn device_number(path: &str) -> Option<(u32, u32)> {
+    let name = path.strip_prefix("/dev/")?;
+    Some(match name {
+        "null" => NULL,
+        "zero" => ZERO,
+        "full" => FULL,
+        "random" => RANDOM,
+        "urandom" => URANDOM,
+        "kmsg" => KMSG,
+        "tty" => TTY,
+        "console" => CONSOLE,
+        "ptmx" | "pts/ptmx" => PTMX,
+        "loop-control" => LOOP_CONTROL,
+        _ if name.starts_with("pts/") => (PTS_MAJOR, name[4..].parse().ok()?),
+        _ => (LOOP_MAJOR, name.strip_prefix("loop")?.parse().ok()?),
+    })
+}
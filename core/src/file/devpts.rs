@@ -0,0 +1,115 @@
+//! The devpts filesystem, listing the slave ends of pseudo-terminals.
+//!
+//! This module only allocates the terminal indices and provides the nodes
+//! `/dev/pts/<n>`; the terminals themselves are implemented by the tty layer
+//! of the API crate.
+use alloc::{
+    collections::btree_set::BTreeSet,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axerrno::{AxError, AxResult};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsOps};
+use spin::Mutex;
+
+use super::{
+    add_node,
+    chardev::{TtyKind, TtyNode},
+    fs::{FileSystem, FsType},
+    mount::{self, MountFlags},
+    proc::dir::{ProcDir, ProcDirOps},
+};
+
+/// The maximum number of pseudo-terminals, like Linux's `kernel/pty/max`.
+pub const MAX_PTYS: u32 = 4096;
+
+static PTYS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Allocates the lowest free pseudo-terminal index.
+///
+/// Returns `StorageFull` (`ENOSPC`) if all indices are in use.
+pub fn alloc_pty_index() -> AxResult<u32> {
+    let mut ptys = PTYS.lock();
+    let index = (0..MAX_PTYS)
+        .find(|index| !ptys.contains(index))
+        .ok_or(AxError::StorageFull)?;
+    ptys.insert(index);
+    Ok(index)
+}
+
+/// Releases a pseudo-terminal index, removing `/dev/pts/<index>`.
+pub fn free_pty_index(index: u32) {
+    PTYS.lock().remove(&index);
+}
+
+/// The root of a devpts instance, with `ptmx` and one node per allocated
+/// pseudo-terminal.
+struct PtsDir;
+
+impl ProcDirOps for PtsDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        if name == "ptmx" {
+            return Some(Arc::new(TtyNode::new(TtyKind::PtyMaster)));
+        }
+        let index = name.parse::<u32>().ok()?;
+        // Reject names such as "01" that parse to an allocated index
+        if index.to_string() != name || !PTYS.lock().contains(&index) {
+            return None;
+        }
+        Some(Arc::new(TtyNode::new(TtyKind::PtySlave(index))))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let mut entries = PTYS
+            .lock()
+            .iter()
+            .map(|index| (index.to_string(), VfsNodeType::CharDevice))
+            .collect::<Vec<_>>();
+        entries.push(("ptmx".to_string(), VfsNodeType::CharDevice));
+        entries
+    }
+}
+
+/// A devpts instance. All instances share the same terminals.
+pub struct DevPtsFileSystem {
+    root: VfsNodeRef,
+}
+
+impl DevPtsFileSystem {
+    /// Creates a new devpts instance.
+    pub fn new() -> Self {
+        Self {
+            root: ProcDir::new(PtsDir),
+        }
+    }
+}
+
+impl Default for DevPtsFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for DevPtsFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl FileSystem for DevPtsFileSystem {}
+
+/// Mounts a devpts at `/dev/pts`.
+pub(crate) fn init_devpts() {
+    // devtmpfs cannot create directories, so graft an empty one
+    if !axfs::api::absolute_path_exists("/dev/pts")
+        && let Err(e) = add_node("/dev", "pts", RamFileSystem::new().root_dir())
+    {
+        warn!("failed to create /dev/pts: {:?}", e);
+    }
+    let flags = MountFlags::NOSUID | MountFlags::NOEXEC;
+    if let Err(e) = mount::mount("devpts", "/dev/pts", FsType::Devpts, flags, "") {
+        warn!("failed to mount devpts: {:?}", e);
+    }
+}
@@ -0,0 +1,477 @@
+//! ext4 filesystem on top of a [`BlockDevice`], backed by lwext4.
+//!
+//! lwext4 keeps global tables of devices and mount points, and hands a path
+//! to the first mount point whose name is a prefix of it. When booting from
+//! ext4, `axfs` mounts the root filesystem there as `/`, which is a prefix of
+//! every absolute path. Each instance here is therefore mounted under a name
+//! without the leading slash, such as `ext4-1/`, and its files are addressed
+//! by paths below that name, which no other mount point matches.
+//!
+//! The tables only have a few slots, shared with the root filesystem. Once
+//! they are full, mounting fails and the error is reported to the caller of
+//! `sys_mount`.
+
+use alloc::{
+    boxed::Box,
+    ffi::CString,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{
+    ffi::{c_int, c_void},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use axerrno::AxError;
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use linux_raw_sys::general::EOPNOTSUPP;
+use lwext4_rust::{
+    Ext4File, InodeTypes,
+    bindings::{
+        O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, SEEK_SET, ext4_blockdev, ext4_blockdev_iface,
+        ext4_cache_flush, ext4_cache_write_back, ext4_device_register, ext4_device_unregister,
+        ext4_journal_start, ext4_journal_stop, ext4_mount, ext4_mount_point_stats,
+        ext4_mount_stats, ext4_recover, ext4_umount,
+    },
+};
+
+use super::FsStats;
+use crate::file::blkdev::{BlockDevice, Disk};
+
+const BLOCK_SIZE: usize = 512;
+
+/// The device of an instance as registered with lwext4, which keeps
+/// pointers into it, so it stays boxed.
+struct Ext4Device {
+    bd: ext4_blockdev,
+    iface: ext4_blockdev_iface,
+    /// The buffer of one physical block that lwext4 requires.
+    buf: Vec<u8>,
+    disk: Disk,
+    block_size: u64,
+}
+
+impl Ext4Device {
+    fn new(dev: Arc<dyn BlockDevice>) -> Box<Self> {
+        let block_size = dev.block_size();
+        let num_blocks = dev.num_blocks();
+        let mut device = Box::new(Self {
+            // SAFETY: both are plain C structures, for which all-zero means
+            // null pointers and missing callbacks
+            bd: unsafe { core::mem::zeroed() },
+            iface: unsafe { core::mem::zeroed() },
+            buf: vec![0; block_size],
+            disk: Disk::new(dev),
+            block_size: block_size as u64,
+        });
+        let user = &mut *device as *mut Self as *mut c_void;
+        device.iface.open = Some(dev_open);
+        device.iface.bread = Some(dev_bread);
+        device.iface.bwrite = Some(dev_bwrite);
+        device.iface.close = Some(dev_close);
+        device.iface.ph_bsize = block_size as u32;
+        device.iface.ph_bcnt = num_blocks;
+        device.iface.ph_bbuf = device.buf.as_mut_ptr();
+        device.iface.p_user = user;
+        device.bd.bdif = &mut device.iface;
+        device.bd.part_offset = 0;
+        device.bd.part_size = num_blocks * block_size as u64;
+        device
+    }
+
+    /// Returns the device `bd` belongs to.
+    ///
+    /// # Safety
+    ///
+    /// `bd` must be the `bd` of a live [`Ext4Device`], and lwext4 must not be
+    /// using the device elsewhere.
+    unsafe fn from_raw<'a>(bd: *mut ext4_blockdev) -> &'a mut Self {
+        unsafe { &mut *((*(*bd).bdif).p_user as *mut Self) }
+    }
+}
+
+unsafe extern "C" fn dev_open(_bd: *mut ext4_blockdev) -> c_int {
+    0
+}
+
+unsafe extern "C" fn dev_close(bd: *mut ext4_blockdev) -> c_int {
+    let device = unsafe { Ext4Device::from_raw(bd) };
+    match device.disk.sync() {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+unsafe extern "C" fn dev_bread(
+    bd: *mut ext4_blockdev,
+    buf: *mut c_void,
+    blk_id: u64,
+    blk_cnt: u32,
+) -> c_int {
+    let device = unsafe { Ext4Device::from_raw(bd) };
+    let len = blk_cnt as usize * device.block_size as usize;
+    // SAFETY: lwext4 passes a buffer of `blk_cnt` blocks
+    let mut buf = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+    device.disk.set_position(blk_id * device.block_size);
+    while !buf.is_empty() {
+        match device.disk.read_one(buf) {
+            Ok(0) | Err(_) => return -1,
+            Ok(n) => buf = &mut buf[n..],
+        }
+    }
+    0
+}
+
+unsafe extern "C" fn dev_bwrite(
+    bd: *mut ext4_blockdev,
+    buf: *const c_void,
+    blk_id: u64,
+    blk_cnt: u32,
+) -> c_int {
+    let device = unsafe { Ext4Device::from_raw(bd) };
+    let len = blk_cnt as usize * device.block_size as usize;
+    // SAFETY: as in `dev_bread`
+    let mut buf = unsafe { core::slice::from_raw_parts(buf as *const u8, len) };
+    device.disk.set_position(blk_id * device.block_size);
+    while !buf.is_empty() {
+        match device.disk.write_one(buf) {
+            Ok(0) | Err(_) => return -1,
+            Ok(n) => buf = &buf[n..],
+        }
+    }
+    0
+}
+
+/// An instance registered and mounted in the tables of lwext4, which is
+/// unmounted once the filesystem and all its nodes are gone.
+struct Ext4Mount {
+    device: Box<Ext4Device>,
+    name: CString,
+    /// The name of the mount point, which prefixes the paths of the files.
+    mount_point: CString,
+    /// Serializes the calls into lwext4, which has no locking of its own.
+    lock: Mutex<()>,
+}
+
+unsafe impl Send for Ext4Mount {}
+unsafe impl Sync for Ext4Mount {}
+
+impl Ext4Mount {
+    fn new(dev: Arc<dyn BlockDevice>) -> VfsResult<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+        let read_only = dev.is_read_only();
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let name = CString::new(format!("ext4-{}", id)).unwrap();
+        let mount_point = CString::new(format!("ext4-{}/", id)).unwrap();
+        let mut device = Ext4Device::new(dev);
+
+        let ret = unsafe { ext4_device_register(&mut device.bd, name.as_ptr()) };
+        if ret != 0 {
+            warn!("failed to register ext4 device: {}", ret);
+            return Err(as_vfs_err(ret));
+        }
+        let ret = unsafe { ext4_mount(name.as_ptr(), mount_point.as_ptr(), read_only) };
+        if ret != 0 {
+            warn!("failed to mount ext4 filesystem: {}", ret);
+            unsafe { ext4_device_unregister(name.as_ptr()) };
+            return Err(as_vfs_err(ret));
+        }
+        let mount = Self {
+            device,
+            name,
+            mount_point,
+            lock: Mutex::new(()),
+        };
+        // Replay the journal of an unclean unmount, if there is one
+        let ret = unsafe { ext4_recover(mount.mount_point.as_ptr()) };
+        if ret != 0 && ret != EOPNOTSUPP as c_int {
+            warn!("failed to recover the ext4 journal: {}", ret);
+            return Err(as_vfs_err(ret));
+        }
+        unsafe {
+            ext4_journal_start(mount.mount_point.as_ptr());
+            ext4_cache_write_back(mount.mount_point.as_ptr(), true);
+        }
+        Ok(mount)
+    }
+
+    /// The path lwext4 knows the file at `path` of the filesystem by.
+    fn path(&self, path: &str) -> String {
+        let mount_point = self.mount_point.to_str().unwrap_or_default();
+        format!("{}{}", mount_point, path.trim_matches('/'))
+    }
+}
+
+impl Drop for Ext4Mount {
+    fn drop(&mut self) {
+        let _guard = self.lock.lock();
+        let mount_point = self.mount_point.as_ptr();
+        unsafe {
+            ext4_cache_write_back(mount_point, false);
+            ext4_journal_stop(mount_point);
+            let ret = ext4_umount(mount_point);
+            if ret != 0 {
+                warn!("failed to unmount ext4 filesystem: {}", ret);
+            }
+            ext4_device_unregister(self.name.as_ptr());
+        }
+        let _ = self.device.disk.sync();
+    }
+}
+
+/// An ext4 filesystem instance.
+pub struct Ext4FileSystem {
+    mount: Arc<Ext4Mount>,
+    root: VfsNodeRef,
+}
+
+impl Ext4FileSystem {
+    /// Opens the ext4 filesystem stored on `dev`.
+    pub fn new(dev: Arc<dyn BlockDevice>) -> VfsResult<Self> {
+        let mount = Arc::new(Ext4Mount::new(dev)?);
+        let root = Arc::new(FileWrapper::new(&mount, "", InodeTypes::EXT4_DE_DIR));
+        Ok(Self { mount, root })
+    }
+}
+
+impl super::FileSystem for Ext4FileSystem {
+    fn stats(&self) -> FsStats {
+        let _guard = self.mount.lock.lock();
+        // SAFETY: all-zero is valid for ext4_mount_stats
+        let mut stats: ext4_mount_stats = unsafe { core::mem::zeroed() };
+        let ret = unsafe { ext4_mount_point_stats(self.mount.mount_point.as_ptr(), &mut stats) };
+        if ret != 0 {
+            warn!("failed to read ext4 statistics: {}", ret);
+            return FsStats::default();
+        }
+        FsStats {
+            block_size: stats.block_size as u64,
+            blocks: stats.blocks_count,
+            blocks_free: stats.free_blocks_count,
+            blocks_available: stats.free_blocks_count,
+            files: stats.inodes_count as u64,
+            files_free: stats.free_inodes_count as u64,
+            ..Default::default()
+        }
+    }
+}
+
+impl VfsOps for Ext4FileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+
+    /// Flushes the cache. The instance is only unmounted from lwext4 once
+    /// its last open file is closed as well.
+    fn umount(&self) -> VfsResult {
+        let _guard = self.mount.lock.lock();
+        match unsafe { ext4_cache_flush(self.mount.mount_point.as_ptr()) } {
+            0 => Ok(()),
+            ret => Err(as_vfs_err(ret)),
+        }
+    }
+}
+
+/// A file or directory, named by its path relative to the root of the
+/// filesystem.
+struct FileWrapper {
+    file: Mutex<Ext4File>,
+    path: String,
+    mount: Arc<Ext4Mount>,
+}
+
+unsafe impl Send for FileWrapper {}
+unsafe impl Sync for FileWrapper {}
+
+impl FileWrapper {
+    fn new(mount: &Arc<Ext4Mount>, path: &str, types: InodeTypes) -> Self {
+        Self {
+            file: Mutex::new(Ext4File::new(&mount.path(path), types)),
+            path: path.to_string(),
+            mount: mount.clone(),
+        }
+    }
+
+    /// Joins `path` to the path of this node.
+    fn join(&self, path: &str) -> String {
+        let path = path.trim_matches('/');
+        let path = path.strip_prefix("./").unwrap_or(path);
+        match (self.path.as_str(), path) {
+            (base, "" | ".") => base.to_string(),
+            ("", path) => path.to_string(),
+            (base, path) => format!("{}/{}", base, path),
+        }
+    }
+
+    /// Runs `f` on the file handle, with lwext4 locked.
+    fn with_file<R>(&self, f: impl FnOnce(&mut Ext4File) -> R) -> R {
+        let _guard = self.mount.lock.lock();
+        f(&mut self.file.lock())
+    }
+}
+
+impl VfsNodeOps for FileWrapper {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let path = self.mount.path(&self.path);
+        self.with_file(|file| {
+            let perm = file.file_mode_get().unwrap_or(0o755);
+            let perm = VfsNodePerm::from_bits_truncate((perm as u16) & 0o777);
+            let ty = match file.file_type_get() {
+                InodeTypes::EXT4_INODE_MODE_FIFO => VfsNodeType::Fifo,
+                InodeTypes::EXT4_INODE_MODE_CHARDEV => VfsNodeType::CharDevice,
+                InodeTypes::EXT4_INODE_MODE_DIRECTORY => VfsNodeType::Dir,
+                InodeTypes::EXT4_INODE_MODE_BLOCKDEV => VfsNodeType::BlockDevice,
+                InodeTypes::EXT4_INODE_MODE_SOFTLINK => VfsNodeType::SymLink,
+                InodeTypes::EXT4_INODE_MODE_SOCKET => VfsNodeType::Socket,
+                _ => VfsNodeType::File,
+            };
+            let size = if ty == VfsNodeType::File {
+                file.file_open(&path, O_RDONLY).map_err(as_vfs_err)?;
+                let size = file.file_size();
+                let _ = file.file_close();
+                size
+            } else {
+                0
+            };
+            Ok(VfsNodeAttr::new(
+                perm,
+                ty,
+                size,
+                size.div_ceil(BLOCK_SIZE as u64),
+            ))
+        })
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = self.join(path);
+        if path == self.path {
+            return Ok(self.clone());
+        }
+        let full_path = self.mount.path(&path);
+        let types = self.with_file(|file| {
+            if file.check_inode_exist(&full_path, InodeTypes::EXT4_DE_DIR) {
+                Ok(InodeTypes::EXT4_DE_DIR)
+            } else if file.check_inode_exist(&full_path, InodeTypes::EXT4_DE_REG_FILE) {
+                Ok(InodeTypes::EXT4_DE_REG_FILE)
+            } else {
+                Err(VfsError::NotFound)
+            }
+        })?;
+        Ok(Arc::new(Self::new(&self.mount, &path, types)))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let path = self.mount.path(&self.join(path));
+        let types = match ty {
+            VfsNodeType::Dir => InodeTypes::EXT4_DE_DIR,
+            VfsNodeType::File => InodeTypes::EXT4_DE_REG_FILE,
+            _ => return Err(VfsError::Unsupported),
+        };
+        self.with_file(|file| {
+            if file.check_inode_exist(&path, types.clone()) {
+                return Ok(());
+            }
+            if types == InodeTypes::EXT4_DE_DIR {
+                file.dir_mk(&path).map_err(as_vfs_err)?;
+            } else {
+                file.file_open(&path, O_WRONLY | O_CREAT | O_TRUNC)
+                    .map_err(as_vfs_err)?;
+                file.file_close().map_err(as_vfs_err)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let path = self.mount.path(&self.join(path));
+        self.with_file(|file| {
+            if file.check_inode_exist(&path, InodeTypes::EXT4_DE_DIR) {
+                file.dir_rm(&path).map_err(as_vfs_err)?;
+            } else {
+                file.file_remove(&path).map_err(as_vfs_err)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let (names, types) =
+            self.with_file(|file| file.lwext4_dir_entries().map_err(as_vfs_err))?;
+        let mut iter = names.iter().zip(types.iter()).skip(start_idx);
+        for (i, out_entry) in dirents.iter_mut().enumerate() {
+            let Some((name, ty)) = iter.next() else {
+                return Ok(i);
+            };
+            let ty = match *ty {
+                InodeTypes::EXT4_DE_DIR => VfsNodeType::Dir,
+                InodeTypes::EXT4_DE_SYMLINK => VfsNodeType::SymLink,
+                _ => VfsNodeType::File,
+            };
+            let name = core::str::from_utf8(name)
+                .map_err(|_| VfsError::InvalidData)?
+                .trim_end_matches('\0');
+            *out_entry = VfsDirEntry::new(name, ty);
+        }
+        Ok(dirents.len())
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let path = self.mount.path(&self.path);
+        self.with_file(|file| {
+            file.file_open(&path, O_RDONLY).map_err(as_vfs_err)?;
+            let result = file
+                .file_seek(offset as i64, SEEK_SET)
+                .and_then(|_| file.file_read(buf));
+            let _ = file.file_close();
+            result.map_err(as_vfs_err)
+        })
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let path = self.mount.path(&self.path);
+        self.with_file(|file| {
+            file.file_open(&path, O_RDWR).map_err(as_vfs_err)?;
+            let result = file
+                .file_seek(offset as i64, SEEK_SET)
+                .and_then(|_| file.file_write(buf));
+            let _ = file.file_close();
+            result.map_err(as_vfs_err)
+        })
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let path = self.mount.path(&self.path);
+        self.with_file(|file| {
+            file.file_open(&path, O_RDWR).map_err(as_vfs_err)?;
+            let result = file.file_truncate(size);
+            let _ = file.file_close();
+            result.map(|_| ()).map_err(as_vfs_err)
+        })
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let src_path = self.mount.path(&self.join(src_path));
+        let dst_path = self.mount.path(&self.join(dst_path));
+        self.with_file(|file| {
+            file.file_rename(&src_path, &dst_path)
+                .map(|_| ())
+                .map_err(as_vfs_err)
+        })
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+fn as_vfs_err(err: i32) -> VfsError {
+    AxError::try_from(err).unwrap_or(VfsError::Io)
+}
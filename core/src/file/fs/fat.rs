@@ -0,0 +1,317 @@
+//! FAT filesystem on top of a [`BlockDevice`].
+
+use alloc::sync::Arc;
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use axsync::Mutex;
+use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+
+use super::FsStats;
+use crate::file::blkdev::{BlockDevice, Disk};
+
+const BLOCK_SIZE: usize = 512;
+
+type FatFs = fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>;
+type FatFile = File<'static, Disk, NullTimeProvider, LossyOemCpConverter>;
+type FatDir = Dir<'static, Disk, NullTimeProvider, LossyOemCpConverter>;
+
+/// A FAT filesystem instance.
+pub struct FatFileSystem {
+    inner: Arc<FatFs>,
+    root_dir: VfsNodeRef,
+}
+
+// Each handle is declared before the filesystem it borrows, so it is dropped
+// first.
+struct FileWrapper {
+    file: Mutex<FatFile>,
+    _fs: Arc<FatFs>,
+}
+
+struct DirWrapper {
+    dir: FatDir,
+    fs: Arc<FatFs>,
+}
+
+unsafe impl Sync for FatFileSystem {}
+unsafe impl Send for FatFileSystem {}
+unsafe impl Send for FileWrapper {}
+unsafe impl Sync for FileWrapper {}
+unsafe impl Send for DirWrapper {}
+unsafe impl Sync for DirWrapper {}
+
+impl FatFileSystem {
+    /// Opens the FAT filesystem stored on `dev`.
+    pub fn new(dev: Arc<dyn BlockDevice>) -> VfsResult<Self> {
+        let inner = FatFs::new(Disk::new(dev), fatfs::FsOptions::new()).map_err(as_vfs_err)?;
+        let inner = Arc::new(inner);
+        let root_dir = new_dir(&inner, borrow_fs(&inner).root_dir());
+        Ok(Self { inner, root_dir })
+    }
+}
+
+impl super::FileSystem for FatFileSystem {
+    fn stats(&self) -> FsStats {
+        match self.inner.stats() {
+            Ok(stats) => FsStats {
+                block_size: stats.cluster_size() as u64,
+                blocks: stats.total_clusters() as u64,
+                blocks_free: stats.free_clusters() as u64,
+                blocks_available: stats.free_clusters() as u64,
+                ..Default::default()
+            },
+            Err(e) => {
+                warn!("failed to read FAT statistics: {:?}", e);
+                FsStats::default()
+            }
+        }
+    }
+}
+
+/// Borrows the filesystem for the handles of the nodes handed out to the VFS.
+///
+/// Directory and file handles borrow the filesystem, so every node keeps a
+/// reference to it next to its handle.
+fn borrow_fs(fs: &Arc<FatFs>) -> &'static FatFs {
+    // SAFETY: the handles are stored together with a clone of `fs` and
+    // dropped before it, and the filesystem never moves out of the `Arc`
+    unsafe { &*Arc::as_ptr(fs) }
+}
+
+fn new_file(fs: &Arc<FatFs>, file: FatFile) -> VfsNodeRef {
+    Arc::new(FileWrapper {
+        file: Mutex::new(file),
+        _fs: fs.clone(),
+    })
+}
+
+fn new_dir(fs: &Arc<FatFs>, dir: FatDir) -> VfsNodeRef {
+    Arc::new(DirWrapper {
+        dir,
+        fs: fs.clone(),
+    })
+}
+
+impl VfsNodeOps for FileWrapper {
+    axfs_vfs::impl_vfs_non_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self
+            .file
+            .lock()
+            .seek(SeekFrom::End(0))
+            .map_err(as_vfs_err)?;
+        let blocks = size.div_ceil(BLOCK_SIZE as u64);
+        // FAT has no permissions, report everything as rwxr-xr-x
+        let perm = VfsNodePerm::from_bits_truncate(0o755);
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?;
+        file.read(buf).map_err(as_vfs_err)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?;
+        file.write(buf).map_err(as_vfs_err)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.file.lock().flush().map_err(as_vfs_err)
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?;
+        file.truncate().map_err(as_vfs_err)
+    }
+}
+
+impl VfsNodeOps for DirWrapper {
+    axfs_vfs::impl_vfs_dir_default! {}
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o755),
+            VfsNodeType::Dir,
+            BLOCK_SIZE as u64,
+            1,
+        ))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.dir
+            .open_dir("..")
+            .ok()
+            .map(|dir| new_dir(&self.fs, dir))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = path.trim_matches('/');
+        if path.is_empty() || path == "." {
+            return Ok(self.clone());
+        }
+        if let Some(rest) = path.strip_prefix("./") {
+            return self.lookup(rest);
+        }
+
+        if let Ok(file) = self.dir.open_file(path) {
+            Ok(new_file(&self.fs, file))
+        } else if let Ok(dir) = self.dir.open_dir(path) {
+            Ok(new_dir(&self.fs, dir))
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let path = path.trim_matches('/');
+        if path.is_empty() || path == "." {
+            return Ok(());
+        }
+        if let Some(rest) = path.strip_prefix("./") {
+            return self.create(rest, ty);
+        }
+
+        match ty {
+            VfsNodeType::File => {
+                self.dir.create_file(path).map_err(as_vfs_err)?;
+                Ok(())
+            }
+            VfsNodeType::Dir => {
+                self.dir.create_dir(path).map_err(as_vfs_err)?;
+                Ok(())
+            }
+            _ => Err(VfsError::Unsupported),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return Err(VfsError::InvalidInput);
+        }
+        if let Some(rest) = path.strip_prefix("./") {
+            return self.remove(rest);
+        }
+        self.dir.remove(path).map_err(as_vfs_err)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut iter = self.dir.iter().skip(start_idx);
+        for (i, out_entry) in dirents.iter_mut().enumerate() {
+            match iter.next() {
+                Some(Ok(entry)) => {
+                    let ty = if entry.is_dir() {
+                        VfsNodeType::Dir
+                    } else {
+                        VfsNodeType::File
+                    };
+                    *out_entry = VfsDirEntry::new(&entry.file_name(), ty);
+                }
+                _ => return Ok(i),
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let dst_path = dst_path.trim_matches('/');
+        self.dir
+            .rename(src_path.trim_matches('/'), &self.dir, dst_path)
+            .map_err(as_vfs_err)
+    }
+}
+
+impl VfsOps for FatFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root_dir.clone()
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.inner.flush_fs_info().map_err(as_vfs_err)
+    }
+}
+
+impl fatfs::IoBase for Disk {
+    type Error = ();
+}
+
+impl Read for Disk {
+    fn read(&mut self, mut buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut read_len = 0;
+        while !buf.is_empty() {
+            match self.read_one(buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                    read_len += n;
+                }
+                Err(_) => return Err(()),
+            }
+        }
+        Ok(read_len)
+    }
+}
+
+impl Write for Disk {
+    fn write(&mut self, mut buf: &[u8]) -> Result<usize, Self::Error> {
+        let mut write_len = 0;
+        while !buf.is_empty() {
+            match self.write_one(buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buf = &buf[n..];
+                    write_len += n;
+                }
+                Err(_) => return Err(()),
+            }
+        }
+        Ok(write_len)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.sync().map_err(|_| ())
+    }
+}
+
+impl Seek for Disk {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        let size = self.size();
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(off) => self.position().checked_add_signed(off),
+            SeekFrom::End(off) => size.checked_add_signed(off),
+        }
+        .ok_or(())?;
+        if new_pos > size {
+            warn!("Seek beyond the end of the block device");
+        }
+        self.set_position(new_pos);
+        Ok(new_pos)
+    }
+}
+
+const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
+    use fatfs::Error::*;
+    match err {
+        AlreadyExists => VfsError::AlreadyExists,
+        CorruptedFileSystem => VfsError::InvalidData,
+        DirectoryIsNotEmpty => VfsError::DirectoryNotEmpty,
+        InvalidInput | InvalidFileNameLength | UnsupportedFileNameCharacter => {
+            VfsError::InvalidInput
+        }
+        NotEnoughSpace => VfsError::StorageFull,
+        NotFound => VfsError::NotFound,
+        UnexpectedEof => VfsError::UnexpectedEof,
+        WriteZero => VfsError::WriteZero,
+        Io(_) => VfsError::Io,
+        _ => VfsError::Io,
+    }
+}
@@ -0,0 +1,191 @@
+//! Filesystem types that can be mounted at runtime.
+
+#[cfg(feature = "lwext4_rs")]
+mod ext4;
+mod fat;
+mod tmpfs;
+
+use alloc::sync::Arc;
+use axerrno::{LinuxError, LinuxResult};
+use axfs_devfs::DeviceFileSystem;
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::VfsOps;
+
+#[cfg(feature = "lwext4_rs")]
+pub use self::ext4::Ext4FileSystem;
+pub use self::{
+    fat::FatFileSystem,
+    tmpfs::{TmpFileSystem, TmpfsOptions},
+};
+
+use super::{
+    blkdev::get_block_device, chardev::char_devices, devpts::DevPtsFileSystem,
+    proc::ProcFileSystem, sysfs::SysFileSystem,
+};
+
+/// A filesystem type known to `sys_mount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsType {
+    /// An unbounded in-memory filesystem.
+    Ramfs,
+    /// An in-memory filesystem for scratch data.
+    Tmpfs,
+    /// The process information pseudo-filesystem.
+    Proc,
+    /// The device filesystem.
+    Devtmpfs,
+    /// The pseudo-terminal filesystem.
+    Devpts,
+    /// The kernel object pseudo-filesystem.
+    Sysfs,
+    /// FAT12/16/32 on a block device.
+    Vfat,
+    /// ext4 on a block device.
+    #[cfg(feature = "lwext4_rs")]
+    Ext4,
+}
+
+impl FsType {
+    /// Parses the `filesystemtype` argument of `mount(2)`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "ramfs" => Self::Ramfs,
+            "tmpfs" => Self::Tmpfs,
+            "proc" => Self::Proc,
+            "devtmpfs" | "devfs" => Self::Devtmpfs,
+            "devpts" => Self::Devpts,
+            "sysfs" => Self::Sysfs,
+            "vfat" | "fat" | "msdos" => Self::Vfat,
+            #[cfg(feature = "lwext4_rs")]
+            "ext4" => Self::Ext4,
+            _ => return None,
+        })
+    }
+
+    /// The name shown in `/proc/mounts`.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Ramfs => "ramfs",
+            Self::Tmpfs => "tmpfs",
+            Self::Proc => "proc",
+            Self::Devtmpfs => "devtmpfs",
+            Self::Devpts => "devpts",
+            Self::Sysfs => "sysfs",
+            Self::Vfat => "vfat",
+            #[cfg(feature = "lwext4_rs")]
+            Self::Ext4 => "ext4",
+        }
+    }
+
+    /// The magic number reported by `statfs(2)`, from `<linux/magic.h>`.
+    pub const fn magic(&self) -> u64 {
+        match self {
+            Self::Ramfs => 0x8584_58f6,
+            Self::Tmpfs | Self::Devtmpfs => 0x0102_1994,
+            Self::Proc => 0x9fa0,
+            Self::Devpts => 0x1cd1,
+            Self::Sysfs => 0x6265_6572,
+            Self::Vfat => 0x4d44,
+            #[cfg(feature = "lwext4_rs")]
+            Self::Ext4 => 0xef53,
+        }
+    }
+
+    /// Whether the filesystem is stored on a block device given as the mount
+    /// source.
+    pub const fn requires_device(&self) -> bool {
+        match self {
+            Self::Vfat => true,
+            #[cfg(feature = "lwext4_rs")]
+            Self::Ext4 => true,
+            _ => false,
+        }
+    }
+}
+
+/// Usage statistics of a filesystem, as reported by `statfs(2)`.
+#[derive(Debug, Clone, Copy)]
+pub struct FsStats {
+    /// The size of a block in bytes.
+    pub block_size: u64,
+    /// The total number of blocks.
+    pub blocks: u64,
+    /// The number of free blocks.
+    pub blocks_free: u64,
+    /// The number of blocks available to unprivileged users.
+    pub blocks_available: u64,
+    /// The total number of inodes.
+    pub files: u64,
+    /// The number of free inodes.
+    pub files_free: u64,
+    /// The maximum length of a file name.
+    pub name_max: u64,
+}
+
+impl Default for FsStats {
+    fn default() -> Self {
+        Self {
+            block_size: 4096,
+            blocks: 0,
+            blocks_free: 0,
+            blocks_available: 0,
+            files: 0,
+            files_free: 0,
+            name_max: 255,
+        }
+    }
+}
+
+/// A filesystem that can be mounted with `sys_mount`.
+pub trait FileSystem: VfsOps {
+    /// Returns the usage statistics. Filesystems without a notion of
+    /// capacity report zeros, like Linux's ramfs.
+    fn stats(&self) -> FsStats {
+        FsStats::default()
+    }
+}
+
+impl FileSystem for RamFileSystem {}
+
+impl FileSystem for DeviceFileSystem {}
+
+/// Creates a new filesystem instance of type `fs_type`.
+///
+/// For disk-based filesystems `source` is the path of a registered block
+/// device; for the others it is only recorded in the mount table. `data` is
+/// the comma separated option string passed to `mount(2)`.
+pub fn new_filesystem(
+    fs_type: FsType,
+    source: &str,
+    data: &str,
+) -> LinuxResult<Arc<dyn FileSystem>> {
+    let device = if fs_type.requires_device() {
+        Some(get_block_device(source).map_err(|_| LinuxError::ENOTBLK)?)
+    } else {
+        None
+    };
+
+    Ok(match fs_type {
+        FsType::Ramfs => Arc::new(RamFileSystem::new()),
+        FsType::Proc => Arc::new(ProcFileSystem::new()),
+        FsType::Sysfs => Arc::new(SysFileSystem::new()),
+        FsType::Devpts => Arc::new(DevPtsFileSystem::new()),
+        FsType::Tmpfs => Arc::new(TmpFileSystem::new(TmpfsOptions::parse(data)?)),
+        FsType::Devtmpfs => {
+            let devfs = DeviceFileSystem::new();
+            for (name, node) in char_devices() {
+                devfs.add(name, node);
+            }
+            Arc::new(devfs)
+        }
+        FsType::Vfat => Arc::new(FatFileSystem::new(device.unwrap()).map_err(|e| {
+            warn!("failed to open FAT filesystem on {}: {:?}", source, e);
+            LinuxError::EINVAL
+        })?),
+        #[cfg(feature = "lwext4_rs")]
+        FsType::Ext4 => Arc::new(Ext4FileSystem::new(device.unwrap()).map_err(|e| {
+            warn!("failed to open ext4 filesystem on {}: {:?}", source, e);
+            LinuxError::EINVAL
+        })?),
+    })
+}
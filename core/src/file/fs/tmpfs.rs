@@ -0,0 +1,536 @@
+//! An in-memory filesystem with size and inode limits.
+//!
+//! Space is accounted in pages. A file releases its pages and inode when its
+//! last reference is dropped, so an unlinked file that is still open keeps
+//! occupying space, like on Linux.
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    any::Any,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use axerrno::{LinuxError, LinuxResult};
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+use spin::RwLock;
+
+use super::{FileSystem, FsStats};
+
+/// The unit in which space is accounted.
+const PAGE_SIZE: u64 = 4096;
+
+/// The longest file name accepted.
+const NAME_MAX: u64 = 255;
+
+/// Mount options of a tmpfs instance.
+#[derive(Debug, Clone, Copy)]
+pub struct TmpfsOptions {
+    /// The maximum number of pages, or `u64::MAX` for no limit.
+    pub max_pages: u64,
+    /// The maximum number of inodes, or `u64::MAX` for no limit.
+    pub max_inodes: u64,
+    /// The mode bits of the root directory, including the setuid, setgid and
+    /// sticky bits.
+    pub mode: u32,
+}
+
+impl Default for TmpfsOptions {
+    fn default() -> Self {
+        // Like Linux: half of the RAM, and one inode per such page
+        let max_pages = axconfig::plat::PHYS_MEMORY_SIZE as u64 / PAGE_SIZE / 2;
+        Self {
+            max_pages,
+            max_inodes: max_pages,
+            mode: 0o1777,
+        }
+    }
+}
+
+impl TmpfsOptions {
+    /// Parses the comma separated `data` argument of `mount(2)`, e.g.
+    /// `size=64m,nr_inodes=1k,mode=755`.
+    pub fn parse(data: &str) -> LinuxResult<Self> {
+        let mut options = Self::default();
+        for option in data.split(',').filter(|s| !s.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "size" => {
+                    let bytes = match value.strip_suffix('%') {
+                        Some(percent) => {
+                            let percent = percent.parse::<u64>().map_err(|_| LinuxError::EINVAL)?;
+                            axconfig::plat::PHYS_MEMORY_SIZE as u64 * percent / 100
+                        }
+                        None => parse_size(value)?,
+                    };
+                    options.max_pages = limit(bytes.div_ceil(PAGE_SIZE));
+                }
+                "nr_blocks" => options.max_pages = limit(parse_size(value)?),
+                "nr_inodes" => options.max_inodes = limit(parse_size(value)?),
+                "mode" => {
+                    options.mode =
+                        u32::from_str_radix(value, 8).map_err(|_| LinuxError::EINVAL)? & 0o7777;
+                }
+                // Ownership is not enforced yet
+                "uid" | "gid" => {}
+                _ => {
+                    warn!("tmpfs: unknown option {:?}", option);
+                    return Err(LinuxError::EINVAL);
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Parses a number with an optional `k`, `m` or `g` suffix.
+fn parse_size(value: &str) -> LinuxResult<u64> {
+    let (digits, shift) = match value.as_bytes().last() {
+        Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+        Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+        Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+    let n = digits.parse::<u64>().map_err(|_| LinuxError::EINVAL)?;
+    n.checked_shl(shift).ok_or(LinuxError::EINVAL)
+}
+
+/// Zero means unlimited in tmpfs options.
+fn limit(n: u64) -> u64 {
+    if n == 0 { u64::MAX } else { n }
+}
+
+/// The space and inode usage shared by all nodes of an instance.
+struct Usage {
+    max_pages: u64,
+    max_inodes: u64,
+    pages: AtomicU64,
+    inodes: AtomicU64,
+}
+
+impl Usage {
+    fn reserve(counter: &AtomicU64, max: u64, n: u64) -> VfsResult {
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(n).filter(|&new| new <= max)
+            })
+            .map(|_| ())
+            .map_err(|_| VfsError::StorageFull)
+    }
+
+    fn reserve_pages(&self, n: u64) -> VfsResult {
+        Self::reserve(&self.pages, self.max_pages, n)
+    }
+
+    fn release_pages(&self, n: u64) {
+        self.pages.fetch_sub(n, Ordering::AcqRel);
+    }
+
+    fn reserve_inode(&self) -> VfsResult {
+        Self::reserve(&self.inodes, self.max_inodes, 1)
+    }
+
+    fn release_inode(&self) {
+        self.inodes.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A tmpfs instance.
+pub struct TmpFileSystem {
+    usage: Arc<Usage>,
+    root: Arc<DirNode>,
+}
+
+impl TmpFileSystem {
+    /// Creates an empty tmpfs with the given limits.
+    pub fn new(options: TmpfsOptions) -> Self {
+        let usage = Arc::new(Usage {
+            max_pages: options.max_pages,
+            max_inodes: options.max_inodes,
+            pages: AtomicU64::new(0),
+            // The root directory
+            inodes: AtomicU64::new(1),
+        });
+        // The setuid, setgid and sticky bits are kept as well, e.g. for the
+        // default 1777
+        let root = DirNode::new(
+            usage.clone(),
+            Weak::<DirNode>::new(),
+            VfsNodePerm::from_bits_retain(options.mode as u16),
+        );
+        Self { usage, root }
+    }
+}
+
+impl VfsOps for TmpFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            *self.root.parent.write() = Arc::downgrade(&parent);
+        }
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl FileSystem for TmpFileSystem {
+    fn stats(&self) -> FsStats {
+        let pages = self.usage.pages.load(Ordering::Acquire);
+        let inodes = self.usage.inodes.load(Ordering::Acquire);
+        let free_pages = self.usage.max_pages.saturating_sub(pages);
+        FsStats {
+            block_size: PAGE_SIZE,
+            blocks: self.usage.max_pages,
+            blocks_free: free_pages,
+            blocks_available: free_pages,
+            files: self.usage.max_inodes,
+            files_free: self.usage.max_inodes.saturating_sub(inodes),
+            name_max: NAME_MAX,
+        }
+    }
+}
+
+/// A regular file or symbolic link.
+struct FileNode {
+    usage: Arc<Usage>,
+    ty: VfsNodeType,
+    content: RwLock<Vec<u8>>,
+}
+
+impl FileNode {
+    fn new(usage: Arc<Usage>, ty: VfsNodeType) -> Self {
+        Self {
+            usage,
+            ty,
+            content: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Resizes `content` to `len` bytes, charging or refunding pages.
+    ///
+    /// Fails with `ENOSPC` if the memory for it can't be had, even when the
+    /// instance has no size limit.
+    fn resize(&self, content: &mut Vec<u8>, len: u64) -> VfsResult {
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= axconfig::plat::PHYS_MEMORY_SIZE)
+            .ok_or(VfsError::StorageFull)?;
+        let old_pages = (content.len() as u64).div_ceil(PAGE_SIZE);
+        let new_pages = (len as u64).div_ceil(PAGE_SIZE);
+        if new_pages > old_pages {
+            self.usage.reserve_pages(new_pages - old_pages)?;
+            if content.try_reserve_exact(len - content.len()).is_err() {
+                self.usage.release_pages(new_pages - old_pages);
+                return Err(VfsError::StorageFull);
+            }
+        } else {
+            self.usage.release_pages(old_pages - new_pages);
+        }
+        content.resize(len, 0);
+        Ok(())
+    }
+}
+
+impl Drop for FileNode {
+    fn drop(&mut self) {
+        let pages = (self.content.get_mut().len() as u64).div_ceil(PAGE_SIZE);
+        self.usage.release_pages(pages);
+        self.usage.release_inode();
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self.content.read().len() as u64;
+        let perm = if self.ty == VfsNodeType::SymLink {
+            VfsNodePerm::from_bits_truncate(0o777)
+        } else {
+            VfsNodePerm::default_file()
+        };
+        Ok(VfsNodeAttr::new(perm, self.ty, size, size.div_ceil(512)))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = self.content.read();
+        let start = (offset as usize).min(content.len());
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut content = self.content.write();
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(VfsError::StorageFull)?;
+        if end > content.len() as u64 {
+            self.resize(&mut content, end)?;
+        }
+        content[offset as usize..end as usize].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut content = self.content.write();
+        self.resize(&mut content, size)
+    }
+
+    fn readlink(&self, _path: &str, buf: &mut [u8]) -> VfsResult<usize> {
+        if self.ty != VfsNodeType::SymLink {
+            return Err(VfsError::InvalidInput);
+        }
+        self.read_at(0, buf)
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.ty == VfsNodeType::SymLink
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// A directory.
+struct DirNode {
+    usage: Arc<Usage>,
+    this: Weak<DirNode>,
+    parent: RwLock<Weak<dyn VfsNodeOps>>,
+    perm: VfsNodePerm,
+    children: RwLock<BTreeMap<String, VfsNodeRef>>,
+}
+
+impl DirNode {
+    fn new(usage: Arc<Usage>, parent: Weak<dyn VfsNodeOps>, perm: VfsNodePerm) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            usage,
+            this: this.clone(),
+            parent: RwLock::new(parent),
+            perm,
+            children: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    fn create_node(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        if name.len() as u64 > NAME_MAX {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        let node: VfsNodeRef = match ty {
+            VfsNodeType::File | VfsNodeType::SymLink => {
+                self.usage.reserve_inode()?;
+                Arc::new(FileNode::new(self.usage.clone(), ty))
+            }
+            VfsNodeType::Dir => {
+                self.usage.reserve_inode()?;
+                let parent: Weak<dyn VfsNodeOps> = self.this.clone();
+                DirNode::new(self.usage.clone(), parent, VfsNodePerm::default_dir())
+            }
+            _ => return Err(VfsError::Unsupported),
+        };
+        children.insert(name.to_string(), node);
+        Ok(())
+    }
+
+    /// Finds the directory containing `path` and the last component of it.
+    fn lookup_parent<'a>(&self, path: &'a str) -> VfsResult<(Arc<DirNode>, &'a str)> {
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let path = path.trim_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::InvalidInput);
+        }
+        let parent = this.lookup(parent)?;
+        let parent = parent
+            .as_any()
+            .downcast_ref::<DirNode>()
+            .ok_or(VfsError::NotADirectory)?
+            .this
+            .upgrade()
+            .ok_or(VfsError::NotFound)?;
+        Ok((parent, name))
+    }
+
+    fn remove_node(&self, name: &str) -> VfsResult {
+        let mut children = self.children.write();
+        let node = children.get(name).ok_or(VfsError::NotFound)?;
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>()
+            && !dir.children.read().is_empty()
+        {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        children.remove(name);
+        Ok(())
+    }
+}
+
+impl Drop for DirNode {
+    fn drop(&mut self) {
+        self.usage.release_inode();
+    }
+}
+
+/// Splits `path` into its first component and the rest.
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let path = path.trim_start_matches('/');
+    match path.find('/') {
+        Some(n) => (&path[..n], Some(&path[n + 1..])),
+        None => (path, None),
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(self.perm, VfsNodeType::Dir, PAGE_SIZE, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.read().upgrade()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .read()
+                .get(name)
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        }?;
+
+        match rest {
+            Some(rest) if !rest.is_empty() => node.lookup(rest),
+            _ => Ok(node),
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let children = self.children.read();
+        let mut children = children.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match children.next() {
+                    Some((name, node)) => {
+                        *ent = VfsDirEntry::new(name, node.get_attr()?.file_type());
+                    }
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        let (name, rest) = split_path(path);
+        match rest {
+            Some(rest) if !rest.is_empty() => match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => {
+                    let subdir = self
+                        .children
+                        .read()
+                        .get(name)
+                        .cloned()
+                        .ok_or(VfsError::NotFound)?;
+                    subdir.create(rest, ty)
+                }
+            },
+            // Already exists
+            _ if matches!(name, "" | "." | "..") => Ok(()),
+            _ => self.create_node(name, ty),
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        let (name, rest) = split_path(path);
+        match rest {
+            Some(rest) if !rest.is_empty() => match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => {
+                    let subdir = self
+                        .children
+                        .read()
+                        .get(name)
+                        .cloned()
+                        .ok_or(VfsError::NotFound)?;
+                    subdir.remove(rest)
+                }
+            },
+            _ if matches!(name, "" | "." | "..") => Err(VfsError::InvalidInput),
+            _ => self.remove_node(name),
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let (src_dir, src_name) = self.lookup_parent(src_path)?;
+        let (dst_dir, dst_name) = self.lookup_parent(dst_path)?;
+        let node = src_dir
+            .children
+            .read()
+            .get(src_name)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+        let moved_dir = node.as_any().downcast_ref::<DirNode>();
+
+        let replaced = dst_dir.children.read().get(dst_name).cloned();
+        if let Some(replaced) = replaced {
+            if Arc::ptr_eq(&node, &replaced) {
+                return Ok(());
+            }
+            match (moved_dir, replaced.as_any().downcast_ref::<DirNode>()) {
+                (Some(_), None) => return Err(VfsError::NotADirectory),
+                (None, Some(_)) => return Err(VfsError::IsADirectory),
+                (Some(_), Some(dir)) if !dir.children.read().is_empty() => {
+                    return Err(VfsError::DirectoryNotEmpty);
+                }
+                _ => {}
+            }
+        }
+        // A directory can't be moved below itself
+        if moved_dir.is_some() {
+            let mut ancestor = Some(dst_dir.clone() as VfsNodeRef);
+            while let Some(dir) = ancestor {
+                if Arc::ptr_eq(&dir, &node) {
+                    return Err(VfsError::InvalidInput);
+                }
+                ancestor = dir.parent();
+            }
+        }
+
+        src_dir.children.write().remove(src_name);
+        if let Some(dir) = moved_dir {
+            let parent: Weak<dyn VfsNodeOps> = dst_dir.this.clone();
+            *dir.parent.write() = parent;
+        }
+        // A replaced destination is released once its last user drops it
+        dst_dir.children.write().insert(dst_name.to_string(), node);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
@@ -0,0 +1,334 @@
+//! Loop devices, which present a regular file as a block device.
+//!
+//! `/dev/loop0` to `/dev/loop7` exist from boot; `/dev/loop-control` hands out
+//! further devices on demand. A bound loop device is registered as a block
+//! device, so its path can be used as the source of `sys_mount`.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axsync::Mutex;
+use spin::RwLock;
+
+use super::{
+    add_node,
+    blkdev::{BlockDevice, register_block_device},
+};
+
+/// The logical block size of loop devices.
+const LOOP_BLOCK_SIZE: usize = 512;
+
+/// The number of loop devices created during boot, like Linux's
+/// `max_loop` default.
+const INITIAL_LOOP_DEVICES: usize = 8;
+
+/// An open file a loop device can be bound to.
+pub trait BackingFile: Send + Sync {
+    /// Reads from `offset`, returning the number of bytes read.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+
+    /// Writes to `offset`, returning the number of bytes written.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize>;
+
+    /// Returns the attributes of the file.
+    fn get_attr(&self) -> AxResult<VfsNodeAttr>;
+
+    /// Flushes written data to the underlying storage.
+    fn flush(&self) -> AxResult;
+}
+
+/// The file a loop device is bound to.
+struct Backing {
+    file: Arc<dyn BackingFile>,
+    path: String,
+    read_only: bool,
+}
+
+/// A snapshot of the state of a bound loop device.
+pub struct LoopStatus {
+    /// The index `N` of `/dev/loopN`.
+    pub index: usize,
+    /// The path of the backing file.
+    pub backing_file: String,
+    /// The size of the backing file in bytes.
+    pub size: u64,
+    /// Whether the device rejects writes.
+    pub read_only: bool,
+}
+
+/// The loop device `/dev/loopN`.
+pub struct LoopDevice {
+    index: usize,
+    backing: Mutex<Option<Backing>>,
+    /// The mounted filesystems and open files using the device.
+    users: AtomicUsize,
+}
+
+impl LoopDevice {
+    const fn new(index: usize) -> Self {
+        Self {
+            index,
+            backing: Mutex::new(None),
+            users: AtomicUsize::new(0),
+        }
+    }
+
+    /// The index `N` of `/dev/loopN`.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The path of the device node.
+    pub fn path(&self) -> String {
+        format!("/dev/loop{}", self.index)
+    }
+
+    /// Whether the device is bound to a file.
+    pub fn is_bound(&self) -> bool {
+        self.backing.lock().is_some()
+    }
+
+    /// Binds the device to `file`, which was opened at `path`.
+    ///
+    /// The device keeps using the open file even if it is renamed or
+    /// unlinked afterwards.
+    pub fn attach(&self, file: Arc<dyn BackingFile>, path: &str, read_only: bool) -> LinuxResult {
+        let mut backing = self.backing.lock();
+        if backing.is_some() {
+            return Err(LinuxError::EBUSY);
+        }
+
+        if file.get_attr()?.is_dir() {
+            return Err(LinuxError::EINVAL);
+        }
+        *backing = Some(Backing {
+            file,
+            path: path.to_string(),
+            read_only,
+        });
+        info!("loop{}: bound to {}", self.index, path);
+        Ok(())
+    }
+
+    /// Unbinds the device from its file.
+    ///
+    /// Fails with `EBUSY` while the device has users besides the open file
+    /// asking for it, such as a mounted filesystem or another open file.
+    pub fn detach(&self) -> LinuxResult {
+        let mut backing = self.backing.lock();
+        if backing.is_none() {
+            return Err(LinuxError::ENXIO);
+        }
+        if self.users.load(Ordering::Acquire) > 1 {
+            return Err(LinuxError::EBUSY);
+        }
+        if let Some(backing) = backing.take() {
+            let _ = backing.file.flush();
+            info!("loop{}: unbound from {}", self.index, backing.path);
+        }
+        Ok(())
+    }
+
+    /// Returns the state of the device, or `ENXIO` if it is not bound.
+    pub fn status(&self) -> LinuxResult<LoopStatus> {
+        let backing = self.backing.lock();
+        let backing = backing.as_ref().ok_or(LinuxError::ENXIO)?;
+        Ok(LoopStatus {
+            index: self.index,
+            backing_file: backing.path.clone(),
+            size: backing.file.get_attr()?.size(),
+            read_only: backing.read_only,
+        })
+    }
+
+    fn size(&self) -> u64 {
+        self.backing
+            .lock()
+            .as_ref()
+            .and_then(|b| b.file.get_attr().ok())
+            .map_or(0, |attr| attr.size())
+    }
+}
+
+impl BlockDevice for LoopDevice {
+    fn block_size(&self) -> usize {
+        LOOP_BLOCK_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.size() / LOOP_BLOCK_SIZE as u64
+    }
+
+    fn read_block(&self, block_id: u64, buf: &mut [u8]) -> AxResult {
+        let backing = self.backing.lock();
+        let backing = backing.as_ref().ok_or(AxError::NotFound)?;
+        let mut offset = block_id * LOOP_BLOCK_SIZE as u64;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let n = backing.file.read_at(offset, buf)?;
+            if n == 0 {
+                // Past the end of the file, like a sparse tail
+                buf.fill(0);
+                break;
+            }
+            offset += n as u64;
+            buf = &mut buf[n..];
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, block_id: u64, buf: &[u8]) -> AxResult {
+        let backing = self.backing.lock();
+        let backing = backing.as_ref().ok_or(AxError::NotFound)?;
+        if backing.read_only {
+            return Err(AxError::PermissionDenied);
+        }
+        let mut offset = block_id * LOOP_BLOCK_SIZE as u64;
+        let mut buf = buf;
+        while !buf.is_empty() {
+            let n = backing.file.write_at(offset, buf)?;
+            if n == 0 {
+                return Err(AxError::StorageFull);
+            }
+            offset += n as u64;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> AxResult {
+        match self.backing.lock().as_ref() {
+            Some(backing) => backing.file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.backing.lock().as_ref().is_some_and(|b| b.read_only)
+    }
+
+    fn acquire(&self) {
+        self.users.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn release(&self) {
+        self.users.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl VfsNodeOps for LoopDevice {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self.size();
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::BlockDevice,
+            size,
+            size.div_ceil(LOOP_BLOCK_SIZE as u64),
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        match self.backing.lock().as_ref() {
+            Some(backing) => backing.file.read_at(offset, buf),
+            None => Ok(0),
+        }
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        match self.backing.lock().as_ref() {
+            Some(backing) if backing.read_only => Err(AxError::PermissionDenied),
+            Some(backing) => backing.file.write_at(offset, buf),
+            None => Err(AxError::NotFound),
+        }
+    }
+
+    fn fsync(&self) -> VfsResult {
+        BlockDevice::flush(self)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// The node `/dev/loop-control`, which only supports ioctls.
+pub struct LoopControl;
+
+impl VfsNodeOps for LoopControl {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(AxError::InvalidInput)
+    }
+
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(AxError::InvalidInput)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+static LOOP_DEVICES: RwLock<Vec<Arc<LoopDevice>>> = RwLock::new(Vec::new());
+
+/// Creates `/dev/loop0` to `/dev/loop7` and `/dev/loop-control`.
+pub(crate) fn init_loop_devices() {
+    for _ in 0..INITIAL_LOOP_DEVICES {
+        if let Err(e) = add_loop_device() {
+            warn!("failed to create loop device: {:?}", e);
+        }
+    }
+    if let Err(e) = add_node("/dev", "loop-control", Arc::new(LoopControl)) {
+        warn!("failed to create /dev/loop-control: {:?}", e);
+    }
+}
+
+/// Creates the next loop device and its node in `/dev`.
+fn add_loop_device() -> AxResult<Arc<LoopDevice>> {
+    let mut devices = LOOP_DEVICES.write();
+    let dev = Arc::new(LoopDevice::new(devices.len()));
+    add_node("/dev", &format!("loop{}", dev.index), dev.clone())?;
+    register_block_device(&dev.path(), dev.clone());
+    devices.push(dev.clone());
+    Ok(dev)
+}
+
+/// Finds the loop device with the given index.
+pub fn loop_device(index: usize) -> Option<Arc<LoopDevice>> {
+    LOOP_DEVICES.read().get(index).cloned()
+}
+
+/// Finds the loop device whose node is at `path`.
+pub fn loop_device_by_path(path: &str) -> Option<Arc<LoopDevice>> {
+    let index = path.strip_prefix("/dev/loop")?.parse().ok()?;
+    loop_device(index)
+}
+
+/// Returns the first unbound loop device, creating one if all are in use.
+pub fn free_loop_device() -> LinuxResult<Arc<LoopDevice>> {
+    let free = LOOP_DEVICES
+        .read()
+        .iter()
+        .find(|dev| !dev.is_bound())
+        .cloned();
+    match free {
+        Some(dev) => Ok(dev),
+        None => Ok(add_loop_device()?),
+    }
+}
+
+/// Whether `path` is the node of `/dev/loop-control`.
+pub fn is_loop_control(path: &str) -> bool {
+    path == "/dev/loop-control"
+}
@@ -4,12 +4,63 @@ use alloc::{
     format,
     string::{String, ToString},
 };
+use axerrno::AxResult;
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{VfsNodeRef, VfsOps};
 
+use self::{fs::FsType, mount::MountFlags};
+
+pub mod blkdev;
+pub mod chardev;
+pub mod devnum;
+pub mod devpts;
+pub mod fs;
+pub mod loopdev;
+pub mod mount;
 pub mod proc;
+pub mod sysfs;
+
+/// The number of file descriptors a process can have open.
+pub const AX_FILE_LIMIT: usize = 1024;
 
 /// Initialize the filesystem by setting up /proc directories.
 pub fn init_filesystem() {
+    mount::init_mount_table();
     proc::init_procfs();
+    sysfs::init_sysfs();
+    loopdev::init_loop_devices();
+    chardev::init_char_devices();
+    devpts::init_devpts();
+    init_tmpfs();
+}
+
+/// Replaces the unbounded ramfs mounted by `axfs` at `/tmp` with a tmpfs,
+/// and mounts another one at `/dev/shm`.
+fn init_tmpfs() {
+    if let Err(e) = axfs::api::umount("/tmp") {
+        warn!("failed to unmount the boot ramfs at /tmp: {:?}", e);
+    }
+    // devtmpfs cannot create directories, so graft an empty one
+    if !axfs::api::absolute_path_exists("/dev/shm")
+        && let Err(e) = add_node("/dev", "shm", RamFileSystem::new().root_dir())
+    {
+        warn!("failed to create /dev/shm: {:?}", e);
+    }
+
+    for target in ["/tmp", "/dev/shm"] {
+        let flags = MountFlags::NOSUID | MountFlags::NODEV;
+        if let Err(e) = mount::mount("tmpfs", target, FsType::Tmpfs, flags, "mode=1777") {
+            warn!("failed to mount tmpfs at {}: {:?}", target, e);
+        }
+    }
+}
+
+/// Add a custom node named `name` into the directory at `dir`.
+pub fn add_node(dir: &str, name: &str, node: VfsNodeRef) -> AxResult {
+    let opts = axfs::fops::OpenOptions::new().set_read(true);
+    let dir = axfs::fops::Directory::open_dir(dir, &opts)?;
+    dir.add_node(name, node)?;
+    Ok(())
 }
 
 /// Resolve a path by following all symbolic links to get the final target.
@@ -0,0 +1,451 @@
+//! The kernel mount table.
+//!
+//! Every filesystem attached to the directory tree is recorded here, including
+//! the ones mounted by `axfs` during boot. The table backs `sys_mount` and
+//! `sys_umount2`, and is rendered as `/proc/mounts` and
+//! `/proc/self/mountinfo`.
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axerrno::{LinuxError, LinuxResult};
+use axfs_vfs::VfsNodeRef;
+use bitflags::bitflags;
+use linux_raw_sys::general::{
+    MS_BIND, MS_NODEV, MS_NOEXEC, MS_NOSUID, MS_RDONLY, MS_REC, MS_REMOUNT,
+};
+use spin::RwLock;
+
+use super::{
+    chardev::char_devices,
+    fs::{FileSystem, FsStats, FsType, new_filesystem},
+};
+
+// Define umount2 flags directly since they are not exported by linux-raw-sys
+const MNT_FORCE: u32 = 1;
+const MNT_DETACH: u32 = 2;
+const MNT_EXPIRE: u32 = 4;
+const UMOUNT_NOFOLLOW: u32 = 8;
+
+bitflags! {
+    /// Flags for `mount(2)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+    pub struct MountFlags: u32 {
+        /// Mount read-only.
+        const RDONLY = MS_RDONLY;
+        /// Ignore set-user-ID and set-group-ID bits.
+        ///
+        /// Files carry no such bits here and `execve` never changes
+        /// credentials, so every mount already behaves this way; the flag is
+        /// only reported back.
+        const NOSUID = MS_NOSUID;
+        /// Disallow access to device special files.
+        const NODEV = MS_NODEV;
+        /// Disallow program execution.
+        const NOEXEC = MS_NOEXEC;
+        /// Change the flags of an existing mount.
+        const REMOUNT = MS_REMOUNT;
+        /// Make a directory tree visible at another path.
+        const BIND = MS_BIND;
+        /// Apply to the mounts below the source as well.
+        const REC = MS_REC;
+
+        /// Flags that are stored per mount point.
+        const ATTRS = Self::RDONLY.bits() | Self::NOSUID.bits() | Self::NODEV.bits() | Self::NOEXEC.bits();
+    }
+}
+
+bitflags! {
+    /// Flags for `umount2(2)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct UmountFlags: u32 {
+        /// Abort pending requests before unmounting. No filesystem here has
+        /// any to abort, so it unmounts normally.
+        const FORCE = MNT_FORCE;
+        /// Detach the mount and everything below it.
+        const DETACH = MNT_DETACH;
+        /// Mark the mount as expired.
+        const EXPIRE = MNT_EXPIRE;
+        /// Don't dereference the target if it is a symbolic link.
+        const NOFOLLOW = UMOUNT_NOFOLLOW;
+    }
+}
+
+/// An entry of the mount table.
+#[derive(Clone)]
+pub struct MountPoint {
+    /// The unique ID of the mount.
+    pub id: usize,
+    /// The ID of the mount this one is attached to.
+    pub parent_id: usize,
+    /// The mount source, e.g. a device path.
+    pub source: String,
+    /// The absolute path the filesystem is attached at.
+    pub target: String,
+    /// The directory of the filesystem that forms the root of this mount.
+    /// It is `/` unless the mount is a bind mount of a subdirectory.
+    pub root: String,
+    /// The filesystem type.
+    pub fs_type: FsType,
+    /// The per-mount flags, a subset of [`MountFlags::ATTRS`].
+    pub flags: MountFlags,
+    /// The filesystem instance, or `None` for mounts created by `axfs` itself
+    /// and for bind mounts.
+    fs: Option<Arc<dyn FileSystem>>,
+    /// For bind mounts, the absolute path of the directory tree made visible
+    /// at `target`.
+    bind_source: Option<String>,
+}
+
+impl MountPoint {
+    /// Whether `path` is the mount point itself or lies below it.
+    pub fn contains(&self, path: &str) -> bool {
+        is_within(path, &self.target)
+    }
+
+    /// Returns the usage statistics of the mounted filesystem.
+    pub fn stats(&self) -> FsStats {
+        self.fs
+            .as_ref()
+            .map_or_else(FsStats::default, |fs| fs.stats())
+    }
+
+    /// Whether this is a bind mount.
+    pub fn is_bind(&self) -> bool {
+        self.bind_source.is_some()
+    }
+
+    /// The mount options, as shown in `/proc/mounts`.
+    pub fn options(&self) -> String {
+        let mut options = String::from(if self.flags.contains(MountFlags::RDONLY) {
+            "ro"
+        } else {
+            "rw"
+        });
+        for (flag, name) in [
+            (MountFlags::NOSUID, ",nosuid"),
+            (MountFlags::NODEV, ",nodev"),
+            (MountFlags::NOEXEC, ",noexec"),
+        ] {
+            if self.flags.contains(flag) {
+                options.push_str(name);
+            }
+        }
+        options
+    }
+}
+
+struct MountTable {
+    mounts: Vec<MountPoint>,
+    next_id: usize,
+}
+
+impl MountTable {
+    const fn new() -> Self {
+        Self {
+            mounts: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Finds the innermost mount containing `path`.
+    fn find(&self, path: &str) -> Option<&MountPoint> {
+        self.mounts
+            .iter()
+            .filter(|m| m.contains(path))
+            .max_by_key(|m| m.target.len())
+    }
+
+    fn insert(&mut self, mut mount: MountPoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        mount.id = id;
+        mount.parent_id = self.find(&mount.target).map_or(id, |m| m.id);
+        mount.flags &= MountFlags::ATTRS;
+        self.mounts.push(mount);
+        id
+    }
+}
+
+impl MountPoint {
+    fn new(source: &str, target: &str, fs_type: FsType, flags: MountFlags) -> Self {
+        Self {
+            id: 0,
+            parent_id: 0,
+            source: source.to_string(),
+            target: target.to_string(),
+            root: String::from("/"),
+            fs_type,
+            flags,
+            fs: None,
+            bind_source: None,
+        }
+    }
+}
+
+static MOUNT_TABLE: RwLock<MountTable> = RwLock::new(MountTable::new());
+
+/// Whether `path` equals `target` or lies below it.
+fn is_within(path: &str, target: &str) -> bool {
+    target == "/"
+        || path
+            .strip_prefix(target)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Strips the trailing slash of a canonical path.
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+/// Records the filesystems mounted by `axfs` during boot.
+pub(crate) fn init_mount_table() {
+    #[cfg(feature = "lwext4_rs")]
+    let root_type = FsType::Ext4;
+    #[cfg(not(feature = "lwext4_rs"))]
+    let root_type = FsType::Vfat;
+
+    let mut table = MOUNT_TABLE.write();
+    for (source, target, fs_type, flags) in [
+        ("/dev/root", "/", root_type, MountFlags::empty()),
+        ("devtmpfs", "/dev", FsType::Devtmpfs, MountFlags::NOSUID),
+    ] {
+        table.insert(MountPoint::new(source, target, fs_type, flags));
+    }
+}
+
+/// Mounts a new filesystem of type `fs_type` at `target`.
+///
+/// `target` must be a canonical absolute path to an existing directory, as
+/// the caller sees it. Bind mounts above it are not resolved, so the
+/// filesystem does not show up in the tree they were bound from.
+/// `data` holds filesystem specific options, such as `size=` for tmpfs.
+pub fn mount(
+    source: &str,
+    target: &str,
+    fs_type: FsType,
+    flags: MountFlags,
+    data: &str,
+) -> LinuxResult {
+    let target = normalize(target);
+    let mut table = MOUNT_TABLE.write();
+    if table.mounts.iter().any(|m| m.target == target) {
+        return Err(LinuxError::EBUSY);
+    }
+
+    let fs = new_filesystem(fs_type, source, data)?;
+    axfs::api::mount(target, fs.clone())?;
+    let id = table.insert(MountPoint {
+        fs: Some(fs),
+        ..MountPoint::new(source, target, fs_type, flags)
+    });
+    info!(
+        "mounted {} ({}) at {} with id {}",
+        source,
+        fs_type.name(),
+        target,
+        id
+    );
+    Ok(())
+}
+
+/// Makes the directory tree at `source` visible at `target`.
+///
+/// Bind mounts are not attached to the VFS; instead [`resolve_path`] rewrites
+/// paths below `target` into `source`, so the source tree is never modified
+/// by mounting or unmounting. With [`MountFlags::REC`], the mounts below
+/// `source` are replicated below `target` as well.
+///
+/// Both paths must be canonical; `source` with bind mounts already resolved,
+/// and `target` as the caller sees it.
+pub fn bind_mount(source: &str, target: &str, flags: MountFlags) -> LinuxResult {
+    let source = normalize(source);
+    let target = normalize(target);
+    let mut table = MOUNT_TABLE.write();
+    if table.mounts.iter().any(|m| m.target == target) {
+        return Err(LinuxError::EBUSY);
+    }
+    let origin = table.find(source).cloned().ok_or(LinuxError::EINVAL)?;
+
+    let submounts = if flags.contains(MountFlags::REC) {
+        table
+            .mounts
+            .iter()
+            .filter(|m| m.target != source && is_within(&m.target, source))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    table.insert(new_bind(&origin, source, target, flags));
+    for sub in submounts {
+        let sub_target = rebase(&sub.target, source, target);
+        table.insert(new_bind(&sub, &sub.target, &sub_target, sub.flags));
+    }
+    info!("bind mounted {} at {}", source, target);
+    Ok(())
+}
+
+/// Creates a bind mount entry showing `source`, which lies on `origin`, at
+/// `target`.
+fn new_bind(origin: &MountPoint, source: &str, target: &str, flags: MountFlags) -> MountPoint {
+    let root = rebase(source, &origin.target, &origin.root);
+    MountPoint {
+        root,
+        bind_source: Some(source.to_string()),
+        ..MountPoint::new(&origin.source, target, origin.fs_type, flags)
+    }
+}
+
+/// Replaces the prefix `from` of `path` with `to`.
+fn rebase(path: &str, from: &str, to: &str) -> String {
+    let rest = if from == "/" {
+        path
+    } else {
+        &path[from.len()..]
+    };
+    match (to.trim_end_matches('/'), rest.trim_start_matches('/')) {
+        ("", "") => String::from("/"),
+        (to, "") => to.to_string(),
+        (to, rest) => alloc::format!("{}/{}", to, rest),
+    }
+}
+
+/// Resolves bind mounts in the canonical path `path`.
+///
+/// Returns the path of the same file in the tree the VFS actually serves,
+/// together with the per-mount flags of every mount traversed on the way, so
+/// that e.g. a read-only bind of a writable directory stays read-only.
+pub fn resolve_path(path: &str) -> (String, MountFlags) {
+    const MAX_BIND_DEPTH: usize = 8;
+
+    let table = MOUNT_TABLE.read();
+    let mut path = path.to_string();
+    let mut flags = MountFlags::empty();
+    for _ in 0..MAX_BIND_DEPTH {
+        let Some(mount) = table.find(normalize(&path)) else {
+            break;
+        };
+        flags |= mount.flags;
+        let Some(bind_source) = &mount.bind_source else {
+            break;
+        };
+        let trailing_slash = path.len() > 1 && path.ends_with('/');
+        path = rebase(normalize(&path), &mount.target, bind_source);
+        if trailing_slash && !path.ends_with('/') {
+            path.push('/');
+        }
+    }
+    (path, flags)
+}
+
+/// Looks up the node at `path` in the tree the VFS serves, i.e. a path
+/// already passed through [`resolve_path`].
+///
+/// Only nodes of filesystems in the mount table can be found; the root
+/// filesystem mounted by `axfs` is opaque and yields `None`.
+pub fn lookup_node(path: &str) -> Option<VfsNodeRef> {
+    let path = normalize(path);
+    let table = MOUNT_TABLE.read();
+    let mount = table.find(path)?;
+    let rel = path
+        .strip_prefix(mount.target.as_str())
+        .unwrap_or(path)
+        .trim_start_matches('/');
+    match &mount.fs {
+        Some(fs) => fs.root_dir().lookup(rel).ok(),
+        // The boot-time devtmpfs holds the same nodes as a mounted one
+        None if mount.fs_type == FsType::Devtmpfs => char_devices()
+            .into_iter()
+            .find(|(name, _)| *name == rel)
+            .map(|(_, node)| node),
+        None => None,
+    }
+}
+
+/// Changes the per-mount flags of the mount at `target`.
+pub fn remount(target: &str, flags: MountFlags) -> LinuxResult {
+    let target = normalize(target);
+    let mut table = MOUNT_TABLE.write();
+    let mount = table
+        .mounts
+        .iter_mut()
+        .find(|m| m.target == target)
+        .ok_or(LinuxError::EINVAL)?;
+    mount.flags = flags & MountFlags::ATTRS;
+    info!("remounted {} with {:?}", target, mount.flags);
+    Ok(())
+}
+
+/// Unmounts the filesystem at `target`.
+///
+/// Without [`UmountFlags::DETACH`], a mount that still has other mounts below
+/// it is busy. With it, the whole subtree is detached at once.
+/// [`UmountFlags::FORCE`] is accepted, but has nothing more to abort.
+pub fn umount(target: &str, flags: UmountFlags) -> LinuxResult {
+    let target = normalize(target);
+    if target == "/" {
+        return Err(LinuxError::EBUSY);
+    }
+    if flags.contains(UmountFlags::EXPIRE)
+        && flags.intersects(UmountFlags::FORCE | UmountFlags::DETACH)
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    if flags.contains(UmountFlags::FORCE) {
+        // Nothing aborts pending requests here, so this is a normal unmount
+        warn!("forced unmount of {} is done as a normal one", target);
+    }
+
+    let mut table = MOUNT_TABLE.write();
+    if !table.mounts.iter().any(|m| m.target == target) {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let mut subtree = table
+        .mounts
+        .iter()
+        .filter(|m| is_within(&m.target, target))
+        .cloned()
+        .collect::<Vec<_>>();
+    if subtree.len() > 1 && !flags.contains(UmountFlags::DETACH) {
+        return Err(LinuxError::EBUSY);
+    }
+
+    // Detach the innermost mounts first
+    subtree.sort_by_key(|m| core::cmp::Reverse(m.target.len()));
+    for mount in subtree {
+        if !mount.is_bind() {
+            axfs::api::umount(&mount.target)?;
+        }
+        if let Some(fs) = &mount.fs
+            && let Err(e) = fs.umount()
+        {
+            warn!("failed to flush filesystem at {}: {:?}", mount.target, e);
+        }
+        table.mounts.retain(|m| m.id != mount.id);
+        info!("unmounted {}", mount.target);
+    }
+    Ok(())
+}
+
+/// Lists all mounts, in the order they were mounted.
+pub fn mounts() -> Vec<MountPoint> {
+    MOUNT_TABLE.read().mounts.clone()
+}
+
+/// Finds the innermost mount containing `path`.
+pub fn find_mount(path: &str) -> Option<MountPoint> {
+    MOUNT_TABLE.read().find(normalize(path)).cloned()
+}
+
+/// Returns the per-mount flags in effect for `path`.
+pub fn mount_flags(path: &str) -> MountFlags {
+    find_mount(path).map_or(MountFlags::empty(), |m| m.flags)
+}
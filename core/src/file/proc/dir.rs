@@ -0,0 +1,97 @@
+//! Implements procfs directories whose entries are generated on lookup.
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult,
+};
+
+/// The contents of a [`ProcDir`].
+pub trait ProcDirOps: Send + Sync + 'static {
+    /// Looks up the entry `name`, which is a single path component.
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef>;
+
+    /// Lists the names and types of all entries.
+    fn entries(&self) -> Vec<(String, VfsNodeType)>;
+}
+
+/// ProcDir 结构体表示一个只读的 procfs 目录。
+/// 目录项在每次查找或遍历时由 [`ProcDirOps`] 动态生成。
+pub struct ProcDir<T> {
+    ops: T,
+}
+
+impl<T: ProcDirOps> ProcDir<T> {
+    /// Creates a directory with the entries provided by `ops`.
+    pub fn new(ops: T) -> Arc<Self> {
+        Arc::new(Self { ops })
+    }
+}
+
+/// Splits `path` into its first component and the rest.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_start_matches('/');
+    path.split_once('/').unwrap_or((path, ""))
+}
+
+impl<T: ProcDirOps> VfsNodeOps for ProcDir<T> {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o555),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        if name.is_empty() || name == "." {
+            return if rest.is_empty() {
+                Ok(self)
+            } else {
+                self.lookup(rest)
+            };
+        }
+
+        let node = self.ops.lookup_child(name).ok_or(VfsError::NotFound)?;
+        if rest.is_empty() {
+            return Ok(node);
+        }
+        if node.is_symlink() {
+            // Follow links that stay inside procfs, such as `self`
+            let mut buf = [0u8; 256];
+            let len = node.readlink("", &mut buf)?;
+            let target = core::str::from_utf8(&buf[..len]).map_err(|_| VfsError::InvalidData)?;
+            if target.starts_with('/') {
+                return Err(VfsError::NotFound);
+            }
+            return self.lookup(&alloc::format!("{}/{}", target, rest));
+        }
+        node.lookup(rest)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.ops.entries();
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => match entries.next() {
+                    Some((name, ty)) => *ent = VfsDirEntry::new(name, *ty),
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
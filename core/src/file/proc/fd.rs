@@ -0,0 +1,153 @@
+//! Implements `/proc/<pid>/fd` and `/proc/<pid>/fdinfo`, and the `/dev` links
+//! into them.
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+use axfs_vfs::{VfsNodeRef, VfsNodeType};
+use axns::AxNamespace;
+use axprocess::Pid;
+
+use super::{
+    dir::ProcDirOps,
+    file::{ProcFile, ProcLink},
+};
+use crate::{
+    file::{add_node, mount},
+    task::{ProcessData, get_process},
+};
+
+/// An open file as shown in `/proc/<pid>/fd/<n>` and `/proc/<pid>/fdinfo/<n>`.
+pub struct FdInfo {
+    /// The target of the `fd/<n>` link, a path or e.g. `pipe:[ino]`.
+    pub target: String,
+    /// The file offset.
+    pub pos: u64,
+    /// The file status flags, such as `O_RDWR`.
+    pub flags: u32,
+    /// The ID of the mount the file lies on.
+    pub mnt_id: usize,
+}
+
+impl FdInfo {
+    /// Describes a file opened at `path`.
+    pub fn path(path: &str, pos: u64, flags: u32) -> Self {
+        let target = match path.trim_end_matches('/') {
+            "" => "/",
+            path => path,
+        };
+        Self {
+            target: target.to_string(),
+            pos,
+            flags,
+            mnt_id: mount::find_mount(target).map_or(0, |m| m.id),
+        }
+    }
+
+    /// Describes a file without a path, such as `pipe:[ino]` or
+    /// `anon_inode:[eventpoll]`.
+    pub fn anon(target: String, flags: u32) -> Self {
+        Self {
+            target,
+            pos: 0,
+            flags,
+            mnt_id: 0,
+        }
+    }
+}
+
+/// The file descriptor tables, which are owned by the syscall layer.
+#[crate_interface::def_interface]
+pub trait FdTableIf {
+    /// Lists the open file descriptors in the namespace `ns`.
+    fn fds(ns: &AxNamespace) -> Vec<usize>;
+
+    /// Describes the file descriptor `fd` in the namespace `ns`.
+    fn fd_info(ns: &AxNamespace, fd: usize) -> Option<FdInfo>;
+}
+
+fn fds(pid: Pid) -> Vec<usize> {
+    get_process(pid)
+        .ok()
+        .and_then(|process| {
+            let data = process.data::<ProcessData>()?;
+            Some(crate_interface::call_interface!(FdTableIf::fds(&data.ns)))
+        })
+        .unwrap_or_default()
+}
+
+fn fd_info(pid: Pid, fd: usize) -> Option<FdInfo> {
+    let process = get_process(pid).ok()?;
+    let data = process.data::<ProcessData>()?;
+    crate_interface::call_interface!(FdTableIf::fd_info(&data.ns, fd))
+}
+
+fn list_fds(pid: Pid, ty: VfsNodeType) -> Vec<(String, VfsNodeType)> {
+    fds(pid)
+        .into_iter()
+        .map(|fd| (fd.to_string(), ty))
+        .collect()
+}
+
+/// The directory `/proc/<pid>/fd`.
+pub struct FdDir {
+    pub(super) pid: Pid,
+}
+
+impl ProcDirOps for FdDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let pid = self.pid;
+        let fd = name.parse().ok()?;
+        fd_info(pid, fd)?;
+        Some(Arc::new(ProcLink::new(move || {
+            fd_info(pid, fd).map(|info| info.target)
+        })))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        list_fds(self.pid, VfsNodeType::SymLink)
+    }
+}
+
+/// The directory `/proc/<pid>/fdinfo`.
+pub struct FdInfoDir {
+    pub(super) pid: Pid,
+}
+
+impl ProcDirOps for FdInfoDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let pid = self.pid;
+        let fd = name.parse().ok()?;
+        fd_info(pid, fd)?;
+        Some(Arc::new(ProcFile::new(move || {
+            fd_info(pid, fd).map_or_else(String::new, |info| {
+                format!(
+                    "pos:\t{}\nflags:\t0{:o}\nmnt_id:\t{}\n",
+                    info.pos, info.flags, info.mnt_id
+                )
+            })
+        })))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        list_fds(self.pid, VfsNodeType::File)
+    }
+}
+
+/// Creates `/dev/fd`, `/dev/stdin`, `/dev/stdout` and `/dev/stderr`.
+pub(crate) fn init_dev_links() {
+    for (name, target) in [
+        ("fd", "/proc/self/fd"),
+        ("stdin", "/proc/self/fd/0"),
+        ("stdout", "/proc/self/fd/1"),
+        ("stderr", "/proc/self/fd/2"),
+    ] {
+        let link = Arc::new(ProcLink::new(move || Some(target.to_string())));
+        if let Err(e) = add_node("/dev", name, link) {
+            warn!("failed to create /dev/{}: {:?}", name, e);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Implements read-only procfs files and links whose content is generated on
+//! read.
+use alloc::{boxed::Box, string::String};
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+/// ProcFile 结构体表示一个只读的 procfs 文件。
+/// 每次读取时都会调用生成函数重新生成文件内容。
+pub struct ProcFile {
+    generate: Box<dyn Fn() -> String + Send + Sync>,
+}
+
+impl ProcFile {
+    /// Creates a file whose content is produced by `generate`.
+    pub fn new(generate: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self {
+            generate: Box::new(generate),
+        }
+    }
+}
+
+/// Copies the part of `content` starting at `offset` into `buf`.
+fn read_content(content: &[u8], offset: u64, buf: &mut [u8]) -> usize {
+    let start = (offset as usize).min(content.len());
+    let len = buf.len().min(content.len() - start);
+    buf[..len].copy_from_slice(&content[start..start + len]);
+    len
+}
+
+impl VfsNodeOps for ProcFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        // Like Linux, procfs files report a size of zero
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.generate)();
+        Ok(read_content(content.as_bytes(), offset, buf))
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// ProcLink 结构体表示一个 procfs 符号链接。
+/// 链接目标在每次读取时动态生成，目标不存在时返回 `NotFound`。
+pub struct ProcLink {
+    target: Box<dyn Fn() -> Option<String> + Send + Sync>,
+}
+
+impl ProcLink {
+    /// Creates a link whose target is produced by `target`.
+    pub fn new(target: impl Fn() -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            target: Box::new(target),
+        }
+    }
+}
+
+impl VfsNodeOps for ProcLink {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o777),
+            VfsNodeType::SymLink,
+            0,
+            0,
+        ))
+    }
+
+    fn readlink(&self, _path: &str, buf: &mut [u8]) -> VfsResult<usize> {
+        let target = (self.target)().ok_or(VfsError::NotFound)?;
+        Ok(read_content(target.as_bytes(), 0, buf))
+    }
+
+    fn is_symlink(&self) -> bool {
+        true
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,211 @@
+//! Implements /proc/<pid>/maps, /proc/<pid>/smaps and
+//! /proc/<pid>/smaps_rollup.
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use axhal::paging::MappingFlags;
+use axprocess::Pid;
+
+use crate::{
+    mm::resident_sizes,
+    task::{ProcessData, get_process, processes},
+};
+
+/// A user memory area as shown in `/proc/<pid>/maps`.
+struct Vma {
+    start: usize,
+    end: usize,
+    flags: MappingFlags,
+    offset: u64,
+    name: String,
+    usage: Usage,
+}
+
+/// Memory usage of an area, in bytes.
+#[derive(Default)]
+struct Usage {
+    rss: usize,
+    pss: usize,
+    shared: usize,
+    private: usize,
+    anonymous: usize,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.rss += other.rss;
+        self.pss += other.pss;
+        self.shared += other.shared;
+        self.private += other.private;
+        self.anonymous += other.anonymous;
+    }
+}
+
+/// Names the areas the loader maps at fixed addresses.
+fn special_name(start: usize, end: usize) -> Option<&'static str> {
+    use axconfig::plat::{SIGNAL_TRAMPOLINE, USER_HEAP_BASE, USER_STACK_TOP};
+    if start == SIGNAL_TRAMPOLINE {
+        Some("[vdso]")
+    } else if (start..end).contains(&USER_HEAP_BASE) {
+        Some("[heap]")
+    } else if end == USER_STACK_TOP {
+        Some("[stack]")
+    } else {
+        None
+    }
+}
+
+/// Splits the `resident` bytes of an area by how they are shared.
+fn usage(resident: usize, shared: bool) -> Usage {
+    if shared {
+        // The trampoline is mapped into every process
+        let sharers = processes().len().max(1);
+        Usage {
+            rss: resident,
+            pss: resident / sharers,
+            shared: resident,
+            ..Default::default()
+        }
+    } else {
+        // Pages are copied on fork and written on load, so all of them are
+        // private and, like written private file pages on Linux, anonymous
+        Usage {
+            rss: resident,
+            pss: resident,
+            private: resident,
+            anonymous: resident,
+            ..Default::default()
+        }
+    }
+}
+
+/// Collects the user memory areas of the process `pid`.
+fn vmas(pid: Pid) -> Vec<Vma> {
+    let Ok(process) = get_process(pid) else {
+        return Vec::new();
+    };
+    let Some(data) = process.data::<ProcessData>() else {
+        return Vec::new();
+    };
+    let aspace = data.aspace.lock();
+    let file_mappings = data.file_mappings.lock();
+    let areas = aspace
+        .areas()
+        .iter()
+        .filter(|area| area.flags().contains(MappingFlags::USER))
+        .collect::<Vec<_>>();
+    let ranges = areas.iter().map(|area| area.va_range()).collect::<Vec<_>>();
+    let resident = resident_sizes(&aspace, &ranges);
+
+    areas
+        .into_iter()
+        .zip(resident)
+        .map(|(area, resident)| {
+            let start = area.start().as_usize();
+            let end = area.end().as_usize();
+            let special = special_name(start, end);
+            let (name, offset) = match file_mappings.lookup(start) {
+                Some((path, offset)) => (String::from(path), offset),
+                None => (String::from(special.unwrap_or_default()), 0),
+            };
+            Vma {
+                start,
+                end,
+                flags: area.flags(),
+                offset,
+                name,
+                usage: usage(resident, special == Some("[vdso]")),
+            }
+        })
+        .collect()
+}
+
+fn write_maps_line(out: &mut String, vma: &Vma) {
+    let perm = |flag, c| if vma.flags.contains(flag) { c } else { '-' };
+    let line = format!(
+        "{:08x}-{:08x} {}{}{}p {:08x} 00:00 0",
+        vma.start,
+        vma.end,
+        perm(MappingFlags::READ, 'r'),
+        perm(MappingFlags::WRITE, 'w'),
+        perm(MappingFlags::EXECUTE, 'x'),
+        vma.offset,
+    );
+    if vma.name.is_empty() {
+        let _ = writeln!(out, "{}", line);
+    } else {
+        // Linux pads the name to start at column 74
+        let _ = writeln!(out, "{:<73}{}", line, vma.name);
+    }
+}
+
+fn write_usage(out: &mut String, usage: &Usage) {
+    let kb = |bytes: usize| bytes / 1024;
+    let _ = writeln!(out, "Rss:            {:>8} kB", kb(usage.rss));
+    let _ = writeln!(out, "Pss:            {:>8} kB", kb(usage.pss));
+    let _ = writeln!(out, "Shared_Clean:   {:>8} kB", kb(usage.shared));
+    let _ = writeln!(out, "Shared_Dirty:   {:>8} kB", 0);
+    let _ = writeln!(out, "Private_Clean:  {:>8} kB", 0);
+    let _ = writeln!(out, "Private_Dirty:  {:>8} kB", kb(usage.private));
+    let _ = writeln!(out, "Referenced:     {:>8} kB", kb(usage.rss));
+    let _ = writeln!(out, "Anonymous:      {:>8} kB", kb(usage.anonymous));
+    let _ = writeln!(out, "Swap:           {:>8} kB", 0);
+    let _ = writeln!(out, "SwapPss:        {:>8} kB", 0);
+    let _ = writeln!(out, "Locked:         {:>8} kB", 0);
+}
+
+/// Renders `/proc/<pid>/maps`.
+pub fn render_maps(pid: Pid) -> String {
+    let mut out = String::new();
+    for vma in vmas(pid) {
+        write_maps_line(&mut out, &vma);
+    }
+    out
+}
+
+/// Renders `/proc/<pid>/smaps`.
+pub fn render_smaps(pid: Pid) -> String {
+    let mut out = String::new();
+    for vma in vmas(pid) {
+        write_maps_line(&mut out, &vma);
+        let _ = writeln!(
+            out,
+            "Size:           {:>8} kB",
+            (vma.end - vma.start) / 1024
+        );
+        let _ = writeln!(out, "KernelPageSize: {:>8} kB", 4);
+        let _ = writeln!(out, "MMUPageSize:    {:>8} kB", 4);
+        write_usage(&mut out, &vma.usage);
+
+        let mut vm_flags = String::from("VmFlags:");
+        for (flag, name) in [
+            (MappingFlags::READ, " rd"),
+            (MappingFlags::WRITE, " wr"),
+            (MappingFlags::EXECUTE, " ex"),
+        ] {
+            if vma.flags.contains(flag) {
+                vm_flags.push_str(name);
+            }
+        }
+        let _ = writeln!(out, "{}", vm_flags);
+    }
+    out
+}
+
+/// Renders `/proc/<pid>/smaps_rollup`.
+pub fn render_smaps_rollup(pid: Pid) -> String {
+    let vmas = vmas(pid);
+    let (Some(first), Some(last)) = (vmas.first(), vmas.last()) else {
+        return String::new();
+    };
+    let mut total = Usage::default();
+    for vma in &vmas {
+        total.add(&vma.usage);
+    }
+
+    let mut out = String::new();
+    let line = format!("{:08x}-{:08x} ---p 00000000 00:00 0", first.start, last.end);
+    let _ = writeln!(out, "{:<73}[rollup]", line);
+    write_usage(&mut out, &total);
+    out
+}
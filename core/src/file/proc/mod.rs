@@ -1,14 +1,131 @@
 //! File management /proc module for the Neon OS kernel.
 
-use alloc::sync::Arc;
+use alloc::{
+    collections::btree_map::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsOps};
+use axprocess::Pid;
+use axtask::{TaskExtRef, current};
+use spin::RwLock;
 
-pub mod selfs;
+use super::{
+    fs::{FileSystem, FsType},
+    mount::{self, MountFlags},
+};
+use crate::task::processes;
 
-/// Initialize the process filesystem by setting up /proc directories.
+pub mod dir;
+pub mod fd;
+pub mod file;
+pub mod maps;
+pub mod mounts;
+pub mod pid;
+pub mod sys;
+mod system;
+
+use self::{
+    dir::{ProcDir, ProcDirOps},
+    file::{ProcFile, ProcLink},
+};
+
+/// Entries of the procfs root other than the per-process directories.
+static ROOT_ENTRIES: RwLock<BTreeMap<String, VfsNodeRef>> = RwLock::new(BTreeMap::new());
+
+/// Adds the entry `name` to the root of every procfs instance.
+pub fn register_entry(name: &str, node: VfsNodeRef) {
+    ROOT_ENTRIES.write().insert(name.to_string(), node);
+}
+
+/// The root directory of procfs.
+struct ProcRoot;
+
+impl ProcDirOps for ProcRoot {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Ok(pid) = name.parse::<Pid>() {
+            return pid::ProcessDir::new(pid);
+        }
+        match name {
+            "self" => Some(Arc::new(ProcLink::new(|| {
+                let curr = current();
+                Some(curr.task_ext().thread.process().pid().to_string())
+            }))),
+            "thread-self" => Some(Arc::new(ProcLink::new(|| {
+                let curr = current();
+                let thread = &curr.task_ext().thread;
+                Some(format!("{}/task/{}", thread.process().pid(), thread.tid()))
+            }))),
+            _ => ROOT_ENTRIES.read().get(name).cloned(),
+        }
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let mut entries = ROOT_ENTRIES
+            .read()
+            .iter()
+            .map(|(name, node)| {
+                let ty = node.get_attr().map_or(VfsNodeType::File, |a| a.file_type());
+                (name.clone(), ty)
+            })
+            .collect::<Vec<_>>();
+        entries.push(("self".to_string(), VfsNodeType::SymLink));
+        entries.push(("thread-self".to_string(), VfsNodeType::SymLink));
+
+        let mut pids = processes().iter().map(|p| p.pid()).collect::<Vec<_>>();
+        pids.sort_unstable();
+        entries.extend(
+            pids.into_iter()
+                .map(|pid| (pid.to_string(), VfsNodeType::Dir)),
+        );
+        entries
+    }
+}
+
+/// A procfs instance. All instances show the same content.
+pub struct ProcFileSystem {
+    root: VfsNodeRef,
+}
+
+impl ProcFileSystem {
+    /// Creates a new procfs instance.
+    pub fn new() -> Self {
+        Self {
+            root: ProcDir::new(ProcRoot),
+        }
+    }
+}
+
+impl Default for ProcFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for ProcFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl FileSystem for ProcFileSystem {}
+
+/// Initialize the process filesystem, replacing the static ramfs `axfs`
+/// mounts at /proc during boot.
 pub fn init_procfs() {
-    let opts = axfs::fops::OpenOptions::new().set_read(true);
-    let procfs = axfs::fops::Directory::open_dir("/proc/self", &opts).unwrap();
+    register_entry("mounts", Arc::new(ProcFile::new(mounts::render_mounts)));
+    system::register_entries();
+    crate::sysctl::init();
+    register_entry("sys", sys::SysDir::root());
+    fd::init_dev_links();
 
-    let self_exe = selfs::SelfExe;
-    let _ = procfs.add_node("exe", Arc::new(self_exe));
+    if let Err(e) = axfs::api::umount("/proc") {
+        warn!("failed to unmount the boot ramfs at /proc: {:?}", e);
+    }
+    let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+    if let Err(e) = mount::mount("proc", "/proc", FsType::Proc, flags, "") {
+        warn!("failed to mount procfs: {:?}", e);
+    }
 }
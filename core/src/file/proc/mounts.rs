@@ -0,0 +1,49 @@
+//! Implements /proc/mounts and /proc/self/mountinfo.
+use alloc::{format, string::String};
+use core::fmt::Write;
+
+use crate::file::mount::mounts;
+
+/// Renders the mount table in the format of `/proc/mounts`.
+pub fn render_mounts() -> String {
+    let mut out = String::new();
+    for m in mounts() {
+        let _ = writeln!(
+            out,
+            "{} {} {} {} 0 0",
+            m.source,
+            m.target,
+            m.fs_type.name(),
+            m.options()
+        );
+    }
+    out
+}
+
+/// Renders the mount table in the format of `/proc/self/mountinfo`.
+pub fn render_mountinfo() -> String {
+    let mut out = String::new();
+    for m in mounts() {
+        // Anonymous device numbers: major 0, minor is the mount ID
+        let dev = format!("0:{}", m.id);
+        let super_options = if m.options().starts_with("ro") {
+            "ro"
+        } else {
+            "rw"
+        };
+        let _ = writeln!(
+            out,
+            "{} {} {} {} {} {} - {} {} {}",
+            m.id,
+            m.parent_id,
+            dev,
+            m.root,
+            m.target,
+            m.options(),
+            m.fs_type.name(),
+            m.source,
+            super_options
+        );
+    }
+    out
+}
@@ -0,0 +1,339 @@
+//! Implements the per-process directories `/proc/<pid>` and
+//! `/proc/<pid>/task/<tid>`.
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use axfs::CURRENT_DIR_PATH;
+use axfs_vfs::{VfsNodeRef, VfsNodeType};
+use axprocess::{Pid, Process};
+use memory_addr::PAGE_SIZE_4K;
+
+use super::{
+    dir::{ProcDir, ProcDirOps},
+    fd::{FdDir, FdInfoDir},
+    file::{ProcFile, ProcLink},
+    maps, mounts,
+};
+use crate::{
+    file::resolve_symlink_path,
+    mm::{mapped_size, resident_size},
+    task::{ProcessData, ThreadData, get_process, get_thread, process_usage},
+    time::clock_ticks,
+};
+
+/// The files of `/proc/<pid>` that are also present in
+/// `/proc/<pid>/task/<tid>`.
+const COMMON_ENTRIES: &[(&str, VfsNodeType)] = &[
+    ("cmdline", VfsNodeType::File),
+    ("comm", VfsNodeType::File),
+    ("cwd", VfsNodeType::SymLink),
+    ("environ", VfsNodeType::File),
+    ("exe", VfsNodeType::SymLink),
+    ("stat", VfsNodeType::File),
+    ("status", VfsNodeType::File),
+];
+
+/// The entries of `/proc/<pid>` only.
+const PROCESS_ENTRIES: &[(&str, VfsNodeType)] = &[
+    ("fd", VfsNodeType::Dir),
+    ("fdinfo", VfsNodeType::Dir),
+    ("maps", VfsNodeType::File),
+    ("mountinfo", VfsNodeType::File),
+    ("mounts", VfsNodeType::File),
+    ("smaps", VfsNodeType::File),
+    ("smaps_rollup", VfsNodeType::File),
+    ("task", VfsNodeType::Dir),
+];
+
+/// The directory `/proc/<pid>`.
+pub struct ProcessDir {
+    pid: Pid,
+}
+
+impl ProcessDir {
+    /// Creates the directory of the process `pid`, if it exists.
+    pub fn new(pid: Pid) -> Option<VfsNodeRef> {
+        get_process(pid).ok()?;
+        Some(ProcDir::new(Self { pid }))
+    }
+}
+
+impl ProcDirOps for ProcessDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let pid = self.pid;
+        Some(match name {
+            "fd" => ProcDir::new(FdDir { pid }),
+            "fdinfo" => ProcDir::new(FdInfoDir { pid }),
+            "maps" => Arc::new(ProcFile::new(move || maps::render_maps(pid))),
+            "smaps" => Arc::new(ProcFile::new(move || maps::render_smaps(pid))),
+            "smaps_rollup" => Arc::new(ProcFile::new(move || maps::render_smaps_rollup(pid))),
+            "mountinfo" => Arc::new(ProcFile::new(mounts::render_mountinfo)),
+            "mounts" => Arc::new(ProcFile::new(mounts::render_mounts)),
+            "task" => ProcDir::new(TaskDir { pid }),
+            _ => common_entry(pid, pid, name)?,
+        })
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        list_entries(&[COMMON_ENTRIES, PROCESS_ENTRIES])
+    }
+}
+
+/// The directory `/proc/<pid>/task`.
+struct TaskDir {
+    pid: Pid,
+}
+
+impl ProcDirOps for TaskDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let tid = name.parse().ok()?;
+        let thread = get_thread(tid).ok()?;
+        if thread.process().pid() != self.pid {
+            return None;
+        }
+        Some(ProcDir::new(ThreadDir { pid: self.pid, tid }))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let Ok(process) = get_process(self.pid) else {
+            return Vec::new();
+        };
+        let mut tids = process
+            .threads()
+            .iter()
+            .map(|thread| thread.tid())
+            .collect::<Vec<_>>();
+        tids.sort_unstable();
+        tids.into_iter()
+            .map(|tid| (tid.to_string(), VfsNodeType::Dir))
+            .collect()
+    }
+}
+
+/// The directory `/proc/<pid>/task/<tid>`.
+struct ThreadDir {
+    pid: Pid,
+    tid: Pid,
+}
+
+impl ProcDirOps for ThreadDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        common_entry(self.pid, self.tid, name)
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        list_entries(&[COMMON_ENTRIES])
+    }
+}
+
+fn list_entries(tables: &[&[(&str, VfsNodeType)]]) -> Vec<(String, VfsNodeType)> {
+    let mut entries = tables
+        .iter()
+        .flat_map(|table| table.iter())
+        .map(|(name, ty)| (name.to_string(), *ty))
+        .collect::<Vec<_>>();
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+/// Creates the entry `name` shared by process and thread directories.
+fn common_entry(pid: Pid, tid: Pid, name: &str) -> Option<VfsNodeRef> {
+    Some(match name {
+        "cmdline" => Arc::new(ProcFile::new(move || {
+            with_process_data(pid, |data| join_nul(&data.cmdline.read()))
+        })),
+        "comm" => Arc::new(ProcFile::new(move || {
+            get_process(pid)
+                .map(|process| format!("{}\n", comm(&process, tid)))
+                .unwrap_or_default()
+        })),
+        "environ" => Arc::new(ProcFile::new(move || {
+            with_process_data(pid, |data| join_nul(&data.environ.read()))
+        })),
+        "stat" => Arc::new(ProcFile::new(move || render_stat(pid, tid))),
+        "status" => Arc::new(ProcFile::new(move || render_status(pid, tid))),
+        "exe" => Arc::new(ProcLink::new(move || {
+            let process = get_process(pid).ok()?;
+            let data = process.data::<ProcessData>()?;
+            Some(resolve_symlink_path(&data.exe_path.read()))
+        })),
+        "cwd" => Arc::new(ProcLink::new(move || {
+            let process = get_process(pid).ok()?;
+            let data = process.data::<ProcessData>()?;
+            let cwd = CURRENT_DIR_PATH.deref_from(&data.ns).lock().clone();
+            // The stored path keeps a trailing slash, which readlink does not
+            Some(match cwd.trim_end_matches('/') {
+                "" => String::from("/"),
+                cwd => cwd.to_string(),
+            })
+        })),
+        _ => return None,
+    })
+}
+
+/// Calls `f` with the data of the process `pid`, or returns an empty
+/// string if the process is gone.
+fn with_process_data(pid: Pid, f: impl FnOnce(&ProcessData) -> String) -> String {
+    get_process(pid)
+        .ok()
+        .and_then(|process| process.data::<ProcessData>().map(f))
+        .unwrap_or_default()
+}
+
+/// Joins `items` with NUL terminators, as in `cmdline` and `environ`.
+fn join_nul(items: &[String]) -> String {
+    let mut out = String::new();
+    for item in items {
+        out.push_str(item);
+        out.push('\0');
+    }
+    out
+}
+
+/// Returns the data of the thread `tid`, if it is still alive.
+fn with_thread_data<R>(tid: Pid, f: impl FnOnce(&ThreadData) -> R) -> Option<R> {
+    get_thread(tid).ok()?.data::<ThreadData>().map(f)
+}
+
+/// The command name of the thread `tid`, truncated like Linux's
+/// `TASK_COMM_LEN`.
+fn comm(process: &Process, tid: Pid) -> String {
+    let name = with_thread_data(tid, ThreadData::name).unwrap_or_else(|| {
+        // Once the thread is gone, fall back to the name execve gives
+        process
+            .data::<ProcessData>()
+            .map_or_else(String::new, |data| {
+                let exe_path = data.exe_path.read();
+                exe_path.rsplit('/').next().unwrap_or_default().into()
+            })
+    });
+    name.chars().take(15).collect()
+}
+
+/// The state letter and description of the thread `tid` of `process`.
+///
+/// Threads blocked waiting for an event sleep; the others are running or
+/// ready to run, which Linux reports alike.
+fn state(process: &Process, tid: Pid) -> (char, &'static str) {
+    if process.is_zombie() {
+        ('Z', "zombie")
+    } else if process
+        .data::<ProcessData>()
+        .is_some_and(|data| data.job.is_stopped())
+    {
+        ('T', "stopped")
+    } else if with_thread_data(tid, ThreadData::is_sleeping) == Some(false) {
+        ('R', "running")
+    } else {
+        ('S', "sleeping")
+    }
+}
+
+/// The terminals, which are owned by the syscall layer.
+#[crate_interface::def_interface]
+pub trait TtyIf {
+    /// Returns the device number and the foreground process group of the
+    /// controlling terminal of the session `sid`, if it has one.
+    fn controlling_tty(sid: Pid) -> Option<(u64, Pid)>;
+}
+
+/// The mapped and resident sizes of the memory of `process`, in bytes.
+fn memory_sizes(process: &Process) -> (usize, usize) {
+    process.data::<ProcessData>().map_or((0, 0), |data| {
+        let aspace = data.aspace.lock();
+        (mapped_size(&aspace), resident_size(&aspace))
+    })
+}
+
+fn parent_pid(process: &Process) -> Pid {
+    process.parent().map_or(0, |parent| parent.pid())
+}
+
+fn render_stat(pid: Pid, tid: Pid) -> String {
+    let Ok(process) = get_process(pid) else {
+        return String::new();
+    };
+    let comm = comm(&process, tid);
+    let (state, _) = state(&process, tid);
+    let group = process.group();
+
+    let mut fields = vec![0u64; 52];
+    fields[0] = tid as _;
+    fields[3] = parent_pid(&process) as _;
+    fields[4] = group.pgid() as _;
+    fields[5] = group.session().sid() as _;
+    let tty = crate_interface::call_interface!(TtyIf::controlling_tty(group.session().sid()));
+    let (tty_nr, tpgid) = tty.map_or((0, -1), |(dev, pgid)| (dev, pgid as i64));
+    fields[6] = tty_nr;
+    let usage = process_usage(&process);
+    let children = process
+        .data::<ProcessData>()
+        .map(|data| *data.children_usage.lock())
+        .unwrap_or_default();
+    fields[9] = usage.minflt as _;
+    fields[10] = children.minflt as _;
+    fields[11] = usage.majflt as _;
+    fields[12] = children.majflt as _;
+    fields[13] = clock_ticks(usage.utime_ns as _);
+    fields[14] = clock_ticks(usage.stime_ns as _);
+    fields[15] = clock_ticks(children.utime_ns as _);
+    fields[16] = clock_ticks(children.stime_ns as _);
+    // priority and rsslim
+    fields[17] = 20;
+    fields[19] = process.threads().len() as _;
+    if let Some(data) = process.data::<ProcessData>() {
+        fields[21] = clock_ticks(data.start_time.as_nanos() as _);
+    }
+    if !process.is_zombie() {
+        let (vsize, rss) = memory_sizes(&process);
+        fields[22] = vsize as _;
+        fields[23] = (rss / PAGE_SIZE_4K) as _;
+    }
+    fields[24] = u64::MAX;
+
+    let mut out = format!("{} ({}) {}", fields[0], comm, state);
+    for (i, field) in fields.iter().enumerate().skip(3) {
+        // Without a controlling terminal, tpgid is -1
+        let _ = match i {
+            7 => write!(out, " {}", tpgid),
+            _ => write!(out, " {}", field),
+        };
+    }
+    out.push('\n');
+    out
+}
+
+fn render_status(pid: Pid, tid: Pid) -> String {
+    let Ok(process) = get_process(pid) else {
+        return String::new();
+    };
+    let comm = comm(&process, tid);
+    let (state, state_name) = state(&process, tid);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "Name:\t{}", comm);
+    let _ = writeln!(out, "Umask:\t0022");
+    let _ = writeln!(out, "State:\t{} ({})", state, state_name);
+    let _ = writeln!(out, "Tgid:\t{}", pid);
+    let _ = writeln!(out, "Ngid:\t0");
+    let _ = writeln!(out, "Pid:\t{}", tid);
+    let _ = writeln!(out, "PPid:\t{}", parent_pid(&process));
+    let _ = writeln!(out, "TracerPid:\t0");
+    // There are no user or group IDs yet, everything runs as root
+    let _ = writeln!(out, "Uid:\t0\t0\t0\t0");
+    let _ = writeln!(out, "Gid:\t0\t0\t0\t0");
+    // Like Linux, there is no memory to show once the process exits
+    if !process.is_zombie() {
+        let (vsize, rss) = memory_sizes(&process);
+        let _ = writeln!(out, "VmSize:\t{:8} kB", vsize / 1024);
+        let _ = writeln!(out, "VmRSS:\t{:8} kB", rss / 1024);
+    }
+    let _ = writeln!(out, "Threads:\t{}", process.threads().len());
+    out
+}
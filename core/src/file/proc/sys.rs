@@ -0,0 +1,100 @@
+//! Implements /proc/sys, the tunables registered with [`crate::sysctl`].
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axfs_vfs::{
+    VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult,
+};
+
+use super::dir::{ProcDir, ProcDirOps};
+use crate::sysctl::{self, Tunable};
+
+/// A directory of `/proc/sys`, holding the tunables whose paths start with
+/// `prefix`.
+pub struct SysDir {
+    prefix: String,
+}
+
+impl SysDir {
+    /// Creates the directory `/proc/sys`.
+    pub fn root() -> VfsNodeRef {
+        ProcDir::new(Self {
+            prefix: String::new(),
+        })
+    }
+}
+
+impl ProcDirOps for SysDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let path = format!("{}{}", self.prefix, name);
+        if let Some(tunable) = sysctl::lookup(&path) {
+            return Some(Arc::new(SysctlFile { tunable }));
+        }
+        let prefix = format!("{}/", path);
+        if sysctl::paths_with_prefix(&prefix).is_empty() {
+            return None;
+        }
+        Some(ProcDir::new(SysDir { prefix }))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let mut entries: Vec<(String, VfsNodeType)> = Vec::new();
+        for path in sysctl::paths_with_prefix(&self.prefix) {
+            let rest = &path[self.prefix.len()..];
+            let entry = match rest.split_once('/') {
+                Some((dir, _)) => (dir.to_string(), VfsNodeType::Dir),
+                None => (rest.to_string(), VfsNodeType::File),
+            };
+            // Paths are sorted, so the entries of a subdirectory are adjacent
+            if entries.last() != Some(&entry) {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+}
+
+/// SysctlFile 结构体表示 `/proc/sys` 下的一个可读写参数文件。
+/// 读取时返回当前值，写入时解析并检查范围后更新参数。
+struct SysctlFile {
+    tunable: &'static dyn Tunable,
+}
+
+impl VfsNodeOps for SysctlFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o644),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = self.tunable.read() + "\n";
+        let content = content.as_bytes();
+        let start = (offset as usize).min(content.len());
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        // Like Linux, the whole value must be written at once
+        if offset != 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let value = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        self.tunable.write(value.trim())?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,146 @@
+//! Implements the system-wide files /proc/meminfo, /proc/cpuinfo,
+//! /proc/stat, /proc/uptime, /proc/loadavg and /proc/version.
+use alloc::{format, string::String, sync::Arc};
+use core::fmt::Write;
+
+use axhal::time::{NANOS_PER_SEC, monotonic_time, monotonic_time_nanos};
+use memory_addr::PAGE_SIZE_4K;
+
+use super::{file::ProcFile, register_entry};
+use crate::{
+    cpustat::{self, CpuTimes},
+    task::{process_count, processes},
+    time::{clock_ticks, realtime},
+};
+
+fn render_meminfo() -> String {
+    let allocator = axalloc::global_allocator();
+    let total = axconfig::plat::PHYS_MEMORY_SIZE / 1024;
+    let free = allocator.available_pages() * PAGE_SIZE_4K / 1024;
+
+    let mut out = String::new();
+    let mut field = |name: &str, kb: usize| {
+        let _ = writeln!(out, "{:<16}{:>8} kB", format!("{}:", name), kb);
+    };
+    field("MemTotal", total);
+    field("MemFree", free);
+    field("MemAvailable", free);
+    field("Buffers", 0);
+    field("Cached", 0);
+    field("SwapCached", 0);
+    field("Active", 0);
+    field("Inactive", 0);
+    field("SwapTotal", 0);
+    field("SwapFree", 0);
+    field("Shmem", 0);
+    out
+}
+
+fn render_cpuinfo() -> String {
+    let mut out = String::new();
+    for cpu in 0..cpustat::cpu_count() {
+        let _ = writeln!(out, "processor\t: {}", cpu);
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "riscv64")] {
+                let _ = writeln!(out, "hart\t\t: {}", cpu);
+                let _ = writeln!(out, "isa\t\t: rv64imafdc");
+                let _ = writeln!(out, "mmu\t\t: sv39");
+            } else if #[cfg(target_arch = "x86_64")] {
+                let _ = writeln!(out, "vendor_id\t: unknown");
+                let _ = writeln!(out, "model name\t: x86_64 processor");
+                let _ = writeln!(out, "physical id\t: 0");
+                let _ = writeln!(out, "core id\t\t: {}", cpu);
+                let _ = writeln!(out, "cpu cores\t: {}", cpustat::cpu_count());
+            } else if #[cfg(target_arch = "aarch64")] {
+                let _ = writeln!(out, "CPU implementer\t: 0x00");
+                let _ = writeln!(out, "CPU architecture: 8");
+            } else if #[cfg(target_arch = "loongarch64")] {
+                let _ = writeln!(out, "model name\t\t: loongarch64 processor");
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn write_cpu_line(out: &mut String, name: &str, times: &CpuTimes) {
+    // user nice system idle iowait irq softirq steal guest guest_nice
+    let _ = writeln!(
+        out,
+        "{} {} 0 {} {} 0 0 0 0 0 0",
+        name,
+        clock_ticks(times.user),
+        clock_ticks(times.system),
+        clock_ticks(times.idle)
+    );
+}
+
+fn render_stat() -> String {
+    let mut out = String::new();
+    write_cpu_line(&mut out, "cpu ", &cpustat::total_cpu_times());
+    for cpu in 0..cpustat::cpu_count() {
+        write_cpu_line(&mut out, &format!("cpu{}", cpu), &cpustat::cpu_times(cpu));
+    }
+    let boot_time = realtime().saturating_sub(monotonic_time());
+    // The interrupts are not broken down by number
+    let _ = writeln!(out, "intr {}", cpustat::interrupt_count());
+    let _ = writeln!(out, "ctxt {}", cpustat::context_switches());
+    let _ = writeln!(out, "btime {}", boot_time.as_secs());
+    let _ = writeln!(out, "processes {}", process_count());
+    let _ = writeln!(out, "procs_running {}", cpustat::nr_running());
+    let _ = writeln!(out, "procs_blocked 0");
+    out
+}
+
+/// Formats nanoseconds as seconds with two decimal places.
+fn seconds(nanos: u64) -> String {
+    let centis = nanos / (NANOS_PER_SEC / 100);
+    format!("{}.{:02}", centis / 100, centis % 100)
+}
+
+fn render_uptime() -> String {
+    let idle = cpustat::total_cpu_times().idle;
+    format!("{} {}\n", seconds(monotonic_time_nanos()), seconds(idle))
+}
+
+fn render_loadavg() -> String {
+    let [avg1, avg5, avg15] = cpustat::load_avg();
+    let threads = processes()
+        .iter()
+        .map(|process| process.threads().len())
+        .sum::<usize>();
+    let last_pid = processes()
+        .iter()
+        .map(|process| process.pid())
+        .max()
+        .unwrap_or_default();
+    format!(
+        "{}.{:02} {}.{:02} {}.{:02} {}/{} {}\n",
+        avg1 / 100,
+        avg1 % 100,
+        avg5 / 100,
+        avg5 % 100,
+        avg15 / 100,
+        avg15 % 100,
+        cpustat::nr_running(),
+        threads,
+        last_pid
+    )
+}
+
+fn render_version() -> String {
+    format!(
+        "Linux version {} (starry@starry-next) #1 SMP\n",
+        crate::KERNEL_RELEASE
+    )
+}
+
+/// Registers the system-wide files in the procfs root.
+pub(super) fn register_entries() {
+    register_entry("meminfo", Arc::new(ProcFile::new(render_meminfo)));
+    register_entry("cpuinfo", Arc::new(ProcFile::new(render_cpuinfo)));
+    register_entry("stat", Arc::new(ProcFile::new(render_stat)));
+    register_entry("uptime", Arc::new(ProcFile::new(render_uptime)));
+    register_entry("loadavg", Arc::new(ProcFile::new(render_loadavg)));
+    register_entry("version", Arc::new(ProcFile::new(render_version)));
+}
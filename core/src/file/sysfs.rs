@@ -0,0 +1,447 @@
+//! A minimal sysfs, describing block devices, network interfaces and CPUs.
+use alloc::{
+    collections::btree_map::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsOps};
+use spin::RwLock;
+
+use super::{
+    blkdev::{BlockDevice, block_devices, get_block_device},
+    fs::{FileSystem, FsType},
+    mount::{self, MountFlags},
+    proc::{
+        dir::{ProcDir, ProcDirOps},
+        file::ProcFile,
+    },
+};
+
+/// A directory with a fixed set of entries.
+struct StaticDir(BTreeMap<&'static str, VfsNodeRef>);
+
+impl StaticDir {
+    fn new(entries: impl IntoIterator<Item = (&'static str, VfsNodeRef)>) -> VfsNodeRef {
+        ProcDir::new(Self(entries.into_iter().collect()))
+    }
+}
+
+impl ProcDirOps for StaticDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        self.0.get(name).cloned()
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        self.0
+            .iter()
+            .map(|(name, node)| {
+                let ty = node.get_attr().map_or(VfsNodeType::File, |a| a.file_type());
+                (name.to_string(), ty)
+            })
+            .collect()
+    }
+}
+
+/// A file whose content is `value` followed by a newline.
+fn attr(value: impl Fn() -> String + Send + Sync + 'static) -> VfsNodeRef {
+    Arc::new(ProcFile::new(move || value() + "\n"))
+}
+
+fn constant(value: &'static str) -> VfsNodeRef {
+    attr(move || value.to_string())
+}
+
+/// A file showing `f` of the block device at `path`, which may be detached
+/// later.
+fn device_attr(path: &str, f: fn(&dyn BlockDevice) -> String) -> VfsNodeRef {
+    let path = path.to_string();
+    attr(move || get_block_device(&path).map_or_else(|_| String::new(), |dev| f(&*dev)))
+}
+
+/// A disk found by the drivers, which the kernel uses itself rather than
+/// through a [`BlockDevice`], shown in `/sys/block`.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskInfo {
+    /// The size in 512-byte sectors.
+    pub sectors: u64,
+}
+
+static DISKS: RwLock<BTreeMap<String, DiskInfo>> = RwLock::new(BTreeMap::new());
+
+/// Adds the disk `name` to `/sys/block`.
+pub fn register_disk(name: &str, disk: DiskInfo) {
+    DISKS.write().insert(name.to_string(), disk);
+}
+
+/// `/sys/block`, with one directory per disk and registered block device.
+struct BlockDir;
+
+impl ProcDirOps for BlockDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        if let Some(disk) = DISKS.read().get(name).copied() {
+            return Some(StaticDir::new([
+                ("size", attr(move || disk.sectors.to_string())),
+                ("ro", constant("0")),
+                ("removable", constant("0")),
+                (
+                    "queue",
+                    StaticDir::new([("logical_block_size", constant("512"))]),
+                ),
+            ]));
+        }
+        let path = format!("/dev/{}", name);
+        get_block_device(&path).ok()?;
+        Some(StaticDir::new([
+            (
+                "size",
+                // In 512-byte sectors regardless of the block size
+                device_attr(&path, |dev| {
+                    (dev.num_blocks() * dev.block_size() as u64 / 512).to_string()
+                }),
+            ),
+            (
+                "ro",
+                device_attr(&path, |dev| (dev.is_read_only() as u8).to_string()),
+            ),
+            ("removable", constant("0")),
+            (
+                "queue",
+                StaticDir::new([(
+                    "logical_block_size",
+                    device_attr(&path, |dev| dev.block_size().to_string()),
+                )]),
+            ),
+        ]))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let mut entries = DISKS
+            .read()
+            .keys()
+            .map(|name| (name.clone(), VfsNodeType::Dir))
+            .collect::<Vec<_>>();
+        entries.extend(
+            block_devices().into_iter().filter_map(|path| {
+                Some((path.strip_prefix("/dev/")?.to_string(), VfsNodeType::Dir))
+            }),
+        );
+        entries
+    }
+}
+
+/// A network interface shown in `/sys/class/net`.
+#[derive(Debug, Clone)]
+pub struct NetInterface {
+    /// The hardware address.
+    pub mac: [u8; 6],
+    /// The maximum transmission unit.
+    pub mtu: usize,
+}
+
+static NET_INTERFACES: RwLock<BTreeMap<String, NetInterface>> = RwLock::new(BTreeMap::new());
+
+/// Adds the network interface `name` to `/sys/class/net`.
+pub fn register_net_interface(name: &str, iface: NetInterface) {
+    NET_INTERFACES.write().insert(name.to_string(), iface);
+}
+
+/// `/sys/class/net`, with one directory per registered interface.
+struct NetDir;
+
+impl ProcDirOps for NetDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let iface = NET_INTERFACES.read().get(name)?.clone();
+        let mac = iface.mac;
+        let address = attr(move || {
+            mac.iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":")
+        });
+        let mtu = attr(move || iface.mtu.to_string());
+        Some(StaticDir::new([
+            ("address", address),
+            ("mtu", mtu),
+            ("operstate", constant("up")),
+        ]))
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        NET_INTERFACES
+            .read()
+            .keys()
+            .map(|name| (name.clone(), VfsNodeType::Dir))
+            .collect()
+    }
+}
+
+/// `/sys/devices/system/cpu`.
+struct CpuDir;
+
+/// The range of CPU IDs, in the list format of `/sys/devices/system/cpu`.
+fn cpu_range() -> String {
+    match axconfig::plat::CPU_NUM {
+        1 => "0".to_string(),
+        n => format!("0-{}", n - 1),
+    }
+}
+
+impl ProcDirOps for CpuDir {
+    fn lookup_child(&self, name: &str) -> Option<VfsNodeRef> {
+        match name {
+            "online" | "possible" | "present" => Some(attr(cpu_range)),
+            "kernel_max" => Some(attr(|| (axconfig::plat::CPU_NUM - 1).to_string())),
+            _ => {
+                let cpu = name.strip_prefix("cpu")?.parse::<usize>().ok()?;
+                (cpu < axconfig::plat::CPU_NUM).then(|| StaticDir::new([("online", constant("1"))]))
+            }
+        }
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let mut entries = (0..axconfig::plat::CPU_NUM)
+            .map(|cpu| (format!("cpu{}", cpu), VfsNodeType::Dir))
+            .collect::<Vec<_>>();
+        for name in ["kernel_max", "online", "possible", "present"] {
+            entries.push((name.to_string(), VfsNodeType::File));
+        }
+        entries
+    }
+}
+
+/// A sysfs instance. All instances show the same content.
+pub struct SysFileSystem {
+    root: VfsNodeRef,
+}
+
+impl SysFileSystem {
+    /// Creates a new sysfs instance.
+    pub fn new() -> Self {
+        let root = StaticDir::new([
+            ("block", ProcDir::new(BlockDir) as VfsNodeRef),
+            (
+                "class",
+                StaticDir::new([("net", ProcDir::new(NetDir) as _)]),
+            ),
+            (
+                "devices",
+                StaticDir::new([(
+                    "system",
+                    StaticDir::new([("cpu", ProcDir::new(CpuDir) as _)]),
+                )]),
+            ),
+            (
+                "kernel",
+                StaticDir::new([(
+                    "mm",
+                    StaticDir::new([(
+                        "transparent_hugepage",
+                        // Huge pages are only used when asked for by mmap
+                        StaticDir::new([
+                            ("enabled", constant("always madvise [never]")),
+                            (
+                                "defrag",
+                                constant("always defer defer+madvise madvise [never]"),
+                            ),
+                        ]),
+                    )]),
+                )]),
+            ),
+        ]);
+        Self { root }
+    }
+}
+
+impl Default for SysFileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsOps for SysFileSystem {
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+impl FileSystem for SysFileSystem {}
+
+const VIRTIO_MMIO_MAGIC: u32 = 0x7472_6976;
+const VIRTIO_ID_NET: u32 = 1;
+const VIRTIO_ID_BLOCK: u32 = 2;
+/// The offset of the device specific configuration in a VirtIO MMIO region.
+const VIRTIO_MMIO_CONFIG: usize = 0x100;
+
+const PCI_VENDOR_VIRTIO: u32 = 0x1af4;
+/// The PCI device ID of a modern VirtIO device is this plus its device ID.
+const PCI_DEVICE_VIRTIO_MODERN: u32 = 0x1040;
+/// The capability of a VirtIO PCI device locating its configuration.
+const PCI_CAP_VENDOR: u32 = 0x09;
+const VIRTIO_PCI_CAP_DEVICE_CFG: u32 = 4;
+
+/// Reads the 32-bit register at `addr`, a mapped device address.
+fn read_reg(addr: usize) -> u32 {
+    // SAFETY: the register is mapped, and reading it has no effects
+    unsafe { core::ptr::read_volatile(addr as *const u32) }
+}
+
+/// The VirtIO devices on the MMIO bus, as their device IDs and the
+/// addresses of their device specific configuration.
+fn virtio_mmio_devices() -> Vec<(u32, usize)> {
+    axconfig::devices::VIRTIO_MMIO_REGIONS
+        .iter()
+        .filter_map(|&(base, _)| {
+            let base = axhal::mem::phys_to_virt(base.into()).as_usize();
+            (read_reg(base) == VIRTIO_MMIO_MAGIC)
+                .then(|| (read_reg(base + 8), base + VIRTIO_MMIO_CONFIG))
+        })
+        .collect()
+}
+
+/// Returns the address of the memory `bar` of the PCI function whose
+/// configuration space is at `cfg`, or `None` if it is not assigned.
+fn pci_bar_address(cfg: usize, bar: u32) -> Option<usize> {
+    let reg = cfg + 0x10 + bar as usize * 4;
+    let low = read_reg(reg);
+    // I/O space
+    if low & 1 != 0 {
+        return None;
+    }
+    let mut addr = (low & !0xf) as u64;
+    // 64-bit
+    if (low >> 1) & 3 == 2 {
+        addr |= (read_reg(reg + 4) as u64) << 32;
+    }
+    (addr != 0).then(|| axhal::mem::phys_to_virt((addr as usize).into()).as_usize())
+}
+
+/// The modern VirtIO devices on the PCI bus, as [`virtio_mmio_devices`].
+///
+/// The drivers assign the base address registers as they probe the bus, so
+/// this only finds the devices after them.
+fn virtio_pci_devices() -> Vec<(u32, usize)> {
+    let ecam_base = axconfig::devices::PCI_ECAM_BASE;
+    if ecam_base == 0 {
+        return Vec::new();
+    }
+    let ecam = axhal::mem::phys_to_virt(ecam_base.into()).as_usize();
+    let mut devices = Vec::new();
+    for bus in 0..=axconfig::devices::PCI_BUS_END {
+        for device in 0..32 {
+            for function in 0..8 {
+                let cfg = ecam + (bus << 20 | device << 15 | function << 12);
+                let id = read_reg(cfg);
+                if id & 0xffff == 0xffff {
+                    // No function 0 means no device
+                    if function == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                if id & 0xffff == PCI_VENDOR_VIRTIO
+                    && let Some(virtio_id) = (id >> 16).checked_sub(PCI_DEVICE_VIRTIO_MODERN)
+                    && let Some(config) = virtio_pci_config(cfg)
+                {
+                    devices.push((virtio_id, config));
+                }
+                // Not a multi-function device
+                if function == 0 && (read_reg(cfg + 0xc) >> 16) & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+    }
+    devices
+}
+
+/// Returns the address of the device specific configuration of the VirtIO
+/// PCI function whose configuration space is at `cfg`.
+fn virtio_pci_config(cfg: usize) -> Option<usize> {
+    // No capability list
+    if (read_reg(cfg + 4) >> 16) & 0x10 == 0 {
+        return None;
+    }
+    let mut cap = (read_reg(cfg + 0x34) & 0xfc) as usize;
+    while cap != 0 {
+        let header = read_reg(cfg + cap);
+        if header & 0xff == PCI_CAP_VENDOR && (header >> 24) == VIRTIO_PCI_CAP_DEVICE_CFG {
+            let bar = read_reg(cfg + cap + 4) & 0xff;
+            let offset = read_reg(cfg + cap + 8) as usize;
+            return Some(pci_bar_address(cfg, bar)? + offset);
+        }
+        cap = ((header >> 8) & 0xfc) as usize;
+    }
+    None
+}
+
+/// The name of the `index`th disk with the prefix `prefix`, like Linux:
+/// `vda` to `vdz`, then `vdaa` to `vdzz`, then `vdaaa`...
+fn disk_name(prefix: &str, mut index: usize) -> String {
+    let mut suffix = Vec::new();
+    loop {
+        suffix.push(b'a' + (index % 26) as u8);
+        index /= 26;
+        if index == 0 {
+            break;
+        }
+        index -= 1;
+    }
+    suffix.reverse();
+    format!("{}{}", prefix, String::from_utf8_lossy(&suffix))
+}
+
+/// Registers the VirtIO disks and network cards, which the drivers own, as
+/// `vda`, `vdb`... and `eth0`, `eth1`...
+///
+/// The drivers keep no handle to the devices once probed, so the buses are
+/// walked again, in the order the drivers probe them, and the configuration
+/// space of the devices is read directly.
+fn register_virtio_devices() {
+    let (mut disks, mut nics) = (0, 0);
+    for (id, config) in virtio_mmio_devices()
+        .into_iter()
+        .chain(virtio_pci_devices())
+    {
+        match id {
+            VIRTIO_ID_BLOCK => {
+                // `capacity`, in 512-byte sectors
+                let sectors = read_reg(config) as u64 | ((read_reg(config + 4) as u64) << 32);
+                register_disk(&disk_name("vd", disks), DiskInfo { sectors });
+                disks += 1;
+            }
+            VIRTIO_ID_NET => {
+                let mut mac = [0; 6];
+                for (i, byte) in mac.iter_mut().enumerate() {
+                    // SAFETY: as in `read_reg`
+                    *byte = unsafe { core::ptr::read_volatile((config + i) as *const u8) };
+                }
+                register_net_interface(&format!("eth{}", nics), NetInterface { mac, mtu: 1500 });
+                nics += 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replaces the boot ramfs at `/sys` with a sysfs.
+pub(crate) fn init_sysfs() {
+    // The loopback interface has no hardware address
+    register_net_interface(
+        "lo",
+        NetInterface {
+            mac: [0; 6],
+            mtu: 65536,
+        },
+    );
+    register_virtio_devices();
+
+    if let Err(e) = axfs::api::umount("/sys") {
+        warn!("failed to unmount the boot ramfs at /sys: {:?}", e);
+    }
+    let flags = MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC;
+    if let Err(e) = mount::mount("sysfs", "/sys", FsType::Sysfs, flags, "") {
+        warn!("failed to mount sysfs: {:?}", e);
+    }
+}
@@ -8,8 +8,15 @@
 extern crate axlog;
 extern crate alloc;
 
+pub mod cpustat;
 pub mod file;
 pub mod futex;
 pub mod mm;
+pub mod random;
+pub mod sysctl;
 pub mod task;
-mod time;
+pub mod time;
+pub mod timer;
+
+/// The kernel release, reported by `uname` and `/proc/version`.
+pub const KERNEL_RELEASE: &str = "10.0.0";
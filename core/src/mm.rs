@@ -1,18 +1,93 @@
 //! User address space management.
 
-use core::ffi::CStr;
+use core::{cell::RefCell, ffi::CStr};
 
-use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use alloc::{borrow::ToOwned, collections::btree_map::BTreeMap, string::String, vec, vec::Vec};
 use axerrno::{AxError, AxResult};
 use axhal::{
     mem::virt_to_phys,
     paging::{MappingFlags, PageSize},
 };
 use axmm::{AddrSpace, kernel_aspace};
-use kernel_elf_parser::{AuxvEntry, ELFParser, app_stack_region};
-use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr};
+use kernel_elf_parser::{AuxvEntry, AuxvType, ELFParser, app_stack_region};
+use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr, VirtAddrRange};
+use page_table_multiarch::{GenericPTE, PageTable64, PagingHandler, PagingMetaData};
 use xmas_elf::{ElfFile, program::SegmentData};
 
+/// A user memory area whose content was loaded from a file.
+#[derive(Debug, Clone)]
+pub struct FileMapping {
+    /// The path of the file.
+    pub path: String,
+    /// The file offset of the start of the area.
+    pub offset: u64,
+    /// The end address of the area.
+    pub end: usize,
+}
+
+/// The files that user memory areas were loaded from, as shown in
+/// `/proc/<pid>/maps`. `AddrSpace` only knows the backing of the pages, so
+/// the loader and `mmap` record the files here.
+#[derive(Debug, Clone, Default)]
+pub struct FileMappings(BTreeMap<usize, FileMapping>);
+
+impl FileMappings {
+    /// Records that `[start, end)` was loaded from `path` at `offset`,
+    /// replacing the records of any overlapping areas.
+    pub fn insert(&mut self, start: usize, end: usize, path: String, offset: u64) {
+        self.remove(start, end);
+        self.0.insert(start, FileMapping { path, offset, end });
+    }
+
+    /// Forgets the files of `[start, end)`, keeping the parts of the records
+    /// outside the range.
+    pub fn remove(&mut self, start: usize, end: usize) {
+        let overlapping = self
+            .0
+            .range(..end)
+            .filter(|(_, m)| m.end > start)
+            .map(|(addr, _)| *addr)
+            .collect::<Vec<_>>();
+        for addr in overlapping {
+            let m = self.0.remove(&addr).unwrap();
+            if addr < start {
+                self.0.insert(
+                    addr,
+                    FileMapping {
+                        end: start,
+                        ..m.clone()
+                    },
+                );
+            }
+            if m.end > end {
+                let offset = m.offset + (end - addr) as u64;
+                self.0.insert(end, FileMapping { offset, ..m });
+            }
+        }
+    }
+
+    /// Forgets all records.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Returns the file and file offset that `addr` was loaded from.
+    pub fn lookup(&self, addr: usize) -> Option<(&str, u64)> {
+        let (start, m) = self.0.range(..=addr).next_back()?;
+        (m.end > addr).then(|| (m.path.as_str(), m.offset + (addr - start) as u64))
+    }
+}
+
+/// Checks whether a new mapping of `size` bytes is allowed by the
+/// `vm/overcommit_memory` policy.
+pub fn may_commit(size: usize) -> bool {
+    match crate::sysctl::OVERCOMMIT_MEMORY.get() {
+        1 => true,
+        2 => size <= axalloc::global_allocator().available_pages() * PAGE_SIZE_4K,
+        _ => size <= axconfig::plat::PHYS_MEMORY_SIZE,
+    }
+}
+
 /// Creates a new empty user address space.
 pub fn new_user_aspace_empty() -> AxResult<AddrSpace> {
     AddrSpace::new_empty(
@@ -51,10 +126,17 @@ pub fn map_trampoline(aspace: &mut AddrSpace) -> AxResult {
 /// # Arguments
 /// - `uspace`: The address space of the user app.
 /// - `elf`: The elf file.
+/// - `path`: The path of the elf file.
+/// - `mappings`: Records the segments as loaded from `path`.
 ///
 /// # Returns
 /// - The entry point of the user app.
-fn map_elf(uspace: &mut AddrSpace, elf: &ElfFile) -> AxResult<(VirtAddr, [AuxvEntry; 17])> {
+fn map_elf(
+    uspace: &mut AddrSpace,
+    elf: &ElfFile,
+    path: &str,
+    mappings: &mut FileMappings,
+) -> AxResult<(VirtAddr, [AuxvEntry; 17])> {
     let uspace_base = uspace.base().as_usize();
     let elf_parser = ELFParser::new(
         elf,
@@ -88,6 +170,13 @@ fn map_elf(uspace: &mut AddrSpace, elf: &ElfFile) -> AxResult<(VirtAddr, [AuxvEn
             .get(segement.offset..segement.offset + segement.filesz as usize)
             .ok_or(AxError::InvalidData)?;
         uspace.write(segement.vaddr, PageSize::Size4K, seg_data)?;
+        let seg_start = segement.vaddr.align_down_4k().as_usize();
+        mappings.insert(
+            seg_start,
+            seg_start + seg_align_size,
+            path.to_owned(),
+            (segement.offset - seg_pad) as u64,
+        );
         // TDOO: flush the I-cache
     }
 
@@ -103,6 +192,7 @@ fn map_elf(uspace: &mut AddrSpace, elf: &ElfFile) -> AxResult<(VirtAddr, [AuxvEn
 /// - `uspace`: The address space of the user app.
 /// - `args`: The arguments of the user app. The first argument is the path of the user app.
 /// - `envs`: The environment variables of the user app.
+/// - `mappings`: Records the files the memory areas are loaded from.
 ///
 /// # Returns
 /// - The entry point of the user app.
@@ -112,6 +202,7 @@ pub fn load_user_app(
     path: &str,
     args: &[String],
     envs: &[String],
+    mappings: &mut FileMappings,
 ) -> AxResult<(VirtAddr, VirtAddr)> {
     if args.is_empty() {
         return Err(AxError::InvalidInput);
@@ -127,7 +218,7 @@ pub fn load_user_app(
             .map(|s| s.trim_ascii().to_owned())
             .chain(args.iter().cloned())
             .collect();
-        return load_user_app(uspace, &new_args[0], &new_args, envs);
+        return load_user_app(uspace, &new_args[0], &new_args, envs, mappings);
     }
     let elf = ElfFile::new(&file_data).map_err(|_| AxError::InvalidData)?;
 
@@ -160,10 +251,11 @@ pub fn load_user_app(
         // Set the first argument to the path of the user app.
         let mut new_args = vec![interp_path];
         new_args.extend_from_slice(args);
-        return load_user_app(uspace, &new_args[0], &new_args, envs);
+        return load_user_app(uspace, &new_args[0], &new_args, envs, mappings);
     }
 
-    let (entry, mut auxv) = map_elf(uspace, &elf)?;
+    let real_path = axfs::api::canonicalize(path)?;
+    let (entry, mut auxv) = map_elf(uspace, &elf, &real_path, mappings)?;
     // The user stack is divided into two parts:
     // `ustack_start` -> `ustack_pointer`: It is the stack space that users actually read and write.
     // `ustack_pointer` -> `ustack_end`: It is the space that contains the arguments, environment variables and auxv passed to the app.
@@ -199,9 +291,76 @@ pub fn load_user_app(
 
     uspace.write(user_sp, PageSize::Size4K, stack_data.as_slice())?;
 
+    // The stack builder leaves fixed bytes at AT_RANDOM, which libc uses for
+    // the stack protector and pointer guard canaries
+    if let Some(entry) = auxv.iter().find(|e| e.get_type() == AuxvType::RANDOM) {
+        let mut bytes = [0u8; 16];
+        crate::random::fill_bytes(&mut bytes);
+        uspace.write(VirtAddr::from(entry.value()), PageSize::Size4K, &bytes)?;
+    }
+
     Ok((entry, user_sp))
 }
 
+/// Returns the size of the user memory mapped in `aspace`, in bytes.
+pub fn mapped_size(aspace: &AddrSpace) -> usize {
+    aspace
+        .areas()
+        .iter()
+        .filter(|area| area.flags().contains(MappingFlags::USER))
+        .map(|area| area.size())
+        .sum()
+}
+
+/// Returns the size of the user memory of `aspace` present in its page
+/// table, in bytes.
+pub fn resident_size(aspace: &AddrSpace) -> usize {
+    let ranges = aspace
+        .areas()
+        .iter()
+        .filter(|area| area.flags().contains(MappingFlags::USER))
+        .map(|area| area.va_range())
+        .collect::<Vec<_>>();
+    resident_sizes(aspace, &ranges).into_iter().sum()
+}
+
+/// Returns the size of the memory present in the page table of `aspace` in
+/// each of `ranges`, which are sorted and disjoint, in bytes.
+///
+/// The page table is walked once, so that unmapped parts cost one entry per
+/// table rather than one query per page.
+pub fn resident_sizes(aspace: &AddrSpace, ranges: &[VirtAddrRange]) -> Vec<usize> {
+    fn walk<M: PagingMetaData, PTE: GenericPTE, H: PagingHandler>(
+        page_table: &PageTable64<M, PTE, H>,
+        ranges: &[VirtAddrRange],
+    ) -> Vec<usize> {
+        let sizes = RefCell::new(vec![0; ranges.len()]);
+        let count_leaf = |level: usize, _: usize, vaddr: VirtAddr, entry: &PTE| {
+            if level + 1 < M::LEVELS && !entry.is_huge() {
+                return;
+            }
+            let page = VirtAddrRange::from_start_size(
+                vaddr,
+                PAGE_SIZE_4K << (9 * (M::LEVELS - 1 - level)),
+            );
+            let first = ranges.partition_point(|range| range.end <= page.start);
+            for (i, range) in ranges.iter().enumerate().skip(first) {
+                if range.start >= page.end {
+                    break;
+                }
+                let overlap =
+                    range.end.min(page.end).as_usize() - range.start.max(page.start).as_usize();
+                sizes.borrow_mut()[i] += overlap;
+            }
+        };
+        if let Err(e) = page_table.walk(usize::MAX, Some(&count_leaf), None) {
+            warn!("failed to walk the page table: {:?}", e);
+        }
+        sizes.into_inner()
+    }
+    walk(aspace.page_table(), ranges)
+}
+
 #[percpu::def_percpu]
 static mut ACCESSING_USER_MEM: bool = false;
 
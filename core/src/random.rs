@@ -0,0 +1,308 @@
+//! The kernel random number generator.
+//!
+//! Entropy from timer jitter, interrupt timing, the RTC and hardware RNG
+//! instructions is mixed into a 256-bit key, which seeds a ChaCha20 stream.
+//! The key is replaced after every request, so earlier output cannot be
+//! recovered from the state.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use axhal::time::{monotonic_time_nanos, wall_time};
+use axtask::WaitQueue;
+use spin::Mutex;
+
+use crate::task::block_current;
+
+/// Bits of entropy needed before output is considered unpredictable.
+const SEED_BITS: usize = 256;
+
+/// "expand 32-byte k"
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// Computes one ChaCha20 block.
+fn chacha20_block(key: &[u32; 8], counter: u64, nonce: u64) -> [u32; 16] {
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&SIGMA);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter as u32;
+    input[13] = (counter >> 32) as u32;
+    input[14] = nonce as u32;
+    input[15] = (nonce >> 32) as u32;
+
+    let mut state = input;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    for (s, i) in state.iter_mut().zip(input) {
+        *s = s.wrapping_add(i);
+    }
+    state
+}
+
+struct Rng {
+    key: [u32; 8],
+    /// Distinguishes the blocks used for mixing from each other.
+    mix_counter: u64,
+    entropy_bits: usize,
+}
+
+/// The nonce of blocks that mix input into the key, so that they never
+/// coincide with output blocks.
+const MIX_NONCE: u64 = u64::MAX;
+
+impl Rng {
+    const fn new() -> Self {
+        Self {
+            key: [0; 8],
+            mix_counter: 0,
+            entropy_bits: 0,
+        }
+    }
+
+    fn mix(&mut self, data: &[u8], bits: usize) {
+        for chunk in data.chunks(32) {
+            for (i, byte) in chunk.iter().enumerate() {
+                self.key[i / 4] ^= (*byte as u32) << (8 * (i % 4));
+            }
+            let block = chacha20_block(&self.key, self.mix_counter, MIX_NONCE);
+            self.mix_counter += 1;
+            self.key.copy_from_slice(&block[..8]);
+        }
+        self.entropy_bits = (self.entropy_bits + bits).min(SEED_BITS);
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        let mut counter = 0;
+        for chunk in buf.chunks_mut(64) {
+            let block = chacha20_block(&self.key, counter, 0);
+            counter += 1;
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = (block[i / 4] >> (8 * (i % 4))) as u8;
+            }
+        }
+        // Fast key erasure
+        let block = chacha20_block(&self.key, counter, 0);
+        self.key.copy_from_slice(&block[..8]);
+    }
+}
+
+static RNG: Mutex<Rng> = Mutex::new(Rng::new());
+static SEEDED: AtomicBool = AtomicBool::new(false);
+static SEEDED_WQ: WaitQueue = WaitQueue::new();
+
+/// Mixes `data` into the pool, crediting it with `bits` of entropy.
+pub fn add_entropy(data: &[u8], bits: usize) {
+    let mut rng = RNG.lock();
+    rng.mix(data, bits);
+    check_seeded(&rng);
+}
+
+fn check_seeded(rng: &Rng) {
+    if rng.entropy_bits >= SEED_BITS && !SEEDED.swap(true, Ordering::Release) {
+        info!("random: crng initialized");
+        SEEDED_WQ.notify_all(false);
+    }
+}
+
+static FAST_POOL: AtomicU64 = AtomicU64::new(0);
+static FAST_POOL_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Mixes the time of an interrupt into the pool.
+///
+/// Timestamps are folded into a small pool that is mixed into the key every
+/// 64 events, crediting one bit. Called from trap handling, so it never
+/// waits for the lock.
+pub fn add_interrupt_randomness() {
+    let now = monotonic_time_nanos();
+    let pool = FAST_POOL.load(Ordering::Relaxed).rotate_left(7) ^ now;
+    FAST_POOL.store(pool, Ordering::Relaxed);
+    if FAST_POOL_EVENTS.fetch_add(1, Ordering::Relaxed) % 64 != 63 {
+        return;
+    }
+    if let Some(mut rng) = RNG.try_lock() {
+        rng.mix(&pool.to_le_bytes(), 1);
+        check_seeded(&rng);
+    }
+}
+
+/// Returns whether enough entropy has been collected.
+pub fn is_seeded() -> bool {
+    SEEDED.load(Ordering::Acquire)
+}
+
+/// Waits until enough entropy has been collected.
+pub fn wait_until_seeded() {
+    if !is_seeded() {
+        block_current(|| SEEDED_WQ.wait_until(is_seeded));
+    }
+}
+
+/// Fills `buf` with random bytes, whether or not the pool is seeded.
+///
+/// `buf` may be user memory, which can fault, so it is filled through a
+/// kernel buffer a chunk at a time, copying out with the lock released.
+pub fn fill_bytes(buf: &mut [u8]) {
+    let mut block = [0u8; 256];
+    for dst in buf.chunks_mut(block.len()) {
+        let src = &mut block[..dst.len()];
+        RNG.lock().fill(src);
+        dst.copy_from_slice(src);
+    }
+    // Don't leave output on the stack
+    block.fill(0);
+}
+
+/// Reads a 64-bit value from the hardware RNG, if the CPU has one, with the
+/// bits of entropy to credit it with.
+fn hardware_random() -> Option<(u64, usize)> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use core::arch::x86_64::{__cpuid, _rdrand64_step};
+        // CPUID.01H:ECX.RDRAND[bit 30]
+        if unsafe { __cpuid(1) }.ecx & (1 << 30) == 0 {
+            return None;
+        }
+        let mut value = 0;
+        // RDRAND may transiently fail, Intel recommends 10 retries
+        for _ in 0..10 {
+            if unsafe { rdrand(&mut value) } {
+                return Some((value, 64));
+            }
+        }
+        #[target_feature(enable = "rdrand")]
+        unsafe fn rdrand(value: &mut u64) -> bool {
+            unsafe { _rdrand64_step(value) == 1 }
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let isar0: u64;
+        unsafe { core::arch::asm!("mrs {}, id_aa64isar0_el1", out(reg) isar0) };
+        // ID_AA64ISAR0_EL1.RNDR[63:60]
+        if isar0 >> 60 == 0 {
+            return None;
+        }
+        // RNDR fails, setting Z, when the generator cannot return a value
+        // in reasonable time, so retry like RDRAND
+        for _ in 0..10 {
+            let (value, ok): (u64, u64);
+            unsafe {
+                core::arch::asm!(
+                    "mrs {value}, s3_3_c2_c4_0",
+                    "cset {ok}, ne",
+                    value = out(reg) value,
+                    ok = out(reg) ok,
+                    options(nomem, nostack),
+                )
+            };
+            if ok != 0 {
+                return Some((value, 64));
+            }
+        }
+    }
+    // Accessing the seed CSR traps unless the firmware grants it, which can't
+    // be probed without trapping, so it is only used by kernels built for Zkr
+    #[cfg(all(target_arch = "riscv64", target_feature = "zkr"))]
+    {
+        let mut value = 0u64;
+        let mut samples = 0;
+        // A sample holds 16 bits when OPST is ES16, and the source may need
+        // time to gather more (WAIT) or be testing itself (BIST)
+        for _ in 0..1000 {
+            let seed: u64;
+            unsafe {
+                core::arch::asm!("csrrw {}, seed, x0", out(reg) seed, options(nomem, nostack))
+            };
+            match (seed >> 30) & 3 {
+                0b10 => {
+                    value = (value << 16) | (seed & 0xffff);
+                    samples += 1;
+                    if samples == 4 {
+                        // The samples are raw noise rather than conditioned
+                        // output, so credit half their bits
+                        return Some((value, 32));
+                    }
+                }
+                0b11 => return None,
+                _ => core::hint::spin_loop(),
+            }
+        }
+    }
+    None
+}
+
+/// Reads the timer in a busy loop, returning its deltas folded together and
+/// how many of them passed the stuck test of jitterentropy, i.e. have
+/// nonzero first, second and third derivatives.
+///
+/// Cache and pipeline effects make the deltas vary unpredictably in the low
+/// bits, unless the timer is too coarse to see them.
+fn timer_jitter() -> (u64, usize) {
+    let mut acc = 0u64;
+    let mut unstuck = 0;
+    let (mut last, mut last_delta, mut last_delta2) = (monotonic_time_nanos(), 0u64, 0u64);
+    for i in 0..64 {
+        let now = monotonic_time_nanos();
+        let delta = now.wrapping_sub(last);
+        let delta2 = delta.wrapping_sub(last_delta);
+        let delta3 = delta2.wrapping_sub(last_delta2);
+        if delta != 0 && delta2 != 0 && delta3 != 0 {
+            unstuck += 1;
+        }
+        acc = acc.rotate_left(7) ^ delta.wrapping_mul(i + 1);
+        (last, last_delta, last_delta2) = (now, delta, delta2);
+        core::hint::spin_loop();
+    }
+    (acc, unstuck)
+}
+
+/// The deltas of the timer credited with one bit of entropy.
+const DELTAS_PER_BIT: usize = 32;
+/// The number of jitter samples to take at most, so that a timer too coarse
+/// to show jitter doesn't hang the boot.
+const MAX_JITTER_SAMPLES: usize = 4096;
+
+/// Seeds the pool during boot.
+pub fn init() {
+    let rtc = wall_time().as_nanos() as u64;
+    add_entropy(&rtc.to_le_bytes(), 0);
+
+    for _ in 0..4 {
+        if let Some((value, bits)) = hardware_random() {
+            add_entropy(&value.to_le_bytes(), bits);
+        }
+    }
+    // Make up for a missing or partly credited hardware RNG
+    let mut unstuck = 0;
+    for _ in 0..MAX_JITTER_SAMPLES {
+        if is_seeded() {
+            return;
+        }
+        let (value, count) = timer_jitter();
+        // Credit the bits completed by this sample
+        let bits = (unstuck + count) / DELTAS_PER_BIT - unstuck / DELTAS_PER_BIT;
+        unstuck += count;
+        add_entropy(&value.to_le_bytes(), bits);
+    }
+    if !is_seeded() {
+        warn!("random: the timer shows too little jitter, waiting for interrupts to seed");
+    }
+}
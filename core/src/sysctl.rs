@@ -0,0 +1,189 @@
+//! Runtime kernel tunables, exposed as files under `/proc/sys`.
+//!
+//! A tunable is registered under a slash-separated path such as
+//! `kernel/hostname` with [`register`], and read by its owner through the
+//! typed static it is declared as.
+
+use alloc::{
+    borrow::Cow,
+    collections::btree_map::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axerrno::{AxError, AxResult};
+use spin::RwLock;
+
+use crate::file::AX_FILE_LIMIT;
+
+/// A value that can be read and written as text through `/proc/sys`.
+pub trait Tunable: Send + Sync {
+    /// Formats the value, without the trailing newline.
+    fn read(&self) -> String;
+
+    /// Parses and stores `value`, which has surrounding whitespace removed.
+    fn write(&self, value: &str) -> AxResult;
+}
+
+/// An integer tunable bounded to `min..=max`.
+pub struct IntTunable {
+    value: AtomicUsize,
+    min: usize,
+    max: usize,
+    on_change: Option<fn(usize)>,
+}
+
+impl IntTunable {
+    /// Creates a tunable with the initial `value` and the inclusive bounds.
+    pub const fn new(value: usize, min: usize, max: usize) -> Self {
+        Self {
+            value: AtomicUsize::new(value),
+            min,
+            max,
+            on_change: None,
+        }
+    }
+
+    /// Calls `f` with the new value after every successful write.
+    pub const fn on_change(mut self, f: fn(usize)) -> Self {
+        self.on_change = Some(f);
+        self
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> usize {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl Tunable for IntTunable {
+    fn read(&self) -> String {
+        self.get().to_string()
+    }
+
+    fn write(&self, value: &str) -> AxResult {
+        let value = value.parse().map_err(|_| AxError::InvalidInput)?;
+        if !(self.min..=self.max).contains(&value) {
+            return Err(AxError::InvalidInput);
+        }
+        self.value.store(value, Ordering::Relaxed);
+        if let Some(f) = self.on_change {
+            f(value);
+        }
+        Ok(())
+    }
+}
+
+/// A string tunable of at most `max_len` bytes.
+pub struct StrTunable {
+    value: RwLock<Cow<'static, str>>,
+    max_len: usize,
+}
+
+impl StrTunable {
+    /// Creates a tunable with the initial `value`.
+    pub const fn new(value: &'static str, max_len: usize) -> Self {
+        Self {
+            value: RwLock::new(Cow::Borrowed(value)),
+            max_len,
+        }
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> String {
+        self.value.read().to_string()
+    }
+}
+
+impl Tunable for StrTunable {
+    fn read(&self) -> String {
+        self.get()
+    }
+
+    fn write(&self, value: &str) -> AxResult {
+        if value.len() > self.max_len {
+            return Err(AxError::InvalidInput);
+        }
+        *self.value.write() = Cow::Owned(value.to_string());
+        Ok(())
+    }
+}
+
+/// `kernel/printk`: the console log level, followed by the fixed default,
+/// minimum and boot-time levels like Linux.
+struct Printk(IntTunable);
+
+impl Tunable for Printk {
+    fn read(&self) -> String {
+        format!("{}\t4\t1\t7", self.0.get())
+    }
+
+    fn write(&self, value: &str) -> AxResult {
+        // Only the console level can be changed
+        let level = value.split_whitespace().next().unwrap_or_default();
+        self.0.write(level)
+    }
+}
+
+/// Maps a console log level to the `axlog` level that prints the same
+/// messages.
+fn set_console_level(level: usize) {
+    axlog::set_max_level(match level {
+        0 => "off",
+        1..=4 => "error",
+        5..=6 => "warn",
+        7 => "info",
+        8 => "debug",
+        _ => "trace",
+    });
+}
+
+/// `kernel/hostname`, reported by `uname`.
+pub static HOSTNAME: StrTunable = StrTunable::new("Starry - machine[0]", 64);
+/// `kernel/pid_max`, one more than the largest process ID. The default and
+/// the limit are Linux's.
+pub static PID_MAX: IntTunable = IntTunable::new(32768, 301, 1 << 22);
+/// `fs/file-max`, the number of file descriptors open in the whole system,
+/// by default enough for 64 processes with full tables.
+pub static FILE_MAX: IntTunable = IntTunable::new(AX_FILE_LIMIT * 64, 0, usize::MAX);
+/// `fs/pipe-max-size`, the largest capacity `F_SETPIPE_SZ` gives a pipe, in
+/// bytes.
+pub static PIPE_MAX_SIZE: IntTunable = IntTunable::new(1 << 20, 4096, 1 << 31);
+/// `vm/overcommit_memory`: 0 refuses mappings larger than physical memory, 1
+/// never refuses and 2 refuses mappings larger than free memory.
+pub static OVERCOMMIT_MEMORY: IntTunable = IntTunable::new(0, 0, 2);
+static PRINTK: Printk = Printk(IntTunable::new(7, 0, 15).on_change(set_console_level));
+
+static TUNABLES: RwLock<BTreeMap<String, &'static dyn Tunable>> = RwLock::new(BTreeMap::new());
+
+/// Exposes `tunable` as `/proc/sys/<path>`.
+pub fn register(path: &str, tunable: &'static dyn Tunable) {
+    TUNABLES.write().insert(path.to_string(), tunable);
+}
+
+/// Finds the tunable registered at `path`.
+pub fn lookup(path: &str) -> Option<&'static dyn Tunable> {
+    TUNABLES.read().get(path).copied()
+}
+
+/// Lists the registered paths that start with `prefix`.
+pub fn paths_with_prefix(prefix: &str) -> Vec<String> {
+    TUNABLES
+        .read()
+        .keys()
+        .filter(|path| path.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
+/// Registers the tunables of the core kernel.
+pub fn init() {
+    register("kernel/hostname", &HOSTNAME);
+    register("kernel/pid_max", &PID_MAX);
+    register("kernel/printk", &PRINTK);
+    register("fs/file-max", &FILE_MAX);
+    register("fs/pipe-max-size", &PIPE_MAX_SIZE);
+    register("vm/overcommit_memory", &OVERCOMMIT_MEMORY);
+}
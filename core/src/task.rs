@@ -2,21 +2,19 @@
 
 use core::{
     alloc::Layout,
-    cell::RefCell,
-    sync::atomic::{AtomicUsize, Ordering},
+    ops::AddAssign,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
     time::Duration,
 };
 
 use alloc::{
+    boxed::Box,
     string::String,
     sync::{Arc, Weak},
     vec::Vec,
 };
 use axerrno::{LinuxError, LinuxResult};
-use axhal::{
-    arch::UspaceContext,
-    time::{NANOS_PER_MICROS, NANOS_PER_SEC, monotonic_time_nanos},
-};
+use axhal::{arch::UspaceContext, time::TimeValue};
 use axmm::{AddrSpace, kernel_aspace};
 use axns::{AxNamespace, AxNamespaceIf};
 use axprocess::{Pid, Process, ProcessGroup, Session, Thread};
@@ -25,12 +23,17 @@ use axsignal::{
     api::{ProcessSignalManager, SignalActions, ThreadSignalManager},
 };
 use axsync::{Mutex, RawMutex};
-use axtask::{TaskExtRef, TaskInner, WaitQueue, current};
+use axtask::{AxTaskRef, TaskExtRef, TaskInner, WaitQueue, WeakAxTaskRef, current};
 use memory_addr::VirtAddrRange;
-use spin::{Once, RwLock};
+use spin::{Mutex as SpinMutex, Once, RwLock};
 use weak_map::WeakMap;
 
-use crate::{futex::FutexTable, time::TimeStat};
+use crate::{
+    cpustat,
+    futex::FutexTable,
+    mm::{FileMappings, resident_size},
+    time::{IntervalTimers, PosixTimers, TimeStat},
+};
 
 /// Create a new user task.
 pub fn new_user_task(
@@ -42,7 +45,7 @@ pub fn new_user_task(
         move || {
             let curr = axtask::current();
             if let Some(tid) = set_child_tid {
-                *tid = curr.id().as_u64() as Pid;
+                *tid = curr.task_ext().thread.tid();
             }
 
             let kstack_top = curr.kernel_stack_top().unwrap();
@@ -61,8 +64,6 @@ pub fn new_user_task(
 
 /// Task extended data for the monolithic kernel.
 pub struct TaskExt {
-    /// The time statistics
-    pub time: RefCell<TimeStat>,
     /// The thread
     pub thread: Arc<Thread>,
 }
@@ -70,22 +71,7 @@ pub struct TaskExt {
 impl TaskExt {
     /// Create a new [`TaskExt`].
     pub fn new(thread: Arc<Thread>) -> Self {
-        Self {
-            time: RefCell::new(TimeStat::new()),
-            thread,
-        }
-    }
-
-    pub(crate) fn time_stat_from_kernel_to_user(&self, current_tick: usize) {
-        self.time.borrow_mut().switch_into_user_mode(current_tick);
-    }
-
-    pub(crate) fn time_stat_from_user_to_kernel(&self, current_tick: usize) {
-        self.time.borrow_mut().switch_into_kernel_mode(current_tick);
-    }
-
-    pub(crate) fn time_stat_output(&self) -> (usize, usize) {
-        self.time.borrow().output()
+        Self { thread }
     }
 
     /// Get the [`ThreadData`] associated with this task.
@@ -103,30 +89,114 @@ axtask::def_task_ext!(TaskExt);
 
 /// Update the time statistics to reflect a switch from kernel mode to user mode.
 pub fn time_stat_from_kernel_to_user() {
-    let curr_task = current();
-    curr_task
-        .task_ext()
-        .time_stat_from_kernel_to_user(monotonic_time_nanos() as usize);
+    cpustat::account_trap(false, true);
 }
 
 /// Update the time statistics to reflect a switch from user mode to kernel mode.
 pub fn time_stat_from_user_to_kernel() {
-    let curr_task = current();
-    curr_task
-        .task_ext()
-        .time_stat_from_user_to_kernel(monotonic_time_nanos() as usize);
-}
-
-/// Get the time statistics for the current task.
-pub fn time_stat_output() -> (usize, usize, usize, usize) {
-    let curr_task = current();
-    let (utime_ns, stime_ns) = curr_task.task_ext().time_stat_output();
-    (
-        utime_ns / NANOS_PER_SEC as usize,
-        utime_ns / NANOS_PER_MICROS as usize,
-        stime_ns / NANOS_PER_SEC as usize,
-        stime_ns / NANOS_PER_MICROS as usize,
-    )
+    cpustat::account_trap(true, false);
+}
+
+/// Brings the times of the current task up to date, charging the time spent
+/// in the kernel since the last trap.
+pub fn update_time_stat() {
+    cpustat::account_trap(false, false);
+}
+
+/// Returns the TID of the current thread, or 0 for a kernel task.
+pub fn current_tid() -> Pid {
+    let curr = current();
+    // SAFETY: only checks whether the task has extended data
+    if unsafe { curr.task_ext_ptr() }.is_null() {
+        return 0;
+    }
+    curr.task_ext().thread.tid()
+}
+
+/// Finds the thread `tid` without waiting for the thread table, for use in
+/// interrupt context.
+fn try_get_thread(tid: Pid) -> Option<Arc<Thread>> {
+    if tid == current_tid() {
+        return Some(current().task_ext().thread.clone());
+    }
+    THREAD_TABLE.try_read()?.get(&tid)
+}
+
+/// Charges `delta_ns` of CPU time to the thread `tid`, as user time if
+/// `from_user`.
+///
+/// [`cpustat`] works out which thread ran from the accounting points on
+/// the CPU, so that time spent sleeping or waiting to run is not charged.
+pub(crate) fn charge_thread(tid: Pid, from_user: bool, delta_ns: u64) {
+    let Some(thread) = try_get_thread(tid) else {
+        return;
+    };
+    let Some(data) = thread.data::<ThreadData>() else {
+        return;
+    };
+    // An interrupt may arrive while the task reads its own times, and the
+    // time is then dropped like a missed tick
+    let Some(mut time) = data.time.try_lock() else {
+        return;
+    };
+    if from_user {
+        time.add_user_time(delta_ns as usize);
+    } else {
+        time.add_kernel_time(delta_ns as usize);
+    }
+    drop(time);
+    if let Some(proc_data) = thread.process().data::<ProcessData>() {
+        proc_data.itimers.charge(from_user, delta_ns);
+    }
+}
+
+/// Counts a context switch of the thread `tid`, a voluntary one if
+/// `voluntary`.
+pub(crate) fn count_switch(tid: Pid, voluntary: bool) {
+    let Some(thread) = try_get_thread(tid) else {
+        return;
+    };
+    let Some(data) = thread.data::<ThreadData>() else {
+        return;
+    };
+    if let Some(mut counters) = data.counters.try_lock() {
+        if voluntary {
+            counters.nvcsw += 1;
+        } else {
+            counters.nivcsw += 1;
+        }
+    }
+}
+
+/// Runs `wait`, which blocks the current thread, as a voluntary context
+/// switch.
+///
+/// The time until the thread runs again is not charged to it, and the
+/// switch counts towards `ru_nvcsw`. Kernel tasks just run `wait`.
+pub fn block_current<R>(wait: impl FnOnce() -> R) -> R {
+    let tid = current_tid();
+    if tid == 0 {
+        return wait();
+    }
+    let curr = current();
+    let data = curr.task_ext().thread_data();
+    data.sleeping.store(true, Ordering::Release);
+    cpustat::account_switch_out();
+    let result = wait();
+    cpustat::account_switch_in();
+    data.sleeping.store(false, Ordering::Release);
+    count_switch(tid, true);
+    result
+}
+
+/// Attaches `thread` to `task` and starts running it.
+pub fn spawn_user_task(mut task: TaskInner, thread: Arc<Thread>) -> AxTaskRef {
+    task.init_task_ext(TaskExt::new(thread.clone()));
+    let task = axtask::spawn_task(task);
+    if let Some(data) = thread.data::<ThreadData>() {
+        data.task.call_once(|| Arc::downgrade(&task));
+    }
+    task
 }
 
 #[doc(hidden)]
@@ -139,9 +209,9 @@ impl Default for WaitQueueWrapper {
 impl axsignal::api::WaitQueue for WaitQueueWrapper {
     fn wait_timeout(&self, timeout: Option<Duration>) -> bool {
         if let Some(timeout) = timeout {
-            self.0.wait_timeout(timeout)
+            block_current(|| self.0.wait_timeout(timeout))
         } else {
-            self.0.wait();
+            block_current(|| self.0.wait());
             true
         }
     }
@@ -151,6 +221,18 @@ impl axsignal::api::WaitQueue for WaitQueueWrapper {
     }
 }
 
+/// How a syscall interrupted by a signal continues once the signal is
+/// handled, like the `-ERESTART*` codes of Linux.
+pub enum SyscallRestart {
+    /// Restarted unless a handler without `SA_RESTART` runs (`ERESTARTSYS`)
+    Sys,
+    /// Restarted only if no handler runs (`ERESTARTNOHAND`)
+    NoHand,
+    /// Continued by `restart_syscall` with the function if no handler runs
+    /// (`ERESTART_RESTARTBLOCK`)
+    Block(Box<dyn FnOnce() -> LinuxResult<isize> + Send>),
+}
+
 /// Extended data for [`Thread`].
 pub struct ThreadData {
     /// The clear thread tid field
@@ -162,6 +244,23 @@ pub struct ThreadData {
 
     /// The thread-level signal manager
     pub signal: ThreadSignalManager<RawMutex, WaitQueueWrapper>,
+
+    /// The time statistics
+    pub time: SpinMutex<TimeStat>,
+    /// The page faults and context switches of the thread
+    pub counters: SpinMutex<ResourceUsage>,
+    /// How the syscall a signal interrupted continues
+    pub restart: SpinMutex<Option<SyscallRestart>>,
+    /// The address of the wait queue the thread sleeps on interruptibly,
+    /// which is notified when a signal is sent to it
+    interrupt_wq: SpinMutex<usize>,
+    /// How many times a signal was sent to the thread, which waits that
+    /// can't check for signals under their queue lock compare instead
+    interrupts: AtomicUsize,
+    /// The task running the thread, set once it is spawned
+    task: Once<WeakAxTaskRef>,
+    /// Whether the thread is blocked in [`block_current`]
+    sleeping: AtomicBool,
 }
 
 impl ThreadData {
@@ -172,9 +271,76 @@ impl ThreadData {
             clear_child_tid: AtomicUsize::new(0),
 
             signal: ThreadSignalManager::new(proc.signal.clone()),
+
+            time: SpinMutex::new(TimeStat::new()),
+            counters: SpinMutex::new(ResourceUsage::default()),
+            restart: SpinMutex::new(None),
+            interrupt_wq: SpinMutex::new(0),
+            interrupts: AtomicUsize::new(0),
+            task: Once::new(),
+            sleeping: AtomicBool::new(false),
         }
     }
 
+    /// Returns the name of the task running the thread, which is set by
+    /// `execve` and `PR_SET_NAME`.
+    pub fn name(&self) -> String {
+        self.task
+            .get()
+            .and_then(Weak::upgrade)
+            .map(|task| String::from(task.name()))
+            .unwrap_or_default()
+    }
+
+    /// Whether the thread is blocked waiting for an event, rather than
+    /// running or ready to run.
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping.load(Ordering::Acquire)
+    }
+
+    /// Sets the wait queue the thread is about to sleep on interruptibly, or
+    /// clears it with `None` once awake. It must be cleared before `wq` goes
+    /// away.
+    pub fn set_interrupt_wq(&self, wq: Option<&WaitQueue>) {
+        *self.interrupt_wq.lock() = wq.map_or(0, |wq| wq as *const WaitQueue as usize);
+    }
+
+    /// Wakes the thread if it sleeps interruptibly, to notice a signal sent
+    /// to it.
+    pub fn interrupt(&self) {
+        self.interrupts.fetch_add(1, Ordering::AcqRel);
+        // The lock is only held to set or clear the queue, either before
+        // the thread checks for signals or once it is awake, so it is
+        // fine to skip, which also makes this safe in interrupt context
+        if let Some(wq) = self.interrupt_wq.try_lock()
+            && *wq != 0
+        {
+            // SAFETY: the queue lives until cleared, which the lock excludes
+            unsafe { &*(*wq as *const WaitQueue) }.notify_all(false);
+        }
+    }
+
+    /// Returns how many times a signal was sent to the thread. A change
+    /// after checking for signals means one may be pending.
+    pub fn interrupt_count(&self) -> usize {
+        self.interrupts.load(Ordering::Acquire)
+    }
+
+    /// Returns the resource usage of the thread.
+    pub fn usage(&self) -> ResourceUsage {
+        let (utime_ns, stime_ns) = self.time.lock().output();
+        let mut usage = *self.counters.lock();
+        usage.utime_ns = utime_ns;
+        usage.stime_ns = stime_ns;
+        usage
+    }
+
+    /// Returns the user and kernel time of the thread together.
+    pub fn cpu_time(&self) -> TimeValue {
+        let (utime_ns, stime_ns) = self.time.lock().output();
+        TimeValue::from_nanos((utime_ns + stime_ns) as u64)
+    }
+
     /// Get the clear child tid field.
     pub fn clear_child_tid(&self) -> usize {
         self.clear_child_tid.load(Ordering::Relaxed)
@@ -191,8 +357,15 @@ impl ThreadData {
 pub struct ProcessData {
     /// The executable path
     pub exe_path: RwLock<String>,
+    /// The command line arguments, as shown in `/proc/<pid>/cmdline`
+    pub cmdline: RwLock<Vec<String>>,
+    /// The initial environment, as shown in `/proc/<pid>/environ`
+    pub environ: RwLock<Vec<String>>,
     /// The virtual memory address space.
     pub aspace: Arc<Mutex<AddrSpace>>,
+    /// The files the memory areas of `aspace` were loaded from. Shared by
+    /// the processes sharing `aspace`.
+    pub file_mappings: Arc<Mutex<FileMappings>>,
     /// The resource namespace
     pub ns: AxNamespace,
     /// The user heap bottom
@@ -207,9 +380,22 @@ pub struct ProcessData {
 
     /// The process signal manager
     pub signal: Arc<ProcessSignalManager<RawMutex, WaitQueueWrapper>>,
+    /// The job control state
+    pub job: JobControl,
+    /// The usage of the exited threads
+    pub usage: Mutex<ResourceUsage>,
+    /// The usage of the reaped children and their own reaped descendants
+    pub children_usage: Mutex<ResourceUsage>,
+    /// The interval timers set by `setitimer`
+    pub itimers: IntervalTimers,
+    /// The POSIX timers created by `timer_create`
+    pub posix_timers: PosixTimers,
 
     /// The futex table.
     pub futex_table: FutexTable,
+
+    /// The monotonic time the process was created at
+    pub start_time: TimeValue,
 }
 
 impl ProcessData {
@@ -217,12 +403,16 @@ impl ProcessData {
     pub fn new(
         exe_path: String,
         aspace: Arc<Mutex<AddrSpace>>,
+        file_mappings: Arc<Mutex<FileMappings>>,
         signal_actions: Arc<Mutex<SignalActions>>,
         exit_signal: Option<Signo>,
     ) -> Self {
         Self {
             exe_path: RwLock::new(exe_path),
+            cmdline: RwLock::new(Vec::new()),
+            environ: RwLock::new(Vec::new()),
             aspace,
+            file_mappings,
             ns: AxNamespace::new_thread_local(),
             heap_bottom: AtomicUsize::new(axconfig::plat::USER_HEAP_BASE),
             heap_top: AtomicUsize::new(axconfig::plat::USER_HEAP_BASE),
@@ -234,11 +424,24 @@ impl ProcessData {
                 signal_actions,
                 axconfig::plat::SIGNAL_TRAMPOLINE,
             )),
+            job: JobControl::new(),
+            usage: Mutex::new(ResourceUsage::default()),
+            children_usage: Mutex::new(ResourceUsage::default()),
+            itimers: IntervalTimers::new(),
+            posix_timers: PosixTimers::new(),
 
             futex_table: FutexTable::new(),
+
+            start_time: axhal::time::monotonic_time(),
         }
     }
 
+    /// Record the command line and environment of a newly loaded program.
+    pub fn set_command_line(&self, args: &[String], envs: &[String]) {
+        *self.cmdline.write() = args.to_vec();
+        *self.environ.write() = envs.to_vec();
+    }
+
     /// Get the bottom address of the user heap.
     pub fn get_heap_bottom(&self) -> usize {
         self.heap_bottom.load(Ordering::Acquire)
@@ -259,6 +462,15 @@ impl ProcessData {
         self.heap_top.store(top, Ordering::Release)
     }
 
+    /// Records the current resident set size for `maxrss`, returning the
+    /// largest size recorded.
+    pub fn sample_rss(&self) -> usize {
+        let rss = resident_size(&self.aspace.lock());
+        let mut usage = self.usage.lock();
+        usage.maxrss = usage.maxrss.max(rss);
+        usage.maxrss
+    }
+
     /// Linux manual: A "clone" child is one which delivers no signal, or a
     /// signal other than SIGCHLD to its parent upon termination.
     pub fn is_clone_child(&self) -> bool {
@@ -266,6 +478,178 @@ impl ProcessData {
     }
 }
 
+/// Resource usage counters, as reported by `wait4` and `getrusage`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResourceUsage {
+    /// The time spent in user mode, in nanoseconds.
+    pub utime_ns: usize,
+    /// The time spent in kernel mode, in nanoseconds.
+    pub stime_ns: usize,
+    /// The page faults handled without I/O.
+    pub minflt: usize,
+    /// The page faults that needed I/O.
+    pub majflt: usize,
+    /// The voluntary context switches.
+    pub nvcsw: usize,
+    /// The involuntary context switches.
+    pub nivcsw: usize,
+    /// The largest resident set size, in bytes.
+    pub maxrss: usize,
+}
+
+impl AddAssign for ResourceUsage {
+    /// Adds up the counters, keeping the larger `maxrss` like Linux does for
+    /// the totals of children.
+    fn add_assign(&mut self, other: Self) {
+        self.utime_ns += other.utime_ns;
+        self.stime_ns += other.stime_ns;
+        self.minflt += other.minflt;
+        self.majflt += other.majflt;
+        self.nvcsw += other.nvcsw;
+        self.nivcsw += other.nivcsw;
+        self.maxrss = self.maxrss.max(other.maxrss);
+    }
+}
+
+/// Returns the resource usage of `process`: that of its exited threads and
+/// of its live ones.
+///
+/// The resident set size is sampled now, so `maxrss` is the largest size
+/// seen when the usage was queried.
+pub fn process_usage(process: &Process) -> ResourceUsage {
+    let Some(data) = process.data::<ProcessData>() else {
+        return ResourceUsage::default();
+    };
+    let mut usage = *data.usage.lock();
+    for thread in process.threads() {
+        if let Some(thread) = thread.data::<ThreadData>() {
+            usage += thread.usage();
+        }
+    }
+    if !process.is_zombie() {
+        usage.maxrss = data.sample_rss();
+    }
+    usage
+}
+
+/// Returns the user and kernel time of all threads of `process` together,
+/// without the cost of sampling the rest of [`process_usage`].
+pub fn process_cpu_time(process: &Process) -> TimeValue {
+    let Some(data) = process.data::<ProcessData>() else {
+        return TimeValue::ZERO;
+    };
+    let exited = {
+        let usage = data.usage.lock();
+        TimeValue::from_nanos((usage.utime_ns + usage.stime_ns) as u64)
+    };
+    process
+        .threads()
+        .iter()
+        .filter_map(|thread| thread.data::<ThreadData>())
+        .fold(exited, |time, thread| time + thread.cpu_time())
+}
+
+/// How a process terminated, encoded as its wait status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Exited with the code.
+    Exited(u8),
+    /// Killed by the signal, dumping core if the flag is set.
+    Signaled(Signo, bool),
+}
+
+impl ExitStatus {
+    /// Returns the wait status, like `WIFEXITED` and `WIFSIGNALED` expect.
+    pub fn wait_status(self) -> i32 {
+        match self {
+            Self::Exited(code) => (code as i32) << 8,
+            Self::Signaled(signo, core_dumped) => signo as i32 | ((core_dumped as i32) << 7),
+        }
+    }
+}
+
+/// A job control state change of a process, waiting to be reported to its
+/// parent by `waitpid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobEvent {
+    /// Stopped by the signal.
+    Stopped(Signo),
+    /// Continued by `SIGCONT`.
+    Continued,
+}
+
+impl JobEvent {
+    /// Returns the wait status, like `WIFSTOPPED` and `WIFCONTINUED` expect.
+    pub fn wait_status(self) -> i32 {
+        match self {
+            Self::Stopped(signo) => ((signo as i32) << 8) | 0x7f,
+            Self::Continued => 0xffff,
+        }
+    }
+}
+
+/// Whether a process is stopped by a signal, and the last change of that
+/// state.
+pub struct JobControl {
+    stopped: AtomicBool,
+    event: Mutex<Option<JobEvent>>,
+    /// The threads of the stopped process.
+    resume_wq: WaitQueue,
+}
+
+impl JobControl {
+    fn new() -> Self {
+        Self {
+            stopped: AtomicBool::new(false),
+            event: Mutex::new(None),
+            resume_wq: WaitQueue::new(),
+        }
+    }
+
+    /// Whether the process is stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    /// Stops the process by `signo`. Returns `false` if it was stopped
+    /// already.
+    pub fn stop(&self, signo: Signo) -> bool {
+        if self.stopped.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        *self.event.lock() = Some(JobEvent::Stopped(signo));
+        true
+    }
+
+    /// Resumes the process, recording the change for the parent if `report`.
+    /// Returns `false` if it was not stopped.
+    pub fn resume(&self, report: bool) -> bool {
+        if !self.stopped.swap(false, Ordering::AcqRel) {
+            return false;
+        }
+        *self.event.lock() = report.then_some(JobEvent::Continued);
+        self.resume_wq.notify_all(false);
+        true
+    }
+
+    /// Blocks the calling thread while the process is stopped.
+    pub fn wait_while_stopped(&self) {
+        if self.is_stopped() {
+            block_current(|| self.resume_wq.wait_until(|| !self.is_stopped()));
+        }
+    }
+
+    /// Returns the change not yet reported to the parent.
+    pub fn event(&self) -> Option<JobEvent> {
+        *self.event.lock()
+    }
+
+    /// Marks the change as reported.
+    pub fn clear_event(&self) {
+        *self.event.lock() = None;
+    }
+}
+
 impl Drop for ProcessData {
     fn drop(&mut self) {
         if !cfg!(target_arch = "aarch64") && !cfg!(target_arch = "loongarch64") {
@@ -305,6 +689,42 @@ static THREAD_TABLE: RwLock<WeakMap<Pid, Weak<Thread>>> = RwLock::new(WeakMap::n
 static PROCESS_TABLE: RwLock<WeakMap<Pid, Weak<Process>>> = RwLock::new(WeakMap::new());
 static PROCESS_GROUP_TABLE: RwLock<WeakMap<Pid, Weak<ProcessGroup>>> = RwLock::new(WeakMap::new());
 static SESSION_TABLE: RwLock<WeakMap<Pid, Weak<Session>>> = RwLock::new(WeakMap::new());
+static PROCESS_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// The last allocated PID, from which the search for a free one continues.
+static LAST_PID: SpinMutex<Pid> = SpinMutex::new(0);
+
+/// PIDs below this are not reused after wrapping around, like Linux's
+/// `RESERVED_PIDS`.
+const RESERVED_PIDS: Pid = 300;
+
+fn pid_in_use(pid: Pid) -> bool {
+    THREAD_TABLE.read().contains_key(&pid)
+        || PROCESS_TABLE.read().contains_key(&pid)
+        || PROCESS_GROUP_TABLE.read().contains_key(&pid)
+        || SESSION_TABLE.read().contains_key(&pid)
+}
+
+/// Allocates an ID for a new thread, the next one after the last allocated
+/// which no thread, process, process group or session holds.
+///
+/// IDs wrap around at `kernel/pid_max`, failing with `EAGAIN` if all are in
+/// use.
+pub fn alloc_pid() -> LinuxResult<Pid> {
+    let pid_max = crate::sysctl::PID_MAX.get() as Pid;
+    let mut last = LAST_PID.lock();
+    let mut pid = *last;
+    for _ in RESERVED_PIDS..pid_max {
+        pid += 1;
+        if pid >= pid_max {
+            pid = RESERVED_PIDS;
+        }
+        if !pid_in_use(pid) {
+            *last = pid;
+            return Ok(pid);
+        }
+    }
+    Err(LinuxError::EAGAIN)
+}
 
 /// Add the thread and possibly its process, process group and session to the
 /// corresponding tables.
@@ -318,13 +738,19 @@ pub fn add_thread_to_table(thread: &Arc<Thread>) {
         return;
     }
     process_table.insert(process.pid(), process);
+    PROCESS_COUNT.fetch_add(1, Ordering::Relaxed);
 
+    add_process_group_to_table(&process.group());
+}
+
+/// Add a process group and possibly its session to the corresponding tables,
+/// for groups created after their first process, by `setpgid` or `setsid`.
+pub fn add_process_group_to_table(process_group: &Arc<ProcessGroup>) {
     let mut process_group_table = PROCESS_GROUP_TABLE.write();
-    let process_group = process.group();
     if process_group_table.contains_key(&process_group.pgid()) {
         return;
     }
-    process_group_table.insert(process_group.pgid(), &process_group);
+    process_group_table.insert(process_group.pgid(), process_group);
 
     let mut session_table = SESSION_TABLE.write();
     let session = process_group.session();
@@ -334,11 +760,32 @@ pub fn add_thread_to_table(thread: &Arc<Thread>) {
     session_table.insert(session.sid(), &session);
 }
 
+/// Returns the number of processes created since boot.
+pub fn process_count() -> usize {
+    PROCESS_COUNT.load(Ordering::Relaxed)
+}
+
 /// Lists all processes.
 pub fn processes() -> Vec<Arc<Process>> {
     PROCESS_TABLE.read().values().collect()
 }
 
+/// Returns the number of threads running or ready to run, or `None` if the
+/// thread table is busy, which only happens in interrupt context.
+pub fn nr_running() -> Option<usize> {
+    let table = THREAD_TABLE.try_read()?;
+    Some(
+        table
+            .values()
+            .filter(|thread| {
+                thread
+                    .data::<ThreadData>()
+                    .is_some_and(|data| !data.is_sleeping())
+            })
+            .count(),
+    )
+}
+
 /// Finds the thread with the given TID.
 pub fn get_thread(tid: Pid) -> LinuxResult<Arc<Thread>> {
     THREAD_TABLE.read().get(&tid).ok_or(LinuxError::ESRCH)
@@ -1,125 +1,410 @@
+//! The settable wall clock, CPU time accounting of tasks, and the interval
+//! and POSIX timers of processes.
+
+use alloc::collections::btree_map::BTreeMap;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+
+use axhal::time::{NANOS_PER_SEC, TimeValue, monotonic_time_nanos, wall_time};
+use axprocess::Pid;
+use axsignal::Signo;
+use axsync::Mutex;
+use spin::Mutex as SpinMutex;
+
+use crate::timer::TimerId;
+
+/// The rate offsets are slewed at by `adjtimex`, in parts per million, like
+/// `adjtime` on Linux.
+const SLEW_PPM: i128 = 500;
+
+/// `CLOCK_REALTIME`, as adjustments on top of the hardware wall time.
+struct WallClock {
+    /// The total of the steps, in nanoseconds
+    step_ns: i128,
+    /// The monotonic time the slew in progress started at, in nanoseconds
+    slew_start: u64,
+    /// The offset the slew in progress applies in total, in nanoseconds
+    slew_ns: i128,
+}
+
+impl WallClock {
+    /// Returns the part of the slew applied by `now`.
+    fn slewed(&self, now: u64) -> i128 {
+        let max = (now.saturating_sub(self.slew_start) as i128) * SLEW_PPM / 1_000_000;
+        self.slew_ns.clamp(-max, max)
+    }
+
+    /// Returns the time of the clock at the monotonic time `now`, in
+    /// nanoseconds.
+    fn read(&self, now: u64) -> i128 {
+        wall_time().as_nanos() as i128 + self.step_ns + self.slewed(now)
+    }
+}
+
+static WALL_CLOCK: SpinMutex<WallClock> = SpinMutex::new(WallClock {
+    step_ns: 0,
+    slew_start: 0,
+    slew_ns: 0,
+});
+
+fn to_time_value(nanos: i128) -> TimeValue {
+    TimeValue::from_nanos(nanos.clamp(0, u64::MAX as i128) as u64)
+}
+
+/// Returns the time of `CLOCK_REALTIME`, which may be set unlike the wall
+/// time of the hardware.
+pub fn realtime() -> TimeValue {
+    to_time_value(WALL_CLOCK.lock().read(monotonic_time_nanos()))
+}
+
+/// Steps `CLOCK_REALTIME` by `delta_ns`, dropping the slew in progress.
+pub fn step_realtime(delta_ns: i128) {
+    let now = monotonic_time_nanos();
+    let mut clock = WALL_CLOCK.lock();
+    clock.step_ns += clock.slewed(now) + delta_ns;
+    clock.slew_start = now;
+    clock.slew_ns = 0;
+}
+
+/// Sets `CLOCK_REALTIME` to `time`, returning by how many nanoseconds it
+/// jumped.
+pub fn set_realtime(time: TimeValue) -> i128 {
+    let now = monotonic_time_nanos();
+    let mut clock = WALL_CLOCK.lock();
+    let delta = time.as_nanos() as i128 - clock.read(now);
+    clock.step_ns += clock.slewed(now) + delta;
+    clock.slew_start = now;
+    clock.slew_ns = 0;
+    delta
+}
+
+/// Starts slewing `CLOCK_REALTIME` by `offset_ns`, replacing the slew in
+/// progress.
+pub fn slew_realtime(offset_ns: i128) {
+    let now = monotonic_time_nanos();
+    let mut clock = WALL_CLOCK.lock();
+    clock.step_ns += clock.slewed(now);
+    clock.slew_start = now;
+    clock.slew_ns = offset_ns;
+}
+
+/// Returns the offset the slew in progress has yet to apply, in nanoseconds.
+pub fn realtime_slew_left() -> i128 {
+    let clock = WALL_CLOCK.lock();
+    clock.slew_ns - clock.slewed(monotonic_time_nanos())
+}
+
+/// `USER_HZ`, the unit of `clock_t` values such as the times of `times()`
+/// and `/proc/stat`.
+pub const USER_HZ: u64 = 100;
+
+/// Converts nanoseconds to `USER_HZ` clock ticks.
+pub fn clock_ticks(nanos: u64) -> u64 {
+    nanos * USER_HZ / NANOS_PER_SEC
+}
+
 numeric_enum_macro::numeric_enum! {
     #[repr(i32)]
     #[allow(non_camel_case_types)]
     #[derive(Eq, PartialEq, Debug, Clone, Copy)]
+    /// The interval timers of a process, as the `which` of `setitimer`.
     pub enum TimerType {
-    /// 表示目前没有任何计时器(不在linux规范中，是os自己规定的)
-    NONE = -1,
-    /// 统计系统实际运行时间
+    /// Counts down in wall-clock time, delivering `SIGALRM`
     REAL = 0,
-    /// 统计用户态运行时间
+    /// Counts down in user time, delivering `SIGVTALRM`
     VIRTUAL = 1,
-    /// 统计进程的所有用户态/内核态运行时间
+    /// Counts down in user and system time, delivering `SIGPROF`
     PROF = 2,
     }
 }
 
-impl From<usize> for TimerType {
-    fn from(num: usize) -> Self {
-        match Self::try_from(num as i32) {
-            Ok(val) => val,
-            Err(_) => Self::NONE,
-        }
-    }
-}
-
+/// The user and kernel time of a task.
+#[derive(Default)]
 pub struct TimeStat {
     utime_ns: usize,
     stime_ns: usize,
-    user_timestamp: usize,
-    kernel_timestamp: usize,
-    timer_type: TimerType,
-    timer_interval_ns: usize,
-    timer_remained_ns: usize,
 }
 
-impl Default for TimeStat {
+impl TimeStat {
+    /// Creates statistics with no time spent.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the user and kernel time, in nanoseconds.
+    pub fn output(&self) -> (usize, usize) {
+        (self.utime_ns, self.stime_ns)
+    }
+
+    /// Charges `delta` nanoseconds spent running in user mode.
+    pub fn add_user_time(&mut self, delta: usize) {
+        self.utime_ns += delta;
+    }
+
+    /// Charges `delta` nanoseconds spent running in the kernel.
+    pub fn add_kernel_time(&mut self, delta: usize) {
+        self.stime_ns += delta;
+    }
+}
+
+/// The setting of an interval timer: the time until it expires, zero if
+/// disarmed, and the interval it is then reloaded with, zero for a one-shot
+/// timer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ITimerValue {
+    /// The reload interval
+    pub interval: TimeValue,
+    /// The time left until expiration
+    pub value: TimeValue,
+}
+
+/// The state of `ITIMER_REAL`, which runs on a kernel timer.
+#[derive(Default)]
+pub struct RealTimer {
+    /// The monotonic time it expires at, if armed
+    pub deadline: Option<TimeValue>,
+    /// The reload interval
+    pub interval: TimeValue,
+    /// The kernel timer that fires it
+    pub timer: Option<TimerId>,
+}
+
+/// The interval timers of a process, shared by its threads.
+pub struct IntervalTimers {
+    /// `ITIMER_REAL`
+    pub real: Mutex<RealTimer>,
+    /// `ITIMER_VIRTUAL` and `ITIMER_PROF`
+    cpu: SpinMutex<[ITimerValue; 2]>,
+    /// The CPU timers which expired but whose signal is not sent yet, as a
+    /// bit mask of [`TimerType`]s
+    expired: AtomicU32,
+}
+
+impl Default for IntervalTimers {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl TimeStat {
+impl IntervalTimers {
+    /// Creates the timers of a new process, all disarmed.
     pub fn new() -> Self {
         Self {
-            utime_ns: 0,
-            stime_ns: 0,
-            user_timestamp: 0,
-            kernel_timestamp: 0,
-            timer_type: TimerType::NONE,
-            timer_interval_ns: 0,
-            timer_remained_ns: 0,
+            real: Mutex::new(RealTimer::default()),
+            cpu: SpinMutex::new([ITimerValue::default(); 2]),
+            expired: AtomicU32::new(0),
         }
     }
 
-    pub fn output(&self) -> (usize, usize) {
-        (self.utime_ns, self.stime_ns)
+    fn cpu_index(which: TimerType) -> usize {
+        match which {
+            TimerType::VIRTUAL => 0,
+            TimerType::PROF => 1,
+            TimerType::REAL => panic!("ITIMER_REAL is not a CPU timer"),
+        }
     }
 
-    pub fn reset(&mut self, current_timestamp: usize) {
-        self.utime_ns = 0;
-        self.stime_ns = 0;
-        self.user_timestamp = 0;
-        self.kernel_timestamp = current_timestamp;
+    /// Returns the setting of the CPU timer `which`.
+    pub fn cpu_timer(&self, which: TimerType) -> ITimerValue {
+        self.cpu.lock()[Self::cpu_index(which)]
     }
 
-    pub fn switch_into_kernel_mode(&mut self, current_timestamp: usize) {
-        let now_time_ns = current_timestamp;
-        let delta = now_time_ns - self.kernel_timestamp;
-        self.utime_ns += delta;
-        self.kernel_timestamp = now_time_ns;
-        if self.timer_type != TimerType::NONE {
-            self.update_timer(delta);
-        };
+    /// Sets the CPU timer `which`, returning its previous setting.
+    pub fn set_cpu_timer(&self, which: TimerType, new: ITimerValue) -> ITimerValue {
+        let old = core::mem::replace(&mut self.cpu.lock()[Self::cpu_index(which)], new);
+        self.expired
+            .fetch_and(!(1 << which as u32), Ordering::Relaxed);
+        old
     }
 
-    pub fn switch_into_user_mode(&mut self, current_timestamp: usize) {
-        let now_time_ns = current_timestamp;
-        let delta = now_time_ns - self.kernel_timestamp;
-        self.stime_ns += delta;
-        self.user_timestamp = now_time_ns;
-        if self.timer_type == TimerType::REAL || self.timer_type == TimerType::PROF {
-            self.update_timer(delta);
+    /// Counts `delta` nanoseconds of CPU time down on the CPU timers, user
+    /// time if `user`. Called from interrupt context.
+    pub(crate) fn charge(&self, user: bool, delta: u64) {
+        // Dropped like a missed tick if a thread is setting the timers
+        let Some(mut timers) = self.cpu.try_lock() else {
+            return;
+        };
+        let delta = TimeValue::from_nanos(delta);
+        for which in [TimerType::VIRTUAL, TimerType::PROF] {
+            if which == TimerType::VIRTUAL && !user {
+                continue;
+            }
+            let timer = &mut timers[Self::cpu_index(which)];
+            if timer.value.is_zero() {
+                continue;
+            }
+            if timer.value > delta {
+                timer.value -= delta;
+                continue;
+            }
+            // Carry the overshoot over to the next period, like Linux
+            let overshoot = delta - timer.value;
+            timer.value = if timer.interval.is_zero() {
+                TimeValue::ZERO
+            } else if overshoot < timer.interval {
+                timer.interval - overshoot
+            } else {
+                timer.interval
+            };
+            self.expired.fetch_or(1 << which as u32, Ordering::Relaxed);
         }
     }
 
-    pub fn switch_from_old_task(&mut self, current_timestamp: usize) {
-        let now_time_ns = current_timestamp;
-        let delta = now_time_ns - self.kernel_timestamp;
-        self.stime_ns += delta;
-        self.kernel_timestamp = now_time_ns;
-        if self.timer_type == TimerType::REAL || self.timer_type == TimerType::PROF {
-            self.update_timer(delta);
+    /// Returns whether the CPU timer `which` expired since the last call.
+    pub fn take_expired(&self, which: TimerType) -> bool {
+        let bit = 1 << which as u32;
+        self.expired.load(Ordering::Relaxed) & bit != 0
+            && self.expired.fetch_and(!bit, Ordering::Relaxed) & bit != 0
+    }
+}
+
+/// A clock selected by a `clockid_t`, which POSIX timers measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerClock {
+    /// `CLOCK_REALTIME` and its variants
+    Realtime,
+    /// `CLOCK_MONOTONIC`, `CLOCK_BOOTTIME` and their variants
+    Monotonic,
+    /// The CPU time of a process
+    ProcessCpu(Pid),
+    /// The CPU time of a thread
+    ThreadCpu(Pid),
+}
+
+impl TimerClock {
+    /// Returns whether the clock counts CPU time, so that the timer is
+    /// checked as time is charged rather than run by a kernel timer.
+    pub fn is_cpu(&self) -> bool {
+        matches!(self, Self::ProcessCpu(_) | Self::ThreadCpu(_))
+    }
+}
+
+/// How a POSIX timer notifies its expirations, from `struct sigevent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerNotify {
+    /// `SIGEV_NONE`: nothing is sent
+    None,
+    /// `SIGEV_SIGNAL`: `signo` is sent to the process, carrying `value`
+    Process {
+        /// The signal
+        signo: Signo,
+        /// The `sigev_value`
+        value: usize,
+    },
+    /// `SIGEV_THREAD_ID`: `signo` is sent to the thread `tid`
+    Thread {
+        /// The signal
+        signo: Signo,
+        /// The `sigev_value`
+        value: usize,
+        /// The target thread, in the same process
+        tid: Pid,
+    },
+}
+
+/// A POSIX timer created by `timer_create`.
+#[derive(Debug)]
+pub struct PosixTimer {
+    /// The clock it measures
+    pub clock: TimerClock,
+    /// How its expirations are notified
+    pub notify: TimerNotify,
+    /// The reload interval
+    pub interval: TimeValue,
+    /// The time of `clock` it expires at, if armed
+    pub expires: Option<TimeValue>,
+    /// Whether it was set to an absolute time, so that it follows changes
+    /// of `CLOCK_REALTIME` instead of keeping its remaining time
+    pub absolute: bool,
+    /// The expirations not notified since the last signal was queued
+    pub pending_overrun: u32,
+    /// The overrun count of the last signal, for `timer_getoverrun`
+    pub overrun: u32,
+    /// The kernel timer firing it, for the timers of wall clocks
+    pub timer: Option<TimerId>,
+}
+
+impl PosixTimer {
+    /// Creates a disarmed timer.
+    pub fn new(clock: TimerClock, notify: TimerNotify) -> Self {
+        Self {
+            clock,
+            notify,
+            interval: TimeValue::ZERO,
+            expires: None,
+            absolute: false,
+            pending_overrun: 0,
+            overrun: 0,
+            timer: None,
         }
     }
 
-    pub fn switch_to_new_task(&mut self, current_timestamp: usize) {
-        let now_time_ns = current_timestamp;
-        let delta = now_time_ns - self.kernel_timestamp;
-        self.kernel_timestamp = now_time_ns;
-        if self.timer_type == TimerType::REAL {
-            self.update_timer(delta);
+    /// Advances the timer past `now`, the current time of its clock, when it
+    /// expired, returning the number of expirations.
+    pub fn advance(&mut self, now: TimeValue) -> u32 {
+        let Some(expires) = self.expires.filter(|expires| *expires <= now) else {
+            return 0;
+        };
+        if self.interval.is_zero() {
+            self.expires = None;
+            return 1;
         }
+        let periods = (now - expires).as_nanos() / self.interval.as_nanos() + 1;
+        let advance = self.interval.as_nanos() * periods;
+        self.expires = Some(expires + TimeValue::from_nanos(advance as u64));
+        periods.min(i32::MAX as u128) as u32
     }
+}
 
-    pub fn set_timer(
-        &mut self,
-        timer_interval_ns: usize,
-        timer_remained_ns: usize,
-        timer_type: usize,
-    ) -> bool {
-        self.timer_type = timer_type.into();
-        self.timer_interval_ns = timer_interval_ns;
-        self.timer_remained_ns = timer_remained_ns;
-        self.timer_type != TimerType::NONE
+/// The POSIX timers of a process, shared by its threads.
+pub struct PosixTimers {
+    /// The timers by id
+    pub table: Mutex<BTreeMap<i32, PosixTimer>>,
+    next_id: AtomicI32,
+    /// Whether a timer of a CPU clock is armed, to skip checking them on
+    /// every trap otherwise
+    cpu_armed: AtomicBool,
+}
+
+impl Default for PosixTimers {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn update_timer(&mut self, delta: usize) {
-        if self.timer_remained_ns == 0 {
-            return;
+impl PosixTimers {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self {
+            table: Mutex::new(BTreeMap::new()),
+            next_id: AtomicI32::new(0),
+            cpu_armed: AtomicBool::new(false),
         }
-        if self.timer_remained_ns > delta {
-            self.timer_remained_ns -= delta;
+    }
+
+    /// Adds a timer, returning its id.
+    pub fn insert(&self, timer: PosixTimer) -> i32 {
+        let mut table = self.table.lock();
+        // Ids are allocated incrementally like Linux, wrapping around
+        let mut id = self.next_id.load(Ordering::Relaxed);
+        while table.contains_key(&id) {
+            id = id.checked_add(1).unwrap_or(0);
         }
+        self.next_id
+            .store(id.checked_add(1).unwrap_or(0), Ordering::Relaxed);
+        table.insert(id, timer);
+        id
+    }
+
+    /// Returns whether a timer of a CPU clock may be armed.
+    pub fn cpu_armed(&self) -> bool {
+        self.cpu_armed.load(Ordering::Relaxed)
+    }
+
+    /// Recomputes [`Self::cpu_armed`] after the timers in `table` changed.
+    pub fn update_cpu_armed(&self, table: &BTreeMap<i32, PosixTimer>) {
+        let armed = table
+            .values()
+            .any(|timer| timer.clock.is_cpu() && timer.expires.is_some());
+        self.cpu_armed.store(armed, Ordering::Relaxed);
     }
 }
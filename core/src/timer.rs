@@ -0,0 +1,96 @@
+//! Kernel timers, running a callback once the monotonic clock reaches a
+//! deadline.
+//!
+//! Callbacks run in a dedicated kernel task rather than in interrupt context,
+//! so they may take locks and send signals.
+
+use alloc::{boxed::Box, collections::btree_map::BTreeMap};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axhal::time::{TimeValue, monotonic_time};
+use axtask::WaitQueue;
+use spin::Mutex;
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// Identifies a timer set by [`set_timer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimerId(u64);
+
+struct TimerList {
+    next_id: u64,
+    /// The callbacks, ordered by deadline
+    events: BTreeMap<(TimeValue, TimerId), Callback>,
+    /// The deadline of each timer, to cancel it by id
+    deadlines: BTreeMap<TimerId, TimeValue>,
+}
+
+static TIMERS: Mutex<TimerList> = Mutex::new(TimerList {
+    next_id: 0,
+    events: BTreeMap::new(),
+    deadlines: BTreeMap::new(),
+});
+/// Bumped when a timer is set, to wake the timer task up early.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static TIMER_WQ: WaitQueue = WaitQueue::new();
+
+/// Runs `callback` once the monotonic clock reaches `deadline`.
+pub fn set_timer(deadline: TimeValue, callback: impl FnOnce() + Send + 'static) -> TimerId {
+    let id = {
+        let mut timers = TIMERS.lock();
+        let id = TimerId(timers.next_id);
+        timers.next_id += 1;
+        timers.events.insert((deadline, id), Box::new(callback));
+        timers.deadlines.insert(id, deadline);
+        id
+    };
+    GENERATION.fetch_add(1, Ordering::Release);
+    TIMER_WQ.notify_one(false);
+    id
+}
+
+/// Cancels a timer, returning whether it had not run yet.
+pub fn cancel_timer(id: TimerId) -> bool {
+    let mut timers = TIMERS.lock();
+    let Some(deadline) = timers.deadlines.remove(&id) else {
+        return false;
+    };
+    timers.events.remove(&(deadline, id));
+    true
+}
+
+fn run_timers() {
+    loop {
+        let generation = GENERATION.load(Ordering::Acquire);
+        let now = monotonic_time();
+        let next = {
+            let mut timers = TIMERS.lock();
+            match timers.events.first_key_value() {
+                Some((&(deadline, id), _)) if deadline <= now => {
+                    timers.deadlines.remove(&id);
+                    timers.events.pop_first().map(|(_, callback)| callback)
+                }
+                Some((&(deadline, _), _)) => {
+                    drop(timers);
+                    TIMER_WQ.wait_timeout_until(deadline - now, || {
+                        GENERATION.load(Ordering::Acquire) != generation
+                    });
+                    None
+                }
+                None => {
+                    drop(timers);
+                    TIMER_WQ.wait_until(|| GENERATION.load(Ordering::Acquire) != generation);
+                    None
+                }
+            }
+        };
+        if let Some(callback) = next {
+            callback();
+        }
+    }
+}
+
+/// Starts the task running the timers.
+pub fn init() {
+    axtask::spawn(run_timers);
+}
@@ -1,13 +1,15 @@
 use alloc::{string::String, sync::Arc};
 use axfs::{CURRENT_DIR, CURRENT_DIR_PATH, api::set_current_dir};
 use axhal::arch::UspaceContext;
-use axprocess::{Pid, init_proc};
+use axprocess::init_proc;
 use axsignal::Signo;
 use axsync::Mutex;
-use starry_api::file::FD_TABLE;
+use starry_api::file::{FD_TABLE, console};
 use starry_core::{
-    mm::{copy_from_kernel, load_user_app, map_trampoline, new_user_aspace_empty},
-    task::{ProcessData, TaskExt, ThreadData, add_thread_to_table, new_user_task},
+    mm::{FileMappings, copy_from_kernel, load_user_app, map_trampoline, new_user_aspace_empty},
+    task::{
+        ProcessData, ThreadData, add_thread_to_table, alloc_pid, new_user_task, spawn_user_task,
+    },
 };
 
 pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
@@ -23,8 +25,10 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
     let (dir, name) = exe_path.rsplit_once('/').unwrap_or(("", &exe_path));
     set_current_dir(dir).expect("Failed to set current dir");
 
-    let (entry_vaddr, ustack_top) = load_user_app(&mut uspace, &exe_path, args, envs)
-        .unwrap_or_else(|e| panic!("Failed to load user app: {}", e));
+    let mut file_mappings = FileMappings::default();
+    let (entry_vaddr, ustack_top) =
+        load_user_app(&mut uspace, &exe_path, args, envs, &mut file_mappings)
+            .unwrap_or_else(|e| panic!("Failed to load user app: {}", e));
 
     let uctx = UspaceContext::new(entry_vaddr.into(), ustack_top, 2333);
 
@@ -34,9 +38,11 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
     let process_data = ProcessData::new(
         exe_path,
         Arc::new(Mutex::new(uspace)),
+        Arc::new(Mutex::new(file_mappings)),
         Arc::default(),
         Some(Signo::SIGCHLD),
     );
+    process_data.set_command_line(args, envs);
 
     FD_TABLE
         .deref_from(&process_data.ns)
@@ -48,7 +54,7 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
         .deref_from(&process_data.ns)
         .init_new(CURRENT_DIR_PATH.copy_inner());
 
-    let tid = task.id().as_u64() as Pid;
+    let tid = alloc_pid().expect("Failed to allocate a PID");
     let process = init_proc().fork(tid).data(process_data).build();
 
     let thread = process
@@ -56,10 +62,12 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
         .data(ThreadData::new(process.data().unwrap()))
         .build();
     add_thread_to_table(&thread);
+    // The first program runs in the foreground of the console, which
+    // becomes the controlling terminal of its session
+    let group = process.group();
+    console().attach(group.session().sid(), group.pgid());
 
-    task.init_task_ext(TaskExt::new(thread));
-
-    let task = axtask::spawn_task(task);
+    let task = spawn_user_task(task, thread);
 
     // TODO: we need a way to wait on the process but not only the main task
     task.join()
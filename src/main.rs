@@ -13,9 +13,14 @@ mod syscall;
 
 #[unsafe(no_mangle)]
 fn main() {
+    starry_core::random::init();
+    starry_core::timer::init();
     starry_core::file::init_filesystem();
+    #[cfg(feature = "console-probe")]
+    starry_api::file::probe_console_size();
     // Create a init process
-    axprocess::Process::new_init(axtask::current().id().as_u64() as _).build();
+    let pid = starry_core::task::alloc_pid().expect("Failed to allocate a PID");
+    axprocess::Process::new_init(pid).build();
 
     let testcases = option_env!("AX_TESTCASES_LIST")
         .unwrap_or_else(|| "Please specify the testcases list by making user_apps")
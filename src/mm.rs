@@ -3,13 +3,14 @@ use axhal::{
     paging::MappingFlags,
     trap::{PAGE_FAULT, register_trap_handler},
 };
+use axsignal::Signo;
 use axtask::{TaskExtRef, current};
-use linux_raw_sys::general::SIGSEGV;
 use starry_api::do_exit;
-use starry_core::mm::is_accessing_user_memory;
+use starry_core::{cpustat, mm::is_accessing_user_memory, task::ExitStatus};
 
 #[register_trap_handler(PAGE_FAULT)]
 fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
+    cpustat::mark_exception();
     warn!(
         "Page fault at {:#x}, access_flags: {:#x?}",
         vaddr, access_flags
@@ -32,7 +33,11 @@ fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool)
             curr.task_ext().thread,
             vaddr
         );
-        do_exit(SIGSEGV as _, true);
+        do_exit(
+            ExitStatus::Signaled(Signo::SIGSEGV, true).wait_status(),
+            true,
+        );
     }
+    curr.task_ext().thread_data().counters.lock().minflt += 1;
     true
 }
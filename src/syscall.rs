@@ -3,15 +3,68 @@ use axhal::{
     arch::TrapFrame,
     trap::{SYSCALL, register_trap_handler},
 };
-use starry_api::*;
-use starry_core::task::{time_stat_from_kernel_to_user, time_stat_from_user_to_kernel};
+use starry_api::{
+    signal::{set_restart_block, take_syscall_restart},
+    *,
+};
+use starry_core::{
+    cpustat,
+    task::{SyscallRestart, time_stat_from_kernel_to_user, time_stat_from_user_to_kernel},
+};
 use syscalls::Sysno;
 
+/// The length of the instruction making syscalls.
+#[cfg(target_arch = "x86_64")]
+const SYSCALL_INSN_LEN: usize = 2;
+#[cfg(not(target_arch = "x86_64"))]
+const SYSCALL_INSN_LEN: usize = 4;
+
+/// Makes the interrupted syscall run again once back in user space, returning
+/// the value for the return register.
+///
+/// The syscall instruction is executed again with the registers it had,
+/// except `ERESTART_RESTARTBLOCK` continues through `restart_syscall`.
+fn restart_syscall(
+    tf: &mut TrapFrame,
+    syscall_num: usize,
+    arg0: usize,
+    restart: SyscallRestart,
+) -> isize {
+    tf.set_ip(tf.ip() - SYSCALL_INSN_LEN);
+    let syscall_num = match restart {
+        SyscallRestart::Block(_) => {
+            set_restart_block(restart);
+            Sysno::restart_syscall.id() as usize
+        }
+        _ => syscall_num,
+    };
+    // The return register holds the syscall number on x86_64, and the first
+    // argument elsewhere
+    #[cfg(target_arch = "x86_64")]
+    {
+        let _ = arg0;
+        syscall_num as isize
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        #[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+        {
+            tf.regs.a7 = syscall_num;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            tf.r[8] = syscall_num as _;
+        }
+        arg0 as isize
+    }
+}
+
 #[register_trap_handler(SYSCALL)]
 fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
     let sysno = Sysno::from(syscall_num as u32);
     info!("Syscall {}", sysno);
     time_stat_from_user_to_kernel();
+    let arg0 = tf.arg0();
     let result = match sysno {
         // fs ctl
         Sysno::ioctl => sys_ioctl(tf.arg0() as _, tf.arg1() as _, tf.arg2().into()),
@@ -56,7 +109,7 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
         Sysno::dup => sys_dup(tf.arg0() as _),
         #[cfg(target_arch = "x86_64")]
         Sysno::dup2 => sys_dup2(tf.arg0() as _, tf.arg1() as _),
-        Sysno::dup3 => sys_dup2(tf.arg0() as _, tf.arg1() as _),
+        Sysno::dup3 => sys_dup3(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
         Sysno::fcntl => sys_fcntl(tf.arg0() as _, tf.arg1() as _, tf.arg2() as _),
 
         // io
@@ -75,6 +128,8 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
             tf.arg4().into(),
         ) as _,
         Sysno::umount2 => sys_umount2(tf.arg0().into(), tf.arg1() as _) as _,
+        Sysno::statfs => sys_statfs(tf.arg0().into(), tf.arg1().into()),
+        Sysno::fstatfs => sys_fstatfs(tf.arg0() as _, tf.arg1().into()),
 
         // pipe
         Sysno::pipe2 => sys_pipe2(tf.arg0().into(), tf.arg1() as _),
@@ -136,6 +191,8 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
         Sysno::gettid => sys_gettid(),
         Sysno::getpgid => sys_getpgid(tf.arg0() as _),
         Sysno::setpgid => sys_setpgid(tf.arg0() as _, tf.arg1() as _),
+        Sysno::getsid => sys_getsid(tf.arg0() as _),
+        Sysno::setsid => sys_setsid(),
 
         // task sched
         Sysno::sched_yield => sys_sched_yield(),
@@ -144,6 +201,7 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
         // task ops
         Sysno::execve => sys_execve(tf, tf.arg0().into(), tf.arg1().into(), tf.arg2().into()),
         Sysno::set_tid_address => sys_set_tid_address(tf.arg0()),
+        Sysno::prctl => sys_prctl(tf.arg0() as _, tf.arg1()),
         #[cfg(target_arch = "x86_64")]
         Sysno::arch_prctl => sys_arch_prctl(tf, tf.arg0() as _, tf.arg1() as _),
 
@@ -160,7 +218,20 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
         Sysno::fork => sys_fork(tf),
         Sysno::exit => sys_exit(tf.arg0() as _),
         Sysno::exit_group => sys_exit_group(tf.arg0() as _),
-        Sysno::wait4 => sys_waitpid(tf.arg0() as _, tf.arg1().into(), tf.arg2() as _),
+        Sysno::wait4 => sys_waitpid(
+            tf.arg0() as _,
+            tf.arg1().into(),
+            tf.arg2() as _,
+            tf.arg3().into(),
+        ),
+        Sysno::waitid => sys_waitid(
+            tf.arg0() as _,
+            tf.arg1() as _,
+            tf.arg2().into(),
+            tf.arg3() as _,
+            tf.arg4().into(),
+        ),
+        Sysno::pidfd_open => sys_pidfd_open(tf.arg0() as _, tf.arg1() as _),
 
         // signal
         Sysno::rt_sigprocmask => sys_rt_sigprocmask(
@@ -177,6 +248,7 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
         ),
         Sysno::rt_sigpending => sys_rt_sigpending(tf.arg0().into(), tf.arg1() as _),
         Sysno::rt_sigreturn => sys_rt_sigreturn(tf),
+        Sysno::restart_syscall => sys_restart_syscall(),
         Sysno::rt_sigtimedwait => sys_rt_sigtimedwait(
             tf.arg0().into(),
             tf.arg1().into(),
@@ -216,11 +288,38 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
         Sysno::getgid => sys_getgid(),
         Sysno::getegid => sys_getegid(),
         Sysno::uname => sys_uname(tf.arg0().into()),
+        Sysno::getrandom => sys_getrandom(tf.arg0().into(), tf.arg1() as _, tf.arg2() as _),
 
         // time
         Sysno::gettimeofday => sys_gettimeofday(tf.arg0().into()),
+        Sysno::settimeofday => sys_settimeofday(tf.arg0().into(), tf.arg1().into()),
+        Sysno::adjtimex => sys_adjtimex(tf.arg0().into()),
         Sysno::times => sys_times(tf.arg0().into()),
+        Sysno::getrusage => sys_getrusage(tf.arg0() as _, tf.arg1().into()),
         Sysno::clock_gettime => sys_clock_gettime(tf.arg0() as _, tf.arg1().into()),
+        Sysno::clock_settime => sys_clock_settime(tf.arg0() as _, tf.arg1().into()),
+        Sysno::clock_adjtime => sys_clock_adjtime(tf.arg0() as _, tf.arg1().into()),
+        Sysno::clock_getres => sys_clock_getres(tf.arg0() as _, tf.arg1().into()),
+        Sysno::clock_nanosleep => sys_clock_nanosleep(
+            tf.arg0() as _,
+            tf.arg1() as _,
+            tf.arg2().into(),
+            tf.arg3().into(),
+        ),
+        Sysno::getitimer => sys_getitimer(tf.arg0() as _, tf.arg1().into()),
+        Sysno::setitimer => sys_setitimer(tf.arg0() as _, tf.arg1().into(), tf.arg2().into()),
+        #[cfg(target_arch = "x86_64")]
+        Sysno::alarm => sys_alarm(tf.arg0() as _),
+        Sysno::timer_create => sys_timer_create(tf.arg0() as _, tf.arg1().into(), tf.arg2().into()),
+        Sysno::timer_settime => sys_timer_settime(
+            tf.arg0() as _,
+            tf.arg1() as _,
+            tf.arg2().into(),
+            tf.arg3().into(),
+        ),
+        Sysno::timer_gettime => sys_timer_gettime(tf.arg0() as _, tf.arg1().into()),
+        Sysno::timer_getoverrun => sys_timer_getoverrun(tf.arg0() as _),
+        Sysno::timer_delete => sys_timer_delete(tf.arg0() as _),
 
         // io multiplexing
         #[cfg(target_arch = "x86_64")]
@@ -267,8 +366,15 @@ fn handle_syscall(tf: &mut TrapFrame, syscall_num: usize) -> isize {
             Err(LinuxError::ENOSYS)
         }
     };
-    let ans = result.unwrap_or_else(|err| -err.code() as _);
+    let ans = match result {
+        Err(LinuxError::EINTR) => match take_syscall_restart() {
+            Some((restart, true)) => restart_syscall(tf, syscall_num, arg0, restart),
+            _ => -(LinuxError::EINTR.code() as isize),
+        },
+        result => result.unwrap_or_else(|err| -err.code() as _),
+    };
     time_stat_from_kernel_to_user();
+    cpustat::mark_exception();
     info!("Syscall {:?} return {}", sysno, ans);
     ans
 }